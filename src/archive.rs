@@ -0,0 +1,146 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checking for headers in files contained in `.zip`, `.tar`, and `.tar.gz` archives.
+//!
+//! Archives are only ever read, never modified. Files inside an archive are reported with paths of
+//! the form `archive!inner/path`, e.g. `dist/release.zip!src/main.rs`.
+//!
+//! This module requires the `archive` feature.
+
+use crate::{CheckStatus, FileResult, FileResults, Header, HeaderChecker};
+use std::{ffi, fs, io, path};
+
+/// Check for `header` in every file in the archive at `archive` whose path inside the archive
+/// matches `path_predicate`.
+///
+/// The archive type is determined from its file name: `.zip`, `.tar`, `.tar.gz`, and `.tgz` are
+/// supported.
+///
+/// Returns a [`FileResults`] object with paths of the form `archive!inner/path`.
+pub fn check_headers_in_archive(
+    archive: &path::Path,
+    path_predicate: impl Fn(&path::Path) -> bool,
+    header: Header<impl HeaderChecker>,
+) -> Result<FileResults, CheckArchiveError> {
+    let kind = ArchiveKind::for_path(archive)
+        .ok_or_else(|| CheckArchiveError::UnrecognizedArchive(archive.to_path_buf()))?;
+    let file = fs::File::open(archive)
+        .map_err(|e| CheckArchiveError::IoError(archive.to_path_buf(), e))?;
+    let mut results = Vec::new();
+    let mut check_entry = |inner: &path::Path, entry: &mut dyn io::Read| {
+        if !path_predicate(inner) {
+            return Ok(());
+        }
+        let p = archive_path(archive, inner);
+        match header.header_present(&mut io::BufReader::new(entry)) {
+            Ok(true) => {}
+            Ok(false) => results.push(FileResult {
+                path: p,
+                status: CheckStatus::HeaderNotFound,
+            }),
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => results.push(FileResult {
+                path: p,
+                status: CheckStatus::BinaryFile,
+            }),
+            Err(e) => return Err(CheckArchiveError::IoError(p, e)),
+        }
+        Ok(())
+    };
+    match kind {
+        ArchiveKind::Zip => {
+            let zip_err_mapper = |e| CheckArchiveError::ZipError(archive.to_path_buf(), e);
+            let mut zip = zip::ZipArchive::new(io::BufReader::new(file)).map_err(zip_err_mapper)?;
+            for i in 0..zip.len() {
+                let mut entry = zip.by_index(i).map_err(zip_err_mapper)?;
+                if entry.is_dir() {
+                    continue;
+                }
+                let inner = path::PathBuf::from(entry.name());
+                check_entry(&inner, &mut entry)?;
+            }
+        }
+        ArchiveKind::Tar => check_tar(archive, io::BufReader::new(file), check_entry)?,
+        ArchiveKind::TarGz => check_tar(
+            archive,
+            flate2::read::GzDecoder::new(io::BufReader::new(file)),
+            check_entry,
+        )?,
+    }
+    Ok(results.into_iter().collect())
+}
+
+/// Apply `check_entry` to each regular file in the tar archive read from `reader`.
+fn check_tar(
+    archive: &path::Path,
+    reader: impl io::Read,
+    mut check_entry: impl FnMut(&path::Path, &mut dyn io::Read) -> Result<(), CheckArchiveError>,
+) -> Result<(), CheckArchiveError> {
+    let err_mapper = |e| CheckArchiveError::IoError(archive.to_path_buf(), e);
+    let mut tar = tar::Archive::new(reader);
+    for entry in tar.entries().map_err(err_mapper)? {
+        let mut entry = entry.map_err(err_mapper)?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let inner = entry.path().map_err(err_mapper)?.into_owned();
+        check_entry(&inner, &mut entry)?;
+    }
+    Ok(())
+}
+
+/// Errors that can occur when checking for headers in an archive
+#[derive(Debug, thiserror::Error)]
+pub enum CheckArchiveError {
+    /// An I/O error occurred while reading the archive, or the file inside the archive, at the path
+    #[error("I/O error at {0:?}: {1}")]
+    IoError(path::PathBuf, io::Error),
+    /// The zip archive at the path could not be read
+    #[error("Zip error at {0:?}: {1}")]
+    ZipError(path::PathBuf, zip::result::ZipError),
+    /// The file at the path is not a recognized archive type
+    #[error("Unknown archive type: {0:?}")]
+    UnrecognizedArchive(path::PathBuf),
+}
+
+/// Supported archive formats
+#[derive(Clone, Copy)]
+enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveKind {
+    fn for_path(p: &path::Path) -> Option<Self> {
+        let name = p.file_name()?.to_str()?.to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// The path used to report `inner` inside `archive`: `archive!inner`
+fn archive_path(archive: &path::Path, inner: &path::Path) -> path::PathBuf {
+    let mut p = ffi::OsString::from(archive.as_os_str());
+    p.push("!");
+    p.push(inner.as_os_str());
+    p.into()
+}
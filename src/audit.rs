@@ -0,0 +1,287 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Evaluating several rules about each file's header in a single pass, rather than running a
+//! separate recursive check for each.
+//!
+//! Each file is read once, and every rule in [`AuditRules`] is evaluated against its contents.
+//!
+//! # Examples
+//!
+//! ```
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use file_header::{audit::*, copyright::YearPolicyChecker, *};
+//!
+//! # let dir = tempfile::tempdir().unwrap();
+//! # let root = dir.path();
+//! # std::fs::write(root.join("main.rs"), "// Copyright 2021 Foo Inc.\n// Foo License\n").unwrap();
+//! let header = Header::new(
+//!     SingleLineChecker::new("Foo License".to_string(), 10),
+//!     "Copyright 2025 Foo Inc.\nFoo License".to_string(),
+//! );
+//! let rules = AuditRules::new(header)
+//!     .within_lines(5)
+//!     .year_policy(YearPolicyChecker::includes_year(2025))
+//!     .holder("Foo Inc.");
+//! let results = audit_headers_recursively(root, |_p| true, &rules, &RecursiveOptions::new())
+//!     .unwrap();
+//! assert!(results.missing_header_files.is_empty());
+//! assert_eq!(vec![root.join("main.rs")], results.year_violation_files);
+//! ```
+
+#[cfg(feature = "spdx")]
+use crate::license::spdx::identifier::{IdentifierError, IdentifierValidator};
+use crate::{
+    copyright::{self, YearPolicyChecker, YearPolicyStatus},
+    filesystem, lines, recursive_optional_operation, Header, HeaderChecker, RecursiveOptions,
+};
+use std::{
+    cell,
+    io::{self, Read as _},
+    path,
+};
+
+/// The rules that [`audit_headers_recursively`] evaluates for each file.
+///
+/// Only the presence of the header is required; the other rules are only evaluated if they're
+/// set.
+pub struct AuditRules<C: HeaderChecker> {
+    header: Header<C>,
+    within_lines: Option<usize>,
+    year_policy: Option<YearPolicyChecker>,
+    holder: Option<String>,
+    #[cfg(feature = "spdx")]
+    identifiers: Option<IdentifierValidator>,
+}
+
+impl<C: HeaderChecker> AuditRules<C> {
+    /// Construct rules that require files to have `header`, as determined by its checker.
+    pub fn new(header: Header<C>) -> Self {
+        Self {
+            header,
+            within_lines: None,
+            year_policy: None,
+            holder: None,
+            #[cfg(feature = "spdx")]
+            identifiers: None,
+        }
+    }
+
+    /// Require the header to be found within the first `lines` lines of each file.
+    pub fn within_lines(mut self, lines: usize) -> Self {
+        self.within_lines = Some(lines);
+        self
+    }
+
+    /// Require the years of each file's copyright line to satisfy `policy`, e.g. to include the
+    /// current year.
+    pub fn year_policy(mut self, policy: YearPolicyChecker) -> Self {
+        self.year_policy = Some(policy);
+        self
+    }
+
+    /// Require a copyright line in the first [`copyright::SEARCH_LINES`] lines of each file to
+    /// name `holder` after its year list, ignoring a trailing `.`.
+    pub fn holder(mut self, holder: impl Into<String>) -> Self {
+        self.holder = Some(holder.into());
+        self
+    }
+
+    /// Require each file's `SPDX-License-Identifier` line to pass `validator`.
+    ///
+    /// This requires the `spdx` feature.
+    #[cfg(feature = "spdx")]
+    pub fn identifiers(mut self, validator: IdentifierValidator) -> Self {
+        self.identifiers = Some(validator);
+        self
+    }
+
+    /// Evaluate the rules against `contents`, adding `p` to the results for each rule it fails.
+    fn evaluate(
+        &self,
+        contents: &[u8],
+        p: path::PathBuf,
+        results: &mut AuditResults,
+    ) -> io::Result<()> {
+        if !self.header.header_present(&mut &*contents)? {
+            results.missing_header_files.push(p.clone());
+        } else if let Some(max_lines) = self.within_lines {
+            if !self
+                .header
+                .header_present(&mut first_lines(contents, max_lines))?
+            {
+                results.misplaced_header_files.push(p.clone());
+            }
+        }
+        if let Some(policy) = &self.year_policy {
+            if policy.evaluate(&mut &*contents)? != YearPolicyStatus::Satisfied {
+                results.year_violation_files.push(p.clone());
+            }
+        }
+        if let Some(holder) = &self.holder {
+            if !names_holder(contents, holder)? {
+                results.wrong_holder_files.push(p.clone());
+            }
+        }
+        #[cfg(feature = "spdx")]
+        if let Some(validator) = &self.identifiers {
+            if let Err(e) = validator.validate_reader(&mut &*contents)? {
+                results.invalid_identifier_files.push((p, e));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Evaluate `rules` for every file in `root` that matches `path_predicate`, reading each file
+/// once, with the behavior adjusted by `options`.
+///
+/// Files that aren't UTF-8 text are reported in [`AuditResults::binary_files`], and no rules are
+/// evaluated for them.
+pub fn audit_headers_recursively(
+    root: &path::Path,
+    path_predicate: impl Fn(&path::Path) -> bool,
+    rules: &AuditRules<impl HeaderChecker>,
+    options: &RecursiveOptions,
+) -> Result<AuditResults, AuditHeadersRecursivelyError> {
+    let results = cell::RefCell::new(AuditResults::default());
+    let skipped = recursive_optional_operation::<AuditHeadersRecursivelyError>(
+        &[root],
+        path_predicate,
+        options,
+        |p| {
+            let mut contents = Vec::new();
+            options
+                .file_system
+                .open(p)
+                .and_then(|mut f| f.read_to_end(&mut contents))
+                .map_err(|e| AuditHeadersRecursivelyError::IoError(p.to_path_buf(), e))?;
+            let relative = options.relative_path(&[root], p.to_path_buf());
+            let mut audit = results.borrow_mut();
+            match rules.evaluate(&contents, relative.clone(), &mut audit) {
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                    // discard the results of any rules evaluated before the binary data was found
+                    audit.remove(&relative);
+                    audit.binary_files.push(relative);
+                    Ok(false)
+                }
+                r => r
+                    .map(|_| false)
+                    .map_err(|e| AuditHeadersRecursivelyError::IoError(p.to_path_buf(), e)),
+            }
+        },
+    )?;
+    let mut results = results.into_inner();
+    results.oversized_files = skipped.oversized_files;
+    results.symlinks = skipped.symlinks;
+    Ok(results)
+}
+
+/// The files that failed each rule of an [`audit_headers_recursively`]
+#[derive(Debug, Default)]
+pub struct AuditResults {
+    /// Files without the header
+    pub missing_header_files: Vec<path::PathBuf>,
+    /// Files with the header, but not within [`AuditRules::within_lines`]
+    pub misplaced_header_files: Vec<path::PathBuf>,
+    /// Files without a copyright line whose years satisfy [`AuditRules::year_policy`]
+    pub year_violation_files: Vec<path::PathBuf>,
+    /// Files without a copyright line naming [`AuditRules::holder`]
+    pub wrong_holder_files: Vec<path::PathBuf>,
+    /// Files whose `SPDX-License-Identifier` line is missing or fails
+    /// [`AuditRules::identifiers`], and why
+    #[cfg(feature = "spdx")]
+    pub invalid_identifier_files: Vec<(path::PathBuf, IdentifierError)>,
+    /// Paths that appeared to be binary, not UTF-8 text
+    pub binary_files: Vec<path::PathBuf>,
+    /// Paths that were skipped because they exceeded [`RecursiveOptions::max_file_size`]
+    pub oversized_files: Vec<path::PathBuf>,
+    /// Symlinks to files that were skipped, as per [`crate::SymlinkPolicy::Report`]
+    pub symlinks: Vec<path::PathBuf>,
+}
+
+impl AuditResults {
+    /// Returns `true` if any file failed any rule.
+    pub fn has_failure(&self) -> bool {
+        let failed = !self.missing_header_files.is_empty()
+            || !self.misplaced_header_files.is_empty()
+            || !self.year_violation_files.is_empty()
+            || !self.wrong_holder_files.is_empty();
+        #[cfg(feature = "spdx")]
+        let failed = failed || !self.invalid_identifier_files.is_empty();
+        failed
+    }
+
+    /// Remove `p` from the results of every rule
+    fn remove(&mut self, p: &path::Path) {
+        for list in [
+            &mut self.missing_header_files,
+            &mut self.misplaced_header_files,
+            &mut self.year_violation_files,
+            &mut self.wrong_holder_files,
+        ] {
+            list.retain(|other| other != p);
+        }
+        #[cfg(feature = "spdx")]
+        self.invalid_identifier_files
+            .retain(|(other, _)| other != p);
+    }
+}
+
+/// Errors that can occur when auditing headers recursively
+#[derive(Debug, thiserror::Error)]
+pub enum AuditHeadersRecursivelyError {
+    /// An I/O error occurred while reading the path
+    #[error("I/O error at {0:?}: {1}")]
+    IoError(path::PathBuf, io::Error),
+    /// `walkdir` could not navigate the directory structure
+    #[error("Walkdir error: {0}")]
+    WalkdirError(#[from] walkdir::Error),
+}
+
+impl From<filesystem::WalkError> for AuditHeadersRecursivelyError {
+    fn from(value: filesystem::WalkError) -> Self {
+        match value {
+            filesystem::WalkError::Walkdir(e) => Self::WalkdirError(e),
+            filesystem::WalkError::Io(p, e) => Self::IoError(p, e),
+        }
+    }
+}
+
+/// The first `max_lines` lines of `contents`
+fn first_lines(contents: &[u8], max_lines: usize) -> &[u8] {
+    if max_lines == 0 {
+        return &[];
+    }
+    let end = contents
+        .iter()
+        .enumerate()
+        .filter(|(_, b)| **b == b'\n')
+        .nth(max_lines - 1)
+        .map_or(contents.len(), |(i, _)| i + 1);
+    &contents[..end]
+}
+
+/// Returns `true` if a copyright line in the first lines of `contents` names `holder` after its
+/// year list.
+fn names_holder(contents: &[u8], holder: &str) -> io::Result<bool> {
+    let holder = holder.trim().trim_end_matches('.');
+    let mut found = false;
+    lines::find_line(&mut &*contents, copyright::SEARCH_LINES, |line| {
+        found = copyright::holder(line).map_or(false, |h| h.trim_end_matches('.') == holder);
+        found
+    })?;
+    Ok(found)
+}
@@ -0,0 +1,114 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `cargo file-header`: check for, or add, headers in the Rust sources of the current workspace.
+
+use file_header::{cargo::Workspace, Header, SingleLineChecker};
+use std::{env, fs, path, process};
+
+const USAGE: &str = "Usage: cargo file-header <check|add> --header-file <path> [options]
+
+Options:
+    --header-file <path>     File containing the plain header text, without comment syntax
+    --pattern <text>         Text identifying the header [default: first line of the header]
+    --lines <n>              Number of lines to search for the pattern [default: 10]
+    --threads <n>            Number of threads to check with [default: 4]
+    --manifest-path <path>   Path to Cargo.toml [default: the current workspace]";
+
+fn main() {
+    match run(env::args().skip(1).collect()) {
+        Ok(success) => process::exit(if success { 0 } else { 1 }),
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    }
+}
+
+/// Run the subcommand, returning `Ok(false)` if headers are missing
+fn run(mut args: Vec<String>) -> Result<bool, String> {
+    // when run as `cargo file-header`, cargo passes the subcommand name as the first argument
+    if args.first().map(|a| a == "file-header").unwrap_or(false) {
+        args.remove(0);
+    }
+    let mut args = args.into_iter();
+    let mode = args.next().ok_or("missing mode")?;
+    let mut header_file = None;
+    let mut pattern = None;
+    let mut lines = 10;
+    let mut threads = 4;
+    let mut manifest_path = None;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for {}", arg));
+        match arg.as_str() {
+            "--header-file" => header_file = Some(path::PathBuf::from(value()?)),
+            "--pattern" => pattern = Some(value()?),
+            "--lines" => {
+                lines = value()?
+                    .parse()
+                    .map_err(|e| format!("bad --lines: {}", e))?
+            }
+            "--threads" => {
+                threads = value()?
+                    .parse()
+                    .map_err(|e| format!("bad --threads: {}", e))?
+            }
+            "--manifest-path" => manifest_path = Some(path::PathBuf::from(value()?)),
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+
+    let header_file = header_file.ok_or("missing --header-file")?;
+    let text = fs::read_to_string(&header_file)
+        .map_err(|e| format!("could not read {:?}: {}", header_file, e))?;
+    let text = text.trim_end().to_string();
+    let pattern = match pattern {
+        Some(p) => p,
+        None => text
+            .lines()
+            .find(|l| !l.trim().is_empty())
+            .ok_or("header file is empty")?
+            .trim()
+            .to_string(),
+    };
+    let header = Header::new(SingleLineChecker::new(pattern, lines), text);
+
+    let workspace = match manifest_path {
+        Some(p) => Workspace::from_manifest(&p),
+        None => Workspace::current(),
+    }
+    .map_err(|e| e.to_string())?;
+
+    match mode.as_str() {
+        "check" => {
+            let results = workspace
+                .check_headers(header, threads)
+                .map_err(|e| e.to_string())?;
+            for p in &results.no_header_files {
+                println!("missing header: {}", p.display());
+            }
+            for p in &results.binary_files {
+                println!("binary file: {}", p.display());
+            }
+            Ok(!results.has_failure())
+        }
+        "add" => {
+            for p in workspace.add_headers(header).map_err(|e| e.to_string())? {
+                println!("added header: {}", p.display());
+            }
+            Ok(true)
+        }
+        _ => Err(format!("unknown mode {}", mode)),
+    }
+}
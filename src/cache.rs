@@ -0,0 +1,288 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A persistent cache of check results, so that repeated runs only check files that changed.
+//!
+//! Files are considered unchanged if their size and modification time are the same as when they
+//! were last checked. Results are only reused with the same options that affect checking, e.g.
+//! [`crate::RecursiveOptions::strict_headers`].
+//!
+//! # Examples
+//!
+//! ```
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use file_header::{cache::CheckCache, *};
+//! use std::{path::Path, sync::Arc};
+//!
+//! # let dir = tempfile::tempdir().unwrap();
+//! # let cache_path = dir.path().join(".file-header-cache");
+//! # let root = dir.path();
+//! let header_text = "Foo License\nmore license text";
+//! let header = Header::new(
+//!     SingleLineChecker::new("Foo License".to_string(), 10),
+//!     header_text.to_string(),
+//! );
+//!
+//! // the key should change whenever the header or checker do
+//! let cache = Arc::new(CheckCache::load(&cache_path, header_text).unwrap());
+//! let results = check_headers_recursively_with_options(
+//!     root,
+//!     |_p| true,
+//!     header,
+//!     4,
+//!     &RecursiveOptions::new().cache(cache.clone()),
+//! )
+//! .unwrap();
+//! cache.save().unwrap();
+//! ```
+
+use crate::{filesystem, fnv, CheckStatus};
+use std::{
+    collections, fs, io,
+    io::Write as _,
+    path,
+    sync::{self, atomic},
+    time,
+};
+
+/// First line of a cache file, followed by the key fingerprint
+const CACHE_FILE_MAGIC: &str = "file-header cache v2";
+
+/// A persistent cache of check results.
+///
+/// Use with [`crate::RecursiveOptions::cache`].
+#[derive(Debug)]
+pub struct CheckCache {
+    /// Where the cache is saved
+    path: path::PathBuf,
+    /// Fingerprint of the key the cache was loaded with
+    fingerprint: String,
+    /// Cached results
+    entries: sync::Mutex<collections::HashMap<path::PathBuf, CacheEntry>>,
+    /// Number of files whose cached results were used
+    hits: atomic::AtomicUsize,
+}
+
+impl CheckCache {
+    /// Load the cache stored at `path`, or start with an empty cache if there isn't one.
+    ///
+    /// `key` identifies the header and checker configuration the cached results are valid for, e.g.
+    /// the header text and checker pattern. If the stored cache was saved with a different key, it
+    /// is discarded. The [`crate::RecursiveOptions`] that affect checking, i.e.
+    /// [`crate::RecursiveOptions::strict_headers`] and
+    /// [`crate::RecursiveOptions::normalize_whitespace`], needn't be included, as results are only
+    /// reused with the same options anyway.
+    pub fn load(path: &path::Path, key: &str) -> io::Result<Self> {
+        let fingerprint = format!("{:016x}", fnv::hash(key.as_bytes()));
+        let mut entries = collections::HashMap::new();
+        match fs::read_to_string(path) {
+            Ok(contents) => {
+                let mut lines = contents.lines();
+                if lines.next() == Some(&format!("{}\t{}", CACHE_FILE_MAGIC, fingerprint)) {
+                    // malformed lines are ignored, as they will simply be re-checked
+                    entries.extend(lines.filter_map(CacheEntry::parse));
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            fingerprint,
+            entries: sync::Mutex::new(entries),
+            hits: atomic::AtomicUsize::new(0),
+        })
+    }
+
+    /// Save the cache to the path it was loaded from.
+    ///
+    /// Only results for files that were encountered since the cache was loaded are saved, so
+    /// entries for deleted files don't accumulate.
+    pub fn save(&self) -> io::Result<()> {
+        let mut out = io::BufWriter::new(fs::File::create(&self.path)?);
+        writeln!(out, "{}\t{}", CACHE_FILE_MAGIC, self.fingerprint)?;
+        for (p, entry) in self.entries.lock().unwrap().iter() {
+            if !entry.seen {
+                continue;
+            }
+            // paths that can't be represented in the line-based format just aren't cached
+            if let Some(p) = p.to_str().filter(|p| !p.contains('\n')) {
+                writeln!(
+                    out,
+                    "{}\t{}\t{}\t{}\t{}",
+                    entry.stamp.len,
+                    entry.stamp.modified_nanos,
+                    entry.options.name(),
+                    status_name(entry.status),
+                    p
+                )?;
+            }
+        }
+        out.flush()
+    }
+
+    /// The number of files whose cached results were used rather than checking the file.
+    pub fn hits(&self) -> usize {
+        self.hits.load(atomic::Ordering::Relaxed)
+    }
+
+    /// Returns the cached result for `p` if it was cached with `stamp` and `options`.
+    ///
+    /// `None` in the result represents the header being present.
+    pub(crate) fn lookup(
+        &self,
+        p: &path::Path,
+        stamp: FileStamp,
+        options: CheckOptions,
+    ) -> Option<Option<CheckStatus>> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries
+            .get_mut(p)
+            .filter(|entry| entry.stamp == stamp && entry.options == options)?;
+        entry.seen = true;
+        self.hits.fetch_add(1, atomic::Ordering::Relaxed);
+        Some(entry.status)
+    }
+
+    /// Record the result of checking `p` with `options` when it had `stamp`.
+    pub(crate) fn record(
+        &self,
+        p: &path::Path,
+        stamp: FileStamp,
+        options: CheckOptions,
+        status: Option<CheckStatus>,
+    ) {
+        self.entries.lock().unwrap().insert(
+            p.to_path_buf(),
+            CacheEntry {
+                stamp,
+                options,
+                status,
+                seen: true,
+            },
+        );
+    }
+}
+
+/// The options that change the result of checking a file, which cached results are only reused with
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct CheckOptions {
+    /// As per [`crate::RecursiveOptions::strict_headers`]
+    pub(crate) strict_headers: bool,
+    /// As per [`crate::RecursiveOptions::normalize_whitespace`]
+    pub(crate) normalize_whitespace: bool,
+}
+
+impl CheckOptions {
+    /// The options as stored in a cache file, e.g. `strict,normalized`, or `-` for neither
+    fn name(self) -> &'static str {
+        match (self.strict_headers, self.normalize_whitespace) {
+            (false, false) => "-",
+            (true, false) => "strict",
+            (false, true) => "normalized",
+            (true, true) => "strict,normalized",
+        }
+    }
+
+    /// Parse options stored as per [`CheckOptions::name`]
+    fn parse(name: &str) -> Option<Self> {
+        let (strict_headers, normalize_whitespace) = match name {
+            "-" => (false, false),
+            "strict" => (true, false),
+            "normalized" => (false, true),
+            "strict,normalized" => (true, true),
+            _ => return None,
+        };
+        Some(Self {
+            strict_headers,
+            normalize_whitespace,
+        })
+    }
+}
+
+/// The attributes of a file that indicate whether it has changed
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct FileStamp {
+    len: u64,
+    modified_nanos: u128,
+}
+
+impl FileStamp {
+    /// Returns the stamp for the file at `p`, or `None` if `fs` can't provide it.
+    pub(crate) fn read(fs: &dyn filesystem::FileSystem, p: &path::Path) -> Option<Self> {
+        let modified_nanos = fs
+            .modified(p)
+            .ok()?
+            .duration_since(time::UNIX_EPOCH)
+            .ok()?
+            .as_nanos();
+        Some(Self {
+            len: fs.file_size(p).ok()?,
+            modified_nanos,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    stamp: FileStamp,
+    /// The options the file was checked with
+    options: CheckOptions,
+    /// `None` if the header was present
+    status: Option<CheckStatus>,
+    /// Whether the file has been encountered since loading the cache
+    seen: bool,
+}
+
+impl CacheEntry {
+    /// Parse a `len \t modified \t options \t status \t path` line
+    fn parse(line: &str) -> Option<(path::PathBuf, Self)> {
+        let mut fields = line.splitn(5, '\t');
+        let len = fields.next()?.parse().ok()?;
+        let modified_nanos = fields.next()?.parse().ok()?;
+        let options = CheckOptions::parse(fields.next()?)?;
+        let status = match fields.next()? {
+            "present" => None,
+            "missing" => Some(CheckStatus::HeaderNotFound),
+            "binary" => Some(CheckStatus::BinaryFile),
+            "partial" => Some(CheckStatus::PartialHeader),
+            "drifted" => Some(CheckStatus::DriftedHeader),
+            _ => return None,
+        };
+        let p = path::PathBuf::from(fields.next()?);
+        Some((
+            p,
+            Self {
+                stamp: FileStamp {
+                    len,
+                    modified_nanos,
+                },
+                options,
+                status,
+                seen: false,
+            },
+        ))
+    }
+}
+
+fn status_name(status: Option<CheckStatus>) -> &'static str {
+    match status {
+        None => "present",
+        Some(CheckStatus::HeaderNotFound) => "missing",
+        Some(CheckStatus::BinaryFile) => "binary",
+        Some(CheckStatus::PartialHeader) => "partial",
+        Some(CheckStatus::DriftedHeader) => "drifted",
+    }
+}
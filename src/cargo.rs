@@ -0,0 +1,156 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Header operations on the Rust sources of a Cargo workspace.
+//!
+//! This is the library entry point used by the `cargo-file-header` binary, which provides
+//! `cargo file-header check` and `cargo file-header add`.
+//!
+//! By default, the Rust sources of a workspace are the `.rs` files in the `src` directory of each
+//! workspace member, excluding anything in the target directory.
+//!
+//! This module requires the `cargo` feature.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use file_header::{cargo::Workspace, *};
+//!
+//! let workspace = Workspace::current().unwrap();
+//! let header = Header::new(
+//!     SingleLineChecker::new("Foo License".to_string(), 10),
+//!     "Foo License".to_string(),
+//! );
+//! let results = workspace.check_headers(header, 4).unwrap();
+//! println!("files without the header: {:?}", results.no_header_files);
+//! ```
+
+use crate::{
+    add_headers_recursively, check_headers_recursively, AddHeadersRecursivelyError,
+    CheckHeadersRecursivelyError, FileResults, Header, HeaderChecker,
+};
+use std::path;
+
+/// The locations of a Cargo workspace's Rust sources.
+#[derive(Clone, Debug)]
+pub struct Workspace {
+    /// The workspace root directory
+    root: path::PathBuf,
+    /// The target directory, which is never scanned
+    target_dir: path::PathBuf,
+    /// The `src` directories of each workspace member
+    source_dirs: Vec<path::PathBuf>,
+}
+
+impl Workspace {
+    /// Load the workspace containing the current directory, via `cargo metadata`.
+    pub fn current() -> Result<Self, CargoError> {
+        Self::load(cargo_metadata::MetadataCommand::new())
+    }
+
+    /// Load the workspace containing the package at `manifest_path`, via `cargo metadata`.
+    pub fn from_manifest(manifest_path: &path::Path) -> Result<Self, CargoError> {
+        let mut command = cargo_metadata::MetadataCommand::new();
+        command.manifest_path(manifest_path);
+        Self::load(command)
+    }
+
+    fn load(mut command: cargo_metadata::MetadataCommand) -> Result<Self, CargoError> {
+        let metadata = command.no_deps().exec()?;
+        let mut source_dirs = metadata
+            .workspace_packages()
+            .iter()
+            .filter_map(|package| package.manifest_path.parent())
+            .map(|package_dir| package_dir.join("src").into_std_path_buf())
+            .filter(|src| src.is_dir())
+            .collect::<Vec<_>>();
+        source_dirs.sort();
+        source_dirs.dedup();
+        Ok(Self {
+            root: metadata.workspace_root.into_std_path_buf(),
+            target_dir: metadata.target_directory.into_std_path_buf(),
+            source_dirs,
+        })
+    }
+
+    /// The workspace root directory.
+    pub fn root(&self) -> &path::Path {
+        &self.root
+    }
+
+    /// The `src` directories of the workspace members.
+    pub fn source_dirs(&self) -> &[path::PathBuf] {
+        &self.source_dirs
+    }
+
+    /// Returns `true` if `p` is a Rust source file that isn't in the target directory.
+    pub fn is_rust_source(&self, p: &path::Path) -> bool {
+        p.extension().map(|ext| ext == "rs").unwrap_or(false) && !p.starts_with(&self.target_dir)
+    }
+
+    /// Check for `header` in the workspace's Rust sources, as per [`check_headers_recursively`].
+    ///
+    /// Paths in the results are sorted.
+    pub fn check_headers(
+        &self,
+        header: Header<impl HeaderChecker>,
+        num_threads: usize,
+    ) -> Result<FileResults, CheckHeadersRecursivelyError> {
+        let mut results = FileResults::default();
+        for dir in &self.source_dirs {
+            let dir_results = check_headers_recursively(
+                dir,
+                |p| self.is_rust_source(p),
+                header.clone(),
+                num_threads,
+            )?;
+            results.no_header_files.extend(dir_results.no_header_files);
+            results.binary_files.extend(dir_results.binary_files);
+            results.oversized_files.extend(dir_results.oversized_files);
+        }
+        results.no_header_files.sort();
+        results.binary_files.sort();
+        results.oversized_files.sort();
+        Ok(results)
+    }
+
+    /// Add `header` to the workspace's Rust sources, as per [`add_headers_recursively`].
+    ///
+    /// Returns a sorted list of paths that had headers added.
+    pub fn add_headers(
+        &self,
+        header: Header<impl HeaderChecker>,
+    ) -> Result<Vec<path::PathBuf>, AddHeadersRecursivelyError> {
+        let mut added = Vec::new();
+        for dir in &self.source_dirs {
+            added.extend(add_headers_recursively(
+                dir,
+                |p| self.is_rust_source(p),
+                header.clone(),
+            )?);
+        }
+        added.sort();
+        Ok(added)
+    }
+}
+
+/// Errors that can occur when loading a Cargo workspace
+#[derive(Debug, thiserror::Error)]
+pub enum CargoError {
+    /// `cargo metadata` failed or produced unexpected output
+    #[error("cargo metadata error: {0}")]
+    MetadataError(#[from] cargo_metadata::Error),
+}
@@ -0,0 +1,163 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Pre-built [`clap`] arguments, so that tools embedding this crate get consistent flags.
+//!
+//! [`PathArgs`] and [`LicenseArgs`] can be flattened into an existing parser, or [`CliArgs`] used
+//! as a complete one.
+//!
+//! # Examples
+//!
+//! ```
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use clap::Parser;
+//! use file_header::{cli::*, *};
+//!
+//! #[derive(Parser)]
+//! struct DevTools {
+//!     #[command(flatten)]
+//!     paths: PathArgs,
+//!     #[command(flatten)]
+//!     license: LicenseArgs,
+//! }
+//!
+//! # let dir = tempfile::tempdir().unwrap();
+//! # let root = dir.path().to_str().unwrap();
+//! let args = DevTools::parse_from(["dev-tools", root, "--glob", "**/*.rs", "--owner", "Foo Inc."]);
+//! let results = check_headers_recursively_with_options(
+//!     &args.paths.root,
+//!     |_p| true,
+//!     args.license.header(),
+//!     args.paths.threads,
+//!     &args.paths.recursive_options(RecursiveOptions::new()),
+//! );
+//! assert_eq!(0, FileResults::exit_code_of(&results));
+//! ```
+
+use crate::{
+    compat::{current_year, glob_match, slash_path},
+    license::spdx::{self, identifier},
+    Header, RecursiveOptions,
+};
+use std::{path, sync};
+
+/// Which files to operate on, and how many threads to use.
+#[derive(Clone, Debug, PartialEq, Eq, clap::Args)]
+pub struct PathArgs {
+    /// Directory to operate on
+    #[arg(default_value = ".")]
+    pub root: path::PathBuf,
+    /// Only operate on files whose path relative to the root matches this glob; may be repeated
+    #[arg(long = "glob", value_name = "GLOB")]
+    pub globs: Vec<String>,
+    /// Skip files and directories whose path relative to the root matches this glob; may be
+    /// repeated
+    #[arg(long = "exclude", value_name = "GLOB")]
+    pub excludes: Vec<String>,
+    /// Number of threads to operate on files with
+    #[arg(long, default_value = "4")]
+    pub threads: usize,
+}
+
+impl PathArgs {
+    /// Returns `true` if `p`, relative to [`PathArgs::root`], matches one of the `--glob` patterns,
+    /// if any were given, and none of the `--exclude` patterns.
+    ///
+    /// `*` matches anything but a `/`, and `**` matches any number of directories.
+    pub fn is_included(&self, p: &path::Path) -> bool {
+        let p = slash_path(p);
+        (self.globs.is_empty() || matches_any(&self.globs, &p)) && !matches_any(&self.excludes, &p)
+    }
+
+    /// Set `options` to only operate on files that are included as per [`PathArgs::is_included`],
+    /// and not to descend into excluded directories.
+    pub fn recursive_options(&self, options: RecursiveOptions) -> RecursiveOptions {
+        let files = sync::Arc::new(self.clone());
+        let dirs = files.clone();
+        options
+            .file_filter(sync::Arc::new(move |p, _metadata| {
+                files.is_included(p.strip_prefix(&files.root).unwrap_or(p))
+            }))
+            .dir_filter(sync::Arc::new(move |p| {
+                let p = slash_path(p.strip_prefix(&dirs.root).unwrap_or(p));
+                !matches_any(&dirs.excludes, &p)
+            }))
+    }
+}
+
+/// The license and copyright of the header to check for or add.
+#[derive(Clone, Debug, PartialEq, Eq, clap::Args)]
+pub struct LicenseArgs {
+    /// SPDX license expression, e.g. `Apache-2.0 OR MIT`
+    #[arg(long, default_value = "Apache-2.0", value_parser = parse_expression)]
+    pub license: String,
+    /// Copyright owner
+    #[arg(long)]
+    pub owner: String,
+    /// Copyright year [default: the current year]
+    #[arg(long)]
+    pub year: Option<u32>,
+}
+
+impl LicenseArgs {
+    /// The copyright year, defaulting to the current year.
+    pub fn year(&self) -> u32 {
+        self.year.unwrap_or_else(|| current_year() as u32)
+    }
+
+    /// A header with the copyright and license, as per [`spdx::short_header_for_expression`].
+    pub fn header(&self) -> Header<spdx::CopyrightSpdxChecker> {
+        spdx::short_header_for_expression(&self.license, self.year(), &self.owner)
+    }
+}
+
+/// What to do with headers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Mode {
+    /// Report files without the header
+    Check,
+    /// Add the header to files without it
+    Add,
+    /// Delete the header from files with it
+    Delete,
+}
+
+/// A complete set of arguments, for tools that don't need any of their own.
+#[derive(Clone, Debug, PartialEq, Eq, clap::Parser)]
+#[command(about = "Check for, add, or delete license headers")]
+pub struct CliArgs {
+    /// What to do with headers
+    #[arg(value_enum)]
+    pub mode: Mode,
+    /// Which files to operate on
+    #[command(flatten)]
+    pub paths: PathArgs,
+    /// The header to check for or add
+    #[command(flatten)]
+    pub license: LicenseArgs,
+}
+
+/// Returns `true` if `p` matches one of `patterns`.
+fn matches_any(patterns: &[String], p: &str) -> bool {
+    patterns
+        .iter()
+        .any(|pattern| glob_match(pattern.as_bytes(), p.as_bytes()))
+}
+
+/// Parse `--license`, rejecting unknown license identifiers.
+fn parse_expression(expression: &str) -> Result<String, identifier::IdentifierError> {
+    identifier::IdentifierValidator::new().validate_expression(expression)?;
+    Ok(expression.to_string())
+}
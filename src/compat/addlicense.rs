@@ -0,0 +1,350 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Loading the command line configuration of Google's
+//! [`addlicense`](https://github.com/google/addlicense), so that its flags and template files can
+//! be reused with this crate.
+//!
+//! The supported flags are `-c` (copyright holder), `-y` (year), `-l` (license type), `-f`
+//! (custom template file), `-ignore` (path patterns, which may be repeated), `-s` (SPDX
+//! identifier), and `-check`. Flags that only affect `addlicense`'s output, like `-v`, are
+//! accepted and ignored.
+//!
+//! # Examples
+//!
+//! ```
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use file_header::compat::addlicense::*;
+//! use std::path::Path;
+//!
+//! let config = AddlicenseConfig::from_args([
+//!     "-c", "Foo Inc.", "-y", "2021", "-l", "mpl", "-s", "-ignore", "vendor/**", ".",
+//! ])
+//! .unwrap();
+//! assert_eq!(
+//!     "Copyright 2021 Foo Inc.\n\n\
+//!     This Source Code Form is subject to the terms of the Mozilla Public\n\
+//!     License, v. 2.0. If a copy of the MPL was not distributed with this\n\
+//!     file, You can obtain one at https://mozilla.org/MPL/2.0/.\n\n\
+//!     SPDX-License-Identifier: MPL-2.0",
+//!     config.header_text().unwrap()
+//! );
+//! assert!(config.is_ignored(Path::new("vendor/foo/bar.rs")));
+//!
+//! let header = config.header().unwrap();
+//! assert!(header.check_str("// copyright 2019 Bar Inc.\n").unwrap());
+//! ```
+
+use crate::{
+    compat::{current_year, glob_match},
+    Header, HeaderChecker,
+};
+use std::{fs, io, path};
+
+/// The number of bytes at the start of a file that `addlicense` looks for an existing license in
+const CHECK_BYTES: usize = 1000;
+
+/// The template of `addlicense`'s `apache` license type
+const APACHE_TEMPLATE: &str = r#"Copyright {{.Year}} {{.Holder}}
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License."#;
+
+/// The template of `addlicense`'s `bsd` license type
+const BSD_TEMPLATE: &str = r#"Copyright (c) {{.Year}} {{.Holder}} All rights reserved.
+Use of this source code is governed by a BSD-style
+license that can be found in the LICENSE file."#;
+
+/// The template of `addlicense`'s `mit` license type
+const MIT_TEMPLATE: &str = r#"Copyright (c) {{.Year}} {{.Holder}}
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of
+this software and associated documentation files (the "Software"), to deal in
+the Software without restriction, including without limitation the rights to
+use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+the Software, and to permit persons to whom the Software is furnished to do so,
+subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE."#;
+
+/// The template of `addlicense`'s `mpl` license type, whose copyright line is added separately
+const MPL_TEMPLATE: &str = r#"This Source Code Form is subject to the terms of the Mozilla Public
+License, v. 2.0. If a copy of the MPL was not distributed with this
+file, You can obtain one at https://mozilla.org/MPL/2.0/."#;
+
+/// How the SPDX identifier is included in the header, as per `addlicense`'s `-s` flag
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SpdxMode {
+    /// No identifier line, the default
+    #[default]
+    Off,
+    /// An identifier line after the license text, with `-s`
+    On,
+    /// Only a copyright line and an identifier line, with `-s=only`
+    Only,
+}
+
+/// The configuration of an `addlicense` invocation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AddlicenseConfig {
+    /// The copyright holder, from `-c`, which defaults to `Google LLC`
+    pub holder: String,
+    /// The copyright year, from `-y`, which defaults to the current year
+    pub year: String,
+    /// The license type, from `-l`, which defaults to `apache`
+    pub license: String,
+    /// The custom template file, from `-f`, which replaces the license type's template
+    pub template_file: Option<path::PathBuf>,
+    /// Patterns of paths to skip, from `-ignore`
+    pub ignore: Vec<String>,
+    /// Whether to include an SPDX identifier, from `-s`
+    pub spdx: SpdxMode,
+    /// Whether to only check for headers rather than adding them, from `-check`
+    pub check: bool,
+    /// The files and directories to operate on
+    pub paths: Vec<path::PathBuf>,
+}
+
+impl Default for AddlicenseConfig {
+    fn default() -> Self {
+        Self {
+            holder: "Google LLC".to_string(),
+            year: current_year().to_string(),
+            license: "apache".to_string(),
+            template_file: None,
+            ignore: Vec::new(),
+            spdx: SpdxMode::Off,
+            check: false,
+            paths: Vec::new(),
+        }
+    }
+}
+
+impl AddlicenseConfig {
+    /// Parse `addlicense` command line arguments, without the program name.
+    ///
+    /// As with Go's `flag` package, flags may start with `-` or `--`, values may follow as the
+    /// next argument or after `=`, and flags end at the first argument that isn't one, or after
+    /// `--`.
+    pub fn from_args(
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<Self, AddlicenseError> {
+        let mut config = Self::default();
+        let mut args = args.into_iter().map(|a| a.as_ref().to_string());
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                break;
+            }
+            let flag = match arg.strip_prefix("--").or_else(|| arg.strip_prefix('-')) {
+                Some(flag) if !flag.is_empty() => flag,
+                _ => {
+                    config.paths.push(arg.into());
+                    break;
+                }
+            };
+            let (name, inline_value) = match flag.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (flag, None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| AddlicenseError::MissingValue(name.to_string()))
+            };
+            match name {
+                "c" => config.holder = value()?,
+                "y" => config.year = value()?,
+                "l" => config.license = value()?,
+                "f" => config.template_file = Some(value()?.into()),
+                "ignore" => config.ignore.push(value()?),
+                "s" => {
+                    config.spdx = match inline_value.as_deref() {
+                        None | Some("true") => SpdxMode::On,
+                        Some("only") => SpdxMode::Only,
+                        Some("false") => SpdxMode::Off,
+                        Some(other) => {
+                            return Err(AddlicenseError::InvalidValue(
+                                name.to_string(),
+                                other.to_string(),
+                            ))
+                        }
+                    }
+                }
+                "check" => config.check = parse_bool(name, inline_value.as_deref())?,
+                "v" => {
+                    parse_bool(name, inline_value.as_deref())?;
+                }
+                _ => return Err(AddlicenseError::UnknownFlag(name.to_string())),
+            }
+        }
+        config.paths.extend(args.map(path::PathBuf::from));
+        Ok(config)
+    }
+
+    /// The SPDX identifier of the license type, or of an unrecognized license type with
+    /// [`SpdxMode::Only`], where `addlicense` treats it as an identifier.
+    pub fn spdx_id(&self) -> Option<String> {
+        match self.license.as_str() {
+            "apache" => Some("Apache-2.0".to_string()),
+            "bsd" => Some("BSD-3-Clause".to_string()),
+            "mit" => Some("MIT".to_string()),
+            "mpl" => Some("MPL-2.0".to_string()),
+            other if self.spdx == SpdxMode::Only => Some(other.to_string()),
+            _ => None,
+        }
+    }
+
+    /// The header text `addlicense` would add, without comment delimiters.
+    ///
+    /// Templates are Go templates, of which only the `{{.Year}}`, `{{.Holder}}`, and `{{.SPDXID}}`
+    /// actions are supported.
+    pub fn header_text(&self) -> Result<String, AddlicenseError> {
+        let template = match (&self.template_file, self.spdx, self.license.as_str()) {
+            (Some(p), _, _) => fs::read_to_string(p)
+                .map_err(|e| AddlicenseError::IoError(p.clone(), e))?
+                .trim_end()
+                .to_string(),
+            (None, SpdxMode::Only, _) => "Copyright {{.Year}} {{.Holder}}".to_string(),
+            (None, _, "apache") => APACHE_TEMPLATE.to_string(),
+            (None, _, "bsd") => BSD_TEMPLATE.to_string(),
+            (None, _, "mit") => MIT_TEMPLATE.to_string(),
+            (None, _, "mpl") => format!(
+                "Copyright {{{{.Year}}}} {{{{.Holder}}}}\n\n{}",
+                MPL_TEMPLATE
+            ),
+            (None, _, other) => return Err(AddlicenseError::UnknownLicense(other.to_string())),
+        };
+        let template = match self.spdx {
+            SpdxMode::Off => template,
+            SpdxMode::On => format!("{}\n\nSPDX-License-Identifier: {{{{.SPDXID}}}}", template),
+            SpdxMode::Only => format!("{}\nSPDX-License-Identifier: {{{{.SPDXID}}}}", template),
+        };
+        self.render(&template)
+    }
+
+    /// A header with the text from [`AddlicenseConfig::header_text`], which is considered present
+    /// as per [`AddlicenseChecker`].
+    pub fn header(&self) -> Result<Header<AddlicenseChecker>, AddlicenseError> {
+        Ok(Header::new(AddlicenseChecker, self.header_text()?))
+    }
+
+    /// Returns `true` if `p` matches one of the `-ignore` patterns.
+    ///
+    /// Patterns are matched against the whole path with `/` separators, where `*` matches
+    /// anything but a separator, `?` matches one character but a separator, and `**` matches any
+    /// number of directories.
+    pub fn is_ignored(&self, p: &path::Path) -> bool {
+        let p = p.to_string_lossy().replace('\\', "/");
+        let p = p.strip_prefix("./").unwrap_or(&p);
+        self.ignore
+            .iter()
+            .any(|pattern| glob_match(pattern.as_bytes(), p.as_bytes()))
+    }
+
+    /// Replace the actions in `template`
+    fn render(&self, template: &str) -> Result<String, AddlicenseError> {
+        let mut out = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            out.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find("}}")
+                .ok_or_else(|| AddlicenseError::InvalidTemplate(rest[start..].to_string()))?;
+            let action = &rest[start + 2..start + end];
+            match action.trim() {
+                ".Year" => out.push_str(&self.year),
+                ".Holder" => out.push_str(&self.holder),
+                ".SPDXID" => out.push_str(
+                    &self
+                        .spdx_id()
+                        .ok_or_else(|| AddlicenseError::UnknownLicense(self.license.clone()))?,
+                ),
+                _ => return Err(AddlicenseError::InvalidTemplate(action.to_string())),
+            }
+            rest = &rest[start + end + 2..];
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+}
+
+/// Checks for a license as `addlicense` does: a file has one if its first 1000 bytes mention
+/// "copyright", "Mozilla Public", or "SPDX-License-Identifier", ignoring case.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AddlicenseChecker;
+
+impl HeaderChecker for AddlicenseChecker {
+    fn check(&self, file: &mut impl io::Read) -> io::Result<bool> {
+        let mut prefix = Vec::with_capacity(CHECK_BYTES);
+        io::Read::read_to_end(&mut io::Read::take(file, CHECK_BYTES as u64), &mut prefix)?;
+        let prefix = String::from_utf8_lossy(&prefix).to_lowercase();
+        Ok(["copyright", "mozilla public", "spdx-license-identifier"]
+            .iter()
+            .any(|s| prefix.contains(s)))
+    }
+}
+
+/// Errors that can occur when loading an `addlicense` configuration
+#[derive(Debug, thiserror::Error)]
+pub enum AddlicenseError {
+    /// The flag isn't one of `addlicense`'s
+    #[error("Unknown flag: -{0}")]
+    UnknownFlag(String),
+    /// The flag needs a value
+    #[error("Flag needs a value: -{0}")]
+    MissingValue(String),
+    /// The flag's value is invalid
+    #[error("Invalid value for -{0}: {1}")]
+    InvalidValue(String, String),
+    /// The license type isn't one `addlicense` has a template for
+    #[error("Unknown license type: {0}")]
+    UnknownLicense(String),
+    /// The template has an action other than the supported substitutions
+    #[error("Unsupported template action: {0}")]
+    InvalidTemplate(String),
+    /// The template file couldn't be read
+    #[error("I/O error at {0:?}: {1}")]
+    IoError(path::PathBuf, io::Error),
+}
+
+/// Parse the value of a boolean flag, which is `true` if it's given without a value.
+fn parse_bool(name: &str, value: Option<&str>) -> Result<bool, AddlicenseError> {
+    match value {
+        None | Some("true") => Ok(true),
+        Some("false") => Ok(false),
+        Some(other) => Err(AddlicenseError::InvalidValue(
+            name.to_string(),
+            other.to_string(),
+        )),
+    }
+}
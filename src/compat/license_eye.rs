@@ -0,0 +1,786 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reading and writing the `.licenserc.yaml` configuration of Apache SkyWalking Eyes'
+//! [`license-eye`](https://github.com/apache/skywalking-eyes).
+//!
+//! The `header` section's license, `paths`, `paths-ignore`, and `language` comment styles are
+//! mapped onto this crate's [`Header`], path filters, and [`HeaderDelimiters`]. Other sections,
+//! like `dependency`, are ignored, and only the single `header` form (not a list of headers) is
+//! supported.
+//!
+//! Only the subset of YAML these files use is understood: nested mappings, block and flow
+//! sequences, quoted and plain scalars, block scalars, and comments.
+//!
+//! # Examples
+//!
+//! ```
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use file_header::compat::license_eye::*;
+//! use std::path::Path;
+//!
+//! let config = LicenseEyeConfig::parse(
+//!     r#"
+//! header:
+//!   license:
+//!     spdx-id: Apache-2.0
+//!     copyright-owner: Foo Inc.
+//!     content: |
+//!       Copyright [year] [owner]
+//!       Licensed under the Foo License
+//!   paths-ignore:
+//!     - 'dist'
+//!     - '**/*.md'
+//!   language:
+//!     Go:
+//!       extensions: [".go"]
+//!       comment_style_id: DoubleSlash
+//! "#,
+//! )
+//! .unwrap();
+//! assert!(config.is_ignored(Path::new("dist/index.js")));
+//! assert!(!config.is_ignored(Path::new("src/main.go")));
+//!
+//! let header = config.header().unwrap();
+//! assert!(header
+//!     .check_str("// Copyright 2019 Foo Inc.\n// Licensed under the Foo License\n")
+//!     .unwrap());
+//!
+//! // write it back out, e.g. after changing the owner
+//! let yaml = config.to_yaml();
+//! assert_eq!(config, LicenseEyeConfig::parse(&yaml).unwrap());
+//! ```
+
+use crate::{
+    compat::{current_year, glob_match, slash_path},
+    Header, HeaderChecker, HeaderDelimiters,
+};
+use std::{collections, fs, io, path};
+
+/// The number of lines at the start of a file that are checked for the header
+const CHECK_LINES: usize = 40;
+
+/// The placeholder for the copyright year in `license-eye` license content
+const YEAR_PLACEHOLDER: &str = "[year]";
+
+/// The configuration in a `license-eye` `.licenserc.yaml` file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LicenseEyeConfig {
+    /// The SPDX identifier of the license, from `header.license.spdx-id`
+    pub spdx_id: Option<String>,
+    /// The copyright owner, from `header.license.copyright-owner`
+    pub copyright_owner: Option<String>,
+    /// The copyright year, from `header.license.copyright-year`, which defaults to the current
+    /// year
+    pub copyright_year: Option<String>,
+    /// The name of the software, from `header.license.software-name`
+    pub software_name: Option<String>,
+    /// The header text, from `header.license.content`, with `[year]`, `[owner]`, and
+    /// `[software-name]` placeholders
+    pub content: Option<String>,
+    /// The regular expression `license-eye` checks for instead of the content, from
+    /// `header.license.pattern`, which is kept for writing but not used for checking
+    pub pattern: Option<String>,
+    /// Patterns of the paths to check, from `header.paths`, which default to every path
+    pub paths: Vec<String>,
+    /// Patterns of the paths to skip, from `header.paths-ignore`
+    pub paths_ignore: Vec<String>,
+    /// When `license-eye` comments on pull requests, from `header.comment`, which is kept for
+    /// writing but otherwise unused
+    pub comment: Option<String>,
+    /// Comment styles for particular languages, from `header.language`, by language name
+    pub languages: collections::BTreeMap<String, LicenseEyeLanguage>,
+}
+
+/// The comment style of a language, from an entry of `header.language` in a `.licenserc.yaml`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LicenseEyeLanguage {
+    /// Extensions of the language's files, including the leading `.`
+    pub extensions: Vec<String>,
+    /// Names of the language's files without a distinguishing extension, e.g. `Dockerfile`
+    pub filenames: Vec<String>,
+    /// The `license-eye` comment style, e.g. `SlashAsterisk`, as per
+    /// [`LicenseEyeConfig::delimiters_for_path`]
+    pub comment_style_id: String,
+}
+
+impl LicenseEyeConfig {
+    /// Parse the contents of a `.licenserc.yaml` file.
+    pub fn parse(yaml: &str) -> Result<Self, LicenseEyeError> {
+        let doc = Parser::new(yaml).parse_document()?;
+        let mut config = Self::default();
+        let header = match doc.get("header") {
+            None => return Ok(config),
+            Some(Yaml::List(_)) => return Err(LicenseEyeError::MultipleHeaders),
+            Some(header) => header,
+        };
+        match header.get("license") {
+            // older configurations have only the content
+            Some(Yaml::Scalar(content)) => config.content = Some(content.clone()),
+            Some(license) => {
+                config.spdx_id = license.get_scalar("spdx-id")?;
+                config.copyright_owner = license.get_scalar("copyright-owner")?;
+                config.copyright_year = license.get_scalar("copyright-year")?;
+                config.software_name = license.get_scalar("software-name")?;
+                config.content = license.get_scalar("content")?;
+                config.pattern = license.get_scalar("pattern")?;
+            }
+            None => {}
+        }
+        config.paths = header.get_list("paths")?;
+        config.paths_ignore = header.get_list("paths-ignore")?;
+        config.comment = header.get_scalar("comment")?;
+        if let Some(Yaml::Map(languages)) = header.get("language") {
+            for (name, language) in languages {
+                let language = LicenseEyeLanguage {
+                    extensions: language.get_list("extensions")?,
+                    filenames: language.get_list("filenames")?,
+                    comment_style_id: language.get_scalar("comment_style_id")?.unwrap_or_default(),
+                };
+                if comment_style_delimiters(&language.comment_style_id).is_none() {
+                    return Err(LicenseEyeError::UnknownCommentStyle(
+                        language.comment_style_id,
+                    ));
+                }
+                config.languages.insert(name.clone(), language);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Load the `.licenserc.yaml` file at `p`.
+    pub fn load(p: &path::Path) -> Result<Self, LicenseEyeError> {
+        let yaml =
+            fs::read_to_string(p).map_err(|e| LicenseEyeError::IoError(p.to_path_buf(), e))?;
+        Self::parse(&yaml)
+    }
+
+    /// Render the configuration as a `.licenserc.yaml` file, which [`LicenseEyeConfig::parse`]
+    /// reads back as the same configuration.
+    pub fn to_yaml(&self) -> String {
+        let mut out = String::from("header:\n");
+        let license = [
+            ("spdx-id", &self.spdx_id),
+            ("copyright-owner", &self.copyright_owner),
+            ("copyright-year", &self.copyright_year),
+            ("software-name", &self.software_name),
+            ("content", &self.content),
+            ("pattern", &self.pattern),
+        ];
+        if license.iter().any(|(_, value)| value.is_some()) {
+            out.push_str("  license:\n");
+            for (key, value) in license {
+                if let Some(value) = value {
+                    write_scalar(&mut out, 4, key, value);
+                }
+            }
+        }
+        write_list(&mut out, 2, "paths", &self.paths);
+        write_list(&mut out, 2, "paths-ignore", &self.paths_ignore);
+        if let Some(comment) = &self.comment {
+            write_scalar(&mut out, 2, "comment", comment);
+        }
+        if !self.languages.is_empty() {
+            out.push_str("  language:\n");
+            for (name, language) in &self.languages {
+                out.push_str(&format!("    {}:\n", quote(name)));
+                write_list(&mut out, 6, "extensions", &language.extensions);
+                write_list(&mut out, 6, "filenames", &language.filenames);
+                write_scalar(&mut out, 6, "comment_style_id", &language.comment_style_id);
+            }
+        }
+        out
+    }
+
+    /// Write the configuration to a `.licenserc.yaml` file at `p`, as per
+    /// [`LicenseEyeConfig::to_yaml`].
+    pub fn save(&self, p: &path::Path) -> Result<(), LicenseEyeError> {
+        fs::write(p, self.to_yaml()).map_err(|e| LicenseEyeError::IoError(p.to_path_buf(), e))
+    }
+
+    /// The header text, without comment delimiters.
+    ///
+    /// This is the content with its placeholders replaced, or a copyright line and an
+    /// `SPDX-License-Identifier` line if there's no content but there is an SPDX identifier.
+    pub fn header_text(&self) -> Result<String, LicenseEyeError> {
+        let year = match &self.copyright_year {
+            Some(year) => year.clone(),
+            None => current_year().to_string(),
+        };
+        Ok(self.template()?.replace(YEAR_PLACEHOLDER, &year))
+    }
+
+    /// A header with the text from [`LicenseEyeConfig::header_text`], which is considered present
+    /// as per [`LicenseEyeChecker`].
+    pub fn header(&self) -> Result<Header<LicenseEyeChecker>, LicenseEyeError> {
+        let template = self.template()?;
+        let checker = LicenseEyeChecker {
+            parts: normalize(&template)
+                .split(&normalize(YEAR_PLACEHOLDER))
+                .map(|part| part.trim().to_string())
+                .collect(),
+        };
+        Ok(Header::new(checker, self.header_text()?))
+    }
+
+    /// Returns the delimiters for the file at `p`, from the language with its extension or
+    /// filename, or else [`HeaderDelimiters::for_path`].
+    ///
+    /// The supported comment styles are `DoubleSlash`, `SlashAsterisk`, `Hashtag`, `AngleBracket`,
+    /// `DoubleDash`, `PercentSign`, `CurlyBracketDash`, `SemiColon`, and `Quotes`.
+    pub fn delimiters_for_path(&self, p: &path::Path) -> Option<HeaderDelimiters> {
+        let file_name = p.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        self.languages
+            .values()
+            .find(|language| {
+                language.filenames.iter().any(|n| n == file_name)
+                    || language
+                        .extensions
+                        .iter()
+                        .any(|ext| !ext.is_empty() && file_name.ends_with(ext.as_str()))
+            })
+            .and_then(|language| comment_style_delimiters(&language.comment_style_id))
+            .or_else(|| HeaderDelimiters::for_path(p))
+    }
+
+    /// Returns `true` if `p`, relative to the directory of the configuration, should be checked:
+    /// it matches one of the `paths` patterns, if there are any, and isn't ignored as per
+    /// [`LicenseEyeConfig::is_ignored`].
+    pub fn is_included(&self, p: &path::Path) -> bool {
+        let p = slash_path(p);
+        (self.paths.is_empty()
+            || self
+                .paths
+                .iter()
+                .any(|pattern| path_or_ancestor_matches(pattern, &p)))
+            && !self
+                .paths_ignore
+                .iter()
+                .any(|pattern| path_or_ancestor_matches(pattern, &p))
+    }
+
+    /// Returns `true` if `p`, relative to the directory of the configuration, or one of its
+    /// ancestor directories, matches one of the `paths-ignore` patterns.
+    pub fn is_ignored(&self, p: &path::Path) -> bool {
+        let p = slash_path(p);
+        self.paths_ignore
+            .iter()
+            .any(|pattern| path_or_ancestor_matches(pattern, &p))
+    }
+
+    /// Set `options` to only operate on the files under `root` that are included as per
+    /// [`LicenseEyeConfig::is_included`], and not to descend into ignored directories.
+    #[cfg(feature = "recursive")]
+    pub fn recursive_options(
+        &self,
+        root: &path::Path,
+        options: crate::RecursiveOptions,
+    ) -> crate::RecursiveOptions {
+        let files = std::sync::Arc::new((root.to_path_buf(), self.clone()));
+        let dirs = files.clone();
+        options
+            .file_filter(std::sync::Arc::new(move |p, _metadata| {
+                let (root, config) = &*files;
+                config.is_included(p.strip_prefix(root).unwrap_or(p))
+            }))
+            .dir_filter(std::sync::Arc::new(move |p| {
+                let (root, config) = &*dirs;
+                !config.is_ignored(p.strip_prefix(root).unwrap_or(p))
+            }))
+    }
+
+    /// The header text with placeholders other than the year replaced
+    fn template(&self) -> Result<String, LicenseEyeError> {
+        let owner = self.copyright_owner.as_deref().unwrap_or("");
+        let text = match (&self.content, &self.spdx_id) {
+            (Some(content), _) => content.trim_end().to_string(),
+            (None, Some(spdx_id)) => format!(
+                "Copyright {} {}\nSPDX-License-Identifier: {}",
+                YEAR_PLACEHOLDER, owner, spdx_id
+            ),
+            (None, None) => return Err(LicenseEyeError::MissingLicense),
+        };
+        Ok(text.replace("[owner]", owner).replace(
+            "[software-name]",
+            self.software_name.as_deref().unwrap_or(""),
+        ))
+    }
+}
+
+/// Checks for a header as `license-eye` does: a file has one if its first lines contain the header
+/// text, ignoring comment delimiters, whitespace, and case, with any year in place of `[year]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LicenseEyeChecker {
+    /// The normalized header text, split around the year placeholders
+    parts: Vec<String>,
+}
+
+impl HeaderChecker for LicenseEyeChecker {
+    fn check(&self, file: &mut impl io::Read) -> io::Result<bool> {
+        let mut head = String::new();
+        for line in io::BufRead::lines(io::BufReader::new(file)).take(CHECK_LINES) {
+            head.push_str(&line?);
+            head.push('\n');
+        }
+        let head = normalize(&head);
+        Ok((0..head.len())
+            .filter(|&i| head.is_char_boundary(i))
+            .any(|i| matches_parts(&self.parts, &head[i..])))
+    }
+}
+
+/// Errors that can occur when reading or writing a `license-eye` configuration
+#[derive(Debug, thiserror::Error)]
+pub enum LicenseEyeError {
+    /// The YAML at the line number (starting from 1) isn't supported
+    #[error("Unsupported YAML at line {0}: {1}")]
+    Syntax(usize, String),
+    /// The value of the key has the wrong type, e.g. a list rather than a string
+    #[error("Invalid value for {0}")]
+    InvalidValue(String),
+    /// The configuration has a list of headers rather than a single one
+    #[error("Multiple header sections are not supported")]
+    MultipleHeaders,
+    /// A language has a comment style that isn't supported
+    #[error("Unknown comment style: {0}")]
+    UnknownCommentStyle(String),
+    /// The configuration has neither license content nor an SPDX identifier
+    #[error("No license content or SPDX identifier")]
+    MissingLicense,
+    /// The configuration file couldn't be read or written
+    #[error("I/O error at {0:?}: {1}")]
+    IoError(path::PathBuf, io::Error),
+}
+
+/// Returns the delimiters of a `license-eye` comment style.
+fn comment_style_delimiters(id: &str) -> Option<HeaderDelimiters> {
+    let (first_line, content_line_prefix, last_line) = match id {
+        "DoubleSlash" => ("", "// ", ""),
+        "SlashAsterisk" => ("/*", " * ", " */"),
+        "Hashtag" => ("", "# ", ""),
+        "AngleBracket" => ("<!--", "  ~ ", "-->"),
+        "DoubleDash" => ("", "-- ", ""),
+        "PercentSign" => ("", "% ", ""),
+        "CurlyBracketDash" => ("{-", "  ", "-}"),
+        "SemiColon" => ("", "; ", ""),
+        "Quotes" => ("", "' ", ""),
+        _ => return None,
+    };
+    Some(HeaderDelimiters::new(
+        first_line,
+        content_line_prefix,
+        last_line,
+    ))
+}
+
+/// Lowercase `text`, and remove comment delimiters and repeated whitespace from each line.
+fn normalize(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            line.trim_start_matches(|c: char| c.is_whitespace() || "/*#-;%!<{~'\"".contains(c))
+                .trim_end_matches(|c: char| c.is_whitespace() || "*/->}".contains(c))
+        })
+        .flat_map(|line| line.split_whitespace())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Returns `true` if `text` starts with `parts`, separated by years or ranges and lists of years.
+fn matches_parts(parts: &[String], text: &str) -> bool {
+    match parts {
+        [] => true,
+        [part, rest @ ..] => match text.strip_prefix(part.as_str()) {
+            None => false,
+            Some(_) if rest.is_empty() => true,
+            Some(after) => {
+                let after = after.trim_start();
+                let year_len = after
+                    .find(|c: char| !(c.is_ascii_digit() || " ,-".contains(c)))
+                    .unwrap_or(after.len());
+                (1..=year_len)
+                    .rev()
+                    .any(|len| matches_parts(rest, after[len..].trim_start()))
+            }
+        },
+    }
+}
+
+/// Returns `true` if `pattern` matches `p` or one of its ancestors.
+fn path_or_ancestor_matches(pattern: &str, p: &str) -> bool {
+    let pattern = pattern.trim_end_matches('/');
+    glob_match(pattern.as_bytes(), p.as_bytes())
+        || p.match_indices('/')
+            .any(|(i, _)| glob_match(pattern.as_bytes(), &p.as_bytes()[..i]))
+}
+
+/// Append `key: value` at `indent`, as a block scalar if `value` has multiple lines.
+fn write_scalar(out: &mut String, indent: usize, key: &str, value: &str) {
+    let pad = " ".repeat(indent);
+    if value.contains('\n') {
+        let chomping = if value.ends_with('\n') { "" } else { "-" };
+        out.push_str(&format!("{}{}: |{}\n", pad, key, chomping));
+        for line in value.trim_end_matches('\n').lines() {
+            if line.is_empty() {
+                out.push('\n');
+            } else {
+                out.push_str(&format!("{}  {}\n", pad, line));
+            }
+        }
+    } else {
+        out.push_str(&format!("{}{}: {}\n", pad, key, quote(value)));
+    }
+}
+
+/// Append `key:` and a block sequence of `values` at `indent`, unless there are no values.
+fn write_list(out: &mut String, indent: usize, key: &str, values: &[String]) {
+    if values.is_empty() {
+        return;
+    }
+    let pad = " ".repeat(indent);
+    out.push_str(&format!("{}{}:\n", pad, key));
+    for value in values {
+        out.push_str(&format!("{}  - {}\n", pad, quote(value)));
+    }
+}
+
+/// Single-quote `value`, so that it's never mistaken for anything but a string.
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// A parsed YAML node
+#[derive(Debug)]
+enum Yaml {
+    Scalar(String),
+    List(Vec<Yaml>),
+    Map(Vec<(String, Yaml)>),
+}
+
+impl Yaml {
+    /// The value of `key`, if this is a map that has it
+    fn get(&self, key: &str) -> Option<&Yaml> {
+        match self {
+            Yaml::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// The value of `key` as a string, if this is a map that has it
+    fn get_scalar(&self, key: &str) -> Result<Option<String>, LicenseEyeError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Yaml::Scalar(s)) => Ok(Some(s.clone())),
+            Some(_) => Err(LicenseEyeError::InvalidValue(key.to_string())),
+        }
+    }
+
+    /// The value of `key` as a list of strings, or a single string, if this is a map that has it
+    fn get_list(&self, key: &str) -> Result<Vec<String>, LicenseEyeError> {
+        match self.get(key) {
+            None => Ok(Vec::new()),
+            Some(Yaml::Scalar(s)) => Ok(vec![s.clone()]),
+            Some(Yaml::List(items)) => items
+                .iter()
+                .map(|item| match item {
+                    Yaml::Scalar(s) => Ok(s.clone()),
+                    _ => Err(LicenseEyeError::InvalidValue(key.to_string())),
+                })
+                .collect(),
+            Some(Yaml::Map(_)) => Err(LicenseEyeError::InvalidValue(key.to_string())),
+        }
+    }
+}
+
+/// A parser for the block-structured subset of YAML used by `.licenserc.yaml` files
+struct Parser<'a> {
+    lines: Vec<&'a str>,
+    /// The index of the next line to parse
+    next: usize,
+    /// Replacement text and indentation for the next line, for the contents of a sequence item
+    /// that continue on the item's line
+    pending: Option<(usize, &'a str)>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(yaml: &'a str) -> Self {
+        Self {
+            lines: yaml.lines().collect(),
+            next: 0,
+            pending: None,
+        }
+    }
+
+    fn parse_document(&mut self) -> Result<Yaml, LicenseEyeError> {
+        let doc = match self.peek() {
+            Some((indent, _)) => self.parse_block(indent)?,
+            None => Yaml::Map(Vec::new()),
+        };
+        match self.peek() {
+            Some((_, text)) => Err(self.error(text)),
+            None => Ok(doc),
+        }
+    }
+
+    /// The indentation and text of the next line with content, skipping blank lines and comments
+    fn peek(&mut self) -> Option<(usize, &'a str)> {
+        if self.pending.is_some() {
+            return self.pending;
+        }
+        while let Some(line) = self.lines.get(self.next) {
+            let text = line.trim_start();
+            if text.is_empty() || text.starts_with('#') || text == "---" {
+                self.next += 1;
+            } else {
+                return Some((line.len() - text.len(), text));
+            }
+        }
+        None
+    }
+
+    /// Consume the line returned by `peek`
+    fn advance(&mut self) {
+        if self.pending.take().is_none() {
+            self.next += 1;
+        }
+    }
+
+    fn error(&self, text: &str) -> LicenseEyeError {
+        LicenseEyeError::Syntax(self.next + 1, text.to_string())
+    }
+
+    /// Parse the sequence or mapping whose lines are at `indent`
+    fn parse_block(&mut self, indent: usize) -> Result<Yaml, LicenseEyeError> {
+        match self.peek() {
+            Some((_, text)) if text == "-" || text.starts_with("- ") => self.parse_sequence(indent),
+            _ => self.parse_mapping(indent),
+        }
+    }
+
+    fn parse_sequence(&mut self, indent: usize) -> Result<Yaml, LicenseEyeError> {
+        let mut items = Vec::new();
+        while let Some((line_indent, text)) = self.peek() {
+            if line_indent != indent || !(text == "-" || text.starts_with("- ")) {
+                break;
+            }
+            let rest = text[1..].trim_start();
+            if rest.is_empty() {
+                self.advance();
+                items.push(self.parse_nested(indent, true)?);
+            } else if rest.starts_with(['\'', '"', '[']) || split_key(rest).is_none() {
+                self.advance();
+                items.push(self.parse_inline(rest)?);
+            } else {
+                // a mapping that starts on the item's line
+                let rest_indent = line_indent + (text.len() - rest.len());
+                self.advance();
+                self.pending = Some((rest_indent, rest));
+                items.push(self.parse_mapping(rest_indent)?);
+            }
+        }
+        Ok(Yaml::List(items))
+    }
+
+    fn parse_mapping(&mut self, indent: usize) -> Result<Yaml, LicenseEyeError> {
+        let mut entries = Vec::new();
+        while let Some((line_indent, text)) = self.peek() {
+            if line_indent < indent {
+                break;
+            }
+            if line_indent > indent {
+                return Err(self.error(text));
+            }
+            let (key, rest) = split_key(text).ok_or_else(|| self.error(text))?;
+            let key = unquote(key).ok_or_else(|| self.error(text))?;
+            let rest = strip_comment(rest).trim();
+            self.advance();
+            let value = if rest.is_empty() {
+                self.parse_nested(indent, false)?
+            } else if let Some(header) = rest.strip_prefix(['|', '>']) {
+                self.parse_block_scalar(indent, rest.starts_with('>'), header)
+            } else {
+                self.parse_inline(rest)?
+            };
+            entries.push((key, value));
+        }
+        Ok(Yaml::Map(entries))
+    }
+
+    /// Parse the value of a key or sequence item at `indent` whose value is on the following lines
+    fn parse_nested(&mut self, indent: usize, in_sequence: bool) -> Result<Yaml, LicenseEyeError> {
+        match self.peek() {
+            Some((next_indent, _)) if next_indent > indent => self.parse_block(next_indent),
+            // sequences may be at the same indentation as their key
+            Some((next_indent, text))
+                if !in_sequence
+                    && next_indent == indent
+                    && (text == "-" || text.starts_with("- ")) =>
+            {
+                self.parse_sequence(indent)
+            }
+            _ => Ok(Yaml::Scalar(String::new())),
+        }
+    }
+
+    /// Parse a `|` or `>` block scalar, whose lines are indented more than `indent`
+    fn parse_block_scalar(&mut self, indent: usize, folded: bool, header: &str) -> Yaml {
+        let mut lines = Vec::new();
+        let mut block_indent = None;
+        while let Some(line) = self.lines.get(self.next) {
+            let text = line.trim_start();
+            let line_indent = line.len() - text.len();
+            if text.is_empty() {
+                lines.push("");
+            } else if line_indent > indent && block_indent.map_or(true, |i| line_indent >= i) {
+                let block_indent = *block_indent.get_or_insert(line_indent);
+                lines.push(&line[block_indent..]);
+            } else {
+                break;
+            }
+            self.next += 1;
+        }
+        let content_len = lines
+            .iter()
+            .rposition(|l| !l.is_empty())
+            .map_or(0, |i| i + 1);
+        let trailing_blank_lines = lines.len() - content_len;
+        lines.truncate(content_len);
+        let mut value = if folded {
+            let mut value = String::new();
+            for (i, line) in lines.iter().enumerate() {
+                // blank lines are line breaks, and other line breaks are spaces
+                if line.is_empty() {
+                    value.push('\n');
+                } else if i > 0 && !lines[i - 1].is_empty() {
+                    value.push(' ');
+                }
+                value.push_str(line);
+            }
+            value
+        } else {
+            lines.join("\n")
+        };
+        let header = strip_comment(header).trim();
+        if !value.is_empty() && !header.contains('-') {
+            value.push('\n');
+            if header.contains('+') {
+                value.push_str(&"\n".repeat(trailing_blank_lines));
+            }
+        }
+        Yaml::Scalar(value)
+    }
+
+    /// Parse a scalar or flow sequence on a single line
+    fn parse_inline(&self, text: &str) -> Result<Yaml, LicenseEyeError> {
+        if let Some(flow) = text.strip_prefix('[') {
+            let flow = strip_comment(flow).trim_end();
+            let inner = flow
+                .strip_suffix(']')
+                .ok_or_else(|| self.error(text))?
+                .trim();
+            if inner.is_empty() {
+                return Ok(Yaml::List(Vec::new()));
+            }
+            return split_flow(inner)
+                .into_iter()
+                .map(|item| {
+                    unquote(item.trim())
+                        .map(Yaml::Scalar)
+                        .ok_or_else(|| self.error(text))
+                })
+                .collect::<Result<_, _>>()
+                .map(Yaml::List);
+        }
+        unquote(strip_comment(text).trim())
+            .map(Yaml::Scalar)
+            .ok_or_else(|| self.error(text))
+    }
+}
+
+/// Split `key: value` into the key and the rest of the line, if the line is a mapping entry.
+fn split_key(text: &str) -> Option<(&str, &str)> {
+    let mut quote = None;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '\'' | '"') if i == 0 => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, ':') if text[i + 1..].is_empty() || text[i + 1..].starts_with(' ') => {
+                return Some((&text[..i], &text[i + 1..]));
+            }
+            (None, '#') if i == 0 || text[..i].ends_with(' ') => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Remove a trailing ` # comment` outside of quotes.
+fn strip_comment(text: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '#') if i == 0 || text[..i].ends_with([' ', '\t']) => return &text[..i],
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Split the items of a flow sequence at commas outside of quotes.
+fn split_flow(text: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, ',') => {
+                items.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&text[start..]);
+    items
+}
+
+/// The value of a plain, single-quoted, or double-quoted scalar, or `None` if it's malformed.
+fn unquote(text: &str) -> Option<String> {
+    if let Some(inner) = text.strip_prefix('\'') {
+        Some(inner.strip_suffix('\'')?.replace("''", "'"))
+    } else if let Some(inner) = text.strip_prefix('"') {
+        let inner = inner.strip_suffix('"')?;
+        let mut value = String::new();
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                value.push(c);
+                continue;
+            }
+            value.push(match chars.next()? {
+                'n' => '\n',
+                't' => '\t',
+                other => other,
+            });
+        }
+        Some(value)
+    } else {
+        Some(text.to_string())
+    }
+}
@@ -0,0 +1,74 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compatibility with the configuration of other license header tools, so that projects migrating
+//! from them can keep their existing setup.
+//!
+//! See the [addlicense] module for Google's `addlicense`, and the [license_eye] module for Apache
+//! SkyWalking Eyes' `license-eye`.
+
+pub mod addlicense;
+pub mod license_eye;
+
+use std::{path, time};
+
+/// Returns `true` if `text` matches the glob `pattern`, where `*` matches anything but a `/`, `?`
+/// matches one character but a `/`, and `**` matches any number of directories, as in the
+/// `doublestar` Go package these tools use.
+pub(crate) fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        [b'*', b'*', b'/', rest @ ..] => {
+            // zero or more whole directories
+            glob_match(rest, text)
+                || text
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, c)| **c == b'/')
+                    .any(|(i, _)| glob_match(rest, &text[i + 1..]))
+        }
+        [b'*', b'*', rest @ ..] => (0..=text.len()).any(|i| glob_match(rest, &text[i..])),
+        [b'*', rest @ ..] => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != b'/')
+            .any(|i| glob_match(rest, &text[i..])),
+        [b'?', rest @ ..] => {
+            matches!(text.first(), Some(c) if *c != b'/') && glob_match(rest, &text[1..])
+        }
+        [c, rest @ ..] => text.first() == Some(c) && glob_match(rest, &text[1..]),
+    }
+}
+
+/// `p` with `/` separators and without a leading `./`, for matching against glob patterns
+pub(crate) fn slash_path(p: &path::Path) -> String {
+    let p = p.to_string_lossy().replace('\\', "/");
+    p.strip_prefix("./").unwrap_or(&p).to_string()
+}
+
+/// The current year in UTC
+pub(crate) fn current_year() -> i64 {
+    let days = time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() / 86_400) as i64;
+    // days since 0000-03-01, in 400-year eras, as per Howard Hinnant's `civil_from_days`
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    // years start in March, so January and February belong to the next year
+    let january_or_february = month_index >= 10;
+    year_of_era + era * 400 + i64::from(january_or_february)
+}
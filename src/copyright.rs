@@ -0,0 +1,375 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Updating the year list in an existing copyright line, for projects that record each year a
+//! file was contributed to.
+//!
+//! Only the year list is edited, e.g. `Copyright 2019, 2021 Foo Inc.` becomes
+//! `Copyright 2019, 2021, 2025 Foo Inc.`, and the rest of the file is left byte-for-byte intact.
+//! The first line containing `Copyright` followed by a year list within the first
+//! [`SEARCH_LINES`] lines is the one updated. Years already covered by the list, including by a
+//! range like `2019-2023`, are not added again.
+//!
+//! # Examples
+//!
+//! ```
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use file_header::copyright::*;
+//!
+//! assert_eq!(
+//!     Some("// Copyright 2019, 2021, 2025 Foo Inc.\n".to_string()),
+//!     append_year_to_str("// Copyright 2019, 2021 Foo Inc.\n", 2025)
+//! );
+//! assert_eq!(None, append_year_to_str("// Copyright 2019-2025 Foo Inc.\n", 2025));
+//! ```
+//!
+//! The years of the same copyright line can be validated with a [`YearPolicyChecker`], e.g. to
+//! require that headers include the current year:
+//!
+//! ```
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use file_header::copyright::*;
+//!
+//! let checker = YearPolicyChecker::includes_year(2025);
+//! assert_eq!(
+//!     YearPolicyStatus::Violation(vec![2019..=2019, 2021..=2021]),
+//!     checker
+//!         .evaluate(&mut "// Copyright 2019, 2021 Foo Inc.\n".as_bytes())
+//!         .unwrap()
+//! );
+//! assert_eq!(
+//!     YearPolicyStatus::Satisfied,
+//!     checker
+//!         .evaluate(&mut "// Copyright 2019-2025 Foo Inc.\n".as_bytes())
+//!         .unwrap()
+//! );
+//! ```
+
+use crate::{filesystem, lines, HeaderChecker};
+#[cfg(feature = "recursive")]
+use crate::{recursive_optional_operation, ModificationResults, RecursiveOptions};
+#[cfg(feature = "recursive")]
+use std::cell;
+use std::{
+    fmt,
+    io::{self, Read as _},
+    ops, path, sync,
+};
+
+/// The number of lines at the start of a file searched for a copyright line
+pub const SEARCH_LINES: usize = 10;
+
+/// Append `year` to the year list of the copyright line in `contents`.
+///
+/// Returns the updated contents, or `None` if there is no copyright line or it already covers
+/// `year`.
+pub fn append_year_to_str(contents: &str, year: u32) -> Option<String> {
+    let mut line_start = 0;
+    for line in contents.split_inclusive('\n').take(SEARCH_LINES) {
+        if let Some((years, end)) = find_year_list(line) {
+            if years.iter().any(|range| range.contains(&year)) {
+                return None;
+            }
+            let insert_at = line_start + end;
+            let mut output = String::with_capacity(contents.len() + 6);
+            output.push_str(&contents[..insert_at]);
+            output.push_str(&format!(", {}", year));
+            output.push_str(&contents[insert_at..]);
+            return Some(output);
+        }
+        line_start += line.len();
+    }
+    None
+}
+
+/// Append `year` to the year list of the copyright line in the file at `p`, as per
+/// [`append_year_to_str`].
+///
+/// Returns `true` if the file was modified.
+pub fn append_year(p: &path::Path, year: u32) -> io::Result<bool> {
+    append_year_with_fs(&filesystem::StdFileSystem, p, year)
+}
+
+/// Append `year` to the year list of the copyright line in the file at `p` in `fs`, as per
+/// [`append_year`].
+pub fn append_year_with_fs(
+    fs: &dyn filesystem::FileSystem,
+    p: &path::Path,
+    year: u32,
+) -> io::Result<bool> {
+    let mut contents = String::new();
+    fs.open(p)?.read_to_string(&mut contents)?;
+    match append_year_to_str(&contents, year) {
+        Some(updated) => {
+            fs.replace(p, updated.as_bytes(), &mut io::empty())?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Append `year` to the copyright line of every file in `root` that matches `path_predicate`, as
+/// per [`append_year`], with the behavior adjusted by `options`.
+///
+/// Files that aren't UTF-8 text are skipped, as they have no copyright line to update.
+#[cfg(feature = "recursive")]
+pub fn append_year_recursively(
+    root: &path::Path,
+    path_predicate: impl Fn(&path::Path) -> bool,
+    year: u32,
+    options: &RecursiveOptions,
+) -> Result<ModificationResults, AppendYearRecursivelyError> {
+    let (modification_fs, staging) = options.staged_modification_file_system();
+    let mut results =
+        recursive_optional_operation(
+            &[root],
+            path_predicate,
+            options,
+            |p| match append_year_with_fs(modification_fs.as_ref(), p, year) {
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => Ok(false),
+                r => r.map_err(|e| AppendYearRecursivelyError::IoError(p.to_path_buf(), e)),
+            },
+        )?;
+    if let Some(staging) = staging {
+        staging
+            .commit()
+            .map_err(|(p, e)| AppendYearRecursivelyError::IoError(p, e))?;
+    }
+    options.relativize(&[root], results.path_lists_mut());
+    Ok(results)
+}
+
+/// Errors that can occur when appending a year, or checking a year policy, recursively
+#[cfg(feature = "recursive")]
+#[derive(Debug, thiserror::Error)]
+pub enum AppendYearRecursivelyError {
+    /// An I/O error occurred while updating the path
+    #[error("I/O error at {0:?}: {1}")]
+    IoError(path::PathBuf, io::Error),
+    /// `walkdir` could not navigate the directory structure
+    #[error("Walkdir error: {0}")]
+    WalkdirError(#[from] walkdir::Error),
+}
+
+#[cfg(feature = "recursive")]
+impl From<filesystem::WalkError> for AppendYearRecursivelyError {
+    fn from(value: filesystem::WalkError) -> Self {
+        match value {
+            filesystem::WalkError::Walkdir(e) => Self::WalkdirError(e),
+            filesystem::WalkError::Io(p, e) => Self::IoError(p, e),
+        }
+    }
+}
+
+/// Checks that the year list of a file's copyright line, as found by [`append_year_to_str`],
+/// satisfies a policy, e.g. that it only has a creation year, or that it includes the current
+/// year.
+///
+/// As a [`HeaderChecker`], a file has the header if its years satisfy the policy. Use
+/// [`YearPolicyChecker::evaluate`] or [`check_year_policy_recursively`] to tell files without a
+/// copyright line apart from those whose years violate the policy.
+#[derive(Clone)]
+pub struct YearPolicyChecker {
+    policy: sync::Arc<YearPolicy>,
+}
+
+/// A policy for the year ranges of a copyright line, as used by [`YearPolicyChecker`]
+type YearPolicy = dyn Fn(&[ops::RangeInclusive<u32>]) -> bool + Send + Sync;
+
+impl YearPolicyChecker {
+    /// Construct a checker whose policy is satisfied by the year ranges for which `policy` returns
+    /// `true`, e.g. `|years| years.len() == 1` to only allow a single year or range.
+    pub fn new(
+        policy: impl Fn(&[ops::RangeInclusive<u32>]) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            policy: sync::Arc::new(policy),
+        }
+    }
+
+    /// Construct a checker whose policy is satisfied if `predicate` returns `true` for every year
+    /// in the list, e.g. `|year| year >= 2020`.
+    pub fn each_year(predicate: impl Fn(u32) -> bool + Send + Sync + 'static) -> Self {
+        Self::new(move |years| years.iter().all(|range| range.clone().all(&predicate)))
+    }
+
+    /// Construct a checker whose policy is satisfied if the list covers `year`, e.g. the current
+    /// year.
+    pub fn includes_year(year: u32) -> Self {
+        Self::new(move |years| years.iter().any(|range| range.contains(&year)))
+    }
+
+    /// Find the copyright line in `input`, and evaluate its years against the policy.
+    pub fn evaluate(&self, input: &mut impl io::Read) -> io::Result<YearPolicyStatus> {
+        let mut found = None;
+        lines::find_line(input, SEARCH_LINES, |line| {
+            found = find_year_list(line).map(|(years, _)| years);
+            found.is_some()
+        })?;
+        Ok(match found {
+            None => YearPolicyStatus::NoCopyright,
+            Some(years) if (self.policy)(&years) => YearPolicyStatus::Satisfied,
+            Some(years) => YearPolicyStatus::Violation(years),
+        })
+    }
+}
+
+impl fmt::Debug for YearPolicyChecker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("YearPolicyChecker").finish_non_exhaustive()
+    }
+}
+
+impl HeaderChecker for YearPolicyChecker {
+    fn check(&self, input: &mut impl io::Read) -> io::Result<bool> {
+        Ok(self.evaluate(input)? == YearPolicyStatus::Satisfied)
+    }
+}
+
+/// The result of evaluating a file with [`YearPolicyChecker::evaluate`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum YearPolicyStatus {
+    /// The copyright line's years satisfy the policy
+    Satisfied,
+    /// There's no copyright line with a year list
+    NoCopyright,
+    /// The copyright line's years, which are included, violate the policy
+    Violation(Vec<ops::RangeInclusive<u32>>),
+}
+
+/// Evaluate every file in `root` that matches `path_predicate` with `checker`, as per
+/// [`YearPolicyChecker::evaluate`], with the behavior adjusted by `options`.
+///
+/// Files that aren't UTF-8 text are skipped.
+#[cfg(feature = "recursive")]
+pub fn check_year_policy_recursively(
+    root: &path::Path,
+    path_predicate: impl Fn(&path::Path) -> bool,
+    checker: &YearPolicyChecker,
+    options: &RecursiveOptions,
+) -> Result<YearPolicyResults, AppendYearRecursivelyError> {
+    let no_copyright_files = cell::RefCell::new(Vec::new());
+    let violating_files = cell::RefCell::new(Vec::new());
+    let results = recursive_optional_operation::<AppendYearRecursivelyError>(
+        &[root],
+        path_predicate,
+        options,
+        |p| {
+            let status = match options
+                .file_system
+                .open(p)
+                .and_then(|mut f| checker.evaluate(&mut f))
+            {
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => return Ok(false),
+                r => r.map_err(|e| AppendYearRecursivelyError::IoError(p.to_path_buf(), e))?,
+            };
+            let p = options.relative_path(&[root], p.to_path_buf());
+            match status {
+                YearPolicyStatus::Satisfied => {}
+                YearPolicyStatus::NoCopyright => no_copyright_files.borrow_mut().push(p),
+                YearPolicyStatus::Violation(years) => violating_files.borrow_mut().push((p, years)),
+            }
+            Ok(false)
+        },
+    )?;
+    Ok(YearPolicyResults {
+        no_copyright_files: no_copyright_files.into_inner(),
+        violating_files: violating_files.into_inner(),
+        oversized_files: results.oversized_files,
+    })
+}
+
+/// The files found by [`check_year_policy_recursively`]
+#[cfg(feature = "recursive")]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct YearPolicyResults {
+    /// Files without a copyright line with a year list
+    pub no_copyright_files: Vec<path::PathBuf>,
+    /// Files whose copyright line's years violate the policy, with those years
+    pub violating_files: Vec<(path::PathBuf, Vec<ops::RangeInclusive<u32>>)>,
+    /// Files that were skipped for being larger than [`RecursiveOptions::max_file_size`]
+    pub oversized_files: Vec<path::PathBuf>,
+}
+
+#[cfg(feature = "recursive")]
+impl YearPolicyResults {
+    /// Returns `true` if any file lacks a copyright line or violates the policy.
+    pub fn has_failure(&self) -> bool {
+        !self.no_copyright_files.is_empty() || !self.violating_files.is_empty()
+    }
+}
+
+/// The copyright holder named after the year list of the copyright line `line`, if it is one
+#[cfg(feature = "recursive")]
+pub(crate) fn holder(line: &str) -> Option<&str> {
+    let (_, end) = find_year_list(line)?;
+    Some(line[end..].trim()).filter(|holder| !holder.is_empty())
+}
+
+/// Find the year list following `Copyright` in `line`, returning the years it covers and the
+/// offset of the end of the list.
+fn find_year_list(line: &str) -> Option<(Vec<ops::RangeInclusive<u32>>, usize)> {
+    let keyword = ["Copyright", "copyright", "COPYRIGHT"]
+        .iter()
+        .filter_map(|k| line.find(k).map(|i| i + k.len()))
+        .min()?;
+    let mut pos = keyword;
+    // skip the copyright symbol, if any
+    loop {
+        let rest = &line[pos..];
+        let trimmed = rest.trim_start_matches(' ');
+        pos += rest.len() - trimmed.len();
+        match ["(c)", "(C)", "©"].iter().find(|s| trimmed.starts_with(*s)) {
+            Some(symbol) => pos += symbol.len(),
+            None => break,
+        }
+    }
+    let mut years = Vec::new();
+    let mut end = None;
+    while let Some((first, after)) = parse_year(line, pos) {
+        let mut range = first..=first;
+        pos = after;
+        let dash = ["-", "–"].iter().find(|d| line[pos..].starts_with(*d));
+        if let Some((last, after)) = dash.and_then(|d| parse_year(line, pos + d.len())) {
+            range = first..=last;
+            pos = after;
+        }
+        years.push(range);
+        end = Some(pos);
+        // continue past a separating comma, if there is one
+        let rest = &line[pos..];
+        let trimmed = rest.trim_start_matches(' ');
+        match trimmed.strip_prefix(',') {
+            Some(after_comma) => {
+                pos += rest.len() - after_comma.trim_start_matches(' ').len();
+            }
+            None => break,
+        }
+    }
+    end.map(|end| (years, end))
+}
+
+/// Parse a four digit year at `pos` in `line`, returning it and the offset following it.
+fn parse_year(line: &str, pos: usize) -> Option<(u32, usize)> {
+    let digits = line[pos..]
+        .bytes()
+        .take_while(|b| b.is_ascii_digit())
+        .count();
+    if digits != 4 {
+        return None;
+    }
+    Some((line[pos..pos + 4].parse().ok()?, pos + 4))
+}
@@ -0,0 +1,77 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rendering headers according to a project's [EditorConfig](https://editorconfig.org) files.
+//!
+//! The `end_of_line`, `indent_style`, `indent_size`, `tab_width`, and `insert_final_newline`
+//! properties are used.
+//!
+//! This module requires the `editorconfig` feature.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use file_header::{editorconfig::header_style_for_path, *};
+//! use std::path::Path;
+//!
+//! let header = Header::new(
+//!     SingleLineChecker::new("Foo License".to_string(), 10),
+//!     "Foo License".to_string(),
+//! )
+//! .style(header_style_for_path);
+//! header.add_header_if_missing(Path::new("src/main.rs")).unwrap();
+//! ```
+
+use crate::{HeaderStyle, Indent, LineEnding};
+use ec4rs::property;
+use std::path;
+
+/// Returns the [`HeaderStyle`] for the file at `p`, as per the `.editorconfig` files that apply
+/// to it.
+///
+/// Properties that are unset or can't be read are left at their defaults.
+pub fn header_style_for_path(p: &path::Path) -> HeaderStyle {
+    let mut style = HeaderStyle::default();
+    let mut properties = match ec4rs::properties_of(p) {
+        Ok(properties) => properties,
+        Err(_) => return style,
+    };
+    properties.use_fallbacks();
+    if let Ok(end_of_line) = properties.get::<property::EndOfLine>() {
+        style.line_ending = match end_of_line {
+            property::EndOfLine::Lf => LineEnding::Lf,
+            property::EndOfLine::CrLf => LineEnding::CrLf,
+            property::EndOfLine::Cr => LineEnding::Cr,
+        };
+    }
+    if let Ok(indent_style) = properties.get::<property::IndentStyle>() {
+        let width = match properties.get::<property::TabWidth>() {
+            Ok(property::TabWidth::Value(width)) => width,
+            _ => match properties.get::<property::IndentSize>() {
+                Ok(property::IndentSize::Value(size)) => size,
+                _ => 4,
+            },
+        };
+        style.indent = Some(match indent_style {
+            property::IndentStyle::Tabs => Indent::Tabs { width },
+            property::IndentStyle::Spaces => Indent::Spaces { width },
+        });
+    }
+    if let Ok(property::FinalNewline::Value(insert)) = properties.get::<property::FinalNewline>() {
+        style.insert_final_newline = insert;
+    }
+    style
+}
@@ -0,0 +1,683 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Abstraction over the filesystem used by header operations.
+//!
+//! The operations in this crate use [`StdFileSystem`] unless told otherwise, but any
+//! [`FileSystem`] implementation can be supplied instead, e.g. to run under wasm, to use a custom
+//! storage backend, or to exercise header operations entirely in memory with
+//! [`MemoryFileSystem`].
+//!
+//! # Examples
+//!
+//! ```
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use file_header::{filesystem::*, *};
+//! use std::path::Path;
+//!
+//! let fs = MemoryFileSystem::new();
+//! fs.insert("src/main.rs", "fn main() {}\n");
+//!
+//! let header = Header::new(
+//!     SingleLineChecker::new("Foo License".to_string(), 10),
+//!     "Foo License".to_string(),
+//! );
+//! header.add_header_if_missing_with_fs(&fs, Path::new("src/main.rs")).unwrap();
+//!
+//! assert_eq!(
+//!     b"// Foo License\n\nfn main() {}\n".to_vec(),
+//!     fs.get("src/main.rs").unwrap()
+//! );
+//! ```
+
+use std::{any, borrow, collections, fmt, fs, io, io::Write as _, path, sync, time};
+
+/// The filesystem operations needed to check for, add, and delete headers.
+pub trait FileSystem: Send + Sync {
+    /// Open the file at `p` for reading.
+    fn open(&self, p: &path::Path) -> io::Result<Box<dyn io::Read + Send + '_>>;
+
+    /// Return the size in bytes of the file at `p`.
+    fn file_size(&self, p: &path::Path) -> io::Result<u64>;
+
+    /// Return the last modification time of the file at `p`.
+    ///
+    /// This is optional, and is used to tell if a file has changed since it was last checked.
+    fn modified(&self, _p: &path::Path) -> io::Result<time::SystemTime> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// Return `true` if `p` is a symbolic link, for paths that weren't found by
+    /// [`FileSystem::walk`], e.g. those given to [`crate::RecursiveOptions::paths`].
+    ///
+    /// This is optional, and by default no path is a symbolic link.
+    fn is_symlink(&self, _p: &path::Path) -> io::Result<bool> {
+        Ok(false)
+    }
+
+    /// Replace the contents of the file at `p` with `head` followed by the contents of `rest`.
+    ///
+    /// `rest` is typically a reader for the remainder of the file's original contents.
+    fn replace(&self, p: &path::Path, head: &[u8], rest: &mut dyn io::Read) -> io::Result<()>;
+
+    /// Take an advisory lock on the file at `p`, which is held while the file is read and
+    /// replaced to add or delete a header, so that other processes that lock it too, e.g. another
+    /// run in watch mode, can't modify it at the same time.
+    ///
+    /// The lock is released when the returned lock is dropped. This is optional, and by default
+    /// no lock is taken. [`StdFileSystem`] only takes locks on Unix.
+    fn lock(&self, _p: &path::Path) -> io::Result<Option<FileLock>> {
+        Ok(None)
+    }
+
+    /// Iterate over all files (not directories) in `root`, recursively, along with their
+    /// metadata, as directed by `options`.
+    fn walk<'a>(
+        &'a self,
+        root: &path::Path,
+        options: WalkOptions<'a>,
+    ) -> Box<dyn Iterator<Item = Result<WalkEntry, WalkError>> + 'a>;
+}
+
+/// An advisory lock on a file, as per [`FileSystem::lock`], which is released when dropped
+#[derive(Debug)]
+pub struct FileLock {
+    /// Releases the lock when dropped
+    _guard: Box<dyn any::Any + Send>,
+}
+
+impl FileLock {
+    /// Construct a lock that's held until `guard` is dropped, e.g. a locked file, whose lock is
+    /// released when it's closed.
+    pub fn new(guard: impl any::Any + Send) -> Self {
+        Self {
+            _guard: Box::new(guard),
+        }
+    }
+}
+
+/// Options that control how [`FileSystem::walk`] traverses a directory tree
+#[derive(Clone, Copy)]
+pub struct WalkOptions<'a> {
+    /// Directories below the root are only descended into if this returns `true` for them, so
+    /// that entire subtrees can be pruned.
+    pub descend: &'a dyn Fn(&path::Path) -> bool,
+    /// Whether to follow symbolic links to directories.
+    ///
+    /// When following links, symlink cycles are skipped, and a file reachable via multiple links
+    /// is only included once.
+    pub follow_symlinks: bool,
+}
+
+impl Default for WalkOptions<'_> {
+    fn default() -> Self {
+        Self {
+            descend: &|_dir| true,
+            follow_symlinks: false,
+        }
+    }
+}
+
+impl fmt::Debug for WalkOptions<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WalkOptions")
+            .field("follow_symlinks", &self.follow_symlinks)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A file found by [`FileSystem::walk`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WalkEntry {
+    /// The path of the file
+    pub path: path::PathBuf,
+    /// The file's metadata, gathered during the walk
+    pub metadata: FileMetadata,
+}
+
+/// Metadata about a file found while walking a directory tree, which can be used to filter files
+/// without further filesystem calls, e.g. with [`crate::RecursiveOptions::file_filter`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileMetadata {
+    /// The size of the file in bytes
+    pub size: u64,
+    /// Whether the path is a symbolic link to the file
+    pub is_symlink: bool,
+    /// How deep the file is below the root of the walk, e.g. `1` for a file directly in the root
+    pub depth: usize,
+}
+
+/// Errors that can occur when walking a directory tree
+#[derive(Debug, thiserror::Error)]
+pub enum WalkError {
+    /// `walkdir` could not navigate the directory structure
+    #[cfg(feature = "recursive")]
+    #[error("Walkdir error: {0}")]
+    Walkdir(#[from] walkdir::Error),
+    /// I/O error while walking the path
+    #[error("I/O error at {0:?}: {1}")]
+    Io(path::PathBuf, io::Error),
+}
+
+/// The filesystem provided by the standard library.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct StdFileSystem;
+
+impl FileSystem for StdFileSystem {
+    fn open(&self, p: &path::Path) -> io::Result<Box<dyn io::Read + Send + '_>> {
+        Ok(Box::new(fs::File::open(long_path(p))?))
+    }
+
+    fn file_size(&self, p: &path::Path) -> io::Result<u64> {
+        fs::metadata(long_path(p)).map(|m| m.len())
+    }
+
+    fn modified(&self, p: &path::Path) -> io::Result<time::SystemTime> {
+        fs::metadata(long_path(p))?.modified()
+    }
+
+    fn is_symlink(&self, p: &path::Path) -> io::Result<bool> {
+        Ok(fs::symlink_metadata(long_path(p))?.file_type().is_symlink())
+    }
+
+    /// The new contents are streamed into a temporary file next to the file, or to the target of
+    /// a symlink, which is then given the file's owner and permissions and renamed over it, so
+    /// that a failure part way through leaves the original intact.
+    ///
+    /// Files with other hard links, whose owner can't be copied, or whose directory can't be
+    /// written to, are instead overwritten in place once the new contents are complete, so that
+    /// the links and owner are kept. Extended attributes and ACLs aren't copied otherwise.
+    ///
+    /// Read-only files fail with [`io::ErrorKind::PermissionDenied`], even though renaming over
+    /// them would only need their directory to be writable.
+    fn replace(&self, p: &path::Path, head: &[u8], rest: &mut dyn io::Read) -> io::Result<()> {
+        // replace the target of a symlink, rather than the link itself
+        let target = fs::canonicalize(long_path(p))?;
+        let metadata = fs::metadata(&target)?;
+        if metadata.permissions().readonly() {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{:?} is read-only", target),
+            ));
+        }
+        let dir = target.parent().unwrap_or_else(|| path::Path::new("."));
+        let (temp, in_place) = match tempfile::NamedTempFile::new_in(dir) {
+            Ok(temp) => (temp, has_other_links(&metadata)),
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                (tempfile::NamedTempFile::new()?, true)
+            }
+            Err(e) => return Err(e),
+        };
+        let mut writer = io::BufWriter::new(temp);
+        writer.write_all(head)?;
+        io::copy(rest, &mut writer)?;
+        let temp = writer.into_inner().map_err(|e| e.into_error())?;
+        if in_place || copy_owner(temp.as_file(), &metadata).is_err() {
+            let mut file = fs::OpenOptions::new()
+                .write(true)
+                .truncate(true)
+                .open(&target)?;
+            io::copy(&mut temp.reopen()?, &mut file)?;
+            return Ok(());
+        }
+        fs::set_permissions(temp.path(), metadata.permissions())?;
+        temp.persist(&target).map_err(|e| e.error)?;
+        Ok(())
+    }
+
+    /// The lock is an exclusive `flock` on Unix. On Windows, where locks are mandatory and would
+    /// stop the file being read while it's locked, no lock is taken.
+    fn lock(&self, p: &path::Path) -> io::Result<Option<FileLock>> {
+        lock_file(&long_path(p))
+    }
+
+    /// Without the `recursive` feature, walking yields a single [`io::ErrorKind::Unsupported`]
+    /// error.
+    #[cfg(not(feature = "recursive"))]
+    fn walk<'a>(
+        &'a self,
+        root: &path::Path,
+        _options: WalkOptions<'a>,
+    ) -> Box<dyn Iterator<Item = Result<WalkEntry, WalkError>> + 'a> {
+        Box::new(std::iter::once(Err(WalkError::Io(
+            root.to_path_buf(),
+            io::ErrorKind::Unsupported.into(),
+        ))))
+    }
+
+    #[cfg(feature = "recursive")]
+    fn walk<'a>(
+        &'a self,
+        root: &path::Path,
+        options: WalkOptions<'a>,
+    ) -> Box<dyn Iterator<Item = Result<WalkEntry, WalkError>> + 'a> {
+        let descend = options.descend;
+        // only needed when following links, as otherwise each file is reached by one path
+        let mut visited = options.follow_symlinks.then(collections::HashSet::new);
+        // walk via the extended-length form of the root on Windows, so that deep trees don't
+        // exceed `MAX_PATH`, but report paths under the root as given
+        let walk_root = extended_length_path(root);
+        // the path as it would be under the root as given, if it differs
+        let original_path = {
+            let root = root.to_path_buf();
+            let walk_root = walk_root.clone();
+            move |p: &path::Path| -> Option<path::PathBuf> {
+                let rel = p.strip_prefix(walk_root.as_ref()?).ok()?;
+                Some(root.join(rel))
+            }
+        };
+        let descend_original_path = original_path.clone();
+        Box::new(
+            walkdir::WalkDir::new(walk_root.as_deref().unwrap_or(root))
+                .follow_links(options.follow_symlinks)
+                .into_iter()
+                .filter_entry(move |entry| {
+                    entry.depth() == 0
+                        || !entry.file_type().is_dir()
+                        || match descend_original_path(entry.path()) {
+                            Some(original) => descend(&original),
+                            None => descend(entry.path()),
+                        }
+                })
+                .filter_map(move |r| match r {
+                    Ok(entry) => walk_entry(entry, visited.as_mut())
+                        .map(|entry| {
+                            entry.map(|mut entry| {
+                                if let Some(original) = original_path(&entry.path) {
+                                    entry.path = original;
+                                }
+                                entry
+                            })
+                        })
+                        .transpose(),
+                    // a link back to one of its ancestors, whose files are already being walked
+                    Err(e) if e.loop_ancestor().is_some() => None,
+                    Err(e) => Some(Err(e.into())),
+                }),
+        )
+    }
+}
+
+/// Returns `true` if the file with `metadata` has hard links other than the one being replaced.
+#[cfg(unix)]
+fn has_other_links(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt as _;
+
+    metadata.nlink() > 1
+}
+
+#[cfg(not(unix))]
+fn has_other_links(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+/// Give `file` the owner and group of the file with `metadata`, if they differ.
+#[cfg(unix)]
+fn copy_owner(file: &fs::File, metadata: &fs::Metadata) -> io::Result<()> {
+    use rustix::fs::{Gid, Uid};
+    use std::os::unix::fs::MetadataExt as _;
+
+    let current = file.metadata()?;
+    if (current.uid(), current.gid()) == (metadata.uid(), metadata.gid()) {
+        return Ok(());
+    }
+    rustix::fs::fchown(
+        file,
+        Some(Uid::from_raw(metadata.uid())),
+        Some(Gid::from_raw(metadata.gid())),
+    )?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn copy_owner(_file: &fs::File, _metadata: &fs::Metadata) -> io::Result<()> {
+    Ok(())
+}
+
+/// Take an exclusive advisory lock on the file at `p`, waiting for other locks on it to be
+/// released.
+#[cfg(unix)]
+fn lock_file(p: &path::Path) -> io::Result<Option<FileLock>> {
+    use fs2::FileExt as _;
+    use std::os::unix::fs::MetadataExt as _;
+
+    loop {
+        let file = fs::File::open(p)?;
+        file.lock_exclusive()?;
+        // files are replaced rather than modified in place, so the file may have been replaced
+        // while waiting, e.g. by the process that held the lock, in which case the new file is
+        // locked instead
+        let locked = file.metadata()?;
+        let current = fs::metadata(p)?;
+        if (locked.dev(), locked.ino()) == (current.dev(), current.ino()) {
+            return Ok(Some(FileLock::new(file)));
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn lock_file(_p: &path::Path) -> io::Result<Option<FileLock>> {
+    Ok(None)
+}
+
+/// Returns `p` in a form that can be used with `std::fs` regardless of its length.
+///
+/// On Windows, paths of `MAX_PATH` (260) characters or more are converted to absolute
+/// extended-length paths with the `\\?\` prefix, as otherwise most APIs reject them. Elsewhere,
+/// and for shorter paths, `p` is returned as is.
+pub fn long_path(p: &path::Path) -> borrow::Cow<'_, path::Path> {
+    const MAX_PATH: usize = 260;
+    if p.as_os_str().len() < MAX_PATH {
+        return borrow::Cow::Borrowed(p);
+    }
+    match extended_length_path(p) {
+        Some(extended) => borrow::Cow::Owned(extended),
+        None => borrow::Cow::Borrowed(p),
+    }
+}
+
+/// Returns the absolute extended-length form of `p`, or `None` if it doesn't have one, e.g. if
+/// it already has the `\\?\` prefix, or on platforms other than Windows.
+#[cfg(windows)]
+fn extended_length_path(p: &path::Path) -> Option<path::PathBuf> {
+    use std::{env, ffi::OsString};
+
+    let absolute = if p.is_absolute() {
+        p.to_path_buf()
+    } else {
+        env::current_dir().ok()?.join(p)
+    };
+    let mut components = absolute.components();
+    let prefix = match components.next()? {
+        path::Component::Prefix(prefix) => prefix,
+        _ => return None,
+    };
+    let mut extended = match prefix.kind() {
+        path::Prefix::Disk(_) => {
+            let mut extended = OsString::from(r"\\?\");
+            extended.push(prefix.as_os_str());
+            extended
+        }
+        path::Prefix::UNC(server, share) => {
+            let mut extended = OsString::from(r"\\?\UNC\");
+            extended.push(server);
+            extended.push("\\");
+            extended.push(share);
+            extended
+        }
+        // already verbatim, or a device
+        _ => return None,
+    };
+    // the prefix disables normalization, so `.` and `..` must be resolved first
+    let mut normal = Vec::new();
+    for component in components {
+        match component {
+            path::Component::Normal(name) => normal.push(name),
+            path::Component::ParentDir => {
+                normal.pop();
+            }
+            _ => {}
+        }
+    }
+    if normal.is_empty() {
+        extended.push("\\");
+    }
+    for name in normal {
+        extended.push("\\");
+        extended.push(name);
+    }
+    Some(extended.into())
+}
+
+/// Returns the absolute extended-length form of `p`, or `None` if it doesn't have one, e.g. if
+/// it already has the `\\?\` prefix, or on platforms other than Windows.
+#[cfg(not(windows))]
+fn extended_length_path(_p: &path::Path) -> Option<path::PathBuf> {
+    None
+}
+
+/// Returns the [`WalkEntry`] for `entry`, or `None` if it's a directory or a file already in
+/// `visited`.
+#[cfg(feature = "recursive")]
+fn walk_entry(
+    entry: walkdir::DirEntry,
+    visited: Option<&mut collections::HashSet<FileId>>,
+) -> Result<Option<WalkEntry>, WalkError> {
+    let err_mapper = |e| WalkError::Io(entry.path().to_path_buf(), e);
+    let is_symlink = entry.path_is_symlink();
+    let metadata = if is_symlink {
+        // the entry's metadata may be for the link itself, not the file or directory it points to
+        fs::metadata(entry.path()).map_err(err_mapper)?
+    } else {
+        entry.metadata()?
+    };
+    if metadata.is_dir() {
+        return Ok(None);
+    }
+    if let Some(visited) = visited {
+        if !visited.insert(file_id(entry.path(), &metadata).map_err(err_mapper)?) {
+            return Ok(None);
+        }
+    }
+    Ok(Some(WalkEntry {
+        metadata: FileMetadata {
+            size: metadata.len(),
+            is_symlink,
+            depth: entry.depth(),
+        },
+        path: entry.into_path(),
+    }))
+}
+
+/// Identifies a file regardless of the path it's reached by
+#[cfg(all(feature = "recursive", unix))]
+type FileId = (u64, u64);
+#[cfg(all(feature = "recursive", not(unix)))]
+type FileId = path::PathBuf;
+
+/// Returns the [`FileId`] of the file at `p`, which has `metadata`.
+#[cfg(all(feature = "recursive", unix))]
+fn file_id(_p: &path::Path, metadata: &fs::Metadata) -> io::Result<FileId> {
+    use std::os::unix::fs::MetadataExt as _;
+    Ok((metadata.dev(), metadata.ino()))
+}
+
+/// Returns the [`FileId`] of the file at `p`, which has `metadata`.
+#[cfg(all(feature = "recursive", not(unix)))]
+fn file_id(p: &path::Path, _metadata: &fs::Metadata) -> io::Result<FileId> {
+    fs::canonicalize(p)
+}
+
+/// A [`FileSystem`] that makes read-only files on disk writable while replacing them, and then
+/// read-only again, as per [`crate::RecursiveOptions::lift_read_only`]
+#[cfg(feature = "recursive")]
+pub(crate) struct LiftingReadOnlyFileSystem {
+    pub(crate) inner: sync::Arc<dyn FileSystem>,
+}
+
+#[cfg(feature = "recursive")]
+impl FileSystem for LiftingReadOnlyFileSystem {
+    fn open(&self, p: &path::Path) -> io::Result<Box<dyn io::Read + Send + '_>> {
+        self.inner.open(p)
+    }
+
+    fn file_size(&self, p: &path::Path) -> io::Result<u64> {
+        self.inner.file_size(p)
+    }
+
+    fn modified(&self, p: &path::Path) -> io::Result<time::SystemTime> {
+        self.inner.modified(p)
+    }
+
+    fn is_symlink(&self, p: &path::Path) -> io::Result<bool> {
+        self.inner.is_symlink(p)
+    }
+
+    fn replace(&self, p: &path::Path, head: &[u8], rest: &mut dyn io::Read) -> io::Result<()> {
+        let disk_path = long_path(p);
+        // files that aren't on disk, e.g. in another file system, have no attribute to lift
+        let permissions = match fs::metadata(&disk_path) {
+            Ok(metadata) if metadata.permissions().readonly() => metadata.permissions(),
+            _ => return self.inner.replace(p, head, rest),
+        };
+        fs::set_permissions(&disk_path, writable(&permissions))
+            .map_err(|e| io::Error::new(e.kind(), LiftReadOnlyError(e)))?;
+        let replaced = self.inner.replace(p, head, rest);
+        // restored even if replacing failed, so that the file is left as it was found
+        let restored = fs::set_permissions(&disk_path, permissions);
+        replaced.and(restored)
+    }
+
+    fn lock(&self, p: &path::Path) -> io::Result<Option<FileLock>> {
+        self.inner.lock(p)
+    }
+
+    fn walk<'a>(
+        &'a self,
+        root: &path::Path,
+        options: WalkOptions<'a>,
+    ) -> Box<dyn Iterator<Item = Result<WalkEntry, WalkError>> + 'a> {
+        self.inner.walk(root, options)
+    }
+}
+
+/// `permissions` with the owner allowed to write
+#[cfg(all(feature = "recursive", unix))]
+fn writable(permissions: &fs::Permissions) -> fs::Permissions {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    fs::Permissions::from_mode(permissions.mode() | 0o200)
+}
+
+/// `permissions` without the read-only attribute
+#[cfg(all(feature = "recursive", not(unix)))]
+fn writable(permissions: &fs::Permissions) -> fs::Permissions {
+    let mut writable = permissions.clone();
+    // only the attribute is cleared, as there are no other permissions to widen
+    #[allow(clippy::permissions_set_readonly_false)]
+    writable.set_readonly(false);
+    writable
+}
+
+/// The error when a read-only file couldn't be made writable, as per
+/// [`crate::RecursiveOptions::lift_read_only`]
+#[cfg(feature = "recursive")]
+#[derive(Debug, thiserror::Error)]
+#[error("Could not make read-only file writable: {0}")]
+pub(crate) struct LiftReadOnlyError(io::Error);
+
+/// Returns `true` if `e` is from failing to make a read-only file writable, as per
+/// [`crate::RecursiveOptions::lift_read_only`].
+#[cfg(feature = "recursive")]
+pub(crate) fn is_lift_read_only_error(e: &io::Error) -> bool {
+    e.get_ref()
+        .map_or(false, |inner| inner.is::<LiftReadOnlyError>())
+}
+
+/// An in-memory filesystem, useful for tests or for embedding header operations in tools that
+/// don't operate on real files.
+///
+/// Directories are implicit: a directory exists if any file path has it as a prefix.
+#[derive(Default, Debug)]
+pub struct MemoryFileSystem {
+    files: sync::RwLock<collections::BTreeMap<path::PathBuf, Vec<u8>>>,
+}
+
+impl MemoryFileSystem {
+    /// Construct an empty `MemoryFileSystem`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create or overwrite the file at `p` with `contents`.
+    pub fn insert(&self, p: impl Into<path::PathBuf>, contents: impl Into<Vec<u8>>) {
+        self.files
+            .write()
+            .unwrap()
+            .insert(p.into(), contents.into());
+    }
+
+    /// Return a copy of the contents of the file at `p`, if it exists.
+    pub fn get(&self, p: impl AsRef<path::Path>) -> Option<Vec<u8>> {
+        self.files.read().unwrap().get(p.as_ref()).cloned()
+    }
+
+    fn not_found(p: &path::Path) -> io::Error {
+        io::Error::new(io::ErrorKind::NotFound, format!("{:?} not found", p))
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn open(&self, p: &path::Path) -> io::Result<Box<dyn io::Read + Send + '_>> {
+        self.get(p)
+            .map(|contents| Box::new(io::Cursor::new(contents)) as Box<dyn io::Read + Send>)
+            .ok_or_else(|| Self::not_found(p))
+    }
+
+    fn file_size(&self, p: &path::Path) -> io::Result<u64> {
+        self.files
+            .read()
+            .unwrap()
+            .get(p)
+            .map(|contents| contents.len() as u64)
+            .ok_or_else(|| Self::not_found(p))
+    }
+
+    fn replace(&self, p: &path::Path, head: &[u8], rest: &mut dyn io::Read) -> io::Result<()> {
+        if !self.files.read().unwrap().contains_key(p) {
+            return Err(Self::not_found(p));
+        }
+        let mut contents = head.to_vec();
+        rest.read_to_end(&mut contents)?;
+        self.insert(p, contents);
+        Ok(())
+    }
+
+    fn walk<'a>(
+        &'a self,
+        root: &path::Path,
+        options: WalkOptions<'a>,
+    ) -> Box<dyn Iterator<Item = Result<WalkEntry, WalkError>> + 'a> {
+        // `follow_symlinks` is irrelevant, as there are no links
+        let descend = options.descend;
+        let entries = self
+            .files
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|(p, contents)| {
+                let depth = p.strip_prefix(root).ok()?.components().count();
+                // the implicit directories between the root and the file
+                let pruned = p
+                    .ancestors()
+                    .skip(1)
+                    .take(depth.saturating_sub(1))
+                    .any(|dir| !descend(dir));
+                if pruned {
+                    return None;
+                }
+                Some(WalkEntry {
+                    path: p.clone(),
+                    metadata: FileMetadata {
+                        size: contents.len() as u64,
+                        is_symlink: false,
+                        depth,
+                    },
+                })
+            })
+            .collect::<Vec<_>>();
+        Box::new(entries.into_iter().map(Ok))
+    }
+}
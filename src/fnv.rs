@@ -0,0 +1,61 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! 64-bit FNV-1a hashing, which unlike `std`'s hashers is stable across releases, so hashes can be
+//! persisted.
+
+use std::io;
+
+/// An incremental FNV-1a hasher
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Hasher(u64);
+
+impl Default for Hasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher {
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        self.0 = bytes.iter().fold(self.0, |hash, b| {
+            (hash ^ u64::from(*b)).wrapping_mul(0x0000_0100_0000_01b3)
+        });
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Hash `bytes` in one go
+pub(crate) fn hash(bytes: &[u8]) -> u64 {
+    let mut hasher = Hasher::default();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+/// A reader that hashes everything read through it
+pub(crate) struct HashingReader<'a, R: ?Sized> {
+    pub(crate) inner: &'a mut R,
+    pub(crate) hasher: Hasher,
+}
+
+impl<R: io::Read + ?Sized> io::Read for HashingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.write(&buf[..n]);
+        Ok(n)
+    }
+}
@@ -0,0 +1,175 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checking for headers only in the files changed between two git refs.
+//!
+//! This is useful when validating pull requests, where only the files a change touches need to be
+//! checked rather than the whole repository.
+//!
+//! The `git` executable must be available on the `PATH`.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use file_header::{git::*, *};
+//! use std::path::Path;
+//!
+//! let header = Header::new(
+//!     SingleLineChecker::new("Foo License".to_string(), 10),
+//!     "Foo License".to_string(),
+//! );
+//! let results = check_headers_in_changed_files(
+//!     Path::new("/some/repo"),
+//!     "origin/main",
+//!     "HEAD",
+//!     |_p| true,
+//!     header,
+//!     4,
+//! )
+//! .unwrap();
+//! println!("changed files without the header: {:?}", results.no_header_files);
+//! ```
+
+use crate::{
+    check_found_files,
+    filesystem::{FileMetadata, WalkEntry, WalkError},
+    publish_files, CheckHeadersRecursivelyError, FileResults, Header, HeaderChecker,
+    RecursiveOptions,
+};
+use std::{io, path, process};
+
+/// Returns the files in `repo` that were added, copied, modified, or renamed between the `base`
+/// and `head` refs, i.e. `git diff base..head`.
+///
+/// Deleted files are not included. Paths are `repo` joined with the path relative to `repo`, and
+/// only files inside `repo` are included if it is a subdirectory of the repository.
+///
+/// Refs starting with `-` are rejected with [`GitError::InvalidRef`], so that refs from untrusted
+/// input, e.g. a pull request, can't be passed to `git` as options.
+pub fn changed_files(
+    repo: &path::Path,
+    base: &str,
+    head: &str,
+) -> Result<Vec<path::PathBuf>, GitError> {
+    if let Some(invalid) = [base, head].into_iter().find(|r| r.starts_with('-')) {
+        return Err(GitError::InvalidRef(invalid.to_string()));
+    }
+    let output = process::Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args([
+            "diff",
+            "--name-only",
+            "--relative",
+            "-z",
+            "--diff-filter=ACMR",
+        ])
+        .arg(format!("{}..{}", base, head))
+        .arg("--")
+        .output()
+        .map_err(GitError::CommandError)?;
+    if !output.status.success() {
+        return Err(GitError::GitFailed(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    let names = String::from_utf8(output.stdout).map_err(|_| GitError::NonUtf8Output)?;
+    Ok(names
+        .split('\0')
+        .filter(|name| !name.is_empty())
+        .map(|name| repo.join(name))
+        .collect())
+}
+
+/// Check for `header` in the files that match `path_predicate` among those changed between `base`
+/// and `head` in `repo`, as per [`changed_files`].
+///
+/// Checking is parallelized across `num_threads` threads.
+///
+/// Returns a [`FileResults`] object, as per [`crate::check_headers_recursively`].
+pub fn check_headers_in_changed_files(
+    repo: &path::Path,
+    base: &str,
+    head: &str,
+    path_predicate: impl Fn(&path::Path) -> bool,
+    header: Header<impl HeaderChecker>,
+    num_threads: usize,
+) -> Result<FileResults, CheckChangedFilesError> {
+    let paths = changed_files(repo, base, head)?;
+    let options = RecursiveOptions::new();
+    Ok(check_found_files(
+        header,
+        num_threads,
+        &options,
+        |path_tx| {
+            // files changed in `head` may not be present in the working tree
+            let existing = paths
+                .into_iter()
+                .filter(|p| p.is_file())
+                .map(|p| changed_file_entry(&options, repo, p));
+            publish_files(existing, path_predicate, &options, path_tx)
+        },
+    )?)
+}
+
+/// Returns the [`WalkEntry`] for the changed file at `p` in `repo`.
+fn changed_file_entry(
+    options: &RecursiveOptions,
+    repo: &path::Path,
+    p: path::PathBuf,
+) -> Result<WalkEntry, WalkError> {
+    let err_mapper = |e| WalkError::Io(p.clone(), e);
+    let size = options.file_system.file_size(&p).map_err(err_mapper)?;
+    let is_symlink = options.file_system.is_symlink(&p).map_err(err_mapper)?;
+    Ok(WalkEntry {
+        metadata: FileMetadata {
+            size,
+            is_symlink,
+            depth: p
+                .strip_prefix(repo)
+                .map_or(0, |rel| rel.components().count()),
+        },
+        path: p,
+    })
+}
+
+/// Errors that can occur when running `git`
+#[derive(Debug, thiserror::Error)]
+pub enum GitError {
+    /// `git` could not be run
+    #[error("Could not run git: {0}")]
+    CommandError(io::Error),
+    /// `git` exited unsuccessfully, with the included error output
+    #[error("git failed: {0}")]
+    GitFailed(String),
+    /// `git` output paths that were not valid UTF-8
+    #[error("git output was not UTF-8")]
+    NonUtf8Output,
+    /// A ref started with `-`, so would have been read as an option
+    #[error("Invalid git ref: {0}")]
+    InvalidRef(String),
+}
+
+/// Errors that can occur when checking for headers in changed files
+#[derive(Debug, thiserror::Error)]
+pub enum CheckChangedFilesError {
+    /// The changed files could not be determined
+    #[error("Git error: {0}")]
+    GitError(#[from] GitError),
+    /// A changed file could not be checked
+    #[error("Check error: {0}")]
+    CheckError(#[from] CheckHeadersRecursivelyError),
+}
@@ -0,0 +1,184 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Finding files that `.gitattributes` mark as `linguist-generated` or `linguist-vendored`, i.e.
+//! that GitHub treats as code that wasn't written by the project's authors.
+
+use crate::{
+    compat::{glob_match, slash_path},
+    filesystem::{FileSystem, WalkError},
+};
+use std::{
+    cell, collections,
+    io::{self, Read as _},
+    path, rc,
+};
+
+/// The attributes that mark files as not authored by the project
+const LINGUIST_ATTRIBUTES: [&str; 2] = ["linguist-generated", "linguist-vendored"];
+
+/// Finds the files in a set of roots that are marked as generated or vendored by the
+/// `.gitattributes` files in those roots and their subdirectories.
+///
+/// Each `.gitattributes` file is read at most once, when a file in its directory is first looked
+/// up.
+pub(crate) struct LinguistFilter<'a> {
+    file_system: &'a dyn FileSystem,
+    roots: Vec<path::PathBuf>,
+    /// The rules in each directory's `.gitattributes`, if it has one
+    rules: cell::RefCell<collections::HashMap<path::PathBuf, rc::Rc<Vec<Rule>>>>,
+}
+
+impl<'a> LinguistFilter<'a> {
+    pub(crate) fn new(file_system: &'a dyn FileSystem, roots: Vec<path::PathBuf>) -> Self {
+        Self {
+            file_system,
+            roots,
+            rules: cell::RefCell::new(collections::HashMap::new()),
+        }
+    }
+
+    /// Returns `true` if `p` is marked as generated or vendored.
+    ///
+    /// Rules in deeper directories take precedence, as do later rules in the same file. Files that
+    /// aren't in any of the roots are never marked.
+    pub(crate) fn is_marked(&self, p: &path::Path) -> Result<bool, WalkError> {
+        let (root, rel) = match self
+            .roots
+            .iter()
+            .find_map(|root| p.strip_prefix(root).ok().map(|rel| (root, rel)))
+        {
+            Some(found) => found,
+            None => return Ok(false),
+        };
+        let mut marked = [None; 2];
+        let mut dir = root.clone();
+        let components = rel.components().collect::<Vec<_>>();
+        for depth in 0..components.len() {
+            let rel_to_dir = components[depth..].iter().collect::<path::PathBuf>();
+            for rule in self.rules_in(&dir)?.iter() {
+                rule.apply(&slash_path(&rel_to_dir), &mut marked);
+            }
+            dir.push(components[depth]);
+        }
+        Ok(marked.contains(&Some(true)))
+    }
+
+    /// The rules in the `.gitattributes` of `dir`
+    fn rules_in(&self, dir: &path::Path) -> Result<rc::Rc<Vec<Rule>>, WalkError> {
+        if let Some(rules) = self.rules.borrow().get(dir) {
+            return Ok(rules.clone());
+        }
+        let p = dir.join(".gitattributes");
+        let mut contents = String::new();
+        let read = self
+            .file_system
+            .open(&p)
+            .and_then(|mut f| f.read_to_string(&mut contents));
+        let rules = rc::Rc::new(match read {
+            Ok(_) => parse(&contents),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(WalkError::Io(p, e)),
+        });
+        self.rules
+            .borrow_mut()
+            .insert(dir.to_path_buf(), rules.clone());
+        Ok(rules)
+    }
+}
+
+/// A line of a `.gitattributes` file that sets or unsets linguist attributes
+struct Rule {
+    pattern: String,
+    /// The state each of [`LINGUIST_ATTRIBUTES`] is set to, with `None` for those the rule
+    /// doesn't mention
+    states: [Option<State>; 2],
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    Set,
+    Unset,
+    /// Reset to unspecified, as with `!linguist-generated`
+    Unspecified,
+}
+
+impl Rule {
+    /// Update `marked` as per the rule, if it matches the slash-separated path `rel`, relative to
+    /// the directory of the rule's `.gitattributes`.
+    fn apply(&self, rel: &str, marked: &mut [Option<bool>; 2]) {
+        if !self.matches(rel) {
+            return;
+        }
+        for (mark, state) in marked.iter_mut().zip(self.states) {
+            match state {
+                Some(State::Set) => *mark = Some(true),
+                Some(State::Unset) => *mark = Some(false),
+                Some(State::Unspecified) => *mark = None,
+                None => {}
+            }
+        }
+    }
+
+    fn matches(&self, rel: &str) -> bool {
+        let pattern = self.pattern.as_str();
+        if pattern.ends_with('/') {
+            // patterns for directories don't apply to the files in them
+            false
+        } else if pattern.contains('/') {
+            glob_match(pattern.trim_start_matches('/').as_bytes(), rel.as_bytes())
+        } else {
+            // patterns without a slash match the file name at any depth
+            let name = rel.rsplit('/').next().unwrap_or(rel);
+            glob_match(pattern.as_bytes(), name.as_bytes())
+        }
+    }
+}
+
+/// The rules in the `.gitattributes` file `contents` that mention linguist attributes
+fn parse(contents: &str) -> Vec<Rule> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut tokens = line.split_whitespace();
+            let pattern = tokens.next()?;
+            // comments, and macro definitions, which can't use linguist attributes
+            if pattern.starts_with('#') || pattern.starts_with("[attr]") {
+                return None;
+            }
+            let mut states = [None; 2];
+            for token in tokens {
+                let (name, state) = if let Some(name) = token.strip_prefix('-') {
+                    (name, State::Unset)
+                } else if let Some(name) = token.strip_prefix('!') {
+                    (name, State::Unspecified)
+                } else if let Some((name, value)) = token.split_once('=') {
+                    match value {
+                        "false" => (name, State::Unset),
+                        _ => (name, State::Set),
+                    }
+                } else {
+                    (token, State::Set)
+                };
+                if let Some(i) = LINGUIST_ATTRIBUTES.iter().position(|a| *a == name) {
+                    states[i] = Some(state);
+                }
+            }
+            states.iter().any(Option::is_some).then(|| Rule {
+                pattern: pattern.to_string(),
+                states,
+            })
+        })
+        .collect()
+}
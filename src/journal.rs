@@ -0,0 +1,271 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Recording the modifications made by recursive operations, so that they can be rolled back.
+//!
+//! # Examples
+//!
+//! ```
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use file_header::{journal::*, *};
+//! use std::sync::Arc;
+//!
+//! # let dir = tempfile::tempdir().unwrap();
+//! # let root = dir.path();
+//! # std::fs::write(root.join("main.rs"), "fn main() {}\n").unwrap();
+//! let header = Header::new(
+//!     SingleLineChecker::new("Foo License".to_string(), 10),
+//!     "Foo License".to_string(),
+//! );
+//! let journal = Arc::new(Journal::new());
+//! add_headers_recursively_with_options(
+//!     root,
+//!     |_p| true,
+//!     header,
+//!     &RecursiveOptions::new().journal(journal.clone()),
+//! )
+//! .unwrap();
+//!
+//! // restore the files to how they were before headers were added
+//! rollback(&journal).unwrap();
+//! # assert_eq!("fn main() {}\n", std::fs::read_to_string(root.join("main.rs")).unwrap());
+//! ```
+
+use crate::{filesystem, fnv};
+use std::{
+    fs,
+    io::{self, BufRead as _, Read as _, Write as _},
+    path, sync,
+};
+
+/// First line of a saved journal
+const JOURNAL_FILE_MAGIC: &str = "file-header journal v1";
+
+/// A record of the files modified by recursive operations, and their original contents.
+///
+/// Use with [`crate::RecursiveOptions::journal`], and undo the modifications with [`rollback`].
+#[derive(Debug, Default)]
+pub struct Journal {
+    entries: sync::Mutex<Vec<JournalEntry>>,
+}
+
+/// A single modification recorded in a [`Journal`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct JournalEntry {
+    path: path::PathBuf,
+    /// The contents before the modification
+    original: Vec<u8>,
+    /// Hash of `original`, to detect corruption of saved journals
+    original_hash: u64,
+    /// Hash of the contents after the modification, to detect later changes
+    modified_hash: u64,
+}
+
+impl Journal {
+    /// Construct an empty journal.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The paths of the modified files, in the order they were modified.
+    pub fn paths(&self) -> Vec<path::PathBuf> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|e| e.path.clone())
+            .collect()
+    }
+
+    /// Returns `true` if no modifications have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.entries.lock().unwrap().is_empty()
+    }
+
+    /// Save the journal to the file at `path`, e.g. so that it can be rolled back by a later
+    /// process.
+    pub fn save(&self, path: &path::Path) -> io::Result<()> {
+        let mut out = io::BufWriter::new(fs::File::create(path)?);
+        writeln!(out, "{}", JOURNAL_FILE_MAGIC)?;
+        for entry in self.entries.lock().unwrap().iter() {
+            let p = entry
+                .path
+                .to_str()
+                .filter(|p| !p.contains('\n'))
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("Path can't be saved in a journal: {:?}", entry.path),
+                    )
+                })?;
+            writeln!(
+                out,
+                "{:016x}\t{:016x}\t{}\t{}",
+                entry.original_hash,
+                entry.modified_hash,
+                entry.original.len(),
+                p
+            )?;
+            out.write_all(&entry.original)?;
+        }
+        out.flush()
+    }
+
+    /// Load a journal saved with [`Journal::save`].
+    pub fn load(path: &path::Path) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Invalid journal");
+        let mut input = io::BufReader::new(fs::File::open(path)?);
+        let mut line = String::new();
+        input.read_line(&mut line)?;
+        if line.trim_end() != JOURNAL_FILE_MAGIC {
+            return Err(invalid());
+        }
+        let mut entries = Vec::new();
+        loop {
+            line.clear();
+            if input.read_line(&mut line)? == 0 {
+                break;
+            }
+            let mut fields = line.trim_end_matches('\n').splitn(4, '\t');
+            let mut next = || fields.next().ok_or_else(invalid);
+            let original_hash = u64::from_str_radix(next()?, 16).map_err(|_| invalid())?;
+            let modified_hash = u64::from_str_radix(next()?, 16).map_err(|_| invalid())?;
+            let len = next()?.parse::<u64>().map_err(|_| invalid())?;
+            let path = path::PathBuf::from(next()?);
+            let mut original = Vec::new();
+            (&mut input).take(len).read_to_end(&mut original)?;
+            if fnv::hash(&original) != original_hash {
+                return Err(invalid());
+            }
+            entries.push(JournalEntry {
+                path,
+                original,
+                original_hash,
+                modified_hash,
+            });
+        }
+        Ok(Self {
+            entries: sync::Mutex::new(entries),
+        })
+    }
+}
+
+/// Undo the modifications recorded in `journal`, most recent first.
+///
+/// A file is only restored if it hasn't changed since it was modified, so edits made after the
+/// recorded run are never lost. Files that already have their original contents are skipped, so
+/// rolling back is safe to retry.
+///
+/// Returns the paths that were restored.
+pub fn rollback(journal: &Journal) -> Result<Vec<path::PathBuf>, RollbackError> {
+    rollback_with_fs(journal, &filesystem::StdFileSystem)
+}
+
+/// Undo the modifications recorded in `journal` in `fs`, as per [`rollback`].
+pub fn rollback_with_fs(
+    journal: &Journal,
+    fs: &dyn filesystem::FileSystem,
+) -> Result<Vec<path::PathBuf>, RollbackError> {
+    let entries = journal.entries.lock().unwrap();
+    let mut restored = Vec::new();
+    for entry in entries.iter().rev() {
+        let err_mapper = |e| RollbackError::IoError(entry.path.clone(), e);
+        let mut current = Vec::new();
+        fs.open(&entry.path)
+            .and_then(|mut f| f.read_to_end(&mut current))
+            .map_err(err_mapper)?;
+        let current_hash = fnv::hash(&current);
+        if current_hash == entry.original_hash {
+            // already restored, e.g. by an earlier rollback
+            continue;
+        }
+        if current_hash != entry.modified_hash {
+            return Err(RollbackError::ChangedSinceModified(entry.path.clone()));
+        }
+        fs.replace(&entry.path, &entry.original, &mut io::empty())
+            .map_err(err_mapper)?;
+        restored.push(entry.path.clone());
+    }
+    Ok(restored)
+}
+
+/// Errors that can occur when rolling back a journal
+#[derive(Debug, thiserror::Error)]
+pub enum RollbackError {
+    /// An I/O error occurred while restoring the path
+    #[error("I/O error at {0:?}: {1}")]
+    IoError(path::PathBuf, io::Error),
+    /// The file at the path has changed since the journal recorded its modification, so it was
+    /// not restored.
+    ///
+    /// Files modified more recently have already been restored.
+    #[error("File changed since it was modified: {0:?}")]
+    ChangedSinceModified(path::PathBuf),
+}
+
+/// A [`filesystem::FileSystem`] that records every replacement in a journal
+pub(crate) struct JournalingFileSystem {
+    pub(crate) inner: sync::Arc<dyn filesystem::FileSystem>,
+    pub(crate) journal: sync::Arc<Journal>,
+}
+
+impl filesystem::FileSystem for JournalingFileSystem {
+    fn open(&self, p: &path::Path) -> io::Result<Box<dyn io::Read + Send + '_>> {
+        self.inner.open(p)
+    }
+
+    fn file_size(&self, p: &path::Path) -> io::Result<u64> {
+        self.inner.file_size(p)
+    }
+
+    fn modified(&self, p: &path::Path) -> io::Result<std::time::SystemTime> {
+        self.inner.modified(p)
+    }
+
+    fn is_symlink(&self, p: &path::Path) -> io::Result<bool> {
+        self.inner.is_symlink(p)
+    }
+
+    fn replace(&self, p: &path::Path, head: &[u8], rest: &mut dyn io::Read) -> io::Result<()> {
+        let mut original = Vec::new();
+        self.inner.open(p)?.read_to_end(&mut original)?;
+        let mut hasher = fnv::Hasher::default();
+        hasher.write(head);
+        let mut rest = fnv::HashingReader {
+            inner: rest,
+            hasher,
+        };
+        self.inner.replace(p, head, &mut rest)?;
+        self.journal.entries.lock().unwrap().push(JournalEntry {
+            path: p.to_path_buf(),
+            original_hash: fnv::hash(&original),
+            original,
+            modified_hash: rest.hasher.finish(),
+        });
+        Ok(())
+    }
+
+    fn lock(&self, p: &path::Path) -> io::Result<Option<filesystem::FileLock>> {
+        self.inner.lock(p)
+    }
+
+    fn walk<'a>(
+        &'a self,
+        root: &path::Path,
+        options: filesystem::WalkOptions<'a>,
+    ) -> Box<dyn Iterator<Item = Result<filesystem::WalkEntry, filesystem::WalkError>> + 'a> {
+        self.inner.walk(root, options)
+    }
+}
@@ -46,116 +46,1375 @@
 
 #![deny(missing_docs, unsafe_code)]
 
+#[cfg(feature = "recursive")]
+use std::{any, iter::FromIterator, panic, sync::atomic, thread};
 use std::{
-    fs,
-    io::{self, BufRead as _, Write as _},
-    iter::FromIterator,
-    path, thread,
+    borrow, cell, collections, fmt, fs,
+    io::{self, BufRead as _, Read as _},
+    ops, path, rc, sync, time,
 };
 
+#[cfg(feature = "archive")]
+pub mod archive;
+#[cfg(feature = "recursive")]
+pub mod audit;
+#[cfg(feature = "recursive")]
+pub mod cache;
+#[cfg(feature = "cargo")]
+pub mod cargo;
+#[cfg(feature = "clap")]
+pub mod cli;
+pub mod compat;
+pub mod copyright;
+#[cfg(feature = "editorconfig")]
+pub mod editorconfig;
+pub mod filesystem;
+#[cfg(feature = "recursive")]
+mod fnv;
+#[cfg(feature = "recursive")]
+pub mod git;
+#[cfg(feature = "recursive")]
+mod gitattributes;
+#[cfg(feature = "recursive")]
+pub mod journal;
 pub mod license;
+mod lines;
+pub mod lint;
+#[cfg(feature = "recursive")]
+mod pool;
+#[cfg(feature = "indicatif")]
+pub mod progress_bar;
+#[cfg(feature = "recursive")]
+pub mod report;
+#[cfg(feature = "recursive")]
+pub mod scanner;
+pub mod similarity;
+#[cfg(feature = "recursive")]
+pub mod statistics;
+#[cfg(feature = "recursive")]
+mod transaction;
+#[cfg(feature = "watch")]
+pub mod watch;
 
 /// A file header to check for, or add to, files.
-#[derive(Clone)]
+///
+/// Cloning a `Header` is cheap, as its checker and text are shared rather than copied.
 pub struct Header<C: HeaderChecker> {
     /// A checker to determine if the desired header is already present.
-    checker: C,
-    /// The header text to add, without comments or other filetype-specific framing.
-    header: String,
+    checker: sync::Arc<C>,
+    /// Provides the header text to add, without comments or other filetype-specific framing.
+    text: sync::Arc<dyn HeaderTextProvider>,
+    /// Where in a file the header goes.
+    placement: Placement,
+    /// How the header is rendered in a particular file
+    style_for_path: StyleForPath,
+    /// The comment delimiters for a particular file
+    delimiters_for_path: DelimitersForPath,
+    /// Whether a blank line separates the header from the rest of the contents
+    blank_line: bool,
+    /// Decoration around the header text, inside the comment delimiters
+    frame: Option<FrameStyle>,
+    /// The width that lines of the header are wrapped to, including the comment prefix
+    line_width: Option<usize>,
+    /// Magic first lines that stay ahead of the header, beyond [`MAGIC_FIRST_LINES`]
+    magic_first_lines: Vec<borrow::Cow<'static, str>>,
+}
+
+/// Returns the style of the header for a file's path, as per [`Header::style`]
+type StyleForPath = sync::Arc<dyn Fn(&path::Path) -> HeaderStyle + Send + Sync>;
+
+/// Returns the comment delimiters for a file's path, as per [`Header::delimiters`]
+type DelimitersForPath = sync::Arc<dyn Fn(&path::Path) -> Option<HeaderDelimiters> + Send + Sync>;
+
+/// The header text is shown as for contents without a path.
+impl<C: HeaderChecker + fmt::Debug> fmt::Debug for Header<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Header")
+            .field("checker", &self.checker)
+            .field("text", &self.text.text(None))
+            .field("placement", &self.placement)
+            .field("blank_line", &self.blank_line)
+            .field("frame", &self.frame)
+            .field("line_width", &self.line_width)
+            .field("magic_first_lines", &self.magic_first_lines)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: HeaderChecker> Clone for Header<C> {
+    fn clone(&self) -> Self {
+        Self {
+            checker: self.checker.clone(),
+            text: self.text.clone(),
+            placement: self.placement,
+            style_for_path: self.style_for_path.clone(),
+            delimiters_for_path: self.delimiters_for_path.clone(),
+            blank_line: self.blank_line,
+            frame: self.frame.clone(),
+            line_width: self.line_width,
+            magic_first_lines: self.magic_first_lines.clone(),
+        }
+    }
+}
+
+/// Provides the text of a [`Header`], which can be computed lazily, or differently for each file,
+/// e.g. to use the year a file was created, or its name.
+///
+/// `String` is the provider of fixed text.
+///
+/// # Examples
+///
+/// ```
+/// // Copyright 2023 Google LLC.
+/// // SPDX-License-Identifier: Apache-2.0
+/// use file_header::*;
+/// use std::{borrow::Cow, path::Path};
+///
+/// struct FileNameText;
+///
+/// impl HeaderTextProvider for FileNameText {
+///     fn text(&self, p: Option<&Path>) -> Cow<'_, str> {
+///         let name = p
+///             .and_then(|p| p.file_name())
+///             .map_or("".into(), |name| name.to_string_lossy());
+///         format!("Foo License\nThis is {}", name).into()
+///     }
+/// }
+///
+/// let header = Header::with_text_provider(
+///     SingleLineChecker::new("Foo License".to_string(), 10),
+///     FileNameText,
+/// );
+/// assert_eq!("Foo License\nThis is main.rs", header.text(Some(Path::new("src/main.rs"))));
+/// ```
+pub trait HeaderTextProvider: Send + Sync {
+    /// The header text for the file at `p`, or for contents without a path when `p` is `None`,
+    /// e.g. when checking a string or sizing the region of a file to check.
+    fn text(&self, p: Option<&path::Path>) -> borrow::Cow<'_, str>;
+}
+
+impl HeaderTextProvider for String {
+    fn text(&self, _p: Option<&path::Path>) -> borrow::Cow<'_, str> {
+        borrow::Cow::Borrowed(self)
+    }
+}
+
+/// Where in a file a header is placed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Placement {
+    /// At the start of the file, after any magic first line like a shebang and any editor
+    /// modelines
+    #[default]
+    Top,
+    /// At the end of the file, after a blank line as per [`Header::blank_line`], but before any
+    /// trailing Vim modelines.
+    ///
+    /// The header is checked for in the last few lines of the file, and files are read in their
+    /// entirety when adding or deleting the header.
+    Bottom,
+    /// After the first `n` lines of the file
+    AfterLine(usize),
+}
+
+/// How a header is rendered in a particular file, beyond its comment delimiters.
+///
+/// The default style uses `\n` line endings, leaves the header's indentation as is, and ends
+/// the file with a line ending.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeaderStyle {
+    /// The line ending used in the header
+    pub line_ending: LineEnding,
+    /// If set, leading indentation in each line of the header text is rendered in this style
+    pub indent: Option<Indent>,
+    /// Whether a header placed at the very end of a file ends with a line ending
+    pub insert_final_newline: bool,
+}
+
+impl Default for HeaderStyle {
+    fn default() -> Self {
+        Self {
+            line_ending: LineEnding::Lf,
+            indent: None,
+            insert_final_newline: true,
+        }
+    }
 }
 
+/// Line endings for [`HeaderStyle`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\n`
+    #[default]
+    Lf,
+    /// `\r\n`
+    CrLf,
+    /// `\r`
+    Cr,
+}
+
+impl LineEnding {
+    /// The characters of the line ending
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+}
+
+/// Indentation for [`HeaderStyle`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Indent {
+    /// Indent with tabs, each of which is `width` columns wide, with spaces for any remainder
+    Tabs {
+        /// Columns per tab
+        width: usize,
+    },
+    /// Indent with spaces, expanding existing tabs to `width` columns
+    Spaces {
+        /// Columns per tab
+        width: usize,
+    },
+}
+
+impl Indent {
+    /// Render the leading whitespace of `line` in this style
+    fn reindent(&self, line: &str) -> String {
+        let (Indent::Tabs { width } | Indent::Spaces { width }) = *self;
+        let width = width.max(1);
+        let content = line.trim_start_matches([' ', '\t']);
+        let columns = line[..line.len() - content.len()]
+            .chars()
+            .fold(0, |col, c| {
+                if c == '\t' {
+                    (col / width + 1) * width
+                } else {
+                    col + 1
+                }
+            });
+        let indent = match self {
+            Indent::Tabs { .. } => {
+                format!(
+                    "{}{}",
+                    "\t".repeat(columns / width),
+                    " ".repeat(columns % width)
+                )
+            }
+            Indent::Spaces { .. } => " ".repeat(columns),
+        };
+        indent + content
+    }
+}
+
+/// Decoration around a header's text, applied inside its comment delimiters, as per
+/// [`Header::frame`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FrameStyle {
+    /// A line of `width` repetitions of `character` above and below the header, e.g. `=====`
+    Rule {
+        /// The character the line is made of
+        character: char,
+        /// The number of characters in the line
+        width: usize,
+    },
+    /// A box of box-drawing characters around the header, as wide as its longest line
+    Box,
+}
+
+impl FrameStyle {
+    /// Returns `text` surrounded by the frame
+    fn apply(&self, text: &str) -> String {
+        match self {
+            FrameStyle::Rule { character, width } => {
+                let rule = character.to_string().repeat(*width);
+                format!("{}\n{}\n{}", rule, text, rule)
+            }
+            FrameStyle::Box => {
+                let width = text.split('\n').map(display_width).max().unwrap_or(0);
+                let horizontal = "─".repeat(width + 2);
+                let mut out = format!("┌{}┐\n", horizontal);
+                for line in text.split('\n') {
+                    let padding = " ".repeat(width - display_width(line));
+                    out.push_str(&format!("│ {}{} │\n", line, padding));
+                }
+                out.push_str(&format!("└{}┘", horizontal));
+                out
+            }
+        }
+    }
+}
+
+/// How many lines beyond the header's own are given to the checker for [`Placement::Bottom`]
+const BOTTOM_CHECK_EXTRA_LINES: usize = 5;
+
 impl<C: HeaderChecker> Header<C> {
     /// Construct a new `Header` with the `checker` used to determine if the header is already
     /// present, and the plain `header` text to add.
     ///
     /// `header` does not need to have applicable comment syntax, etc, as that will be added for
     /// each file type encountered.
+    ///
+    /// The header is placed at the top of files unless otherwise specified with
+    /// [`Header::placement`].
     pub fn new(checker: C, header: String) -> Self {
-        Self { checker, header }
+        Self::with_text_provider(checker, header)
+    }
+
+    /// Construct a new `Header` as per [`Header::new`], but with text from `text`, which can
+    /// differ for each file.
+    pub fn with_text_provider(checker: C, text: impl HeaderTextProvider + 'static) -> Self {
+        Self {
+            checker: sync::Arc::new(checker),
+            text: sync::Arc::new(text),
+            placement: Placement::Top,
+            style_for_path: sync::Arc::new(|_p: &path::Path| HeaderStyle::default()),
+            delimiters_for_path: sync::Arc::new(HeaderDelimiters::for_path),
+            blank_line: true,
+            frame: None,
+            line_width: None,
+            magic_first_lines: Vec::new(),
+        }
+    }
+
+    /// Place the header according to `placement` when checking for, adding, or deleting it.
+    pub fn placement(mut self, placement: Placement) -> Self {
+        self.placement = placement;
+        self
+    }
+
+    /// Separate the header from the rest of the file's contents with a blank line if `blank_line`
+    /// is `true`, which is the default.
+    ///
+    /// Deleting the header only removes the blank line if it is configured, so use the same
+    /// setting as when the header was added.
+    pub fn blank_line(mut self, blank_line: bool) -> Self {
+        self.blank_line = blank_line;
+        self
+    }
+
+    /// Decorate the header text with `frame` inside its comment delimiters, e.g. to add a banner
+    /// line above and below it.
+    ///
+    /// Deleting the header only removes the frame if it is configured, so use the same frame as
+    /// when the header was added.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// // Copyright 2023 Google LLC.
+    /// // SPDX-License-Identifier: Apache-2.0
+    /// use file_header::*;
+    /// use std::path::Path;
+    ///
+    /// let header = Header::new(
+    ///     SingleLineChecker::new("Foo License".to_string(), 10),
+    ///     "Foo License".to_string(),
+    /// )
+    /// .frame(FrameStyle::Rule {
+    ///     character: '=',
+    ///     width: 11,
+    /// });
+    /// let delimiters = HeaderDelimiters::for_path(Path::new("main.rs")).unwrap();
+    /// assert_eq!(
+    ///     Some("// ===========\n// Foo License\n// ===========\n\nfn main() {}\n".to_string()),
+    ///     header
+    ///         .add_header_to_str(&delimiters, "fn main() {}\n")
+    ///         .unwrap()
+    /// );
+    /// ```
+    pub fn frame(mut self, frame: FrameStyle) -> Self {
+        self.frame = Some(frame);
+        self
+    }
+
+    /// Render the header in the style returned by `style_for_path` for each file's path when
+    /// adding or deleting it, rather than [`HeaderStyle::default`].
+    ///
+    /// See `editorconfig::header_style_for_path` (with the `editorconfig` feature) to use each
+    /// project's `.editorconfig`. The string-based operations always use the default style.
+    pub fn style(
+        mut self,
+        style_for_path: impl Fn(&path::Path) -> HeaderStyle + Send + Sync + 'static,
+    ) -> Self {
+        self.style_for_path = sync::Arc::new(style_for_path);
+        self
+    }
+
+    /// Wrap the header in the delimiters returned by `delimiters_for_path` for each file's path
+    /// when adding or deleting it, rather than [`HeaderDelimiters::for_path`].
+    ///
+    /// This allows choosing the comment syntax for extensions that are ambiguous, or whose
+    /// conventional syntax varies by tool, e.g. `#` comments for MySQL `.sql` dumps, or `%` for
+    /// MATLAB `.m` files rather than Objective-C. Returning `None` means the file's extension is
+    /// unrecognized. `delimiters_for_path` can capture configuration chosen at runtime, e.g. the
+    /// SQL dialect given on the command line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// // Copyright 2023 Google LLC.
+    /// // SPDX-License-Identifier: Apache-2.0
+    /// use file_header::*;
+    ///
+    /// let header = Header::new(
+    ///     SingleLineChecker::new("Foo License".to_string(), 10),
+    ///     "Foo License".to_string(),
+    /// )
+    /// .delimiters(|p| match p.extension().and_then(|ext| ext.to_str()) {
+    ///     Some("sql") => Some(HeaderDelimiters::new("", "# ", "")),
+    ///     Some("m") => Some(HeaderDelimiters::new("", "% ", "")),
+    ///     _ => HeaderDelimiters::for_path(p),
+    /// });
+    /// ```
+    pub fn delimiters(
+        mut self,
+        delimiters_for_path: impl Fn(&path::Path) -> Option<HeaderDelimiters> + Send + Sync + 'static,
+    ) -> Self {
+        self.delimiters_for_path = sync::Arc::new(delimiters_for_path);
+        self
+    }
+
+    /// Convert to a [`DynHeader`], erasing the type of the checker, e.g. to store headers with
+    /// different checkers in the same collection.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// // Copyright 2023 Google LLC.
+    /// // SPDX-License-Identifier: Apache-2.0
+    /// use file_header::*;
+    ///
+    /// let headers: Vec<DynHeader> = vec![
+    ///     Header::new(
+    ///         SingleLineChecker::new("Foo License".to_string(), 10),
+    ///         "Foo License".to_string(),
+    ///     )
+    ///     .into_dyn(),
+    ///     Header::new(
+    ///         SingleLineChecker::new("Bar License".to_string(), 10),
+    ///         "Bar License".to_string(),
+    ///     )
+    ///     .placement(Placement::Bottom)
+    ///     .into_dyn(),
+    /// ];
+    /// assert!(headers[1].check_str("fn main() {}\n// Bar License\n").unwrap());
+    /// ```
+    pub fn into_dyn(self) -> DynHeader
+    where
+        C: 'static,
+    {
+        Header {
+            checker: sync::Arc::new(DynChecker {
+                inner: self.checker,
+            }),
+            text: self.text,
+            placement: self.placement,
+            style_for_path: self.style_for_path,
+            delimiters_for_path: self.delimiters_for_path,
+            blank_line: self.blank_line,
+            frame: self.frame,
+            line_width: self.line_width,
+            magic_first_lines: self.magic_first_lines,
+        }
+    }
+
+    /// The plain header text for the file at `p`, or for contents without a path when `p` is
+    /// `None`.
+    pub fn text(&self, p: Option<&path::Path>) -> borrow::Cow<'_, str> {
+        self.text.text(p)
+    }
+
+    /// The checker that determines if the header is present, which clones of this header share.
+    pub fn checker(&self) -> &C {
+        &self.checker
     }
 
     /// Return `true` if the file has the desired header, false otherwise.
+    ///
+    /// The checker is given the part of the file where the header would be placed, i.e. the file
+    /// after the first `n` lines for [`Placement::AfterLine`], or the last few lines for
+    /// [`Placement::Bottom`].
     pub fn header_present(&self, input: &mut impl io::Read) -> io::Result<bool> {
-        self.checker.check(input)
+        self.check_placed(input, |mut placed| self.checker.check(&mut placed))
+    }
+
+    /// Return `true` if the file has a header that drifted from the desired header, as per
+    /// [`HeaderChecker::drifted`].
+    ///
+    /// The checker is given the same part of the file as for [`Header::header_present`].
+    pub fn header_drifted(&self, input: &mut impl io::Read) -> io::Result<bool> {
+        self.check_placed(input, |mut placed| self.checker.drifted(&mut placed))
+    }
+
+    /// The byte range of the header in `input`, as per [`HeaderChecker::header_span`], relative
+    /// to the start of `input`.
+    fn placed_header_span(
+        &self,
+        input: &mut impl io::Read,
+    ) -> io::Result<Option<ops::Range<usize>>> {
+        match self.placement {
+            Placement::Top => self.checker.header_span(input),
+            Placement::AfterLine(n) => {
+                let mut reader = io::BufReader::new(input);
+                let mut skipped = Vec::new();
+                for _ in 0..n {
+                    reader.read_until(b'\n', &mut skipped)?;
+                }
+                Ok(self
+                    .checker
+                    .header_span(&mut reader)?
+                    .map(|span| span.start + skipped.len()..span.end + skipped.len()))
+            }
+            Placement::Bottom => Ok(None),
+        }
+    }
+
+    /// Call `check` with the part of `input` where the header would be placed.
+    fn check_placed(
+        &self,
+        input: &mut impl io::Read,
+        check: impl FnOnce(&mut dyn io::Read) -> io::Result<bool>,
+    ) -> io::Result<bool> {
+        match self.placement {
+            Placement::Top => check(input),
+            Placement::AfterLine(n) => {
+                let mut reader = io::BufReader::new(input);
+                let mut skipped = Vec::new();
+                for _ in 0..n {
+                    reader.read_until(b'\n', &mut skipped)?;
+                }
+                check(&mut reader)
+            }
+            Placement::Bottom => {
+                let max_lines = self.text(None).lines().count() + BOTTOM_CHECK_EXTRA_LINES;
+                let mut reader = io::BufReader::new(input);
+                let mut tail = collections::VecDeque::with_capacity(max_lines + 1);
+                loop {
+                    let mut line = Vec::new();
+                    if reader.read_until(b'\n', &mut line)? == 0 {
+                        break;
+                    }
+                    tail.push_back(line);
+                    if tail.len() > max_lines {
+                        tail.pop_front();
+                    }
+                }
+                check(&mut tail.into_iter().flatten().collect::<Vec<_>>().as_slice())
+            }
+        }
     }
 
     /// Add the header, with appropriate formatting for the type of file indicated by `p`'s
     /// extension, if the header is not already present.
     /// Returns `true` if the header was added.
+    ///
+    /// The file is read once, checking for the header as it goes. Only the head of the file is
+    /// held in memory: the rest of the contents are streamed into a temporary file, which then
+    /// replaces the original. Files that aren't entirely valid UTF-8 fail with an
+    /// [`io::ErrorKind::InvalidData`] error, and are left unmodified.
     pub fn add_header_if_missing(&self, p: &path::Path) -> Result<bool, AddHeaderError> {
+        self.add_header_if_missing_with_fs(&filesystem::StdFileSystem, p)
+    }
+
+    /// Add the header to the file at `p` in `fs`, as per [`Header::add_header_if_missing`].
+    pub fn add_header_if_missing_with_fs(
+        &self,
+        fs: &dyn filesystem::FileSystem,
+        p: &path::Path,
+    ) -> Result<bool, AddHeaderError> {
+        self.add_header_if_missing_cached(fs, p, &EffectiveHeaderCache::default())
+    }
+
+    /// Add the header to the file at `p` in `fs`, reusing the effective headers in `cache`.
+    fn add_header_if_missing_cached(
+        &self,
+        fs: &dyn filesystem::FileSystem,
+        p: &path::Path,
+        cache: &EffectiveHeaderCache,
+    ) -> Result<bool, AddHeaderError> {
         let err_mapper = |e| AddHeaderError::IoError(p.to_path_buf(), e);
-        let contents = fs::read_to_string(p).map_err(err_mapper)?;
-        if self
-            .header_present(&mut contents.as_bytes())
-            .map_err(err_mapper)?
-        {
+        // held until the file is replaced, so that it can't change after it's read
+        let _lock = fs.lock(p).map_err(err_mapper)?;
+        let stamp = FileStamp::of(fs, p).map_err(err_mapper)?;
+        let mut recorder = RecordingReader::new(Utf8Reader::new(fs.open(p).map_err(err_mapper)?));
+        if self.header_present(&mut recorder).map_err(err_mapper)? {
             return Ok(false);
         }
-        let mut effective_header = header_delimiters(p)
-            .ok_or_else(|| AddHeaderError::UnrecognizedExtension(p.to_path_buf()))
-            .map(|d| wrap_header(&self.header, d))?;
-        let mut after_header = contents.as_str();
-        // check for a magic first line and if present, add the license after the first line
-        if let Some((first_line, rest)) = contents.split_once('\n') {
-            if MAGIC_FIRST_LINES.iter().any(|l| first_line.contains(l)) {
-                let mut first_line = first_line.to_string();
-                first_line.push('\n');
-                effective_header.insert_str(0, &first_line);
-                after_header = rest;
-            }
-        }
-        // write the license
-        let mut f = fs::OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .open(p)
+        let style = (self.style_for_path)(p);
+        let delimiters = self
+            .delimiters_for_file(p, &mut recorder)
+            .map_err(err_mapper)?
+            .ok_or_else(|| AddHeaderError::UnrecognizedExtension(p.to_path_buf()))?;
+        let effective_header = self.cached_effective_header(p, &delimiters, &style, cache);
+        if let Some(sequence) = comment_breaking_sequence(&effective_header, &delimiters) {
+            return Err(AddHeaderError::CommentTerminator(p.to_path_buf(), sequence));
+        }
+        // continue from what the checker read, rather than reading the file again
+        let mut reader = io::BufReader::new(recorder.replay());
+        let (mut head, pending) = self
+            .split_at_insertion(&mut reader, &delimiters, &style)
             .map_err(err_mapper)?;
-        f.write_all(effective_header.as_bytes())
+        self.push_header(&mut head, &effective_header, &pending, &mut reader, &style)
             .map_err(err_mapper)?;
-        // newline to separate the header from previous contents
-        f.write_all("\n".as_bytes()).map_err(err_mapper)?;
-        f.write_all(after_header.as_bytes()).map_err(err_mapper)?;
+        let mut rest = UnchangedReader {
+            inner: io::Cursor::new(pending).chain(reader),
+            fs,
+            path: p,
+            stamp,
+        };
+        fs.replace(p, &head, &mut rest).map_err(|e| {
+            if is_concurrent_modification(&e) {
+                AddHeaderError::ConcurrentModification(p.to_path_buf())
+            } else {
+                err_mapper(e)
+            }
+        })?;
         Ok(true)
     }
 
     /// Delete the header, with appropriate formatting for the type of file indicated by `p`'s
     /// extension, if the header is already present.
     /// Returns `true` if the header was deleted.
+    ///
+    /// The header is only searched for in the head of the file (up to [`DELETE_SEARCH_BYTES`]
+    /// past the length of the header itself, after any lines skipped as per
+    /// [`Placement::AfterLine`]), and the rest of the contents are streamed into a temporary file,
+    /// which then replaces the original. For [`Placement::Bottom`], the last copy of the header
+    /// is deleted. As for [`Header::add_header_if_missing`], files that aren't valid UTF-8 fail.
     pub fn delete_header_if_present(&self, p: &path::Path) -> Result<bool, DeleteHeaderError> {
+        self.delete_header_if_present_with_fs(&filesystem::StdFileSystem, p)
+    }
+
+    /// Delete the header from the file at `p` in `fs`, as per
+    /// [`Header::delete_header_if_present`].
+    pub fn delete_header_if_present_with_fs(
+        &self,
+        fs: &dyn filesystem::FileSystem,
+        p: &path::Path,
+    ) -> Result<bool, DeleteHeaderError> {
+        self.delete_header_if_present_cached(fs, p, &EffectiveHeaderCache::default())
+    }
+
+    /// Delete the header from the file at `p` in `fs`, reusing the effective headers in `cache`.
+    fn delete_header_if_present_cached(
+        &self,
+        fs: &dyn filesystem::FileSystem,
+        p: &path::Path,
+        cache: &EffectiveHeaderCache,
+    ) -> Result<bool, DeleteHeaderError> {
         let err_mapper = |e| DeleteHeaderError::IoError(p.to_path_buf(), e);
-        let contents = fs::read_to_string(p).map_err(err_mapper)?;
-        if !self
-            .header_present(&mut contents.as_bytes())
-            .map_err(err_mapper)?
-        {
+        // held until the file is replaced, so that it can't change after it's read
+        let _lock = fs.lock(p).map_err(err_mapper)?;
+        let stamp = FileStamp::of(fs, p).map_err(err_mapper)?;
+        let mut recorder = RecordingReader::new(Utf8Reader::new(fs.open(p).map_err(err_mapper)?));
+        if !self.header_present(&mut recorder).map_err(err_mapper)? {
             return Ok(false);
         }
-        let mut effective_header = header_delimiters(p)
-            .ok_or_else(|| DeleteHeaderError::UnrecognizedExtension(p.to_path_buf()))
-            .map(|d| wrap_header(&self.header, d))?;
-        // include the newline separator appended by add_header_if_missing()
-        effective_header.push('\n');
+        // continue from what the checker read, rather than reading the file again
+        let mut recorder = RecordingReader::new(recorder.replay());
+        let (head, reader) = match self.placed_header_span(&mut recorder).map_err(err_mapper)? {
+            Some(span) => {
+                let mut reader = io::BufReader::new(recorder.replay());
+                let head = read_without_span(&mut reader, span).map_err(err_mapper)?;
+                (head, reader)
+            }
+            None => {
+                let style = (self.style_for_path)(p);
+                let effective_header = self
+                    .delimiters_for_file(p, &mut recorder)
+                    .map_err(err_mapper)?
+                    .ok_or_else(|| DeleteHeaderError::UnrecognizedExtension(p.to_path_buf()))
+                    .map(|d| self.cached_effective_header(p, &d, &style, cache))?;
+                let mut reader = io::BufReader::new(recorder.replay());
+                let head = self
+                    .read_without_header(&mut reader, &effective_header, &style)
+                    .map_err(err_mapper)?;
+                (head, reader)
+            }
+        };
+        let head = match head {
+            Some(head) => head,
+            None => return Ok(false),
+        };
+        // write the remainder
+        let mut rest = UnchangedReader {
+            inner: reader,
+            fs,
+            path: p,
+            stamp,
+        };
+        fs.replace(p, &head, &mut rest).map_err(|e| {
+            if is_concurrent_modification(&e) {
+                DeleteHeaderError::ConcurrentModification(p.to_path_buf())
+            } else {
+                err_mapper(e)
+            }
+        })?;
+        Ok(true)
+    }
+
+    /// Returns `true` if the entire header, as [`Header::delete_header_if_present`] would look
+    /// for it, is in the file at `p` in `fs`.
+    ///
+    /// Files whose delimiters aren't known don't have the entire header.
+    #[cfg(feature = "recursive")]
+    fn whole_header_present(
+        &self,
+        fs: &dyn filesystem::FileSystem,
+        p: &path::Path,
+    ) -> io::Result<bool> {
+        let mut recorder = RecordingReader::new(fs.open(p)?);
+        let delimiters = match self.delimiters_for_file(p, &mut recorder)? {
+            Some(delimiters) => delimiters,
+            None => return Ok(false),
+        };
+        let style = (self.style_for_path)(p);
+        let effective_header = self.effective_header(&self.text(Some(p)), &delimiters, &style);
+        let mut reader = io::BufReader::new(recorder.replay());
+        Ok(self
+            .read_without_header(&mut reader, &effective_header, &style)?
+            .is_some())
+    }
+
+    /// Returns `true` if the header's text is in a comment block near where it's placed in the
+    /// file at `p` in `fs`, with runs of whitespace and blank lines collapsed in both, as per
+    /// [`RecursiveOptions::normalize_whitespace`].
+    #[cfg(feature = "recursive")]
+    fn normalized_header_present(
+        &self,
+        fs: &dyn filesystem::FileSystem,
+        p: &path::Path,
+    ) -> io::Result<bool> {
+        let mut recorder = RecordingReader::new(fs.open(p)?);
+        let delimiters = match self.delimiters_for_file(p, &mut recorder)? {
+            Some(delimiters) => delimiters,
+            None => return Ok(false),
+        };
+        let text = self.text(Some(p));
+        let (limit, max_lines) = match self.placement {
+            Placement::Top => (text.len() + DELETE_SEARCH_BYTES, NORMALIZED_SEARCH_LINES),
+            Placement::AfterLine(n) => (
+                text.len() + DELETE_SEARCH_BYTES,
+                n + NORMALIZED_SEARCH_LINES,
+            ),
+            Placement::Bottom => (usize::MAX, usize::MAX),
+        };
+        let mut head = Vec::new();
+        recorder
+            .replay()
+            .take(limit as u64)
+            .read_to_end(&mut head)?;
+        let expected = collapse_whitespace(&text);
+        Ok(
+            lint::comment_blocks(&delimiters, &String::from_utf8_lossy(&head), max_lines)
+                .iter()
+                .any(|block| collapse_whitespace(&block.lines.join(" ")).contains(&expected)),
+        )
+    }
+
+    /// The delimiters for the file at `p` from `delimiters_for_path`, or else as per
+    /// [`HeaderDelimiters::for_shebang`] with the first line of the file, which `recorder` reads.
+    fn delimiters_for_file<R: io::Read>(
+        &self,
+        p: &path::Path,
+        recorder: &mut RecordingReader<R>,
+    ) -> io::Result<Option<HeaderDelimiters>> {
+        if let Some(delimiters) = (self.delimiters_for_path)(p) {
+            return Ok(Some(delimiters));
+        }
+        // make sure the first line has been read, if the checker didn't get that far
+        let unread = MAX_SHEBANG_BYTES.saturating_sub(recorder.recorded.len() as u64);
+        io::copy(&mut recorder.take(unread), &mut io::sink())?;
+        let first_line = recorder
+            .recorded
+            .split(|b| *b == b'\n')
+            .next()
+            .unwrap_or(&[]);
+        Ok(std::str::from_utf8(first_line)
+            .ok()
+            .and_then(HeaderDelimiters::for_shebang))
+    }
 
-        // the checker is conservative: it may look for only a substring of the license, but
-        // deletion will only have an effect if the entire wrapped header is present.
-        if !contents.contains(&effective_header) {
-            return Ok(false);
+    /// Return `true` if `contents` has the desired header, `false` otherwise.
+    pub fn check_str(&self, contents: &str) -> io::Result<bool> {
+        self.header_present(&mut contents.as_bytes())
+    }
+
+    /// Add the header, formatted with `delimiters`, to `contents` if the header is not already
+    /// present.
+    /// Returns the new contents, or `None` if the header was already present.
+    ///
+    /// This is the same as [`Header::add_header_if_missing`], but without touching the filesystem.
+    pub fn add_header_to_str(
+        &self,
+        delimiters: &HeaderDelimiters,
+        contents: &str,
+    ) -> io::Result<Option<String>> {
+        if self.check_str(contents)? {
+            return Ok(None);
+        }
+        let style = HeaderStyle::default();
+        let mut reader = contents.as_bytes();
+        let (mut out, pending) = self.split_at_insertion(&mut reader, delimiters, &style)?;
+        let effective_header = self.effective_header(&self.text(None), delimiters, &style);
+        if let Some(sequence) = comment_breaking_sequence(&effective_header, delimiters) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Header contains {:?}, which would end its comment early",
+                    sequence
+                ),
+            ));
         }
+        self.push_header(&mut out, &effective_header, &pending, &mut reader, &style)?;
+        out.extend_from_slice(&pending);
+        out.extend_from_slice(reader);
+        String::from_utf8(out)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
 
-        // remove the first copy of the header to avoid touching the license text in a string
-        // literal, etc.
-        let remainder = contents.replacen(&effective_header, "", 1);
-        // write the remainder
-        let mut f = fs::OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .open(p)
-            .map_err(err_mapper)?;
-        f.write_all(remainder.as_bytes()).map_err(err_mapper)?;
-        Ok(true)
+    /// Delete the header, formatted with `delimiters`, from `contents` if the header is present.
+    /// Returns the new contents, or `None` if the header was not present.
+    ///
+    /// This is the same as [`Header::delete_header_if_present`], but without touching the
+    /// filesystem.
+    pub fn delete_header_from_str(
+        &self,
+        delimiters: &HeaderDelimiters,
+        contents: &str,
+    ) -> io::Result<Option<String>> {
+        if !self.check_str(contents)? {
+            return Ok(None);
+        }
+        let style = HeaderStyle::default();
+        let mut reader = contents.as_bytes();
+        let head = match self.placed_header_span(&mut contents.as_bytes())? {
+            Some(span) => read_without_span(&mut reader, span)?,
+            None => {
+                let effective_header = self.effective_header(&self.text(None), delimiters, &style);
+                self.read_without_header(&mut reader, &effective_header, &style)?
+            }
+        };
+        let mut out = match head {
+            Some(head) => head,
+            None => return Ok(None),
+        };
+        out.extend_from_slice(reader);
+        String::from_utf8(out)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// The header for the file at `p` as per [`Header::effective_header`], reusing the effective
+    /// header in `cache` if the file's header text, `delimiters`, and `style` are the same as a
+    /// previous file's.
+    fn cached_effective_header(
+        &self,
+        p: &path::Path,
+        delimiters: &HeaderDelimiters,
+        style: &HeaderStyle,
+        cache: &EffectiveHeaderCache,
+    ) -> rc::Rc<str> {
+        let text = self.text(Some(p));
+        let mut entries = cache.entries.borrow_mut();
+        if let Some(entry) = entries
+            .iter()
+            .find(|e| e.delimiters == *delimiters && e.style == *style)
+        {
+            if entry.text == *text {
+                return entry.effective_header.clone();
+            }
+        }
+        let effective_header: rc::Rc<str> = self.effective_header(&text, delimiters, style).into();
+        // per-file text replaces the previous entry, rather than accumulating entries
+        entries.retain(|e| e.delimiters != *delimiters || e.style != *style);
+        entries.push(EffectiveHeaderCacheEntry {
+            delimiters: delimiters.clone(),
+            style: style.clone(),
+            text: text.into_owned(),
+            effective_header: effective_header.clone(),
+        });
+        effective_header
+    }
+
+    /// The header `text` wrapped with `delimiters` and rendered in `style`, along with the blank
+    /// line that separates it from the rest of the file's contents, if configured.
+    fn effective_header(
+        &self,
+        text: &str,
+        delimiters: &HeaderDelimiters,
+        style: &HeaderStyle,
+    ) -> String {
+        let text = match self.line_width {
+            Some(width) => borrow::Cow::Owned(wrap_text(
+                text,
+                width.saturating_sub(display_width(&delimiters.content_line_prefix)),
+            )),
+            None => borrow::Cow::Borrowed(text),
+        };
+        let header = match style.indent {
+            Some(indent) => text
+                .split('\n')
+                .map(|line| indent.reindent(line))
+                .collect::<Vec<_>>()
+                .join("\n")
+                .into(),
+            None => text,
+        };
+        let header = match &self.frame {
+            Some(frame) => frame.apply(&header).into(),
+            None => header,
+        };
+        let wrapped = wrap_header(&header, delimiters);
+        let effective_header = match self.placement {
+            _ if !self.blank_line => wrapped,
+            // newline to separate the header from previous contents
+            Placement::Bottom => format!("\n{}", wrapped),
+            // newline to separate the header from following contents
+            Placement::Top | Placement::AfterLine(_) => format!("{}\n", wrapped),
+        };
+        match style.line_ending {
+            LineEnding::Lf => effective_header,
+            line_ending => effective_header.replace('\n', line_ending.as_str()),
+        }
+    }
+
+    /// Append `effective_header` to `head`, which will be followed by `pending` and then the rest
+    /// of `reader`.
+    ///
+    /// A header at the very end of the file omits its final line ending if `style` says so.
+    fn push_header(
+        &self,
+        head: &mut Vec<u8>,
+        effective_header: &str,
+        pending: &[u8],
+        reader: &mut impl io::BufRead,
+        style: &HeaderStyle,
+    ) -> io::Result<()> {
+        let at_end = pending.is_empty() && reader.fill_buf()?.is_empty();
+        if self.placement == Placement::Bottom && at_end && !style.insert_final_newline {
+            head.extend_from_slice(
+                effective_header
+                    .strip_suffix(style.line_ending.as_str())
+                    .unwrap_or(effective_header)
+                    .as_bytes(),
+            );
+        } else {
+            head.extend_from_slice(effective_header.as_bytes());
+        }
+        Ok(())
+    }
+
+    /// Read the part of `reader` that precedes where the header is inserted.
+    ///
+    /// Returns that part, along with any bytes that were read but belong after the header.
+    fn split_at_insertion(
+        &self,
+        reader: &mut impl io::BufRead,
+        delimiters: &HeaderDelimiters,
+        style: &HeaderStyle,
+    ) -> io::Result<(Vec<u8>, Vec<u8>)> {
+        let line_ending = style.line_ending.as_str().as_bytes();
+        let mut head = Vec::new();
+        match self.placement {
+            Placement::Top => {
+                // keep a magic first line, and any modelines that follow, ahead of the header
+                // whether only YAML directives have been kept so far
+                let mut in_yaml_prolog = delimiters.yaml_prolog;
+                let mut kept_lines = 0;
+                loop {
+                    let mut line = Vec::new();
+                    reader.read_until(b'\n', &mut line)?;
+                    if head.is_empty() && markup_prolog(&line).is_some() {
+                        // the whole prolog stays ahead of the header, not just its first line
+                        return read_markup_prolog(reader, line, line_ending);
+                    }
+                    let line_str = String::from_utf8_lossy(&line);
+                    // YAML directives, and the document start marker that ends them, stay first
+                    let yaml_prolog_line = in_yaml_prolog
+                        && (is_yaml_directive(&line_str) || is_yaml_document_start(&line_str));
+                    in_yaml_prolog = yaml_prolog_line && is_yaml_directive(&line_str);
+                    // Emacs only reads the first line, or the second after a shebang
+                    let emacs_line =
+                        kept_lines == 0 || (kept_lines == 1 && head.starts_with(b"#!"));
+                    let keep = line.ends_with(b"\n")
+                        && ((head.is_empty() && self.is_magic_first_line(&line_str))
+                            || yaml_prolog_line
+                            || (emacs_line && is_emacs_modeline(&line_str))
+                            || is_vim_modeline(&line_str));
+                    if !keep {
+                        // the line is ordinary content, so it goes after the header
+                        return Ok((head, line));
+                    }
+                    head.extend_from_slice(&line);
+                    kept_lines += 1;
+                }
+            }
+            Placement::AfterLine(n) => {
+                for _ in 0..n {
+                    reader.read_until(b'\n', &mut head)?;
+                }
+            }
+            Placement::Bottom => {
+                reader.read_to_end(&mut head)?;
+                if !head.is_empty() && !head.ends_with(b"\n") {
+                    head.extend_from_slice(line_ending);
+                }
+                // keep trailing Vim modelines at the end of the file, after the header
+                let mut modelines_start = head.len();
+                while modelines_start > 0 {
+                    let line_start = head[..modelines_start - 1]
+                        .iter()
+                        .rposition(|b| *b == b'\n')
+                        .map_or(0, |i| i + 1);
+                    let line = String::from_utf8_lossy(&head[line_start..modelines_start]);
+                    if !is_vim_modeline(&line) {
+                        break;
+                    }
+                    modelines_start = line_start;
+                }
+                let modelines = head.split_off(modelines_start);
+                return Ok((head, modelines));
+            }
+        }
+        // files without a trailing newline need one before the header
+        if !head.is_empty() && !head.ends_with(b"\n") {
+            head.extend_from_slice(line_ending);
+        }
+        Ok((head, Vec::new()))
+    }
+
+    /// Returns `true` if `first_line` is one of [`MAGIC_FIRST_LINES`], or one of this header's
+    /// additional magic first lines.
+    fn is_magic_first_line(&self, first_line: &str) -> bool {
+        is_magic_first_line(first_line)
+            || self
+                .magic_first_lines
+                .iter()
+                .any(|l| first_line.contains(l.as_ref()))
+    }
+
+    /// Read the part of `reader` that may contain `effective_header`, as per
+    /// [`Header::delete_header_if_present`].
+    ///
+    /// Returns that part with the header removed, or `None` if the header isn't in it. The rest of
+    /// the contents remain to be read from `reader`.
+    fn read_without_header(
+        &self,
+        reader: &mut impl io::BufRead,
+        effective_header: &str,
+        style: &HeaderStyle,
+    ) -> io::Result<Option<Vec<u8>>> {
+        let mut head = Vec::new();
+        let header_start = match self.placement {
+            Placement::Top | Placement::AfterLine(_) => {
+                if let Placement::AfterLine(n) = self.placement {
+                    for _ in 0..n {
+                        reader.read_until(b'\n', &mut head)?;
+                    }
+                }
+                let search_start = head.len();
+                reader
+                    .take((effective_header.len() + DELETE_SEARCH_BYTES) as u64)
+                    .read_to_end(&mut head)?;
+                // the checker is conservative: it may look for only a substring of the license,
+                // but deletion will only have an effect if the entire wrapped header is present.
+                // Only the first copy of the header is removed to avoid touching the license text
+                // in a string literal, etc.
+                find_subslice(&head[search_start..], effective_header.as_bytes())
+                    .map(|start| start + search_start)
+            }
+            Placement::Bottom => {
+                reader.read_to_end(&mut head)?;
+                // the header's final line ending is omitted at the end of the file, as per
+                // `push_header`
+                let unterminated = effective_header
+                    .strip_suffix(style.line_ending.as_str())
+                    .filter(|h| head.ends_with(h.as_bytes()));
+                match unterminated {
+                    Some(h) if !style.insert_final_newline => {
+                        let start = head.len() - h.len();
+                        head.truncate(start);
+                        return Ok(Some(head));
+                    }
+                    _ => rfind_subslice(&head, effective_header.as_bytes()),
+                }
+            }
+        };
+        Ok(header_start.map(|start| {
+            head.drain(start..start + effective_header.len());
+            head
+        }))
+    }
+}
+
+/// Builds a [`Header`] with all of its options in one place, as an alternative to
+/// [`Header::new`] followed by its setters.
+///
+/// # Examples
+///
+/// ```
+/// // Copyright 2023 Google LLC.
+/// // SPDX-License-Identifier: Apache-2.0
+/// use file_header::*;
+/// use std::path::Path;
+///
+/// let header = Header::builder()
+///     .checker(SingleLineChecker::new("Foo License".to_string(), 10))
+///     .text("Foo License, which is quite long".to_string())
+///     .line_width(20)
+///     .magic_first_line("# shellcheck")
+///     .build()
+///     .unwrap();
+/// let delimiters = HeaderDelimiters::for_path(Path::new("run.sh")).unwrap();
+/// assert_eq!(
+///     Some("# shellcheck shell=sh\n# Foo License, which\n# is quite long\n\necho\n".to_string()),
+///     header
+///         .add_header_to_str(&delimiters, "# shellcheck shell=sh\necho\n")
+///         .unwrap()
+/// );
+/// ```
+pub struct HeaderBuilder<C: HeaderChecker> {
+    checker: Option<C>,
+    text: Option<sync::Arc<dyn HeaderTextProvider>>,
+    placement: Placement,
+    style_for_path: StyleForPath,
+    delimiters_for_path: DelimitersForPath,
+    blank_line: bool,
+    frame: Option<FrameStyle>,
+    line_width: Option<usize>,
+    magic_first_lines: Vec<borrow::Cow<'static, str>>,
+}
+
+impl<C: HeaderChecker> Header<C> {
+    /// Start building a `Header` with a [`HeaderBuilder`], which needs at least a checker and
+    /// header text.
+    pub fn builder() -> HeaderBuilder<C> {
+        HeaderBuilder {
+            checker: None,
+            text: None,
+            placement: Placement::Top,
+            style_for_path: sync::Arc::new(|_p: &path::Path| HeaderStyle::default()),
+            delimiters_for_path: sync::Arc::new(HeaderDelimiters::for_path),
+            blank_line: true,
+            frame: None,
+            line_width: None,
+            magic_first_lines: Vec::new(),
+        }
+    }
+}
+
+impl<C: HeaderChecker> HeaderBuilder<C> {
+    /// Determine if the header is already present with `checker`, as per [`Header::new`].
+    pub fn checker(mut self, checker: C) -> Self {
+        self.checker = Some(checker);
+        self
+    }
+
+    /// Add the plain `text` as the header, as per [`Header::new`].
+    pub fn text(self, text: String) -> Self {
+        self.text_provider(text)
+    }
+
+    /// Add the header text provided by `text`, as per [`Header::with_text_provider`].
+    pub fn text_provider(mut self, text: impl HeaderTextProvider + 'static) -> Self {
+        self.text = Some(sync::Arc::new(text));
+        self
+    }
+
+    /// Place the header as per [`Header::placement`].
+    pub fn placement(mut self, placement: Placement) -> Self {
+        self.placement = placement;
+        self
+    }
+
+    /// Separate the header from the rest of the contents with a blank line, as per
+    /// [`Header::blank_line`].
+    pub fn blank_line(mut self, blank_line: bool) -> Self {
+        self.blank_line = blank_line;
+        self
+    }
+
+    /// Render the header in the style returned by `style_for_path`, as per [`Header::style`].
+    pub fn style(
+        mut self,
+        style_for_path: impl Fn(&path::Path) -> HeaderStyle + Send + Sync + 'static,
+    ) -> Self {
+        self.style_for_path = sync::Arc::new(style_for_path);
+        self
+    }
+
+    /// Wrap the header in the delimiters returned by `delimiters_for_path`, as per
+    /// [`Header::delimiters`].
+    pub fn delimiters(
+        mut self,
+        delimiters_for_path: impl Fn(&path::Path) -> Option<HeaderDelimiters> + Send + Sync + 'static,
+    ) -> Self {
+        self.delimiters_for_path = sync::Arc::new(delimiters_for_path);
+        self
+    }
+
+    /// Decorate the header with `frame`, as per [`Header::frame`].
+    pub fn frame(mut self, frame: FrameStyle) -> Self {
+        self.frame = Some(frame);
+        self
+    }
+
+    /// Wrap lines of the header text at whitespace so that, with their comment prefix, they're
+    /// no wider than `width` columns where possible.
+    ///
+    /// Widths are measured as displayed, so East Asian wide characters, e.g. in CJK copyright
+    /// holder names, count as two columns, and lines may also be wrapped next to them, as CJK
+    /// text doesn't separate words with spaces.
+    ///
+    /// Wrapped lines keep the indentation of the line they came from. As with the other options
+    /// that change how the header is rendered, deleting the header only works with the same
+    /// width as when it was added.
+    pub fn line_width(mut self, width: usize) -> Self {
+        self.line_width = Some(width);
+        self
+    }
+
+    /// Keep a first line containing `line` ahead of the header, in addition to the built-in
+    /// magic first lines like shebangs and XML declarations.
+    pub fn magic_first_line(mut self, line: impl Into<borrow::Cow<'static, str>>) -> Self {
+        self.magic_first_lines.push(line.into());
+        self
     }
+
+    /// Build the `Header`.
+    pub fn build(self) -> Result<Header<C>, HeaderBuilderError> {
+        Ok(Header {
+            checker: sync::Arc::new(self.checker.ok_or(HeaderBuilderError::MissingChecker)?),
+            text: self.text.ok_or(HeaderBuilderError::MissingText)?,
+            placement: self.placement,
+            style_for_path: self.style_for_path,
+            delimiters_for_path: self.delimiters_for_path,
+            blank_line: self.blank_line,
+            frame: self.frame,
+            line_width: self.line_width,
+            magic_first_lines: self.magic_first_lines,
+        })
+    }
+}
+
+/// Errors that can occur when building a [`Header`] with a [`HeaderBuilder`]
+#[derive(Debug, thiserror::Error)]
+pub enum HeaderBuilderError {
+    /// No checker was provided
+    #[error("Header has no checker")]
+    MissingChecker,
+    /// No header text was provided
+    #[error("Header has no text")]
+    MissingText,
+}
+
+/// Wrap each line of `text` at whitespace, or next to wide characters, so that it's at most
+/// `width` columns wide, if possible.
+///
+/// Continuation lines have the same indentation as the line they're wrapped from.
+fn wrap_text(text: &str, width: usize) -> String {
+    let mut out = Vec::new();
+    for line in text.split('\n') {
+        let content = line.trim_start_matches([' ', '\t']);
+        let indent = &line[..line.len() - content.len()];
+        let mut current = indent.to_string();
+        for word in content.split_whitespace() {
+            for (i, unit) in word_units(word).enumerate() {
+                let has_words = current.len() > indent.len();
+                // units after the first are joined to the previous one without a space
+                let separator = usize::from(i == 0 && has_words);
+                if has_words && display_width(&current) + separator + display_width(unit) > width {
+                    out.push(current);
+                    current = indent.to_string();
+                } else if separator == 1 {
+                    current.push(' ');
+                }
+                current.push_str(unit);
+            }
+        }
+        out.push(current);
+    }
+    out.join("\n")
+}
+
+/// The parts of `word` that lines can be wrapped between: each wide character on its own, and
+/// the runs of other characters between them
+fn word_units(word: &str) -> impl Iterator<Item = &str> {
+    let mut rest = word;
+    std::iter::from_fn(move || {
+        let mut chars = rest.char_indices();
+        let (_, first) = chars.next()?;
+        let end = if is_wide(first) {
+            first.len_utf8()
+        } else {
+            chars
+                .find(|(_, c)| is_wide(*c))
+                .map_or(rest.len(), |(i, _)| i)
+        };
+        let (unit, remainder) = rest.split_at(end);
+        rest = remainder;
+        Some(unit)
+    })
 }
 
+/// Returns `true` if `c` is displayed two columns wide, as East Asian wide characters are.
+fn is_wide(c: char) -> bool {
+    unicode_width::UnicodeWidthChar::width(c) == Some(2)
+}
+
+/// The number of columns `text` is displayed in, with East Asian wide characters counting as
+/// two
+fn display_width(text: &str) -> usize {
+    unicode_width::UnicodeWidthStr::width(text)
+}
+
+/// Read the head of `reader` up to the end of `span`, without the bytes in `span`, leaving the
+/// rest of `reader` unread, or `None` if `reader` ends before `span` does.
+fn read_without_span(
+    reader: &mut impl io::Read,
+    span: ops::Range<usize>,
+) -> io::Result<Option<Vec<u8>>> {
+    let mut head = Vec::new();
+    reader.take(span.end as u64).read_to_end(&mut head)?;
+    if head.len() < span.end || span.start > span.end {
+        return Ok(None);
+    }
+    head.drain(span);
+    Ok(Some(head))
+}
+
+/// Returns the index of the first occurrence of `needle` in `haystack`, if any.
+fn find_subslice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Returns the index of the last occurrence of `needle` in `haystack`, if any.
+fn rfind_subslice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|w| w == needle)
+}
+
+/// How many lines into a file a comment block with the header may start, as per
+/// [`RecursiveOptions::normalize_whitespace`]
+#[cfg(feature = "recursive")]
+const NORMALIZED_SEARCH_LINES: usize = 10;
+
+/// `text` with each run of whitespace, including line endings and blank lines, replaced with a
+/// single space, and typographic characters replaced as per [`SingleLineChecker`]
+#[cfg(feature = "recursive")]
+fn collapse_whitespace(text: &str) -> String {
+    lines::normalize_typography(text)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// How far past the length of the wrapped header [`Header::delete_header_if_present`] will search
+/// for the header in a file.
+pub const DELETE_SEARCH_BYTES: usize = 64 * 1024;
+
 /// Errors that can occur when adding a header
 #[derive(Debug, thiserror::Error)]
 pub enum AddHeaderError {
@@ -165,6 +1424,14 @@ pub enum AddHeaderError {
     /// The file at the path had an unrecognized extension
     #[error("Unknown file extension: {0:?}")]
     UnrecognizedExtension(path::PathBuf),
+    /// The header contains the sequence, which would end the comment around it early in the file
+    /// at the path, as per [`HeaderDelimiters::comment_breaking_sequence`]
+    #[error("Header contains {1:?}, which would end its comment early in {0:?}")]
+    CommentTerminator(path::PathBuf, String),
+    /// The file at the path changed while the header was being added, so it was left as is,
+    /// rather than overwriting the changes
+    #[error("File changed while adding the header: {0:?}")]
+    ConcurrentModification(path::PathBuf),
 }
 
 /// Errors that can occur when deleting a header
@@ -176,21 +1443,55 @@ pub enum DeleteHeaderError {
     /// The file at the path had an unrecognized extension
     #[error("Unknown file extension: {0:?}")]
     UnrecognizedExtension(path::PathBuf),
+    /// The file at the path changed while the header was being deleted, so it was left as is,
+    /// rather than overwriting the changes
+    #[error("File changed while deleting the header: {0:?}")]
+    ConcurrentModification(path::PathBuf),
 }
 
 /// Checks for headers in files, like licenses or author attribution.
 ///
-/// This is intended to be used via [`Header`], not called directly.
-pub trait HeaderChecker: Send + Clone {
+/// This is intended to be used via [`Header`], not called directly. A header's checker is shared
+/// by the threads of recursive operations rather than cloned for each of them.
+pub trait HeaderChecker: Send + Sync {
     /// Return `true` if the file has the desired header, `false` otherwise.
     fn check(&self, file: &mut impl io::Read) -> io::Result<bool>;
-}
+
+    /// Return `true` if the file has a header that's similar to, but not, the desired header,
+    /// e.g. one that was edited, so that it can be reported as drifted rather than missing.
+    ///
+    /// This is only used for files where [`HeaderChecker::check`] didn't find the header. The
+    /// default is `false`, i.e. headers are either present or missing, but see
+    /// [`similarity::SimilarityChecker`].
+    fn drifted(&self, file: &mut impl io::Read) -> io::Result<bool> {
+        let _ = file;
+        Ok(false)
+    }
+
+    /// Return the byte range of the header in `file`, e.g. a comment block with a license
+    /// written by another tool, if the checker can tell where it is.
+    ///
+    /// When a range is returned, [`Header::delete_header_if_present`] deletes those bytes rather
+    /// than looking for the exact header text, so it should include any blank line after the
+    /// header that's to be deleted along with it. The checker is given the same part of the file
+    /// as for [`HeaderChecker::check`], and the range is relative to the start of that part. It
+    /// isn't used for [`Placement::Bottom`].
+    ///
+    /// The default is `None`, i.e. only the exact header text is deleted.
+    fn header_span(&self, file: &mut impl io::Read) -> io::Result<Option<ops::Range<usize>>> {
+        let _ = file;
+        Ok(None)
+    }
+}
 
 /// Checks for a pattern in the first several lines of each file.
+///
+/// Smart quotes, dashes, and non-breaking spaces in the pattern or the file are treated as their
+/// ASCII equivalents, as license texts pasted from web pages often have them.
 #[derive(Clone)]
 pub struct SingleLineChecker {
     /// Pattern to do a substring match on in each of the first `max_lines` lines of the file
-    pattern: String,
+    pattern: memchr::memmem::Finder<'static>,
     /// Number of lines to search through
     max_lines: usize,
 }
@@ -198,43 +1499,124 @@ pub struct SingleLineChecker {
 impl SingleLineChecker {
     /// Construct a `SingleLineChecker` that looks for `pattern` in the first `max_lines` of a file.
     pub fn new(pattern: String, max_lines: usize) -> Self {
-        Self { pattern, max_lines }
+        Self {
+            pattern: memchr::memmem::Finder::new(lines::normalize_typography(&pattern).as_bytes())
+                .into_owned(),
+            max_lines,
+        }
+    }
+}
+
+impl fmt::Debug for SingleLineChecker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SingleLineChecker")
+            .field("pattern", &String::from_utf8_lossy(self.pattern.needle()))
+            .field("max_lines", &self.max_lines)
+            .finish()
+    }
+}
+
+impl PartialEq for SingleLineChecker {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern.needle() == other.pattern.needle() && self.max_lines == other.max_lines
     }
 }
 
+impl Eq for SingleLineChecker {}
+
 impl HeaderChecker for SingleLineChecker {
     fn check(&self, input: &mut impl io::Read) -> io::Result<bool> {
-        let mut reader = io::BufReader::new(input);
-        let mut lines_read = 0;
-        // reuse buffer to minimize allocation
-        let mut line = String::new();
         // only read the first bit of the file
-        while lines_read < self.max_lines {
-            line.clear();
-            let bytes = reader.read_line(&mut line)?;
-            if bytes == 0 {
-                // EOF
-                return Ok(false);
-            }
-            lines_read += 1;
-            if line.contains(&self.pattern) {
-                return Ok(true);
-            }
+        lines::find_line(input, self.max_lines, |line| {
+            self.pattern
+                .find(lines::normalize_typography(line).as_bytes())
+                .is_some()
+        })
+    }
+}
+
+/// A type-erased [`HeaderChecker`], so that headers with different checkers have the same type,
+/// e.g. to store them together in a collection, as per [`DynHeader`].
+#[derive(Clone)]
+pub struct DynChecker {
+    inner: sync::Arc<dyn ErasedChecker>,
+}
+
+impl DynChecker {
+    /// Construct a `DynChecker` that checks as per `checker`.
+    pub fn new(checker: impl HeaderChecker + 'static) -> Self {
+        Self {
+            inner: sync::Arc::new(checker),
         }
-        Ok(false)
     }
 }
 
+impl fmt::Debug for DynChecker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DynChecker").finish_non_exhaustive()
+    }
+}
+
+impl HeaderChecker for DynChecker {
+    fn check(&self, file: &mut impl io::Read) -> io::Result<bool> {
+        self.inner.check_dyn(file)
+    }
+
+    fn drifted(&self, file: &mut impl io::Read) -> io::Result<bool> {
+        self.inner.drifted_dyn(file)
+    }
+
+    fn header_span(&self, file: &mut impl io::Read) -> io::Result<Option<ops::Range<usize>>> {
+        self.inner.header_span_dyn(file)
+    }
+}
+
+/// The object-safe equivalent of [`HeaderChecker`], which [`DynChecker`] boxes
+trait ErasedChecker: Send + Sync {
+    fn check_dyn(&self, file: &mut dyn io::Read) -> io::Result<bool>;
+
+    fn drifted_dyn(&self, file: &mut dyn io::Read) -> io::Result<bool>;
+
+    fn header_span_dyn(&self, file: &mut dyn io::Read) -> io::Result<Option<ops::Range<usize>>>;
+}
+
+impl<C: HeaderChecker + 'static> ErasedChecker for C {
+    fn check_dyn(&self, mut file: &mut dyn io::Read) -> io::Result<bool> {
+        self.check(&mut file)
+    }
+
+    fn drifted_dyn(&self, mut file: &mut dyn io::Read) -> io::Result<bool> {
+        self.drifted(&mut file)
+    }
+
+    fn header_span_dyn(
+        &self,
+        mut file: &mut dyn io::Read,
+    ) -> io::Result<Option<ops::Range<usize>>> {
+        self.header_span(&mut file)
+    }
+}
+
+/// A [`Header`] whose checker's type is erased, which can be used wherever a `Header` can.
+pub type DynHeader = Header<DynChecker>;
+
 /// Reasons why a file may not have a header
-#[derive(Copy, Clone)]
+#[cfg(feature = "recursive")]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 enum CheckStatus {
     /// The header was not found in the file
     HeaderNotFound,
     /// A file appears to be binary
     BinaryFile,
+    /// The checker found the header, but not the entire header, as per
+    /// [`RecursiveOptions::strict_headers`]
+    PartialHeader,
+    /// The header was not found, but the checker found a header that drifted from it
+    DriftedHeader,
 }
 
 /// The output of checking a single file
+#[cfg(feature = "recursive")]
 #[derive(Clone)]
 struct FileResult {
     path: path::PathBuf,
@@ -242,21 +1624,149 @@ struct FileResult {
 }
 
 /// Aggregated results for recursively checking a directory tree of files.
+#[cfg(feature = "recursive")]
 #[derive(Clone, Default, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FileResults {
     /// Paths that did not have a header
     pub no_header_files: Vec<path::PathBuf>,
     /// Paths that appeared to be binary, not UTF-8 text
     pub binary_files: Vec<path::PathBuf>,
+    /// Paths that were skipped because they exceeded [`RecursiveOptions::max_file_size`]
+    pub oversized_files: Vec<path::PathBuf>,
+    /// Symlinks to files that were skipped, as per [`SymlinkPolicy::Report`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub symlinks: Vec<path::PathBuf>,
+    /// Paths where the checker found the header, but not the entire header, as per
+    /// [`RecursiveOptions::strict_headers`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub partial_header_files: Vec<path::PathBuf>,
+    /// Paths without the header, but with a header that drifted from it, as per
+    /// [`HeaderChecker::drifted`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub drifted_header_files: Vec<path::PathBuf>,
+    /// Whether the check stopped early at [`RecursiveOptions::max_failures`] with files left
+    /// unchecked, in which case the failures are a lower bound, and unchecked files may also fail.
+    /// Finding exactly the maximum number of failures without skipping any files doesn't count.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub stopped_early: bool,
 }
 
+#[cfg(feature = "recursive")]
 impl FileResults {
-    /// Returns `true` if any files scanned did not have a header
+    /// The lists of paths in the results
+    pub(crate) fn path_lists_mut(&mut self) -> [&mut Vec<path::PathBuf>; 6] {
+        [
+            &mut self.no_header_files,
+            &mut self.binary_files,
+            &mut self.oversized_files,
+            &mut self.symlinks,
+            &mut self.partial_header_files,
+            &mut self.drifted_header_files,
+        ]
+    }
+
+    /// Add `result` to the list for its status
+    fn push(&mut self, result: FileResult) {
+        let list = match result.status {
+            CheckStatus::HeaderNotFound => &mut self.no_header_files,
+            CheckStatus::BinaryFile => &mut self.binary_files,
+            CheckStatus::PartialHeader => &mut self.partial_header_files,
+            CheckStatus::DriftedHeader => &mut self.drifted_header_files,
+        };
+        list.push(result.path);
+    }
+
+    /// Returns `true` if any files scanned did not have a header, or only had part of it
     pub fn has_failure(&self) -> bool {
-        !self.no_header_files.is_empty() || !self.binary_files.is_empty()
+        !self.no_header_files.is_empty()
+            || !self.binary_files.is_empty()
+            || !self.partial_header_files.is_empty()
+            || !self.drifted_header_files.is_empty()
+    }
+
+    /// The conventional process exit code for these results: `0` if no file failed, or `1` if
+    /// any did, as per [`FileResults::has_failure`].
+    ///
+    /// See [`FileResults::exit_code_of`] to also map errors to `2`.
+    pub fn exit_code(&self) -> i32 {
+        if self.has_failure() {
+            1
+        } else {
+            0
+        }
+    }
+
+    /// The conventional process exit code for the `result` of a recursive check: `2` if the
+    /// check failed with an error, otherwise as per [`FileResults::exit_code`].
+    pub fn exit_code_of<E>(result: &Result<FileResults, E>) -> i32 {
+        result.as_ref().map_or(2, FileResults::exit_code)
+    }
+
+    /// A human-readable summary of these results, as per [`report::Report`], e.g. for a CLI to
+    /// print after a check.
+    ///
+    /// This is the same as the results' [`fmt::Display`] rendering.
+    pub fn render_summary(&self) -> String {
+        self.to_string()
+    }
+
+    /// Compare these results against the `previous` results for the same files, e.g. from an
+    /// earlier run saved via the `serde` feature.
+    ///
+    /// This supports only failing on new violations in codebases that have existing ones.
+    pub fn diff(&self, previous: &FileResults) -> FileResultsDiff {
+        let current = self.failed_paths();
+        let previous = previous.failed_paths();
+        FileResultsDiff {
+            regressions: current.difference(&previous).cloned().collect(),
+            fixed: previous.difference(&current).cloned().collect(),
+        }
+    }
+
+    /// All paths that count as failures in [`FileResults::has_failure`]
+    fn failed_paths(&self) -> collections::BTreeSet<path::PathBuf> {
+        self.no_header_files
+            .iter()
+            .chain(self.binary_files.iter())
+            .chain(self.partial_header_files.iter())
+            .chain(self.drifted_header_files.iter())
+            .cloned()
+            .collect()
+    }
+}
+
+/// The differences between two [`FileResults`], as per [`FileResults::diff`].
+#[cfg(feature = "recursive")]
+#[derive(Clone, Default, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileResultsDiff {
+    /// Sorted paths that are failures now, but were not previously
+    pub regressions: Vec<path::PathBuf>,
+    /// Sorted paths that were failures previously, but are not now.
+    ///
+    /// This includes paths that were not checked in the newer results, e.g. because the file was
+    /// deleted.
+    pub fixed: Vec<path::PathBuf>,
+}
+
+#[cfg(feature = "recursive")]
+impl FileResultsDiff {
+    /// Returns `true` if any paths are newly failing
+    pub fn has_regression(&self) -> bool {
+        !self.regressions.is_empty()
     }
 }
 
+/// Renders an uncolored [`report::Report`] of the results.
+#[cfg(feature = "recursive")]
+impl fmt::Display for FileResults {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        report::Report::new(self).fmt(f)
+    }
+}
+
+#[cfg(feature = "recursive")]
 impl FromIterator<FileResult> for FileResults {
     fn from_iter<I>(iter: I) -> FileResults
     where
@@ -264,123 +1774,2320 @@ impl FromIterator<FileResult> for FileResults {
     {
         let mut results = FileResults::default();
         for result in iter {
-            match result.status {
-                CheckStatus::HeaderNotFound => results.no_header_files.push(result.path),
-                CheckStatus::BinaryFile => results.binary_files.push(result.path),
-            }
+            results.push(result);
         }
         results
     }
 }
 
-/// Recursively check for `header` in every file in `root` that matches `path_predicate`.
-///
-/// Checking the discovered files is parallelized across `num_threads` threads.
-///
-/// [`globset`](https://crates.io/crates/globset) is a useful crate for ignoring unwanted files in
-/// `path_predicate`.
+/// The results of a recursive operation that carries on past errors, such as
+/// [`check_headers_recursively_partial`], along with the errors.
+#[cfg(feature = "recursive")]
+#[derive(Debug)]
+pub struct PartialResults<R, E> {
+    /// The results for the files that were processed
+    pub results: R,
+    /// The errors for files that could not be processed, and for parts of the directory tree that
+    /// could not be searched
+    pub errors: Vec<E>,
+}
+
+#[cfg(feature = "recursive")]
+impl<R, E> PartialResults<R, E> {
+    /// Returns `true` if there were no errors, so that the results cover every file.
+    pub fn is_complete(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// The results if there were no errors, or else the first error, as the operations that stop
+    /// at the first error would return.
+    pub fn into_result(self) -> Result<R, E> {
+        match self.errors.into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(self.results),
+        }
+    }
+}
+
+/// Aggregated results for recursively adding or deleting headers in a directory tree of files.
+#[cfg(feature = "recursive")]
+#[derive(Clone, Default, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ModificationResults {
+    /// Paths that were modified
+    pub modified_files: Vec<path::PathBuf>,
+    /// Paths that were skipped because they exceeded [`RecursiveOptions::max_file_size`]
+    pub oversized_files: Vec<path::PathBuf>,
+    /// Paths that were left unmodified because adding the header failed
+    /// [`RecursiveOptions::verify_round_trip`]
+    pub round_trip_failures: Vec<path::PathBuf>,
+    /// Symlinks to files that were skipped, as per [`SymlinkPolicy::Report`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub symlinks: Vec<path::PathBuf>,
+}
+
+#[cfg(feature = "recursive")]
+impl ModificationResults {
+    /// The lists of paths in the results
+    pub(crate) fn path_lists_mut(&mut self) -> [&mut Vec<path::PathBuf>; 4] {
+        [
+            &mut self.modified_files,
+            &mut self.oversized_files,
+            &mut self.round_trip_failures,
+            &mut self.symlinks,
+        ]
+    }
+}
+
+/// The default capacity of the channels between the search for files, the threads processing
+/// them, and the collection of results, as per [`RecursiveOptions::channel_capacities`].
+#[cfg(feature = "recursive")]
+pub const DEFAULT_CHANNEL_CAPACITY: usize = 1024;
+
+/// Options that control the recursive operations, e.g. [`check_headers_recursively_with_options`].
 ///
-/// Returns a [`FileResults`] object containing the paths without headers detected, and the paths
-/// which were not UTF-8 text.
-pub fn check_headers_recursively(
-    root: &path::Path,
-    path_predicate: impl Fn(&path::Path) -> bool,
-    header: Header<impl HeaderChecker + 'static>,
-    num_threads: usize,
-) -> Result<FileResults, CheckHeadersRecursivelyError> {
-    let (path_tx, path_rx) = crossbeam::channel::unbounded::<path::PathBuf>();
-    let (result_tx, result_rx) = crossbeam::channel::unbounded();
-    // spawn a few threads to handle files in parallel
-    let handles = (0..num_threads)
-        .map(|_| {
-            let path_rx = path_rx.clone();
-            let result_tx = result_tx.clone();
-            let header = header.clone();
-            thread::spawn(move || {
-                for p in path_rx {
-                    match fs::File::open(&p).and_then(|mut f| header.header_present(&mut f)) {
-                        Ok(header_present) => {
-                            if header_present {
-                                // no op
-                            } else {
-                                let res = FileResult {
-                                    path: p,
-                                    status: CheckStatus::HeaderNotFound,
-                                };
-                                result_tx.send(Ok(res)).unwrap();
-                            }
-                        }
-                        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
-                            let res = FileResult {
-                                path: p,
-                                status: CheckStatus::BinaryFile,
-                            };
-                            result_tx.send(Ok(res)).unwrap();
-                        }
-                        Err(e) => result_tx
-                            .send(Err(CheckHeadersRecursivelyError::IoError(p, e)))
-                            .unwrap(),
+/// The default options process every file that matches the path predicate.
+#[cfg(feature = "recursive")]
+#[derive(Clone)]
+pub struct RecursiveOptions {
+    /// Files larger than this many bytes are skipped
+    max_file_size: Option<u64>,
+    /// The filesystem to find and operate on files in
+    file_system: sync::Arc<dyn filesystem::FileSystem>,
+    /// Cache of check results
+    cache: Option<sync::Arc<cache::CheckCache>>,
+    /// Callback for progress events
+    progress: Option<ProgressCallback>,
+    /// Periodically reported statistics about checks
+    statistics: Option<sync::Arc<statistics::Statistics>>,
+    /// Callback to confirm each modification
+    confirm: Option<ConfirmCallback>,
+    /// Journal to record modifications in
+    journal: Option<sync::Arc<journal::Journal>>,
+    /// Whether modifications are all or nothing
+    transactional: bool,
+    /// Whether added headers are verified to be deletable
+    verify_round_trip: bool,
+    /// Whether result paths are relative to the root they were found in
+    relative_paths: bool,
+    /// Capacity of the channel of found paths awaiting processing
+    path_capacity: usize,
+    /// Capacity of the channel of results awaiting collection
+    result_capacity: usize,
+    /// Checking stops once this many files without headers have been found
+    max_failures: Option<usize>,
+    /// Filter on files' metadata
+    file_filter: Option<FileFilter>,
+    /// Filter on which directories to descend into
+    dir_filter: Option<DirFilter>,
+    /// Whether to follow symlinks to directories
+    follow_symlinks: bool,
+    /// How binary files are treated when checking
+    binary_file_policy: BinaryFilePolicy,
+    /// How symlinks to files are treated
+    symlink_policy: SymlinkPolicy,
+    /// Whether checks require the entire header, rather than just what the checker looks for
+    strict_headers: bool,
+    /// Whether checks compare the header to comments with whitespace collapsed
+    normalize_whitespace: bool,
+    /// Where to get the paths to process from, rather than walking directories
+    path_source: Option<sync::Arc<dyn PathSource>>,
+    /// Whether files marked as generated or vendored in `.gitattributes` are skipped
+    respect_gitattributes: bool,
+    /// Literal paths and prefixes to skip
+    exclusions: Option<sync::Arc<PathExclusions>>,
+    /// Whether read-only files are made writable while they're modified
+    lift_read_only: bool,
+}
+
+#[cfg(feature = "recursive")]
+impl Default for RecursiveOptions {
+    fn default() -> Self {
+        Self {
+            max_file_size: None,
+            file_system: sync::Arc::new(filesystem::StdFileSystem),
+            cache: None,
+            progress: None,
+            statistics: None,
+            confirm: None,
+            journal: None,
+            transactional: false,
+            verify_round_trip: false,
+            relative_paths: false,
+            path_capacity: DEFAULT_CHANNEL_CAPACITY,
+            result_capacity: DEFAULT_CHANNEL_CAPACITY,
+            max_failures: None,
+            file_filter: None,
+            dir_filter: None,
+            follow_symlinks: false,
+            binary_file_policy: BinaryFilePolicy::default(),
+            symlink_policy: SymlinkPolicy::default(),
+            strict_headers: false,
+            normalize_whitespace: false,
+            path_source: None,
+            respect_gitattributes: false,
+            exclusions: None,
+            lift_read_only: false,
+        }
+    }
+}
+
+#[cfg(feature = "recursive")]
+impl fmt::Debug for RecursiveOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecursiveOptions")
+            .field("max_file_size", &self.max_file_size)
+            .field("cache", &self.cache)
+            .field("journal", &self.journal)
+            .field("transactional", &self.transactional)
+            .field("verify_round_trip", &self.verify_round_trip)
+            .field("relative_paths", &self.relative_paths)
+            .field("path_capacity", &self.path_capacity)
+            .field("result_capacity", &self.result_capacity)
+            .field("max_failures", &self.max_failures)
+            .field("follow_symlinks", &self.follow_symlinks)
+            .field("binary_file_policy", &self.binary_file_policy)
+            .field("symlink_policy", &self.symlink_policy)
+            .field("strict_headers", &self.strict_headers)
+            .field("normalize_whitespace", &self.normalize_whitespace)
+            .field("respect_gitattributes", &self.respect_gitattributes)
+            .field("exclusions", &self.exclusions)
+            .field("lift_read_only", &self.lift_read_only)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "recursive")]
+impl RecursiveOptions {
+    /// Construct the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Skip files larger than `bytes` rather than checking or modifying them.
+    ///
+    /// Skipped files are reported in the `oversized_files` of the operation's results. Huge
+    /// generated or data files are rarely legitimate header targets, and can otherwise dominate
+    /// the time spent on I/O.
+    pub fn max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = Some(bytes);
+        self
+    }
+
+    /// Find and operate on files in `file_system` rather than [`filesystem::StdFileSystem`].
+    pub fn file_system(mut self, file_system: sync::Arc<dyn filesystem::FileSystem>) -> Self {
+        self.file_system = file_system;
+        self
+    }
+
+    /// Use and update `cache` when checking, so that files that haven't changed since they were
+    /// last checked aren't read again.
+    ///
+    /// Only applies to checking, not to adding or deleting headers. Files are only cached if the
+    /// filesystem supports [`filesystem::FileSystem::modified`].
+    pub fn cache(mut self, cache: sync::Arc<cache::CheckCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Call `progress` as files are found and processed.
+    ///
+    /// Files are processed while the search for more files is still underway, so the total number
+    /// of files is only known once [`ProgressEvent::SearchFinished`] is reported. `progress` may be
+    /// called from multiple threads.
+    pub fn progress(mut self, progress: ProgressCallback) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Call `callback` with statistics about checks every `interval` while they run, and once more
+    /// when they finish, e.g. to log throughput during long checks.
+    ///
+    /// `callback` is called from a separate thread. See the [`statistics`] module.
+    pub fn statistics(
+        mut self,
+        interval: time::Duration,
+        callback: statistics::StatisticsCallback,
+    ) -> Self {
+        self.statistics = Some(sync::Arc::new(statistics::Statistics::new(
+            interval, callback,
+        )));
+        self
+    }
+
+    /// Show progress on `bar`, as per [`RecursiveOptions::progress`].
+    ///
+    /// The bar's length grows as files are found. It is not finished when the operation completes,
+    /// so keep a clone of it to finish it as desired.
+    ///
+    /// This requires the `indicatif` feature.
+    #[cfg(feature = "indicatif")]
+    pub fn progress_bar(self, bar: ::indicatif::ProgressBar) -> Self {
+        self.progress(progress_bar::progress_callback(bar))
+    }
+
+    /// Call `confirm` before each file is modified when adding or deleting headers, and act on
+    /// the [`Decision`] it returns, e.g. to prompt the user in an interactive tool.
+    ///
+    /// `confirm` is only called for files that would actually be modified.
+    pub fn confirm(mut self, confirm: ConfirmCallback) -> Self {
+        self.confirm = Some(confirm);
+        self
+    }
+
+    /// Record each modification made when adding or deleting headers in `journal`, so that they
+    /// can be undone with [`journal::rollback`].
+    ///
+    /// The original contents of each modified file are held in memory by the journal.
+    pub fn journal(mut self, journal: sync::Arc<journal::Journal>) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Make adding or deleting headers all or nothing if `transactional` is `true`.
+    ///
+    /// The new contents of every file are staged in memory and validated before any file is
+    /// written, so an error part way through (e.g. an unrecognized extension) leaves every file
+    /// untouched. When adding headers, validation checks that the header is detected in each
+    /// modified file.
+    ///
+    /// If writing the staged files fails, the files already written are restored on a best effort
+    /// basis.
+    pub fn transactional(mut self, transactional: bool) -> Self {
+        self.transactional = transactional;
+        self
+    }
+
+    /// Verify each header before it's added if `verify_round_trip` is `true`: the header must be
+    /// detected in the new contents, and deleting it must restore the original contents exactly.
+    ///
+    /// Files that fail verification are left unmodified and reported in `round_trip_failures`, so
+    /// that adding headers can later be safely undone by deleting them. Each file is modified in
+    /// memory first, so this is slower, but a useful precaution before modifying a large tree.
+    pub fn verify_round_trip(mut self, verify_round_trip: bool) -> Self {
+        self.verify_round_trip = verify_round_trip;
+        self
+    }
+
+    /// Report the paths in the operation's results relative to the root they were found in if
+    /// `relative_paths` is `true`, e.g. `src/main.rs` rather than `/path/to/repo/src/main.rs`.
+    ///
+    /// Paths given to callbacks, e.g. [`RecursiveOptions::progress`], are unaffected.
+    pub fn relative_paths(mut self, relative_paths: bool) -> Self {
+        self.relative_paths = relative_paths;
+        self
+    }
+
+    /// Queue at most `paths` found files awaiting processing, and `results` processed files
+    /// awaiting collection, when processing files in parallel.
+    ///
+    /// The search for files waits while the path queue is full, and the processing threads wait
+    /// while the result queue is full, which bounds memory use on huge trees. Both default to
+    /// [`DEFAULT_CHANNEL_CAPACITY`].
+    pub fn channel_capacities(mut self, paths: usize, results: usize) -> Self {
+        self.path_capacity = paths;
+        self.result_capacity = results;
+        self
+    }
+
+    /// Only process files for which `file_filter` returns `true`, in addition to the operation's
+    /// path predicate.
+    ///
+    /// `file_filter` is given the metadata gathered while finding files, so filtering e.g. by size
+    /// or to skip symlinks doesn't need more filesystem calls.
+    pub fn file_filter(mut self, file_filter: FileFilter) -> Self {
+        self.file_filter = Some(file_filter);
+        self
+    }
+
+    /// Only descend into directories for which `dir_filter` returns `true`, pruning entire
+    /// subtrees like `node_modules` or `target` from the search for files.
+    ///
+    /// The root directory of the operation is always searched.
+    pub fn dir_filter(mut self, dir_filter: DirFilter) -> Self {
+        self.dir_filter = Some(dir_filter);
+        self
+    }
+
+    /// Follow symlinks to directories when searching for files if `follow_symlinks` is `true`.
+    ///
+    /// Symlink cycles are skipped, and files reachable via multiple links are only processed
+    /// once, so that no file is modified twice in one operation.
+    pub fn follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Treat binary files found when checking as per `policy`, rather than reporting them in
+    /// `binary_files`.
+    pub fn binary_files(mut self, policy: BinaryFilePolicy) -> Self {
+        self.binary_file_policy = policy;
+        self
+    }
+
+    /// Treat symlinks to files as per `policy`, rather than processing the files they point to.
+    ///
+    /// Adding or deleting headers through a symlink modifies its target, which may be outside the
+    /// tree being processed, and leaves the symlink itself as it is.
+    pub fn symlinks(mut self, policy: SymlinkPolicy) -> Self {
+        self.symlink_policy = policy;
+        self
+    }
+
+    /// Stop checking once `count` files without headers (including binary files) have been found,
+    /// rather than checking every file.
+    ///
+    /// The search for files and the worker threads are stopped early, so the results contain at
+    /// most `count` failures and are otherwise incomplete, as indicated by
+    /// [`FileResults::stopped_early`]. Only applies to checking.
+    pub fn max_failures(mut self, count: usize) -> Self {
+        self.max_failures = Some(count);
+        self
+    }
+
+    /// Stop checking at the first file without a header if `fail_fast` is `true`, as per
+    /// [`RecursiveOptions::max_failures`], e.g. for a quick pre-commit check.
+    pub fn fail_fast(mut self, fail_fast: bool) -> Self {
+        self.max_failures = if fail_fast { Some(1) } else { None };
+        self
+    }
+
+    /// When checking, require the entire wrapped header to be present if `strict_headers` is
+    /// `true`, i.e. the same text that deleting the header looks for, rather than just what the
+    /// header's checker looks for.
+    ///
+    /// Files where the checker finds the header, but not all of it, e.g. because it was edited or
+    /// reformatted, are reported in [`FileResults::partial_header_files`], and are left unmodified
+    /// by [`fix_headers_recursively`].
+    pub fn strict_headers(mut self, strict_headers: bool) -> Self {
+        self.strict_headers = strict_headers;
+        self
+    }
+
+    /// If `normalize_whitespace` is `true`, files where the header isn't found, or only part of
+    /// it as per [`RecursiveOptions::strict_headers`], still have the header if its text is in a
+    /// comment block near where it's placed once runs of whitespace and blank lines are collapsed
+    /// in both.
+    ///
+    /// This avoids false results for headers that a formatter rewrapped or reindented. Adding
+    /// headers also leaves such files alone, rather than adding another copy.
+    pub fn normalize_whitespace(mut self, normalize_whitespace: bool) -> Self {
+        self.normalize_whitespace = normalize_whitespace;
+        self
+    }
+
+    /// Process the paths from `source` rather than walking the roots given to each operation,
+    /// e.g. to process the files in a build system's file graph.
+    ///
+    /// The path predicate, [`RecursiveOptions::file_filter`], and
+    /// [`RecursiveOptions::max_file_size`] still apply, but as no directories are walked,
+    /// [`RecursiveOptions::dir_filter`] doesn't.
+    pub fn path_source(mut self, source: sync::Arc<dyn PathSource>) -> Self {
+        self.path_source = Some(source);
+        self
+    }
+
+    /// Process `paths` rather than walking the roots given to each operation, as per
+    /// [`RecursiveOptions::path_source`], e.g. the output of `git ls-files`.
+    pub fn paths(self, paths: impl IntoIterator<Item = path::PathBuf>) -> Self {
+        self.path_source(sync::Arc::new(paths.into_iter().collect::<Vec<_>>()))
+    }
+
+    /// Skip files that are marked `linguist-generated` or `linguist-vendored` by the
+    /// `.gitattributes` files in the roots of each operation and their subdirectories, if
+    /// `respect_gitattributes` is `true`, as GitHub doesn't treat them as the project's code.
+    ///
+    /// As with git, patterns without a `/` match file names at any depth, rules in deeper
+    /// directories take precedence, and e.g. `-linguist-generated` or `linguist-generated=false`
+    /// unmarks files.
+    pub fn respect_gitattributes(mut self, respect_gitattributes: bool) -> Self {
+        self.respect_gitattributes = respect_gitattributes;
+        self
+    }
+
+    /// Skip the files that `exclusions` exclude, in addition to the operation's path predicate.
+    pub fn exclusions(mut self, exclusions: PathExclusions) -> Self {
+        self.exclusions = Some(sync::Arc::new(exclusions));
+        self
+    }
+
+    /// Modify read-only files by making them writable, and then read-only again once they're
+    /// modified, if `lift_read_only` is `true`.
+    ///
+    /// If a file can't be made writable, e.g. because it belongs to another user, the operation
+    /// fails with a `ReadOnly` error for it, e.g. [`AddHeadersRecursivelyError::ReadOnly`].
+    /// Otherwise, modifying read-only files on disk fails with an
+    /// [`io::ErrorKind::PermissionDenied`] error, as per [`filesystem::StdFileSystem`].
+    pub fn lift_read_only(mut self, lift_read_only: bool) -> Self {
+        self.lift_read_only = lift_read_only;
+        self
+    }
+
+    /// The filesystem to modify files in, which records modifications in the journal if there
+    /// is one, and lifts read-only attributes as per [`RecursiveOptions::lift_read_only`]
+    fn modification_file_system(&self) -> sync::Arc<dyn filesystem::FileSystem> {
+        let fs: sync::Arc<dyn filesystem::FileSystem> = if self.lift_read_only {
+            sync::Arc::new(filesystem::LiftingReadOnlyFileSystem {
+                inner: self.file_system.clone(),
+            })
+        } else {
+            self.file_system.clone()
+        };
+        match &self.journal {
+            Some(journal) => sync::Arc::new(journal::JournalingFileSystem {
+                inner: fs,
+                journal: journal.clone(),
+            }),
+            None => fs,
+        }
+    }
+
+    /// The filesystem to modify files in, as per [`RecursiveOptions::modification_file_system`],
+    /// along with the staging filesystem to commit if the options are transactional.
+    ///
+    /// If there is a staging filesystem, it's also the returned filesystem.
+    pub(crate) fn staged_modification_file_system(
+        &self,
+    ) -> (
+        sync::Arc<dyn filesystem::FileSystem>,
+        Option<sync::Arc<transaction::StagingFileSystem>>,
+    ) {
+        let fs = self.modification_file_system();
+        if self.transactional {
+            let staging = sync::Arc::new(transaction::StagingFileSystem::new(fs));
+            (staging.clone(), Some(staging))
+        } else {
+            (fs, None)
+        }
+    }
+
+    /// Make the paths in `lists` relative to the first of `roots` they're in, as per
+    /// [`RecursiveOptions::relative_paths`].
+    pub(crate) fn relativize<'a>(
+        &self,
+        roots: &[impl AsRef<path::Path>],
+        lists: impl IntoIterator<Item = &'a mut Vec<path::PathBuf>>,
+    ) {
+        if !self.relative_paths {
+            return;
+        }
+        for p in lists.into_iter().flatten() {
+            *p = self.relative_path(roots, std::mem::take(p));
+        }
+    }
+
+    /// Returns `p` relative to the first of `roots` it's in, as per
+    /// [`RecursiveOptions::relative_paths`].
+    pub(crate) fn relative_path(
+        &self,
+        roots: &[impl AsRef<path::Path>],
+        p: path::PathBuf,
+    ) -> path::PathBuf {
+        if !self.relative_paths {
+            return p;
+        }
+        roots
+            .iter()
+            .filter_map(|root| p.strip_prefix(root).ok())
+            // a root that's a file is left as is
+            .find(|relative| !relative.as_os_str().is_empty())
+            .map_or_else(|| p.clone(), path::Path::to_path_buf)
+    }
+
+    /// Returns `true` if adding `header` to the file at `p` passes verification as per
+    /// [`RecursiveOptions::verify_round_trip`], or if verification is disabled.
+    fn round_trips(
+        &self,
+        header: &Header<impl HeaderChecker>,
+        p: &path::Path,
+        cache: &EffectiveHeaderCache,
+    ) -> Result<bool, AddHeaderError> {
+        if !self.verify_round_trip {
+            return Ok(true);
+        }
+        let err_mapper = |e| AddHeaderError::IoError(p.to_path_buf(), e);
+        let read = |fs: &dyn filesystem::FileSystem| {
+            let mut contents = Vec::new();
+            fs.open(p)?.read_to_end(&mut contents).map(|_| contents)
+        };
+        let staging = transaction::StagingFileSystem::new(self.file_system.clone());
+        if !header.add_header_if_missing_cached(&staging, p, cache)? {
+            // the header is already present, so there's nothing to add
+            return Ok(true);
+        }
+        if !header_present_with_fs(header, &staging, p).map_err(err_mapper)? {
+            return Ok(false);
+        }
+        let deleted = header
+            .delete_header_if_present_cached(&staging, p, cache)
+            .map_err(|e| match e {
+                DeleteHeaderError::IoError(p, e) => AddHeaderError::IoError(p, e),
+                DeleteHeaderError::UnrecognizedExtension(p) => {
+                    AddHeaderError::UnrecognizedExtension(p)
+                }
+                DeleteHeaderError::ConcurrentModification(p) => {
+                    AddHeaderError::ConcurrentModification(p)
+                }
+            })?;
+        let restored = read(&staging).map_err(err_mapper)?;
+        Ok(deleted && restored == read(self.file_system.as_ref()).map_err(err_mapper)?)
+    }
+
+    /// The status of the binary file at `p` when checking for `header`, as per the binary file
+    /// policy
+    fn binary_file_status(
+        &self,
+        fs: &dyn filesystem::FileSystem,
+        header: &Header<impl HeaderChecker>,
+        p: &path::Path,
+    ) -> io::Result<Option<CheckStatus>> {
+        match self.binary_file_policy {
+            BinaryFilePolicy::Report => Ok(Some(CheckStatus::BinaryFile)),
+            BinaryFilePolicy::Skip => Ok(None),
+            BinaryFilePolicy::Fail => Ok(Some(CheckStatus::HeaderNotFound)),
+            BinaryFilePolicy::Sidecar => {
+                let mut sidecar = p.as_os_str().to_owned();
+                sidecar.push(".license");
+                match header_present_with_fs(header, fs, path::Path::new(&sidecar)) {
+                    Ok(true) => Ok(None),
+                    Ok(false) => Ok(Some(CheckStatus::HeaderNotFound)),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                        Ok(Some(CheckStatus::HeaderNotFound))
                     }
+                    Err(e) => Err(e),
                 }
-                // no more files
-            })
-        })
-        .collect::<Vec<thread::JoinHandle<()>>>();
-    // make sure result channel closes when threads complete
-    drop(result_tx);
-    find_files(root, path_predicate, path_tx)?;
-    let res: FileResults = result_rx.into_iter().collect::<Result<_, _>>()?;
-    for h in handles {
-        h.join().unwrap();
+            }
+        }
     }
-    Ok(res)
-}
 
-/// Errors that can occur when checking for headers recursively
-#[derive(Debug, thiserror::Error)]
-pub enum CheckHeadersRecursivelyError {
-    /// An I/O error occurred while checking the path
-    #[error("I/O error at {0:?}: {1}")]
-    IoError(path::PathBuf, io::Error),
-    /// `walkdir` could not navigate the directory structure
-    #[error("Walkdir error: {0}")]
-    WalkdirError(#[from] walkdir::Error),
+    /// Report `event` to the progress callback, if there is one
+    fn report_progress(&self, event: ProgressEvent<'_>) {
+        if let Some(statistics) = &self.statistics {
+            statistics.record(event);
+        }
+        if let Some(progress) = &self.progress {
+            progress(event)
+        }
+    }
+
+    /// Decide whether to make `change` to `p`, as per the confirmation callback.
+    ///
+    /// `is_applicable` determines whether `change` would modify the file, and is only called if
+    /// there is a callback to consult.
+    fn decide(
+        &self,
+        p: &path::Path,
+        change: PlannedChange,
+        is_applicable: impl FnOnce() -> io::Result<bool>,
+    ) -> io::Result<Decision> {
+        match &self.confirm {
+            None => Ok(Decision::Apply),
+            Some(confirm) => Ok(if is_applicable()? {
+                confirm(p, &change)
+            } else {
+                Decision::Skip
+            }),
+        }
+    }
 }
 
-/// Add the provided `header` to any file in `root` that matches `path_predicate` and that doesn't
-/// already have a header as determined by `checker`.
+/// A filter on the paths and metadata of files, as used by [`RecursiveOptions::file_filter`].
+#[cfg(feature = "recursive")]
+pub type FileFilter =
+    sync::Arc<dyn Fn(&path::Path, &filesystem::FileMetadata) -> bool + Send + Sync>;
+
+/// A filter on which directories to descend into, as used by [`RecursiveOptions::dir_filter`].
+#[cfg(feature = "recursive")]
+pub type DirFilter = sync::Arc<dyn Fn(&path::Path) -> bool + Send + Sync>;
+
+/// A source of paths for recursive operations to process rather than walking directories, as per
+/// [`RecursiveOptions::path_source`], e.g. a build system's file graph or a database.
 ///
-/// Returns a list of paths that had headers added.
-pub fn add_headers_recursively(
-    root: &path::Path,
-    path_predicate: impl Fn(&path::Path) -> bool,
+/// # Examples
+///
+/// ```
+/// // Copyright 2023 Google LLC.
+/// // SPDX-License-Identifier: Apache-2.0
+/// use file_header::*;
+/// use std::{path::{Path, PathBuf}, sync::Arc};
+///
+/// /// Paths relative to each root
+/// struct Manifest(Vec<String>);
+///
+/// impl PathSource for Manifest {
+///     fn paths(&self, roots: &[&Path]) -> Box<dyn Iterator<Item = PathBuf> + '_> {
+///         let roots = roots.iter().map(|r| r.to_path_buf()).collect::<Vec<_>>();
+///         Box::new(
+///             roots
+///                 .into_iter()
+///                 .flat_map(move |root| self.0.iter().map(move |name| root.join(name))),
+///         )
+///     }
+/// }
+///
+/// let options = RecursiveOptions::new()
+///     .path_source(Arc::new(Manifest(vec!["src/main.rs".to_string()])));
+/// ```
+#[cfg(feature = "recursive")]
+pub trait PathSource: Send + Sync {
+    /// The paths of the files to process, given the `roots` of the operation.
+    ///
+    /// Paths that don't exist are reported as errors when they're processed.
+    fn paths(&self, roots: &[&path::Path]) -> Box<dyn Iterator<Item = path::PathBuf> + '_>;
+}
+
+#[cfg(feature = "recursive")]
+impl PathSource for Vec<path::PathBuf> {
+    fn paths(&self, _roots: &[&path::Path]) -> Box<dyn Iterator<Item = path::PathBuf> + '_> {
+        Box::new(self.iter().cloned())
+    }
+}
+
+/// Literal paths and path prefixes to exclude from recursive operations, as used by
+/// [`RecursiveOptions::exclusions`], e.g. from a list of exempted files checked into a repository.
+///
+/// Relative paths are relative to the root of each operation, and absolute paths match files'
+/// full paths. Prefixes match whole path components, so the prefix `src/gen` excludes
+/// `src/gen/api.rs`, but not `src/generated.rs`.
+///
+/// # Examples
+///
+/// ```
+/// // Copyright 2023 Google LLC.
+/// // SPDX-License-Identifier: Apache-2.0
+/// use file_header::*;
+/// use std::path::Path;
+///
+/// let exclusions = PathExclusions::parse("# exempt from headers\nsrc/lib.rs\nthird_party/\n");
+/// assert!(exclusions.excludes(Path::new("src/lib.rs")));
+/// assert!(exclusions.excludes(Path::new("third_party/foo/foo.c")));
+/// assert!(!exclusions.excludes(Path::new("src/main.rs")));
+/// let options = RecursiveOptions::new().exclusions(exclusions);
+/// ```
+#[cfg(feature = "recursive")]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PathExclusions {
+    paths: collections::BTreeSet<path::PathBuf>,
+    prefixes: Vec<path::PathBuf>,
+}
+
+#[cfg(feature = "recursive")]
+impl PathExclusions {
+    /// Construct `PathExclusions` that don't exclude anything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Exclude the file at `p`.
+    pub fn path(mut self, p: impl Into<path::PathBuf>) -> Self {
+        self.paths.insert(without_current_dir(p.into()));
+        self
+    }
+
+    /// Exclude the files in the directory `prefix`, and any file at `prefix` itself.
+    pub fn prefix(mut self, prefix: impl Into<path::PathBuf>) -> Self {
+        self.prefixes.push(without_current_dir(prefix.into()));
+        self
+    }
+
+    /// Parse a list of exclusions with one per line, where lines ending with `/` are prefixes,
+    /// and other lines are paths. Blank lines, and lines starting with `#`, are ignored.
+    pub fn parse(list: &str) -> Self {
+        list.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .fold(Self::new(), |exclusions, line| {
+                match line.strip_suffix('/') {
+                    Some(prefix) => exclusions.prefix(prefix),
+                    None => exclusions.path(line),
+                }
+            })
+    }
+
+    /// Returns `true` if the file at `p` is excluded.
+    pub fn excludes(&self, p: &path::Path) -> bool {
+        let p = p.strip_prefix(".").unwrap_or(p);
+        self.paths.contains(p) || self.prefixes.iter().any(|prefix| p.starts_with(prefix))
+    }
+}
+
+/// `p` without a leading `./`
+#[cfg(feature = "recursive")]
+fn without_current_dir(p: path::PathBuf) -> path::PathBuf {
+    match p.strip_prefix(".") {
+        Ok(rel) => rel.to_path_buf(),
+        Err(_) => p,
+    }
+}
+
+/// How binary files are treated when checking for headers, as used by
+/// [`RecursiveOptions::binary_files`]
+#[cfg(feature = "recursive")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BinaryFilePolicy {
+    /// Report binary files in [`FileResults::binary_files`]
+    #[default]
+    Report,
+    /// Leave binary files out of the results
+    Skip,
+    /// Report binary files in [`FileResults::no_header_files`], as if they were missing headers
+    Fail,
+    /// Check for the header in a [REUSE](https://reuse.software/) sidecar file, i.e.
+    /// `image.png.license` for `image.png`, and report the binary file in
+    /// [`FileResults::no_header_files`] if the sidecar is missing or lacks the header.
+    Sidecar,
+}
+
+/// How symlinks to files are treated by recursive operations, as used by
+/// [`RecursiveOptions::symlinks`]
+///
+/// Only the path predicate and [`RecursiveOptions::file_filter`] are applied to symlinks before
+/// the policy, so skipped and reported symlinks are those that would otherwise be processed.
+#[cfg(feature = "recursive")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Process the file that each symlink points to, as if it were in the symlink's place
+    #[default]
+    Follow,
+    /// Leave symlinks out of the operation and its results
+    Skip,
+    /// Leave symlinks out of the operation, but report them in the `symlinks` of its results,
+    /// e.g. [`FileResults::symlinks`]
+    Report,
+}
+
+/// A callback that decides whether to make a [`PlannedChange`] to a file, as used by
+/// [`RecursiveOptions::confirm`].
+#[cfg(feature = "recursive")]
+pub type ConfirmCallback = sync::Arc<dyn Fn(&path::Path, &PlannedChange) -> Decision + Send + Sync>;
+
+/// A modification that is about to be made to a file
+#[cfg(feature = "recursive")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlannedChange {
+    /// The header will be added
+    AddHeader,
+    /// The header will be deleted
+    DeleteHeader,
+}
+
+/// Whether to make a [`PlannedChange`]
+#[cfg(feature = "recursive")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decision {
+    /// Make the change
+    Apply,
+    /// Leave the file unmodified, and continue with the next file
+    Skip,
+    /// Leave the file unmodified, and stop the operation with an `Aborted` error
+    Abort,
+}
+
+/// A callback for [`ProgressEvent`]s, as used by [`RecursiveOptions::progress`].
+#[cfg(feature = "recursive")]
+pub type ProgressCallback = sync::Arc<dyn Fn(ProgressEvent<'_>) + Send + Sync>;
+
+/// Progress through a recursive operation, as reported to [`RecursiveOptions::progress`].
+#[cfg(feature = "recursive")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgressEvent<'a> {
+    /// A file to be processed was found
+    Found(&'a path::Path),
+    /// Processing a file finished, successfully or not
+    Processed(&'a path::Path),
+    /// The search for files finished, so there will be no more [`ProgressEvent::Found`] events
+    SearchFinished,
+}
+
+/// Recursively check for `header` in every file in `root` that matches `path_predicate`.
+///
+/// Checking the discovered files is parallelized across `num_threads` threads.
+///
+/// [`globset`](https://crates.io/crates/globset) is a useful crate for ignoring unwanted files in
+/// `path_predicate`.
+///
+/// Returns a [`FileResults`] object containing the paths without headers detected, and the paths
+/// which were not UTF-8 text.
+#[cfg(feature = "recursive")]
+pub fn check_headers_recursively(
+    root: &path::Path,
+    path_predicate: impl Fn(&path::Path) -> bool,
+    header: Header<impl HeaderChecker>,
+    num_threads: usize,
+) -> Result<FileResults, CheckHeadersRecursivelyError> {
+    check_headers_recursively_with_options(
+        root,
+        path_predicate,
+        header,
+        num_threads,
+        &RecursiveOptions::new(),
+    )
+}
+
+/// Recursively check for `header` in every file in `root` that matches `path_predicate`, as per
+/// [`check_headers_recursively`], with the behavior adjusted by `options`.
+#[cfg(feature = "recursive")]
+pub fn check_headers_recursively_with_options(
+    root: &path::Path,
+    path_predicate: impl Fn(&path::Path) -> bool,
+    header: Header<impl HeaderChecker>,
+    num_threads: usize,
+    options: &RecursiveOptions,
+) -> Result<FileResults, CheckHeadersRecursivelyError> {
+    check_headers_recursively_in_roots(&[root], path_predicate, header, num_threads, options)
+}
+
+/// Recursively check for `header` in every file in each of `roots` that matches
+/// `path_predicate`, as per [`check_headers_recursively_with_options`], merging the results.
+///
+/// The roots are searched in turn, sharing the same threads, and files under more than one root
+/// are only checked once.
+#[cfg(feature = "recursive")]
+pub fn check_headers_recursively_in_roots(
+    roots: &[impl AsRef<path::Path>],
+    path_predicate: impl Fn(&path::Path) -> bool,
+    header: Header<impl HeaderChecker>,
+    num_threads: usize,
+    options: &RecursiveOptions,
+) -> Result<FileResults, CheckHeadersRecursivelyError> {
+    let mut results = check_found_files(header, num_threads, options, |path_tx| {
+        find_files(roots, path_predicate, options, path_tx)
+    })
+    .map_err(|e| e.relativize(roots, options))?;
+    options.relativize(roots, results.path_lists_mut());
+    Ok(results)
+}
+
+/// Recursively check for `header` in every file in `root` that matches `path_predicate`, as per
+/// [`check_headers_recursively_with_options`], carrying on past errors.
+///
+/// Errors checking a file, or searching part of the directory tree, are returned with the results
+/// for the rest of the files, rather than discarding them. Each file whose check panicked is a
+/// separate [`CheckHeadersRecursivelyError::Unprocessed`] error, with empty results.
+///
+/// # Examples
+///
+/// ```
+/// // Copyright 2023 Google LLC.
+/// // SPDX-License-Identifier: Apache-2.0
+/// use file_header::*;
+///
+/// # let dir = tempfile::tempdir().unwrap();
+/// # let root = dir.path();
+/// # std::fs::write(root.join("main.rs"), "fn main() {}\n").unwrap();
+/// let header = Header::new(
+///     SingleLineChecker::new("Foo License".to_string(), 10),
+///     "Foo License".to_string(),
+/// );
+/// let partial = check_headers_recursively_partial(root, |_p| true, header, 4, &RecursiveOptions::new());
+/// assert!(partial.is_complete());
+/// assert_eq!(vec![root.join("main.rs")], partial.results.no_header_files);
+/// ```
+#[cfg(feature = "recursive")]
+pub fn check_headers_recursively_partial(
+    root: &path::Path,
+    path_predicate: impl Fn(&path::Path) -> bool,
+    header: Header<impl HeaderChecker>,
+    num_threads: usize,
+    options: &RecursiveOptions,
+) -> PartialResults<FileResults, CheckHeadersRecursivelyError> {
+    let mut walk_errors = Vec::new();
+    let mut partial = check_found_files_partial(header, num_threads, options, true, |path_tx| {
+        find_files_with(&[root], path_predicate, options, path_tx, |e| {
+            walk_errors.push(e);
+            Ok(())
+        })
+    });
+    partial
+        .errors
+        .extend(walk_errors.into_iter().map(Into::into));
+    partial.errors = partial
+        .errors
+        .into_iter()
+        .map(|e| e.relativize(&[root], options))
+        .collect();
+    options.relativize(&[root], partial.results.path_lists_mut());
+    partial
+}
+
+/// Recursively check for `header` in every file in `root` that matches `path_predicate`, as per
+/// [`check_headers_recursively_with_options`], returning the status of every file found.
+///
+/// Unlike the other checks, errors reading a file don't stop the check, but are reported as that
+/// file's status, so that every file is accounted for. [`RecursiveOptions::max_failures`] is not
+/// used.
+#[cfg(feature = "recursive")]
+pub fn check_headers_recursively_detailed(
+    root: &path::Path,
+    path_predicate: impl Fn(&path::Path) -> bool,
+    header: Header<impl HeaderChecker>,
+    num_threads: usize,
+    options: &RecursiveOptions,
+) -> Result<collections::BTreeMap<path::PathBuf, FileStatus>, CheckHeadersRecursivelyError> {
+    let (path_tx, path_rx) = crossbeam::channel::bounded::<path::PathBuf>(options.path_capacity);
+    let (status_tx, status_rx) = crossbeam::channel::bounded(options.result_capacity);
+    let _ticker = options.statistics.as_ref().map(|s| s.start());
+    let statuses = thread::scope(|s| {
+        for _ in 0..num_threads {
+            let path_rx = path_rx.clone();
+            let status_tx = status_tx.clone();
+            let header = &header;
+            s.spawn(move || {
+                for p in path_rx {
+                    let status = file_status(header, options, &p);
+                    options.report_progress(ProgressEvent::Processed(&p));
+                    if status_tx.send((p, status)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(status_tx);
+        drop(path_rx);
+        // statuses are collected during the search, as the channels are bounded
+        let collector = s.spawn(move || {
+            status_rx
+                .into_iter()
+                .collect::<collections::BTreeMap<_, _>>()
+        });
+        let skipped = find_files(&[root], path_predicate, options, path_tx)?;
+        let mut statuses = collector
+            .join()
+            .unwrap_or_else(|payload| panic::resume_unwind(payload));
+        statuses.extend(
+            skipped
+                .oversized
+                .into_iter()
+                .map(|p| (p, FileStatus::Skipped))
+                .chain(
+                    skipped
+                        .symlinks
+                        .into_iter()
+                        .map(|p| (p, FileStatus::Symlink)),
+                ),
+        );
+        Ok::<_, CheckHeadersRecursivelyError>(statuses)
+    })?;
+    Ok(statuses
+        .into_iter()
+        .map(|(p, status)| (options.relative_path(&[root], p), status))
+        .collect())
+}
+
+/// The status of a single file, as per [`check_headers_recursively_detailed`]
+#[cfg(feature = "recursive")]
+#[derive(Debug)]
+pub enum FileStatus {
+    /// The file has the header
+    Ok,
+    /// The file does not have the header
+    MissingHeader,
+    /// The file has only part of the header, as per [`RecursiveOptions::strict_headers`]
+    PartialHeader,
+    /// The file has a header that drifted from the header, as per [`HeaderChecker::drifted`]
+    DriftedHeader,
+    /// The file appears to be binary, and is reported as per [`BinaryFilePolicy::Report`]
+    Binary,
+    /// The file was not checked, because it exceeded [`RecursiveOptions::max_file_size`], or is
+    /// binary and skipped as per [`BinaryFilePolicy::Skip`]
+    Skipped,
+    /// The file is a symlink, which was not checked, and is reported as per
+    /// [`SymlinkPolicy::Report`]
+    Symlink,
+    /// The file could not be checked
+    Error(io::Error),
+}
+
+/// Recursively check every file in `root` that matches `path_predicate` for each of `headers`, as
+/// per [`check_headers_recursively_with_options`], returning which header each file has.
+///
+/// Files are attributed to the first of `headers` they have, so more specific headers should
+/// come first. This supports policies on which headers are acceptable where, e.g. only allowing
+/// the GPL in `third_party`.
+///
+/// [`RecursiveOptions::cache`] and [`RecursiveOptions::max_failures`] are not used.
+///
+/// # Examples
+///
+/// ```
+/// // Copyright 2023 Google LLC.
+/// // SPDX-License-Identifier: Apache-2.0
+/// use file_header::*;
+///
+/// # let dir = tempfile::tempdir().unwrap();
+/// # let root = dir.path();
+/// # std::fs::create_dir(root.join("third_party")).unwrap();
+/// # std::fs::write(root.join("third_party/lib.rs"), "// GPL\n").unwrap();
+/// # std::fs::write(root.join("main.rs"), "// Apache\n").unwrap();
+/// let headers = [
+///     Header::new(SingleLineChecker::new("Apache".to_string(), 10), "Apache".to_string()),
+///     Header::new(SingleLineChecker::new("GPL".to_string(), 10), "GPL".to_string()),
+/// ];
+/// let matches = check_headers_recursively_matching(
+///     root,
+///     |_p| true,
+///     &headers,
+///     4,
+///     &RecursiveOptions::new().relative_paths(true),
+/// )
+/// .unwrap();
+/// let gpl_outside_third_party = matches
+///     .files_matching(1)
+///     .filter(|p| !p.starts_with("third_party"))
+///     .count();
+/// assert_eq!(0, gpl_outside_third_party);
+/// ```
+#[cfg(feature = "recursive")]
+pub fn check_headers_recursively_matching<C: HeaderChecker>(
+    root: &path::Path,
+    path_predicate: impl Fn(&path::Path) -> bool,
+    headers: &[Header<C>],
+    num_threads: usize,
+    options: &RecursiveOptions,
+) -> Result<HeaderMatches, CheckHeadersRecursivelyError> {
+    let (path_tx, path_rx) = crossbeam::channel::bounded::<path::PathBuf>(options.path_capacity);
+    let (outcome_tx, outcome_rx) = crossbeam::channel::bounded(options.result_capacity);
+    let _ticker = options.statistics.as_ref().map(|s| s.start());
+    let mut matches = thread::scope(|s| {
+        for _ in 0..num_threads {
+            let path_rx = path_rx.clone();
+            let outcome_tx = outcome_tx.clone();
+            s.spawn(move || {
+                for p in path_rx {
+                    let outcome = match_headers(headers, options, &p)
+                        .map_err(|e| CheckHeadersRecursivelyError::IoError(p.clone(), e));
+                    options.report_progress(ProgressEvent::Processed(&p));
+                    let failed = outcome.is_err();
+                    if outcome_tx.send(outcome.map(|o| (p, o))).is_err() || failed {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(outcome_tx);
+        drop(path_rx);
+        // outcomes are collected during the search, as the channels are bounded
+        let collector = s.spawn(move || {
+            let mut matches = HeaderMatches::default();
+            for outcome in outcome_rx {
+                match outcome? {
+                    (p, HeaderMatch::Header(index)) => {
+                        matches.matches.insert(p, index);
+                    }
+                    (p, HeaderMatch::Missing(status)) => {
+                        matches.results.push(FileResult { path: p, status })
+                    }
+                    (_, HeaderMatch::Skipped) => {}
+                }
+            }
+            Ok::<_, CheckHeadersRecursivelyError>(matches)
+        });
+        let skipped = find_files(&[root], path_predicate, options, path_tx)?;
+        let mut matches = collector
+            .join()
+            .unwrap_or_else(|payload| panic::resume_unwind(payload))?;
+        matches.results.oversized_files = skipped.oversized;
+        matches.results.symlinks = skipped.symlinks;
+        Ok::<_, CheckHeadersRecursivelyError>(matches)
+    })?;
+    options.relativize(&[root], matches.results.path_lists_mut());
+    matches.matches = std::mem::take(&mut matches.matches)
+        .into_iter()
+        .map(|(p, index)| (options.relative_path(&[root], p), index))
+        .collect();
+    Ok(matches)
+}
+
+/// Which header each file has, as per [`check_headers_recursively_matching`]
+#[cfg(feature = "recursive")]
+#[derive(Clone, Default, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HeaderMatches {
+    /// Paths that have one of the headers, with the index of the first header they have
+    pub matches: collections::BTreeMap<path::PathBuf, usize>,
+    /// Paths that have none of the headers, and those that were skipped as oversized
+    pub results: FileResults,
+}
+
+#[cfg(feature = "recursive")]
+impl HeaderMatches {
+    /// The paths that have the header at `index`, in order.
+    pub fn files_matching(&self, index: usize) -> impl Iterator<Item = &path::Path> {
+        self.matches
+            .iter()
+            .filter(move |(_, i)| **i == index)
+            .map(|(p, _)| p.as_path())
+    }
+}
+
+/// How a file matched, as per [`match_headers`]
+#[cfg(feature = "recursive")]
+enum HeaderMatch {
+    /// The index of the first header the file has
+    Header(usize),
+    /// The reason the file has none of the headers
+    Missing(CheckStatus),
+    /// A binary file skipped as per [`BinaryFilePolicy::Skip`]
+    Skipped,
+}
+
+/// Check for each of `headers` in the file at `p`, in turn, until one is found.
+#[cfg(feature = "recursive")]
+fn match_headers(
+    headers: &[Header<impl HeaderChecker>],
+    options: &RecursiveOptions,
+    p: &path::Path,
+) -> io::Result<HeaderMatch> {
+    let fs = options.file_system.as_ref();
+    let mut missing = CheckStatus::HeaderNotFound;
+    for (index, header) in headers.iter().enumerate() {
+        match check_file_with_options(fs, header, options, p)? {
+            None => return Ok(HeaderMatch::Header(index)),
+            Some(CheckStatus::BinaryFile) => match options.binary_file_status(fs, header, p)? {
+                // the sidecar file has this header
+                None if options.binary_file_policy == BinaryFilePolicy::Sidecar => {
+                    return Ok(HeaderMatch::Header(index))
+                }
+                None => return Ok(HeaderMatch::Skipped),
+                Some(CheckStatus::BinaryFile) => {
+                    return Ok(HeaderMatch::Missing(CheckStatus::BinaryFile))
+                }
+                Some(_) => {}
+            },
+            // a partial or drifted header is more useful to report than a missing one
+            Some(CheckStatus::HeaderNotFound) => {}
+            Some(status) => missing = status,
+        }
+    }
+    Ok(HeaderMatch::Missing(missing))
+}
+
+/// Check for `header` in every path that `find` publishes into the channel it's given, with
+/// checking parallelized across `num_threads` scoped threads.
+///
+/// `find` returns the paths that were skipped, as per [`publish_files`].
+#[cfg(feature = "recursive")]
+pub(crate) fn check_found_files(
+    header: Header<impl HeaderChecker>,
+    num_threads: usize,
+    options: &RecursiveOptions,
+    find: impl FnOnce(
+        crossbeam::channel::Sender<path::PathBuf>,
+    ) -> Result<SkippedFiles, filesystem::WalkError>,
+) -> Result<FileResults, CheckHeadersRecursivelyError> {
+    check_found_files_partial(header, num_threads, options, false, find).into_check_result()
+}
+
+/// Check for `header` in every path that `find` publishes, as per [`check_found_files`], returning
+/// every error along with the results.
+///
+/// If `keep_going` is set, errors checking a file don't stop the rest being checked.
+#[cfg(feature = "recursive")]
+fn check_found_files_partial(
+    header: Header<impl HeaderChecker>,
+    num_threads: usize,
+    options: &RecursiveOptions,
+    keep_going: bool,
+    find: impl FnOnce(
+        crossbeam::channel::Sender<path::PathBuf>,
+    ) -> Result<SkippedFiles, filesystem::WalkError>,
+) -> PartialResults<FileResults, CheckHeadersRecursivelyError> {
+    let (path_tx, path_rx) = crossbeam::channel::bounded::<path::PathBuf>(options.path_capacity);
+    let (result_tx, result_rx) = crossbeam::channel::bounded(options.result_capacity);
+    let limit = FailureLimit::new(options.max_failures);
+    let _ticker = options.statistics.as_ref().map(|s| s.start());
+    // the scope joins the threads, and propagates any panics, before returning
+    let mut partial = thread::scope(|s| {
+        // spawn a few threads to handle files in parallel
+        for _ in 0..num_threads {
+            let path_rx = path_rx.clone();
+            let result_tx = result_tx.clone();
+            let header = &header;
+            let limit = &limit;
+            s.spawn(move || check_paths(header, options, path_rx, result_tx, limit, keep_going));
+        }
+        collect_check_results(path_tx, path_rx, result_tx, result_rx, find)
+    });
+    partial.results.stopped_early = limit.is_incomplete();
+    partial
+}
+
+/// Check for `header` in every path that `find` publishes into the channel it's given, as per
+/// [`check_found_files`], with checking parallelized across the threads in `pool`.
+#[cfg(feature = "recursive")]
+pub(crate) fn check_found_files_in_pool(
+    header: Header<impl HeaderChecker + 'static>,
+    pool: &pool::ThreadPool,
+    options: &RecursiveOptions,
+    find: impl FnOnce(
+        crossbeam::channel::Sender<path::PathBuf>,
+    ) -> Result<SkippedFiles, filesystem::WalkError>,
+) -> Result<FileResults, CheckHeadersRecursivelyError> {
+    let (path_tx, path_rx) = crossbeam::channel::bounded::<path::PathBuf>(options.path_capacity);
+    let (result_tx, result_rx) = crossbeam::channel::bounded(options.result_capacity);
+    let limit = sync::Arc::new(FailureLimit::new(options.max_failures));
+    let _ticker = options.statistics.as_ref().map(|s| s.start());
+    // use every thread in the pool to handle files in parallel
+    let jobs = (0..pool.size())
+        .map(|_| {
+            let path_rx = path_rx.clone();
+            let result_tx = result_tx.clone();
+            let header = header.clone();
+            let options = options.clone();
+            let limit = limit.clone();
+            pool.execute(move || check_paths(&header, &options, path_rx, result_tx, &limit, false))
+        })
+        .collect::<Vec<_>>();
+    let mut partial = collect_check_results(path_tx, path_rx, result_tx, result_rx, find);
+    for job in jobs {
+        if let Ok(Err(payload)) = job.recv() {
+            panic::resume_unwind(payload);
+        }
+    }
+    partial.results.stopped_early = limit.is_incomplete();
+    partial.into_check_result()
+}
+
+/// Run `find`, and collect the results and errors sent by the threads checking the paths it finds.
+///
+/// The channel ends held for cloning into the threads are dropped first, so that the result
+/// channel closes when the threads complete, and the search stops if they all stop early. Results
+/// are collected on another thread during the search, as the channels may be bounded.
+#[cfg(feature = "recursive")]
+fn collect_check_results(
+    path_tx: crossbeam::channel::Sender<path::PathBuf>,
+    path_rx: crossbeam::channel::Receiver<path::PathBuf>,
+    result_tx: crossbeam::channel::Sender<Result<FileResult, CheckHeadersRecursivelyError>>,
+    result_rx: crossbeam::channel::Receiver<Result<FileResult, CheckHeadersRecursivelyError>>,
+    find: impl FnOnce(
+        crossbeam::channel::Sender<path::PathBuf>,
+    ) -> Result<SkippedFiles, filesystem::WalkError>,
+) -> PartialResults<FileResults, CheckHeadersRecursivelyError> {
+    drop(result_tx);
+    drop(path_rx);
+    thread::scope(|s| {
+        let collector = s.spawn(move || {
+            let mut files = Vec::new();
+            let mut errors = Vec::new();
+            for result in result_rx {
+                match result {
+                    Ok(file) => files.push(file),
+                    Err(e) => errors.push(e),
+                }
+            }
+            (files.into_iter().collect::<FileResults>(), errors)
+        });
+        let found = find(path_tx);
+        let (mut results, mut errors) = collector
+            .join()
+            .unwrap_or_else(|payload| panic::resume_unwind(payload));
+        match found {
+            Ok(skipped) => {
+                results.oversized_files = skipped.oversized;
+                results.symlinks = skipped.symlinks;
+            }
+            // the search failing takes precedence over the errors for the files it found
+            Err(e) => errors.insert(0, e.into()),
+        }
+        PartialResults { results, errors }
+    })
+}
+
+#[cfg(feature = "recursive")]
+impl PartialResults<FileResults, CheckHeadersRecursivelyError> {
+    /// The result of a check that fails at the first error, other than files that panicked, which
+    /// are reported together with the results for the rest of the files.
+    fn into_check_result(self) -> Result<FileResults, CheckHeadersRecursivelyError> {
+        let mut unprocessed = Vec::new();
+        for e in self.errors {
+            match e {
+                CheckHeadersRecursivelyError::Unprocessed(_, files) => unprocessed.extend(files),
+                e => return Err(e),
+            }
+        }
+        if unprocessed.is_empty() {
+            Ok(self.results)
+        } else {
+            unprocessed.sort();
+            Err(CheckHeadersRecursivelyError::Unprocessed(
+                Box::new(self.results),
+                unprocessed,
+            ))
+        }
+    }
+}
+
+/// Tracks failures across the threads of a check, so that they can stop once
+/// [`RecursiveOptions::max_failures`] is reached
+#[cfg(feature = "recursive")]
+struct FailureLimit {
+    max: usize,
+    failures: atomic::AtomicUsize,
+    /// Set when the threads should stop early, e.g. after enough failures
+    stop: atomic::AtomicBool,
+    /// Set when a file was left unchecked or unreported due to stopping early
+    incomplete: atomic::AtomicBool,
+}
+
+#[cfg(feature = "recursive")]
+impl FailureLimit {
+    fn new(max: Option<usize>) -> Self {
+        Self {
+            max: max.unwrap_or(usize::MAX),
+            failures: atomic::AtomicUsize::new(0),
+            stop: atomic::AtomicBool::new(false),
+            incomplete: atomic::AtomicBool::new(false),
+        }
+    }
+
+    /// Record a failure, returning `true` if it's within the limit and should be reported.
+    fn record_failure(&self) -> bool {
+        // claim a slot so that no more than the maximum are reported
+        let count = self.failures.fetch_add(1, atomic::Ordering::Relaxed) + 1;
+        if count >= self.max {
+            self.stop();
+        }
+        if count > self.max {
+            self.mark_incomplete();
+        }
+        count <= self.max
+    }
+
+    fn stop(&self) {
+        self.stop.store(true, atomic::Ordering::Relaxed);
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.stop.load(atomic::Ordering::Relaxed)
+    }
+
+    /// Record that a file was left unchecked, e.g. as it was received after stopping.
+    fn mark_incomplete(&self) {
+        self.incomplete.store(true, atomic::Ordering::Relaxed);
+    }
+
+    /// Returns `true` if any file was left unchecked or unreported, so that the check stopped
+    /// early. Finding exactly the maximum number of failures in the last files doesn't count.
+    fn is_incomplete(&self) -> bool {
+        self.incomplete.load(atomic::Ordering::Relaxed)
+    }
+}
+
+/// Check for `header` in each path received from `paths`, sending the files without headers and
+/// any errors to `results`, until there are no more paths or `limit` is reached.
+///
+/// Unless `keep_going` is set, an error checking a file stops the other threads too.
+#[cfg(feature = "recursive")]
+fn check_paths(
+    header: &Header<impl HeaderChecker>,
+    options: &RecursiveOptions,
+    paths: crossbeam::channel::Receiver<path::PathBuf>,
+    results: crossbeam::channel::Sender<Result<FileResult, CheckHeadersRecursivelyError>>,
+    limit: &FailureLimit,
+    keep_going: bool,
+) {
+    let fs = options.file_system.as_ref();
+    for p in paths {
+        if limit.is_stopped() {
+            // the search only stops once every thread has, so this covers any files it skips
+            limit.mark_incomplete();
+            break;
+        }
+        // a panic is recorded against the file, rather than ending the thread
+        let outcome = match panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            check_path(header, options, &p).and_then(|status| match status {
+                // the policy is applied after caching, so the cache is valid whatever the policy
+                Some(CheckStatus::BinaryFile) => options.binary_file_status(fs, header, &p),
+                status => Ok(status),
+            })
+        })) {
+            Ok(outcome) => outcome.map_err(|e| CheckHeadersRecursivelyError::IoError(p.clone(), e)),
+            Err(payload) => Err(CheckHeadersRecursivelyError::Unprocessed(
+                Box::default(),
+                vec![(p.clone(), panic_message(payload.as_ref()))],
+            )),
+        };
+        options.report_progress(ProgressEvent::Processed(&p));
+        let sent = match outcome {
+            Ok(Some(status)) if limit.record_failure() => {
+                results.send(Ok(FileResult { path: p, status })).is_ok()
+            }
+            Ok(_) => true,
+            // the rest of the files can still be checked
+            Err(e) if keep_going || matches!(e, CheckHeadersRecursivelyError::Unprocessed(..)) => {
+                results.send(Err(e)).is_ok()
+            }
+            Err(e) => {
+                // the whole check fails, so there's no point checking more files
+                limit.stop();
+                results.send(Err(e)).is_ok()
+            }
+        };
+        if !sent {
+            // results are no longer being collected, e.g. due to an error
+            break;
+        }
+    }
+    // no more files, or stopped early
+}
+
+/// Check for `header` in the file at `p`, using and updating the cache if there is one.
+#[cfg(feature = "recursive")]
+fn check_path(
+    header: &Header<impl HeaderChecker>,
+    options: &RecursiveOptions,
+    p: &path::Path,
+) -> io::Result<Option<CheckStatus>> {
+    let counting;
+    let fs: &dyn filesystem::FileSystem = match &options.statistics {
+        Some(statistics) => {
+            counting = statistics.counting(options.file_system.as_ref());
+            &counting
+        }
+        None => options.file_system.as_ref(),
+    };
+    let cache = options.cache.as_ref();
+    let stamp = cache.and_then(|_| cache::FileStamp::read(fs, p));
+    // results depend on these options, so are only reused with the same ones
+    let check_options = cache::CheckOptions {
+        strict_headers: options.strict_headers,
+        normalize_whitespace: options.normalize_whitespace,
+    };
+    let cached = cache
+        .zip(stamp)
+        .and_then(|(cache, stamp)| cache.lookup(p, stamp, check_options));
+    let outcome = match cached {
+        Some(status) => Ok(status),
+        None => check_file_with_options(fs, header, options, p),
+    };
+    if let (Ok(status), Some((cache, stamp))) = (&outcome, cache.zip(stamp)) {
+        cache.record(p, stamp, check_options, *status);
+    }
+    outcome
+}
+
+/// Check the file at `p` for `header` as per [`check_file`], adjusted by
+/// [`RecursiveOptions::strict_headers`] and [`RecursiveOptions::normalize_whitespace`].
+#[cfg(feature = "recursive")]
+fn check_file_with_options(
+    fs: &dyn filesystem::FileSystem,
+    header: &Header<impl HeaderChecker>,
+    options: &RecursiveOptions,
+    p: &path::Path,
+) -> io::Result<Option<CheckStatus>> {
+    let status = match check_file(fs, header, p)? {
+        None if options.strict_headers && !header.whole_header_present(fs, p)? => {
+            Some(CheckStatus::PartialHeader)
+        }
+        status => status,
+    };
+    match status {
+        Some(
+            CheckStatus::HeaderNotFound | CheckStatus::PartialHeader | CheckStatus::DriftedHeader,
+        ) if options.normalize_whitespace && header.normalized_header_present(fs, p)? => Ok(None),
+        status => Ok(status),
+    }
+}
+
+/// The [`FileStatus`] of the file at `p` when checking for `header`.
+#[cfg(feature = "recursive")]
+fn file_status(
+    header: &Header<impl HeaderChecker>,
+    options: &RecursiveOptions,
+    p: &path::Path,
+) -> FileStatus {
+    let status = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        match check_path(header, options, p)? {
+            None => Ok(FileStatus::Ok),
+            Some(CheckStatus::HeaderNotFound) => Ok(FileStatus::MissingHeader),
+            Some(CheckStatus::PartialHeader) => Ok(FileStatus::PartialHeader),
+            Some(CheckStatus::DriftedHeader) => Ok(FileStatus::DriftedHeader),
+            Some(CheckStatus::BinaryFile) => match options.binary_file_policy {
+                BinaryFilePolicy::Report => Ok(FileStatus::Binary),
+                BinaryFilePolicy::Skip => Ok(FileStatus::Skipped),
+                _ => Ok(
+                    match options.binary_file_status(options.file_system.as_ref(), header, p)? {
+                        None => FileStatus::Ok,
+                        Some(_) => FileStatus::MissingHeader,
+                    },
+                ),
+            },
+        }
+    }));
+    match status {
+        Ok(Ok(status)) => status,
+        Ok(Err(e)) => FileStatus::Error(e),
+        Err(payload) => FileStatus::Error(io::Error::new(
+            io::ErrorKind::Other,
+            panic_message(payload.as_ref()),
+        )),
+    }
+}
+
+/// Returns the message a thread panicked with, given the panic's payload.
+#[cfg(feature = "recursive")]
+fn panic_message(payload: &(dyn any::Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic payload".to_string())
+}
+
+/// Returns `true` if `header` is present in the file at `p` in `fs`.
+#[cfg(feature = "recursive")]
+fn header_present_with_fs(
+    header: &Header<impl HeaderChecker>,
+    fs: &dyn filesystem::FileSystem,
+    p: &path::Path,
+) -> io::Result<bool> {
+    header.header_present(&mut fs.open(p)?)
+}
+
+/// Records the bytes read from `inner`, so that they can be read again without re-reading `inner`.
+struct RecordingReader<R> {
+    inner: R,
+    recorded: Vec<u8>,
+}
+
+impl<R: io::Read> RecordingReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            recorded: Vec::new(),
+        }
+    }
+
+    /// A reader of the recorded bytes, followed by the rest of `inner`.
+    fn replay(self) -> io::Chain<io::Cursor<Vec<u8>>, R> {
+        io::Cursor::new(self.recorded).chain(self.inner)
+    }
+}
+
+impl<R: io::Read> io::Read for RecordingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.recorded.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Passes on the bytes read from `inner`, failing with an [`io::ErrorKind::InvalidData`] error if
+/// they aren't valid UTF-8, so that files are validated as they're streamed.
+struct Utf8Reader<R> {
+    inner: R,
+    /// The start of a character that continues in the next read
+    incomplete: Vec<u8>,
+}
+
+impl<R: io::Read> Utf8Reader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            incomplete: Vec::new(),
+        }
+    }
+}
+
+impl<R: io::Read> io::Read for Utf8Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n == 0 && !buf.is_empty() && !self.incomplete.is_empty() {
+            // the input ended part way through a character
+            return Err(invalid_utf8());
+        }
+        let mut bytes = &buf[..n];
+        if let Some(&lead) = self.incomplete.first() {
+            // finish the character split across reads before validating the rest
+            let width = match lead {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                _ => 4,
+            };
+            let needed = (width - self.incomplete.len()).min(bytes.len());
+            self.incomplete.extend_from_slice(&bytes[..needed]);
+            bytes = &bytes[needed..];
+            if incomplete_utf8_suffix(&self.incomplete)?.is_empty() {
+                self.incomplete.clear();
+            } else {
+                return Ok(n);
+            }
+        }
+        self.incomplete
+            .extend_from_slice(incomplete_utf8_suffix(bytes)?);
+        Ok(n)
+    }
+}
+
+/// Returns the start of a character at the end of `bytes` that continues past them, failing if
+/// `bytes` are otherwise not valid UTF-8.
+fn incomplete_utf8_suffix(bytes: &[u8]) -> io::Result<&[u8]> {
+    match std::str::from_utf8(bytes) {
+        Ok(_) => Ok(&[]),
+        Err(e) if e.error_len().is_none() => Ok(&bytes[e.valid_up_to()..]),
+        Err(_) => Err(invalid_utf8()),
+    }
+}
+
+/// The error for bytes that aren't valid UTF-8, as from [`io::Read::read_to_string`]
+fn invalid_utf8() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "stream did not contain valid UTF-8",
+    )
+}
+
+/// The size and modification time of a file, to tell if it changed while it was being rewritten
+#[derive(PartialEq)]
+struct FileStamp {
+    size: u64,
+    /// `None` if the file system doesn't support modification times
+    modified: Option<time::SystemTime>,
+}
+
+impl FileStamp {
+    fn of(fs: &dyn filesystem::FileSystem, p: &path::Path) -> io::Result<Self> {
+        Ok(Self {
+            size: fs.file_size(p)?,
+            modified: fs.modified(p).ok(),
+        })
+    }
+}
+
+/// Reads the rest of the file at `path` from `inner` for its replacement, failing at the end if
+/// the file no longer has `stamp`, so that changes made since it was read aren't overwritten.
+struct UnchangedReader<'a, R> {
+    inner: R,
+    fs: &'a dyn filesystem::FileSystem,
+    path: &'a path::Path,
+    stamp: FileStamp,
+}
+
+impl<R: io::Read> io::Read for UnchangedReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n == 0 && !buf.is_empty() && FileStamp::of(self.fs, self.path)? != self.stamp {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                ConcurrentModificationError,
+            ));
+        }
+        Ok(n)
+    }
+}
+
+/// The error from [`UnchangedReader`] when the file changed
+#[derive(Debug, thiserror::Error)]
+#[error("File changed while it was being rewritten")]
+struct ConcurrentModificationError;
+
+/// Returns `true` if `e` is from a file changing while it was being rewritten, as per
+/// [`UnchangedReader`].
+fn is_concurrent_modification(e: &io::Error) -> bool {
+    e.get_ref()
+        .map_or(false, |inner| inner.is::<ConcurrentModificationError>())
+}
+
+/// Check the file at `p` for `header`, returning the reason the header is not present, if any.
+#[cfg(feature = "recursive")]
+fn check_file(
+    fs: &dyn filesystem::FileSystem,
+    header: &Header<impl HeaderChecker>,
+    p: &path::Path,
+) -> io::Result<Option<CheckStatus>> {
+    let mut file = fs.open(p)?;
+    let prefix = read_prefix(&mut file)?;
+    if is_binary_prefix(&prefix) {
+        return Ok(Some(CheckStatus::BinaryFile));
+    }
+    // invalid UTF-8 after the prefix is still detected by the checker, if it reads that far
+    let mut recorder = RecordingReader::new(io::Cursor::new(prefix).chain(file));
+    let status = match header.header_present(&mut recorder) {
+        Ok(true) => return Ok(None),
+        // the checker looks at the same part of the file again for a drifted header
+        Ok(false) => header
+            .header_drifted(&mut recorder.replay())
+            .map(|drifted| {
+                if drifted {
+                    CheckStatus::DriftedHeader
+                } else {
+                    CheckStatus::HeaderNotFound
+                }
+            }),
+        Err(e) => Err(e),
+    };
+    match status {
+        Ok(status) => Ok(Some(status)),
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => Ok(Some(CheckStatus::BinaryFile)),
+        Err(e) => Err(e),
+    }
+}
+
+/// Number of bytes at the start of a file that are inspected to tell if it's binary
+const BINARY_SNIFF_LEN: u64 = 8 * 1024;
+
+/// Returns `true` if the file at `p` appears to be binary rather than UTF-8 text, because its
+/// first few KB contain a NUL byte or invalid UTF-8.
+///
+/// This avoids reading the whole file, but as a result a file with invalid UTF-8 only further in
+/// is not detected.
+pub fn is_probably_binary(p: &path::Path) -> io::Result<bool> {
+    let prefix = read_prefix(&mut fs::File::open(filesystem::long_path(p))?)?;
+    Ok(is_binary_prefix(&prefix))
+}
+
+/// Read the first [`BINARY_SNIFF_LEN`] bytes of `input`, or all of it if it's shorter.
+fn read_prefix(input: &mut impl io::Read) -> io::Result<Vec<u8>> {
+    let mut prefix = Vec::with_capacity(BINARY_SNIFF_LEN as usize);
+    input.take(BINARY_SNIFF_LEN).read_to_end(&mut prefix)?;
+    Ok(prefix)
+}
+
+/// Returns `true` if `prefix`, the start of a file, contains a NUL byte or invalid UTF-8.
+fn is_binary_prefix(prefix: &[u8]) -> bool {
+    if prefix.contains(&0) {
+        return true;
+    }
+    match std::str::from_utf8(prefix) {
+        Ok(_) => false,
+        // a multi-byte character may have been cut off at the end of the prefix
+        Err(e) => e.error_len().is_some(),
+    }
+}
+
+/// Errors that can occur when checking for headers recursively
+#[cfg(feature = "recursive")]
+#[derive(Debug, thiserror::Error)]
+pub enum CheckHeadersRecursivelyError {
+    /// An I/O error occurred while checking the path
+    #[error("I/O error at {0:?}: {1}")]
+    IoError(path::PathBuf, io::Error),
+    /// `walkdir` could not navigate the directory structure
+    #[error("Walkdir error: {0}")]
+    WalkdirError(#[from] walkdir::Error),
+    /// Threads panicked while checking some files, so they weren't checked, but the rest were.
+    ///
+    /// Includes the results for the rest of the files, and each file that wasn't checked with the
+    /// panic message.
+    #[error("Failed to check {}", describe_unprocessed(.1))]
+    Unprocessed(Box<FileResults>, Vec<(path::PathBuf, String)>),
+}
+
+#[cfg(feature = "recursive")]
+impl CheckHeadersRecursivelyError {
+    /// Make the paths in an `Unprocessed` error relative, as per
+    /// [`RecursiveOptions::relative_paths`].
+    pub(crate) fn relativize(
+        mut self,
+        roots: &[impl AsRef<path::Path>],
+        options: &RecursiveOptions,
+    ) -> Self {
+        if let Self::Unprocessed(results, files) = &mut self {
+            options.relativize(roots, results.path_lists_mut());
+            for (p, _) in files {
+                *p = options.relative_path(roots, std::mem::take(p));
+            }
+        }
+        self
+    }
+}
+
+/// Describe files that weren't processed, and why, for `Unprocessed` errors.
+#[cfg(feature = "recursive")]
+fn describe_unprocessed(files: &[(path::PathBuf, String)]) -> String {
+    files
+        .iter()
+        .map(|(p, reason)| format!("{:?} ({})", p, reason))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(feature = "recursive")]
+impl From<filesystem::WalkError> for CheckHeadersRecursivelyError {
+    fn from(value: filesystem::WalkError) -> Self {
+        match value {
+            filesystem::WalkError::Walkdir(e) => Self::WalkdirError(e),
+            filesystem::WalkError::Io(p, e) => Self::IoError(p, e),
+        }
+    }
+}
+
+/// Add the provided `header` to any file in `root` that matches `path_predicate` and that doesn't
+/// already have a header as determined by `checker`.
+///
+/// Returns a list of paths that had headers added.
+#[cfg(feature = "recursive")]
+pub fn add_headers_recursively(
+    root: &path::Path,
+    path_predicate: impl Fn(&path::Path) -> bool,
     header: Header<impl HeaderChecker>,
 ) -> Result<Vec<path::PathBuf>, AddHeadersRecursivelyError> {
+    add_headers_recursively_with_options(root, path_predicate, header, &RecursiveOptions::new())
+        .map(|results| results.modified_files)
+}
+
+/// Add the provided `header` to files in `root`, as per [`add_headers_recursively`], with the
+/// behavior adjusted by `options`.
+#[cfg(feature = "recursive")]
+pub fn add_headers_recursively_with_options(
+    root: &path::Path,
+    path_predicate: impl Fn(&path::Path) -> bool,
+    header: Header<impl HeaderChecker>,
+    options: &RecursiveOptions,
+) -> Result<ModificationResults, AddHeadersRecursivelyError> {
+    add_headers_recursively_in_roots(&[root], path_predicate, header, options)
+}
+
+/// Add the provided `header` to files in each of `roots`, as per
+/// [`add_headers_recursively_with_options`], merging the results.
+///
+/// Files under more than one root are only processed once.
+#[cfg(feature = "recursive")]
+pub fn add_headers_recursively_in_roots(
+    roots: &[impl AsRef<path::Path>],
+    path_predicate: impl Fn(&path::Path) -> bool,
+    header: Header<impl HeaderChecker>,
+    options: &RecursiveOptions,
+) -> Result<ModificationResults, AddHeadersRecursivelyError> {
+    add_headers_in_roots(roots, path_predicate, header, options, false).into_result()
+}
+
+/// Add the provided `header` to files in `root`, as per [`add_headers_recursively_with_options`],
+/// carrying on past errors.
+///
+/// Errors adding the header to a file, or searching part of the directory tree, are returned with
+/// the results for the rest of the files, rather than discarding them. The operation still stops
+/// if it's aborted by [`RecursiveOptions::confirm`], and if it's
+/// [`RecursiveOptions::transactional`], no files are modified if there are any errors.
+#[cfg(feature = "recursive")]
+pub fn add_headers_recursively_partial(
+    root: &path::Path,
+    path_predicate: impl Fn(&path::Path) -> bool,
+    header: Header<impl HeaderChecker>,
+    options: &RecursiveOptions,
+) -> PartialResults<ModificationResults, AddHeadersRecursivelyError> {
+    add_headers_in_roots(&[root], path_predicate, header, options, true)
+}
+
+/// Add the provided `header` to files in each of `roots`, as per
+/// [`add_headers_recursively_in_roots`], carrying on past errors if `keep_going` is set.
+#[cfg(feature = "recursive")]
+fn add_headers_in_roots(
+    roots: &[impl AsRef<path::Path>],
+    path_predicate: impl Fn(&path::Path) -> bool,
+    header: Header<impl HeaderChecker>,
+    options: &RecursiveOptions,
+    keep_going: bool,
+) -> PartialResults<ModificationResults, AddHeadersRecursivelyError> {
+    let (modification_fs, staging) = options.staged_modification_file_system();
+    let cache = EffectiveHeaderCache::default();
+    let round_trip_failures = cell::RefCell::new(Vec::new());
     // likely no need for threading since adding headers is only done occasionally
-    recursive_optional_operation(root, path_predicate, |p| {
-        header.add_header_if_missing(p).map_err(|e| e.into())
-    })
+    let operation = |p: &path::Path| {
+        let err_mapper = |e| AddHeadersRecursivelyError::IoError(p.to_path_buf(), e);
+        let fs = options.file_system.as_ref();
+        // a rewrapped header is left alone, rather than adding another copy
+        if options.normalize_whitespace
+            && !header_present_with_fs(&header, fs, p).map_err(err_mapper)?
+            && header
+                .normalized_header_present(fs, p)
+                .map_err(err_mapper)?
+        {
+            return Ok(false);
+        }
+        let decision = options
+            .decide(p, PlannedChange::AddHeader, || {
+                header_present_with_fs(&header, fs, p).map(|b| !b)
+            })
+            .map_err(err_mapper)?;
+        match decision {
+            Decision::Apply if !options.round_trips(&header, p, &cache)? => {
+                round_trip_failures.borrow_mut().push(p.to_path_buf());
+                Ok(false)
+            }
+            Decision::Apply => header
+                .add_header_if_missing_cached(modification_fs.as_ref(), p, &cache)
+                .map_err(|e| e.into()),
+            Decision::Skip => Ok(false),
+            Decision::Abort => Err(AddHeadersRecursivelyError::Aborted(p.to_path_buf())),
+        }
+    };
+    // only being aborted stops an operation that carries on past errors
+    let aborts: Option<fn(&AddHeadersRecursivelyError) -> bool> =
+        keep_going.then_some(|e| matches!(e, AddHeadersRecursivelyError::Aborted(_)));
+    let mut partial =
+        recursive_optional_operation_partial(roots, path_predicate, options, aborts, operation);
+    partial.results.round_trip_failures = round_trip_failures.into_inner();
+    if let Some(staging) = staging {
+        // a transaction is all or nothing, so nothing is committed if anything failed
+        if partial.errors.is_empty() {
+            let committed = partial
+                .results
+                .modified_files
+                .iter()
+                .try_for_each(
+                    |p| match header_present_with_fs(&header, staging.as_ref(), p) {
+                        Ok(true) => Ok(()),
+                        Ok(false) => Err(AddHeadersRecursivelyError::ValidationFailed(p.clone())),
+                        Err(e) => Err(AddHeadersRecursivelyError::IoError(p.clone(), e)),
+                    },
+                )
+                .and_then(|_| {
+                    staging
+                        .commit()
+                        .map_err(|(p, e)| AddHeaderError::IoError(p, e).into())
+                });
+            partial.errors.extend(committed.err());
+        }
+        if !partial.errors.is_empty() {
+            partial.results.modified_files.clear();
+        }
+    }
+    options.relativize(roots, partial.results.path_lists_mut());
+    partial
+}
+
+/// Errors that can occur when adding a header recursively
+#[cfg(feature = "recursive")]
+#[derive(Debug, thiserror::Error)]
+pub enum AddHeadersRecursivelyError {
+    /// An I/O error occurred while adding the header to the path
+    #[error("I/O error at {0:?}: {1}")]
+    IoError(path::PathBuf, io::Error),
+    /// `walkdir` could not navigate the directory structure
+    #[error("Walkdir error: {0}")]
+    WalkdirError(#[from] walkdir::Error),
+    /// A file with an unrecognized extension was encountered at the path
+    #[error("Unknown file extension: {0:?}")]
+    UnrecognizedExtension(path::PathBuf),
+    /// The header contains the sequence, which would end the comment around it early in the file
+    /// at the path, as per [`HeaderDelimiters::comment_breaking_sequence`]
+    #[error("Header contains {1:?}, which would end its comment early in {0:?}")]
+    CommentTerminator(path::PathBuf, String),
+    /// The operation was aborted by [`RecursiveOptions::confirm`] at the path, which was left
+    /// unmodified.
+    ///
+    /// Files before it may already have been modified, unless the operation is
+    /// [`RecursiveOptions::transactional`].
+    #[error("Aborted at {0:?}")]
+    Aborted(path::PathBuf),
+    /// The header was not detected in the path after adding it, so no files were modified, as
+    /// per [`RecursiveOptions::transactional`].
+    #[error("Header not detected after adding it: {0:?}")]
+    ValidationFailed(path::PathBuf),
+    /// Threads panicked while processing some files, so they weren't processed, but the rest
+    /// were, as per [`fix_headers_recursively`].
+    ///
+    /// Includes the results for the rest of the files, and each file that wasn't processed with
+    /// the panic message. No files were modified if the operation is
+    /// [`RecursiveOptions::transactional`].
+    #[error("Failed to process {}", describe_unprocessed(.1))]
+    Unprocessed(Box<FixResults>, Vec<(path::PathBuf, String)>),
+    /// The path is read-only, and could not be made writable to add the header, as per
+    /// [`RecursiveOptions::lift_read_only`]
+    #[error("Read-only file could not be made writable at {0:?}: {1}")]
+    ReadOnly(path::PathBuf, io::Error),
+    /// The path changed while the header was being added, so it was left as is, as per
+    /// [`AddHeaderError::ConcurrentModification`]
+    #[error("File changed while adding the header: {0:?}")]
+    ConcurrentModification(path::PathBuf),
+}
+
+#[cfg(feature = "recursive")]
+impl From<filesystem::WalkError> for AddHeadersRecursivelyError {
+    fn from(value: filesystem::WalkError) -> Self {
+        match value {
+            filesystem::WalkError::Walkdir(e) => Self::WalkdirError(e),
+            filesystem::WalkError::Io(p, e) => Self::IoError(p, e),
+        }
+    }
+}
+
+#[cfg(feature = "recursive")]
+impl From<AddHeaderError> for AddHeadersRecursivelyError {
+    fn from(value: AddHeaderError) -> Self {
+        match value {
+            AddHeaderError::IoError(p, e) if filesystem::is_lift_read_only_error(&e) => {
+                Self::ReadOnly(p, e)
+            }
+            AddHeaderError::IoError(p, e) => Self::IoError(p, e),
+            AddHeaderError::UnrecognizedExtension(p) => Self::UnrecognizedExtension(p),
+            AddHeaderError::CommentTerminator(p, sequence) => Self::CommentTerminator(p, sequence),
+            AddHeaderError::ConcurrentModification(p) => Self::ConcurrentModification(p),
+        }
+    }
+}
+
+/// Check for `header` in every file in `root` that matches `path_predicate`, adding it to the files
+/// that don't have it, in a single pass over the files.
+///
+/// This is equivalent to [`check_headers_recursively_with_options`] followed by
+/// [`add_headers_recursively_with_options`], but each file is only found and checked once, with
+/// checking and adding parallelized across `num_threads` threads.
+///
+/// Binary files are handled as per [`RecursiveOptions::binary_files`], but never modified.
+/// [`RecursiveOptions::cache`] and [`RecursiveOptions::max_failures`] are not used, as files are
+/// modified rather than failing.
+#[cfg(feature = "recursive")]
+pub fn fix_headers_recursively(
+    root: &path::Path,
+    path_predicate: impl Fn(&path::Path) -> bool,
+    header: Header<impl HeaderChecker>,
+    num_threads: usize,
+    options: &RecursiveOptions,
+) -> Result<FixResults, AddHeadersRecursivelyError> {
+    let (modification_fs, staging) = options.staged_modification_file_system();
+    let (path_tx, path_rx) = crossbeam::channel::bounded::<path::PathBuf>(options.path_capacity);
+    let (result_tx, result_rx) = crossbeam::channel::bounded(options.result_capacity);
+    let limit = FailureLimit::new(None);
+    let (mut results, mut unprocessed) = thread::scope(|s| {
+        for _ in 0..num_threads {
+            let path_rx = path_rx.clone();
+            let result_tx = result_tx.clone();
+            let header = &header;
+            let modification_fs = modification_fs.as_ref();
+            let limit = &limit;
+            s.spawn(move || fix_paths(header, options, modification_fs, path_rx, result_tx, limit));
+        }
+        drop(result_tx);
+        drop(path_rx);
+        // results are collected during the search, so that the threads don't stall on a full
+        // result channel while the search stalls on a full path channel
+        let collector = s.spawn(move || {
+            let mut results = FixResults::default();
+            let mut unprocessed = Vec::new();
+            for result in result_rx {
+                let (p, outcome) = match result {
+                    Ok(result) => result,
+                    // the other threads carry on, so the rest of the files are still processed
+                    Err(AddHeadersRecursivelyError::Unprocessed(_, failed)) => {
+                        unprocessed.extend(failed);
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                };
+                match outcome {
+                    FixOutcome::Fixed => results.fixed_files.push(p),
+                    FixOutcome::Compliant => results.compliant_files.push(p),
+                    FixOutcome::Unfixed => results.unfixed_files.push(p),
+                    FixOutcome::Binary => results.binary_files.push(p),
+                    FixOutcome::RoundTripFailed => results.round_trip_failures.push(p),
+                    FixOutcome::Drifted => results.drifted_header_files.push(p),
+                    FixOutcome::Partial => results.partial_header_files.push(p),
+                    FixOutcome::Skipped => {}
+                }
+            }
+            Ok::<_, AddHeadersRecursivelyError>((results, unprocessed))
+        });
+        let skipped = find_files(&[root], path_predicate, options, path_tx)?;
+        let (mut results, unprocessed) = collector
+            .join()
+            .unwrap_or_else(|payload| panic::resume_unwind(payload))?;
+        results.oversized_files = skipped.oversized;
+        results.symlinks = skipped.symlinks;
+        Ok::<_, AddHeadersRecursivelyError>((results, unprocessed))
+    })?;
+    // threads finish files in any order
+    results.fixed_files.sort();
+    results.compliant_files.sort();
+    results.unfixed_files.sort();
+    results.binary_files.sort();
+    results.round_trip_failures.sort();
+    results.drifted_header_files.sort();
+    results.partial_header_files.sort();
+    if !unprocessed.is_empty() {
+        // staged modifications aren't committed, as not every file was processed
+        unprocessed.sort();
+        for (p, _) in &mut unprocessed {
+            *p = options.relative_path(&[root], std::mem::take(p));
+        }
+        options.relativize(&[root], results.path_lists_mut());
+        return Err(AddHeadersRecursivelyError::Unprocessed(
+            Box::new(results),
+            unprocessed,
+        ));
+    }
+    if let Some(staging) = staging {
+        for p in &results.fixed_files {
+            if !header_present_with_fs(&header, staging.as_ref(), p)
+                .map_err(|e| AddHeadersRecursivelyError::IoError(p.clone(), e))?
+            {
+                return Err(AddHeadersRecursivelyError::ValidationFailed(p.clone()));
+            }
+        }
+        staging
+            .commit()
+            .map_err(|(p, e)| AddHeaderError::IoError(p, e))?;
+    }
+    options.relativize(&[root], results.path_lists_mut());
+    Ok(results)
+}
+
+/// Aggregated results for recursively fixing headers, as per [`fix_headers_recursively`].
+#[cfg(feature = "recursive")]
+#[derive(Clone, Default, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FixResults {
+    /// Paths that did not have a header, and had it added
+    pub fixed_files: Vec<path::PathBuf>,
+    /// Paths that already had a header
+    pub compliant_files: Vec<path::PathBuf>,
+    /// Paths that did not have a header, but were skipped by [`RecursiveOptions::confirm`]
+    pub unfixed_files: Vec<path::PathBuf>,
+    /// Binary files that count as failures per [`RecursiveOptions::binary_files`]
+    pub binary_files: Vec<path::PathBuf>,
+    /// Paths that were skipped because they exceeded [`RecursiveOptions::max_file_size`]
+    pub oversized_files: Vec<path::PathBuf>,
+    /// Paths that did not have a header, and were left unmodified because adding it failed
+    /// [`RecursiveOptions::verify_round_trip`]
+    pub round_trip_failures: Vec<path::PathBuf>,
+    /// Symlinks to files that were skipped, as per [`SymlinkPolicy::Report`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub symlinks: Vec<path::PathBuf>,
+    /// Paths with a drifted header, as per [`HeaderChecker::drifted`], that were left unmodified
+    /// as adding the header would leave the drifted copy in place
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub drifted_header_files: Vec<path::PathBuf>,
+    /// Paths with only part of the header, as per [`RecursiveOptions::strict_headers`], that were
+    /// left unmodified as adding the header wouldn't complete it
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub partial_header_files: Vec<path::PathBuf>,
 }
 
-/// Errors that can occur when adding a header recursively
-#[derive(Debug, thiserror::Error)]
-pub enum AddHeadersRecursivelyError {
-    /// An I/O error occurred while adding the header to the path
-    #[error("I/O error at {0:?}: {1}")]
-    IoError(path::PathBuf, io::Error),
-    /// `walkdir` could not navigate the directory structure
-    #[error("Walkdir error: {0}")]
-    WalkdirError(#[from] walkdir::Error),
-    /// A file with an unrecognized extension was encountered at the path
-    #[error("Unknown file extension: {0:?}")]
-    UnrecognizedExtension(path::PathBuf),
+#[cfg(feature = "recursive")]
+impl FixResults {
+    /// The lists of paths in the results
+    fn path_lists_mut(&mut self) -> [&mut Vec<path::PathBuf>; 9] {
+        [
+            &mut self.fixed_files,
+            &mut self.compliant_files,
+            &mut self.unfixed_files,
+            &mut self.binary_files,
+            &mut self.oversized_files,
+            &mut self.round_trip_failures,
+            &mut self.symlinks,
+            &mut self.drifted_header_files,
+            &mut self.partial_header_files,
+        ]
+    }
+
+    /// Returns `true` if any files scanned still do not have a header
+    pub fn has_failure(&self) -> bool {
+        !self.unfixed_files.is_empty()
+            || !self.binary_files.is_empty()
+            || !self.round_trip_failures.is_empty()
+            || !self.drifted_header_files.is_empty()
+            || !self.partial_header_files.is_empty()
+    }
 }
 
-impl From<AddHeaderError> for AddHeadersRecursivelyError {
-    fn from(value: AddHeaderError) -> Self {
-        match value {
-            AddHeaderError::IoError(p, e) => Self::IoError(p, e),
-            AddHeaderError::UnrecognizedExtension(p) => Self::UnrecognizedExtension(p),
+/// What happened to a single file when fixing headers
+#[cfg(feature = "recursive")]
+enum FixOutcome {
+    Fixed,
+    Compliant,
+    Unfixed,
+    Binary,
+    /// Files left without a header as per [`RecursiveOptions::verify_round_trip`]
+    RoundTripFailed,
+    /// Binary files skipped as per [`BinaryFilePolicy::Skip`]
+    Skipped,
+    /// Files with a drifted header, which are left for it to be rewritten
+    Drifted,
+    /// Files with part of the header, as per [`RecursiveOptions::strict_headers`]
+    Partial,
+}
+
+/// Check for `header` in each path received from `paths`, adding it via `modification_fs` where
+/// it's missing, and sending the outcomes and any errors to `results`.
+#[cfg(feature = "recursive")]
+fn fix_paths(
+    header: &Header<impl HeaderChecker>,
+    options: &RecursiveOptions,
+    modification_fs: &dyn filesystem::FileSystem,
+    paths: crossbeam::channel::Receiver<path::PathBuf>,
+    results: crossbeam::channel::Sender<
+        Result<(path::PathBuf, FixOutcome), AddHeadersRecursivelyError>,
+    >,
+    limit: &FailureLimit,
+) {
+    let cache = EffectiveHeaderCache::default();
+    for p in paths {
+        if limit.is_stopped() {
+            break;
+        }
+        // a panic is recorded against the file, rather than ending the thread
+        let outcome = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            fix_file(header, options, modification_fs, &p, &cache)
+        }))
+        .unwrap_or_else(|payload| {
+            Err(AddHeadersRecursivelyError::Unprocessed(
+                Box::default(),
+                vec![(p.clone(), panic_message(payload.as_ref()))],
+            ))
+        });
+        options.report_progress(ProgressEvent::Processed(&p));
+        match &outcome {
+            // the rest of the files can still be processed
+            Ok(_) | Err(AddHeadersRecursivelyError::Unprocessed(..)) => {}
+            Err(_) => limit.stop(),
+        }
+        if results.send(outcome.map(|o| (p, o))).is_err() {
+            // results are no longer being collected, e.g. due to an error
+            break;
+        }
+    }
+}
+
+/// Check for `header` in the file at `p`, adding it via `modification_fs` if it's missing.
+#[cfg(feature = "recursive")]
+fn fix_file(
+    header: &Header<impl HeaderChecker>,
+    options: &RecursiveOptions,
+    modification_fs: &dyn filesystem::FileSystem,
+    p: &path::Path,
+    cache: &EffectiveHeaderCache,
+) -> Result<FixOutcome, AddHeadersRecursivelyError> {
+    let fs = options.file_system.as_ref();
+    let err_mapper = |e| AddHeadersRecursivelyError::IoError(p.to_path_buf(), e);
+    match check_file_with_options(fs, header, options, p).map_err(err_mapper)? {
+        None => Ok(FixOutcome::Compliant),
+        // adding a header doesn't complete a partial one, so it's left alone
+        Some(CheckStatus::PartialHeader) => Ok(FixOutcome::Partial),
+        Some(CheckStatus::BinaryFile) => {
+            match options
+                .binary_file_status(fs, header, p)
+                .map_err(err_mapper)?
+            {
+                None if options.binary_file_policy == BinaryFilePolicy::Skip => {
+                    Ok(FixOutcome::Skipped)
+                }
+                None => Ok(FixOutcome::Compliant),
+                Some(_) => Ok(FixOutcome::Binary),
+            }
+        }
+        // adding the header would leave the drifted copy below it, so it's left to be rewritten
+        Some(CheckStatus::DriftedHeader) => Ok(FixOutcome::Drifted),
+        Some(CheckStatus::HeaderNotFound) => {
+            match options
+                .decide(p, PlannedChange::AddHeader, || Ok(true))
+                .map_err(err_mapper)?
+            {
+                Decision::Apply if !options.round_trips(header, p, cache)? => {
+                    Ok(FixOutcome::RoundTripFailed)
+                }
+                Decision::Apply => {
+                    let added = header.add_header_if_missing_cached(modification_fs, p, cache)?;
+                    // the header may have been added since it was checked for
+                    Ok(if added {
+                        FixOutcome::Fixed
+                    } else {
+                        FixOutcome::Compliant
+                    })
+                }
+                Decision::Skip => Ok(FixOutcome::Unfixed),
+                Decision::Abort => Err(AddHeadersRecursivelyError::Aborted(p.to_path_buf())),
+            }
         }
     }
 }
@@ -389,17 +4096,106 @@ impl From<AddHeaderError> for AddHeadersRecursivelyError {
 /// already has a header as determined by `header`'s checker.
 ///
 /// Returns a list of paths that had headers removed.
+#[cfg(feature = "recursive")]
 pub fn delete_headers_recursively(
     root: &path::Path,
     path_predicate: impl Fn(&path::Path) -> bool,
     header: Header<impl HeaderChecker>,
 ) -> Result<Vec<path::PathBuf>, DeleteHeadersRecursivelyError> {
-    recursive_optional_operation(root, path_predicate, |p| {
-        header.delete_header_if_present(p).map_err(|e| e.into())
-    })
+    delete_headers_recursively_with_options(root, path_predicate, header, &RecursiveOptions::new())
+        .map(|results| results.modified_files)
+}
+
+/// Delete the provided `header` from files in `root`, as per [`delete_headers_recursively`], with
+/// the behavior adjusted by `options`.
+#[cfg(feature = "recursive")]
+pub fn delete_headers_recursively_with_options(
+    root: &path::Path,
+    path_predicate: impl Fn(&path::Path) -> bool,
+    header: Header<impl HeaderChecker>,
+    options: &RecursiveOptions,
+) -> Result<ModificationResults, DeleteHeadersRecursivelyError> {
+    delete_headers_recursively_in_roots(&[root], path_predicate, header, options)
+}
+
+/// Delete the provided `header` from files in each of `roots`, as per
+/// [`delete_headers_recursively_with_options`], merging the results.
+///
+/// Files under more than one root are only processed once.
+#[cfg(feature = "recursive")]
+pub fn delete_headers_recursively_in_roots(
+    roots: &[impl AsRef<path::Path>],
+    path_predicate: impl Fn(&path::Path) -> bool,
+    header: Header<impl HeaderChecker>,
+    options: &RecursiveOptions,
+) -> Result<ModificationResults, DeleteHeadersRecursivelyError> {
+    delete_headers_in_roots(roots, path_predicate, header, options, false).into_result()
+}
+
+/// Delete the provided `header` from files in `root`, as per
+/// [`delete_headers_recursively_with_options`], carrying on past errors.
+///
+/// Errors deleting the header from a file, or searching part of the directory tree, are returned
+/// with the results for the rest of the files, as per [`add_headers_recursively_partial`].
+#[cfg(feature = "recursive")]
+pub fn delete_headers_recursively_partial(
+    root: &path::Path,
+    path_predicate: impl Fn(&path::Path) -> bool,
+    header: Header<impl HeaderChecker>,
+    options: &RecursiveOptions,
+) -> PartialResults<ModificationResults, DeleteHeadersRecursivelyError> {
+    delete_headers_in_roots(&[root], path_predicate, header, options, true)
+}
+
+/// Delete the provided `header` from files in each of `roots`, as per
+/// [`delete_headers_recursively_in_roots`], carrying on past errors if `keep_going` is set.
+#[cfg(feature = "recursive")]
+fn delete_headers_in_roots(
+    roots: &[impl AsRef<path::Path>],
+    path_predicate: impl Fn(&path::Path) -> bool,
+    header: Header<impl HeaderChecker>,
+    options: &RecursiveOptions,
+    keep_going: bool,
+) -> PartialResults<ModificationResults, DeleteHeadersRecursivelyError> {
+    let (modification_fs, staging) = options.staged_modification_file_system();
+    let cache = EffectiveHeaderCache::default();
+    let operation = |p: &path::Path| {
+        let decision = options
+            .decide(p, PlannedChange::DeleteHeader, || {
+                header_present_with_fs(&header, options.file_system.as_ref(), p)
+            })
+            .map_err(|e| DeleteHeadersRecursivelyError::IoError(p.to_path_buf(), e))?;
+        match decision {
+            Decision::Apply => header
+                .delete_header_if_present_cached(modification_fs.as_ref(), p, &cache)
+                .map_err(|e| e.into()),
+            Decision::Skip => Ok(false),
+            Decision::Abort => Err(DeleteHeadersRecursivelyError::Aborted(p.to_path_buf())),
+        }
+    };
+    // only being aborted stops an operation that carries on past errors
+    let aborts: Option<fn(&DeleteHeadersRecursivelyError) -> bool> =
+        keep_going.then_some(|e| matches!(e, DeleteHeadersRecursivelyError::Aborted(_)));
+    let mut partial =
+        recursive_optional_operation_partial(roots, path_predicate, options, aborts, operation);
+    if let Some(staging) = staging {
+        // a transaction is all or nothing, so nothing is committed if anything failed
+        if partial.errors.is_empty() {
+            let committed = staging
+                .commit()
+                .map_err(|(p, e)| DeleteHeaderError::IoError(p, e).into());
+            partial.errors.extend(committed.err());
+        }
+        if !partial.errors.is_empty() {
+            partial.results.modified_files.clear();
+        }
+    }
+    options.relativize(roots, partial.results.path_lists_mut());
+    partial
 }
 
 /// Errors that can occur when adding a header recursively
+#[cfg(feature = "recursive")]
 #[derive(Debug, thiserror::Error)]
 pub enum DeleteHeadersRecursivelyError {
     /// An I/O error occurred while removing the header from the path
@@ -411,47 +4207,341 @@ pub enum DeleteHeadersRecursivelyError {
     /// A file with an unrecognized extension was encountered at the path
     #[error("Unknown file extension: {0:?}")]
     UnrecognizedExtension(path::PathBuf),
+    /// The operation was aborted by [`RecursiveOptions::confirm`] at the path, which was left
+    /// unmodified.
+    ///
+    /// Files before it may already have been modified, unless the operation is
+    /// [`RecursiveOptions::transactional`].
+    #[error("Aborted at {0:?}")]
+    Aborted(path::PathBuf),
+    /// The path is read-only, and could not be made writable to delete the header, as per
+    /// [`RecursiveOptions::lift_read_only`]
+    #[error("Read-only file could not be made writable at {0:?}: {1}")]
+    ReadOnly(path::PathBuf, io::Error),
+    /// The path changed while the header was being deleted, so it was left as is, as per
+    /// [`DeleteHeaderError::ConcurrentModification`]
+    #[error("File changed while deleting the header: {0:?}")]
+    ConcurrentModification(path::PathBuf),
+}
+
+#[cfg(feature = "recursive")]
+impl From<filesystem::WalkError> for DeleteHeadersRecursivelyError {
+    fn from(value: filesystem::WalkError) -> Self {
+        match value {
+            filesystem::WalkError::Walkdir(e) => Self::WalkdirError(e),
+            filesystem::WalkError::Io(p, e) => Self::IoError(p, e),
+        }
+    }
 }
 
+#[cfg(feature = "recursive")]
 impl From<DeleteHeaderError> for DeleteHeadersRecursivelyError {
     fn from(value: DeleteHeaderError) -> Self {
         match value {
+            DeleteHeaderError::IoError(p, e) if filesystem::is_lift_read_only_error(&e) => {
+                Self::ReadOnly(p, e)
+            }
             DeleteHeaderError::IoError(p, e) => Self::IoError(p, e),
             DeleteHeaderError::UnrecognizedExtension(p) => Self::UnrecognizedExtension(p),
+            DeleteHeaderError::ConcurrentModification(p) => Self::ConcurrentModification(p),
         }
     }
 }
 
-/// Find all files starting from `root` that do not match the globs in `ignore`, publishing the
+/// Find all files starting from each of `roots` that match `path_predicate`, publishing the
 /// resulting paths into `dest`.
-fn find_files(
-    root: &path::Path,
+///
+/// Returns the paths that were not published as per `options`, as per [`publish_files`].
+#[cfg(feature = "recursive")]
+pub(crate) fn find_files(
+    roots: &[impl AsRef<path::Path>],
+    path_predicate: impl Fn(&path::Path) -> bool,
+    options: &RecursiveOptions,
+    dest: crossbeam::channel::Sender<path::PathBuf>,
+) -> Result<SkippedFiles, filesystem::WalkError> {
+    find_files_with(roots, path_predicate, options, dest, Err)
+}
+
+/// Find all files starting from each of `roots` that match `path_predicate`, as per
+/// [`find_files`], passing errors to `on_error`, which returns the error again if it should stop
+/// the search.
+#[cfg(feature = "recursive")]
+pub(crate) fn find_files_with(
+    roots: &[impl AsRef<path::Path>],
+    path_predicate: impl Fn(&path::Path) -> bool,
+    options: &RecursiveOptions,
+    dest: crossbeam::channel::Sender<path::PathBuf>,
+    mut on_error: impl FnMut(filesystem::WalkError) -> Result<(), filesystem::WalkError>,
+) -> Result<SkippedFiles, filesystem::WalkError> {
+    if let Some(source) = &options.path_source {
+        let roots = roots.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+        let entries = source
+            .paths(&roots)
+            .map(|p| source_entry(options, &roots, p));
+        let entries = without_excluded(entries, &roots, options).filter_map(|entry| {
+            entry
+                .map(Some)
+                .or_else(|e| on_error(e).map(|_| None))
+                .transpose()
+        });
+        return publish_files(entries, path_predicate, options, dest);
+    }
+    let descend = |dir: &path::Path| {
+        options
+            .dir_filter
+            .as_ref()
+            .map_or(true, |dir_filter| dir_filter(dir))
+    };
+    let walk_options = filesystem::WalkOptions {
+        descend: &descend,
+        follow_symlinks: options.follow_symlinks,
+    };
+    let mut seen = collections::HashSet::new();
+    let entries = roots
+        .iter()
+        .flat_map(|root| options.file_system.walk(root.as_ref(), walk_options))
+        // files under more than one root, e.g. nested roots, are only found once
+        .filter(|entry| match entry {
+            Ok(entry) if roots.len() > 1 => seen.insert(entry.path.clone()),
+            _ => true,
+        });
+    let roots = roots.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+    let entries = without_excluded(entries, &roots, options).filter_map(|entry| {
+        entry
+            .map(Some)
+            .or_else(|e| on_error(e).map(|_| None))
+            .transpose()
+    });
+    publish_files(entries, path_predicate, options, dest)
+}
+
+/// Publish the paths of the files in `entries` that match `path_predicate` and `options`' file
+/// filter into `dest`.
+///
+/// Returns the paths that were not published because they exceed `options`' maximum file size,
+/// or are symlinks to report as per [`SymlinkPolicy::Report`].
+#[cfg(feature = "recursive")]
+pub(crate) fn publish_files(
+    entries: impl IntoIterator<Item = Result<filesystem::WalkEntry, filesystem::WalkError>>,
     path_predicate: impl Fn(&path::Path) -> bool,
+    options: &RecursiveOptions,
     dest: crossbeam::channel::Sender<path::PathBuf>,
-) -> Result<(), walkdir::Error> {
-    for r in walkdir::WalkDir::new(root).into_iter() {
-        let entry = r?;
-        if entry.path().is_dir() || !path_predicate(entry.path()) {
-            continue;
+) -> Result<SkippedFiles, filesystem::WalkError> {
+    let mut skipped = SkippedFiles::default();
+    for r in entries {
+        let filesystem::WalkEntry { path: p, metadata } = r?;
+        match select_file(&p, &metadata, &path_predicate, options) {
+            Selection::Excluded => continue,
+            Selection::Oversized => {
+                skipped.oversized.push(p);
+                continue;
+            }
+            Selection::Symlink => {
+                skipped.symlinks.push(p);
+                continue;
+            }
+            Selection::Selected => {}
+        }
+        options.report_progress(ProgressEvent::Found(&p));
+        if dest.send(p).is_err() {
+            // nothing is processing files any more, e.g. checking stopped early
+            break;
+        }
+    }
+    options.report_progress(ProgressEvent::SearchFinished);
+    Ok(skipped)
+}
+
+/// Files that were found but not processed, as per [`publish_files`]
+#[cfg(feature = "recursive")]
+#[derive(Default)]
+pub(crate) struct SkippedFiles {
+    /// Files that exceed [`RecursiveOptions::max_file_size`]
+    pub(crate) oversized: Vec<path::PathBuf>,
+    /// Symlinks to report, as per [`SymlinkPolicy::Report`]
+    pub(crate) symlinks: Vec<path::PathBuf>,
+}
+
+/// Iterate over the files in `root` that match `path_predicate` and `options`, i.e. the files that
+/// recursive operations like [`check_headers_recursively_with_options`] would process.
+///
+/// Directories are filtered with [`RecursiveOptions::dir_filter`], and files with
+/// [`RecursiveOptions::file_filter`]. Files that exceed [`RecursiveOptions::max_file_size`] are
+/// left out. The files are found lazily, in the order that
+/// [`RecursiveOptions::file_system`] walks them, and no progress is reported.
+///
+/// # Examples
+///
+/// ```
+/// // Copyright 2023 Google LLC.
+/// // SPDX-License-Identifier: Apache-2.0
+/// use file_header::*;
+///
+/// # let dir = tempfile::tempdir().unwrap();
+/// # let root = dir.path();
+/// # std::fs::write(root.join("main.rs"), "fn main() {}\n").unwrap();
+/// let options = RecursiveOptions::new().max_file_size(1024 * 1024);
+/// for p in discover(root, |p| p.extension().map_or(false, |e| e == "rs"), &options) {
+///     println!("{}", p.unwrap().display());
+/// }
+/// ```
+#[cfg(feature = "recursive")]
+pub fn discover<'a>(
+    root: &path::Path,
+    path_predicate: impl Fn(&path::Path) -> bool + 'a,
+    options: &'a RecursiveOptions,
+) -> impl Iterator<Item = Result<path::PathBuf, filesystem::WalkError>> + 'a {
+    let descend: &'a dyn Fn(&path::Path) -> bool = match &options.dir_filter {
+        Some(dir_filter) => dir_filter.as_ref(),
+        None => &DESCEND_ALL,
+    };
+    let walk_options = filesystem::WalkOptions {
+        descend,
+        follow_symlinks: options.follow_symlinks,
+    };
+    let entries: Box<dyn Iterator<Item = _> + 'a> = match &options.path_source {
+        Some(source) => {
+            let root = root.to_path_buf();
+            let paths = source.paths(&[root.as_path()]);
+            Box::new(paths.map(move |p| source_entry(options, &[root.as_path()], p)))
+        }
+        None => options.file_system.walk(root, walk_options),
+    };
+    without_excluded(entries, &[root], options).filter_map(move |entry| match entry {
+        Ok(filesystem::WalkEntry { path: p, metadata }) => {
+            match select_file(&p, &metadata, &path_predicate, options) {
+                Selection::Selected => Some(Ok(p)),
+                Selection::Excluded | Selection::Oversized | Selection::Symlink => None,
+            }
+        }
+        Err(e) => Some(Err(e)),
+    })
+}
+
+/// `entries` without the files in `roots` that are excluded by [`RecursiveOptions::exclusions`],
+/// or marked as generated or vendored if [`RecursiveOptions::respect_gitattributes`] is set
+#[cfg(feature = "recursive")]
+fn without_excluded<'a>(
+    entries: impl Iterator<Item = Result<filesystem::WalkEntry, filesystem::WalkError>> + 'a,
+    roots: &[&path::Path],
+    options: &'a RecursiveOptions,
+) -> impl Iterator<Item = Result<filesystem::WalkEntry, filesystem::WalkError>> + 'a {
+    let roots = roots
+        .iter()
+        .map(|root| root.to_path_buf())
+        .collect::<Vec<_>>();
+    let filter = options
+        .respect_gitattributes
+        .then(|| gitattributes::LinguistFilter::new(options.file_system.as_ref(), roots.clone()));
+    let is_excluded = move |p: &path::Path| match &options.exclusions {
+        Some(exclusions) => {
+            exclusions.excludes(p)
+                || roots
+                    .iter()
+                    .filter_map(|root| p.strip_prefix(root).ok())
+                    .any(|rel| exclusions.excludes(rel))
+        }
+        None => false,
+    };
+    entries.filter_map(move |entry| {
+        let skip = match (&entry, &filter) {
+            (Ok(entry), _) if is_excluded(&entry.path) => Ok(true),
+            (Ok(entry), Some(filter)) => filter.is_marked(&entry.path),
+            _ => Ok(false),
+        };
+        match skip {
+            Ok(true) => None,
+            Ok(false) => Some(entry),
+            Err(e) => Some(Err(e)),
+        }
+    })
+}
+
+/// The entry for the file at `p` from [`RecursiveOptions::path_source`], with its depth below the
+/// first of `roots` it's in
+#[cfg(feature = "recursive")]
+fn source_entry(
+    options: &RecursiveOptions,
+    roots: &[&path::Path],
+    p: path::PathBuf,
+) -> Result<filesystem::WalkEntry, filesystem::WalkError> {
+    let err_mapper = |e| filesystem::WalkError::Io(p.clone(), e);
+    let size = options.file_system.file_size(&p).map_err(err_mapper)?;
+    let is_symlink = options.file_system.is_symlink(&p).map_err(err_mapper)?;
+    let depth = roots
+        .iter()
+        .find_map(|root| p.strip_prefix(root).ok())
+        .map_or(0, |rel| rel.components().count());
+    Ok(filesystem::WalkEntry {
+        path: p,
+        metadata: filesystem::FileMetadata {
+            size,
+            is_symlink,
+            depth,
+        },
+    })
+}
+
+/// Descends into every directory, for walks without [`RecursiveOptions::dir_filter`]
+#[cfg(feature = "recursive")]
+static DESCEND_ALL: fn(&path::Path) -> bool = |_| true;
+
+/// Whether a file found by a walk is processed, as per [`select_file`]
+#[cfg(feature = "recursive")]
+enum Selection {
+    /// The file doesn't match the path predicate or [`RecursiveOptions::file_filter`], or is a
+    /// symlink to skip as per [`SymlinkPolicy::Skip`]
+    Excluded,
+    /// The file exceeds [`RecursiveOptions::max_file_size`]
+    Oversized,
+    /// The file is a symlink to report as per [`SymlinkPolicy::Report`]
+    Symlink,
+    /// The file is processed
+    Selected,
+}
+
+/// Whether the file at `p`, with `metadata`, is processed as per `path_predicate` and `options`
+#[cfg(feature = "recursive")]
+fn select_file(
+    p: &path::Path,
+    metadata: &filesystem::FileMetadata,
+    path_predicate: impl Fn(&path::Path) -> bool,
+    options: &RecursiveOptions,
+) -> Selection {
+    if !path_predicate(p) {
+        return Selection::Excluded;
+    }
+    if let Some(file_filter) = &options.file_filter {
+        if !file_filter(p, metadata) {
+            return Selection::Excluded;
         }
-        dest.send(entry.into_path()).unwrap()
     }
-    Ok(())
+    if metadata.is_symlink {
+        match options.symlink_policy {
+            SymlinkPolicy::Follow => {}
+            SymlinkPolicy::Skip => return Selection::Excluded,
+            SymlinkPolicy::Report => return Selection::Symlink,
+        }
+    }
+    match options.max_file_size {
+        Some(max) if metadata.size > max => Selection::Oversized,
+        _ => Selection::Selected,
+    }
 }
 
 /// Prepare a header for inclusion in a particular file syntax by wrapping it with
 /// comment characters as per the provided `delim`.
 ///
 /// Trailing whitespace will be removed to avoid linters disliking the resulting text.
-fn wrap_header(orig_header: &str, delim: HeaderDelimiters) -> String {
+fn wrap_header(orig_header: &str, delim: &HeaderDelimiters) -> String {
     let mut out = String::new();
     if !delim.first_line.is_empty() {
-        out.push_str(delim.first_line);
+        out.push_str(&delim.first_line);
         out.push('\n');
     }
     // assumes header uses \n
     for line in orig_header.split('\n') {
-        out.push_str(delim.content_line_prefix);
+        out.push_str(&delim.content_line_prefix);
         out.push_str(line);
         // Remove any trailing whitespaces (excluding newlines) from `content_line_prefix + line`.
         // For example, if `content_line_prefix` is `// ` and `line` is empty, the resulting string
@@ -460,70 +4550,342 @@ fn wrap_header(orig_header: &str, delim: HeaderDelimiters) -> String {
         out.push('\n');
     }
     if !delim.last_line.is_empty() {
-        out.push_str(delim.last_line);
+        out.push_str(&delim.last_line);
         out.push('\n');
     }
     out
 }
 
-/// Returns the header prefix line, content line prefix, and suffix line for the extension of the
-/// provided path, or `None` if the extension is not recognized.
-fn header_delimiters(p: &path::Path) -> Option<HeaderDelimiters> {
-    match p
-        .extension()
-        // if the extension isn't UTF-8, oh well
-        .and_then(|os_str| os_str.to_str())
-        .unwrap_or("")
-    {
-        "c" | "h" | "gv" | "java" | "scala" | "kt" | "kts" => Some(("/*", " * ", " */")),
-        "js" | "mjs" | "cjs" | "jsx" | "tsx" | "css" | "scss" | "sass" | "ts" => {
-            Some(("/**", " * ", " */"))
-        }
-        "cc" | "cpp" | "cs" | "go" | "hcl" | "hh" | "hpp" | "m" | "mm" | "proto" | "rs"
-        | "swift" | "dart" | "groovy" | "v" | "sv" => Some(("", "// ", "")),
-        "py" | "sh" | "yaml" | "yml" | "dockerfile" | "rb" | "gemfile" | "tcl" | "tf" | "bzl"
-        | "pl" | "pp" | "build" => Some(("", "# ", "")),
-        "el" | "lisp" => Some(("", ";; ", "")),
-        "erl" => Some(("", "% ", "")),
-        "hs" | "lua" | "sql" | "sdl" => Some(("", "-- ", "")),
-        "html" | "xml" | "vue" | "wxi" | "wxl" | "wxs" => Some(("<!--", " ", "-->")),
-        "php" => Some(("", "// ", "")),
-        "ml" | "mli" | "mll" | "mly" => Some(("(**", "   ", "*)")),
-        // also handle whole filenames if extensions didn't match
-        _ => match p
-            .file_name()
-            .and_then(|os_str| os_str.to_str())
-            .unwrap_or("")
-        {
-            "Dockerfile" => Some(("", "# ", "")),
-            _ => None,
-        },
+/// Returns a sequence that ends the comment early in `wrapped`, a header wrapped with `delim`.
+///
+/// A block comment's closing sequence may only appear in its last line. XML comments also can't
+/// contain `--`, other than in their delimiters.
+fn comment_breaking_sequence(wrapped: &str, delim: &HeaderDelimiters) -> Option<String> {
+    let terminator = delim.last_line.trim();
+    if terminator.is_empty() {
+        // line comments end at the end of each line
+        return None;
     }
-    .map(
-        |(first_line, content_line_prefix, last_line)| HeaderDelimiters {
-            first_line,
-            content_line_prefix,
-            last_line,
-        },
-    )
+    let mut sequences = vec![terminator];
+    if delim.first_line.trim_start().starts_with("<!--") {
+        sequences.push("--");
+    }
+    sequences
+        .into_iter()
+        .find(|sequence| {
+            let allowed = delim.first_line.matches(sequence).count()
+                + delim.last_line.matches(sequence).count();
+            wrapped.matches(sequence).count() > allowed
+        })
+        .map(str::to_string)
+}
+
+/// Effective headers rendered during an operation, so that files with the same delimiters and
+/// style don't each re-render the header.
+#[derive(Default)]
+struct EffectiveHeaderCache {
+    entries: cell::RefCell<Vec<EffectiveHeaderCacheEntry>>,
+}
+
+struct EffectiveHeaderCacheEntry {
+    delimiters: HeaderDelimiters,
+    style: HeaderStyle,
+    /// The header text the effective header was rendered from
+    text: String,
+    effective_header: rc::Rc<str>,
 }
 
 /// Delimiters to use around and inside a header for a particular file syntax.
-#[derive(Clone, Copy)]
-struct HeaderDelimiters {
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeaderDelimiters {
     /// Line to prepend before the header
-    first_line: &'static str,
+    first_line: borrow::Cow<'static, str>,
     /// Prefix before each line of the header itself
-    content_line_prefix: &'static str,
+    content_line_prefix: borrow::Cow<'static, str>,
     /// Line to append after the header
-    last_line: &'static str,
+    last_line: borrow::Cow<'static, str>,
+    /// Whether YAML directives and a leading document start marker stay ahead of the header
+    yaml_prolog: bool,
+}
+
+impl HeaderDelimiters {
+    /// Construct delimiters that put `first_line` before the header, prefix each line of the
+    /// header with `content_line_prefix`, and put `last_line` after the header.
+    ///
+    /// An empty `first_line` or `last_line` is omitted entirely rather than producing a blank line.
+    pub fn new(
+        first_line: impl Into<borrow::Cow<'static, str>>,
+        content_line_prefix: impl Into<borrow::Cow<'static, str>>,
+        last_line: impl Into<borrow::Cow<'static, str>>,
+    ) -> Self {
+        Self {
+            first_line: first_line.into(),
+            content_line_prefix: content_line_prefix.into(),
+            last_line: last_line.into(),
+            yaml_prolog: false,
+        }
+    }
+
+    /// Keep YAML directives, e.g. `%YAML 1.2`, and a leading `---` document start marker ahead of
+    /// headers added with [`Placement::Top`], as a header before them would be a separate document.
+    ///
+    /// This is set by [`HeaderDelimiters::for_path`] for `.yaml` and `.yml` files.
+    pub fn yaml_prolog(mut self, keep: bool) -> Self {
+        self.yaml_prolog = keep;
+        self
+    }
+
+    /// Returns the header prefix line, content line prefix, and suffix line for the extension of
+    /// the provided path, or `None` if the extension is not recognized.
+    ///
+    /// Extensions and filenames are matched case-insensitively, e.g. `.RS` is the same as `.rs`.
+    pub fn for_path(p: &path::Path) -> Option<Self> {
+        // if the extension or name isn't UTF-8, oh well
+        let extension = p
+            .extension()
+            .and_then(|os_str| os_str.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        let name = p
+            .file_name()
+            .and_then(|os_str| os_str.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        FILE_TYPES
+            .iter()
+            .find(|(extensions, _, _)| extensions.contains(&extension.as_str()))
+            // also handle whole filenames if extensions didn't match
+            .or_else(|| {
+                FILE_TYPES.iter().find(|(_, filenames, _)| {
+                    filenames.iter().any(|f| is_filename_variant(&name, f))
+                })
+            })
+            .map(Self::for_file_type)
+    }
+
+    /// Returns the delimiters for an entry of [`FILE_TYPES`].
+    fn for_file_type(
+        (extensions, _, (first_line, content_line_prefix, last_line)): &FileTypeEntry,
+    ) -> Self {
+        Self::new(*first_line, *content_line_prefix, *last_line)
+            .yaml_prolog(extensions.contains(&"yaml"))
+    }
+
+    /// Returns the delimiters for a script with the shebang line `line`, e.g.
+    /// `#!/usr/bin/env python3`, according to its interpreter, or `None` if the line isn't a
+    /// shebang or the interpreter isn't recognized.
+    ///
+    /// Adding or deleting headers falls back to this for files whose paths aren't recognized, such
+    /// as scripts without an extension.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// // Copyright 2023 Google LLC.
+    /// // SPDX-License-Identifier: Apache-2.0
+    /// use file_header::*;
+    ///
+    /// assert_eq!(
+    ///     Some(HeaderDelimiters::new("", "# ", "")),
+    ///     HeaderDelimiters::for_shebang("#!/usr/bin/env -S python3.11 -u")
+    /// );
+    /// assert_eq!(
+    ///     Some(HeaderDelimiters::new("", "-- ", "")),
+    ///     HeaderDelimiters::for_shebang("#!/usr/local/bin/lua")
+    /// );
+    /// assert_eq!(None, HeaderDelimiters::for_shebang("#!/bin/unknown"));
+    /// ```
+    pub fn for_shebang(line: &str) -> Option<Self> {
+        let mut words = line.strip_prefix("#!")?.split_whitespace();
+        let mut interpreter = words.next()?.rsplit('/').next()?;
+        if interpreter == "env" {
+            // skip options and variable assignments, e.g. `env -S VAR=1 python3`
+            interpreter = words.find(|w| !w.starts_with('-') && !w.contains('='))?;
+        }
+        // ignore versions, e.g. `python3.11`
+        let interpreter = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+        INTERPRETERS
+            .iter()
+            .find(|(_, interpreters)| interpreters.contains(&interpreter))
+            .and_then(|(extension, _)| {
+                Self::for_path(&path::Path::new("script").with_extension(extension))
+            })
+    }
+
+    /// Returns the file types recognized by [`HeaderDelimiters::for_path`], with their delimiters,
+    /// e.g. to list them in documentation, or to warn about unsupported files before a run.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// // Copyright 2023 Google LLC.
+    /// // SPDX-License-Identifier: Apache-2.0
+    /// use file_header::*;
+    ///
+    /// let rust = HeaderDelimiters::supported_file_types()
+    ///     .into_iter()
+    ///     .find(|t| t.extensions.contains(&"rs"))
+    ///     .unwrap();
+    /// assert_eq!(HeaderDelimiters::new("", "// ", ""), rust.delimiters);
+    /// ```
+    pub fn supported_file_types() -> Vec<SupportedFileType> {
+        FILE_TYPES
+            .iter()
+            .map(|entry| SupportedFileType {
+                extensions: entry.0,
+                filenames: entry.1,
+                delimiters: Self::for_file_type(entry),
+            })
+            .collect()
+    }
+
+    /// The line put before the header, which is empty if there isn't one.
+    pub fn first_line(&self) -> &str {
+        &self.first_line
+    }
+
+    /// The prefix of each line of the header.
+    pub fn content_line_prefix(&self) -> &str {
+        &self.content_line_prefix
+    }
+
+    /// The line put after the header, which is empty if there isn't one.
+    pub fn last_line(&self) -> &str {
+        &self.last_line
+    }
+
+    /// Returns a sequence in `text` that would end a comment with these delimiters before the end
+    /// of the header, e.g. `*/` for `/* ... */` comments, or `None` if `text` is safe to wrap.
+    ///
+    /// Adding a header fails with [`AddHeaderError::CommentTerminator`] rather than producing a
+    /// file that doesn't compile.
+    pub fn comment_breaking_sequence(&self, text: &str) -> Option<String> {
+        comment_breaking_sequence(&wrap_header(text, self), self)
+    }
+}
+
+/// File types that share delimiters, as returned by [`HeaderDelimiters::supported_file_types`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SupportedFileType {
+    /// Lowercase extensions, without the leading `.`
+    pub extensions: &'static [&'static str],
+    /// Lowercase filenames recognized whatever their extension, which also match variants with a
+    /// suffix, e.g. `Dockerfile.dev` for `dockerfile`
+    pub filenames: &'static [&'static str],
+    /// The delimiters used for these files
+    pub delimiters: HeaderDelimiters,
 }
 
+/// Extensions and filenames, with the header prefix line, content line prefix, and suffix line
+/// for them
+type FileTypeEntry = (
+    &'static [&'static str],
+    &'static [&'static str],
+    (&'static str, &'static str, &'static str),
+);
+
+/// The file types [`HeaderDelimiters::for_path`] recognizes
+const FILE_TYPES: [FileTypeEntry; 16] = [
+    (
+        &["c", "h", "gv", "java", "scala", "kt", "kts"],
+        &[],
+        ("/*", " * ", " */"),
+    ),
+    (
+        &[
+            "js", "mjs", "cjs", "jsx", "tsx", "css", "scss", "sass", "ts", "less", "styl",
+            "postcss", "pcss",
+        ],
+        &[],
+        ("/**", " * ", " */"),
+    ),
+    (
+        &[
+            "cc", "cpp", "cs", "go", "hcl", "hh", "hpp", "m", "mm", "proto", "rs", "swift", "dart",
+            "groovy", "v", "sv", "cue", "adoc", "asciidoc",
+        ],
+        &[],
+        ("", "// ", ""),
+    ),
+    (
+        &[
+            "py",
+            "sh",
+            "dockerfile",
+            "containerfile",
+            "rb",
+            "gemfile",
+            "tcl",
+            "tf",
+            "bzl",
+            "pl",
+            "pp",
+            "build",
+            "nix",
+        ],
+        &["dockerfile", "containerfile"],
+        ("", "# ", ""),
+    ),
+    // YAML has its own entry, as directives are kept ahead of the header
+    (&["yaml", "yml"], &[], ("", "# ", "")),
+    (
+        &[
+            "el", "lisp", "asd", "scm", "rkt", "clj", "cljs", "cljc", "edn", "fnl",
+        ],
+        &[],
+        ("", ";; ", ""),
+    ),
+    (&["erl"], &[], ("", "% ", "")),
+    (&["hs", "lua", "sql", "sdl", "dhall"], &[], ("", "-- ", "")),
+    (
+        &["html", "xml", "vue", "wxi", "wxl", "wxs"],
+        &[],
+        ("<!--", " ", "-->"),
+    ),
+    (&["php"], &[], ("", "// ", "")),
+    (&["rst"], &[], ("", ".. ", "")),
+    (&["j2", "jinja"], &[], ("{#", " ", "#}")),
+    (&["hbs"], &[], ("{{!--", " ", "--}}")),
+    (&["erb"], &[], ("<%#", " ", "%>")),
+    (&["pug"], &[], ("", "//- ", "")),
+    (&["ml", "mli", "mll", "mly"], &[], ("(**", "   ", "*)")),
+];
+
+/// The extensions of the scripts of interpreters, without versions, for
+/// [`HeaderDelimiters::for_shebang`]
+const INTERPRETERS: [(&str, &[&str]); 18] = [
+    (
+        "sh",
+        &[
+            "sh", "bash", "zsh", "ksh", "mksh", "dash", "ash", "fish", "csh", "tcsh",
+        ],
+    ),
+    ("py", &["python", "pypy"]),
+    ("rb", &["ruby"]),
+    ("pl", &["perl"]),
+    ("tcl", &["tclsh", "wish", "expect"]),
+    ("js", &["node", "nodejs", "deno", "bun"]),
+    ("ts", &["ts-node"]),
+    ("lua", &["lua", "luajit"]),
+    ("hs", &["runghc", "runhaskell"]),
+    ("erl", &["escript"]),
+    ("lisp", &["sbcl", "clisp", "ccl"]),
+    ("el", &["emacs"]),
+    ("scm", &["guile", "csi", "chibi-scheme"]),
+    ("clj", &["bb", "clojure"]),
+    ("php", &["php"]),
+    ("swift", &["swift"]),
+    ("kts", &["kotlin", "kscript"]),
+    ("groovy", &["groovy"]),
+];
+
+/// The most bytes read looking for a shebang line, as per [`HeaderDelimiters::for_shebang`]
+const MAX_SHEBANG_BYTES: u64 = 256;
+
 /// Magic first lines that we need to check for before adding the license text to a file
-const MAGIC_FIRST_LINES: [&str; 8] = [
+const MAGIC_FIRST_LINES: [&str; 7] = [
     "#!",                       // shell script
     "<?xml",                    // XML declaratioon
-    "<!doctype",                // HTML doctype
     "# encoding:",              // Ruby encoding
     "# frozen_string_literal:", // Ruby interpreter instruction
     "<?php",                    // PHP opening tag
@@ -531,32 +4893,325 @@ const MAGIC_FIRST_LINES: [&str; 8] = [
     "# syntax", // Dockerfile directive https://docs.docker.com/engine/reference/builder/#parser-directives
 ];
 
-/// Apply `operation` to each discovered path in `root` that passes `path_predicate`.
+/// Returns `true` if the lowercase filename `name` is `base`, or a variant like `Dockerfile.dev` for
+/// `dockerfile` (`dev.Dockerfile` is recognized by its extension instead).
+fn is_filename_variant(name: &str, base: &str) -> bool {
+    name.strip_prefix(base)
+        .map_or(false, |rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+/// The most bytes read looking for the end of an XML or HTML prolog, beyond which it's assumed to
+/// be malformed
+const MAX_PROLOG_BYTES: usize = 64 * 1024;
+
+/// Read the rest of the XML or HTML prolog that starts in `first_line` from `reader`, as per
+/// [`markup_prolog`].
+///
+/// Returns the prolog, ending with a line ending, and the content after it, which includes
+/// anything on the same line as the end of the prolog, e.g. `<html>`. If the prolog's first
+/// declaration doesn't end, only `first_line` is treated as the prolog.
+fn read_markup_prolog(
+    reader: &mut impl io::BufRead,
+    first_line: Vec<u8>,
+    line_ending: &[u8],
+) -> io::Result<(Vec<u8>, Vec<u8>)> {
+    let first_line_len = first_line.len();
+    let mut text = first_line;
+    let end = loop {
+        let (end, more) = markup_prolog(&text).unwrap_or((0, false));
+        if !more {
+            break end;
+        }
+        if text.len() > MAX_PROLOG_BYTES || reader.read_until(b'\n', &mut text)? == 0 {
+            // malformed, or the prolog is all there is
+            break if end > 0 { end } else { first_line_len };
+        }
+    };
+    let mut rest = text.split_off(end);
+    // the rest of the prolog's last line goes with it, if there's nothing else on it
+    let line_len = rest.iter().position(|b| *b == b'\n').map(|i| i + 1);
+    match line_len {
+        Some(len) if rest[..len].iter().all(u8::is_ascii_whitespace) => {
+            text.extend(rest.drain(..len));
+        }
+        _ => {
+            // the content after the prolog starts on a new line after the header
+            let content_start = rest.iter().position(|b| !b.is_ascii_whitespace());
+            rest.drain(..content_start.unwrap_or(rest.len()));
+            text.extend_from_slice(line_ending);
+        }
+    }
+    Ok((text, rest))
+}
+
+/// Returns the length of the XML or HTML prolog at the start of `text`, i.e. the XML declaration,
+/// and any document type declaration along with the comments, processing instructions, and
+/// whitespace before it, or `None` if `text` doesn't start with an XML declaration or a document
+/// type declaration.
+///
+/// Document type declarations are matched case-insensitively, as HTML allows.
+///
+/// Also returns whether more text could extend the prolog, i.e. if `text` ends part way through
+/// the prolog, or before it's clear whether a document type declaration follows.
+fn markup_prolog(text: &[u8]) -> Option<(usize, bool)> {
+    let bom_len = if text.starts_with(b"\xef\xbb\xbf") {
+        3
+    } else {
+        0
+    };
+    let (end, mut i) = if text[bom_len..].starts_with(b"<?xml") {
+        match find_subslice(&text[bom_len..], b"?>") {
+            Some(i) => (bom_len + i + 2, bom_len + i + 2),
+            // the declaration continues on the next line
+            None => return Some((0, true)),
+        }
+    } else {
+        // HTML documents start with just a document type declaration, perhaps indented
+        let indent = text[bom_len..]
+            .iter()
+            .take_while(|b| **b == b' ' || **b == b'\t')
+            .count();
+        if !starts_with_ignore_ascii_case(&text[bom_len + indent..], b"<!doctype") {
+            return None;
+        }
+        (0, bom_len + indent)
+    };
+    loop {
+        while text.get(i).map_or(false, u8::is_ascii_whitespace) {
+            i += 1;
+        }
+        let rest = &text[i..];
+        if starts_with_ignore_ascii_case(rest, b"<!doctype") {
+            // nothing else in the prolog may follow the document type declaration
+            return Some(match doctype_len(rest) {
+                Some(len) => (i + len, false),
+                None => (end, true),
+            });
+        }
+        let construct_len = if rest.starts_with(b"<!--") {
+            find_subslice(rest, b"-->").map(|j| j + 3)
+        } else if rest.starts_with(b"<?") {
+            find_subslice(rest, b"?>").map(|j| j + 2)
+        } else if rest.len() < b"<!doctype".len()
+            && starts_with_ignore_ascii_case(b"<!doctype", rest)
+        {
+            // too little text to tell what follows
+            None
+        } else {
+            // the document itself
+            return Some((end, false));
+        };
+        match construct_len {
+            Some(len) => i += len,
+            None => return Some((end, true)),
+        }
+    }
+}
+
+/// Returns the length of the document type declaration at the start of `text`, or `None` if it
+/// doesn't end in `text`.
+fn doctype_len(text: &[u8]) -> Option<usize> {
+    let mut quote = None;
+    // nesting of the internal subset's `[...]`
+    let mut depth = 0_usize;
+    let mut i = 0;
+    while i < text.len() {
+        let b = text[i];
+        match quote {
+            Some(q) if b == q => quote = None,
+            Some(_) => {}
+            None => match b {
+                b'"' | b'\'' => quote = Some(b),
+                b'[' => depth += 1,
+                b']' => depth = depth.saturating_sub(1),
+                b'>' if depth == 0 => return Some(i + 1),
+                // comments in the internal subset may contain anything
+                b'<' if text[i..].starts_with(b"<!--") => {
+                    i += find_subslice(&text[i..], b"-->")? + 2;
+                }
+                _ => {}
+            },
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Returns `true` if `text` starts with `prefix`, ignoring ASCII case.
+fn starts_with_ignore_ascii_case(text: &[u8], prefix: &[u8]) -> bool {
+    text.len() >= prefix.len() && text[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+/// Returns `true` if `first_line` must stay at the start of the file, ahead of the header.
+fn is_magic_first_line(first_line: &str) -> bool {
+    MAGIC_FIRST_LINES.iter().any(|l| first_line.contains(l))
+}
+
+/// Returns `true` if `line` is a YAML directive, e.g. `%YAML 1.2`, which must precede the
+/// document.
+fn is_yaml_directive(line: &str) -> bool {
+    line.trim_start_matches('\u{feff}')
+        .strip_prefix('%')
+        .map_or(false, |rest| {
+            rest.starts_with(|c: char| c.is_ascii_alphabetic())
+        })
+}
+
+/// Returns `true` if `line` is a bare YAML document start marker, i.e. `---` with nothing but
+/// perhaps a comment after it.
+///
+/// Markers followed by content, e.g. `--- |`, aren't matched, as a header after them would be
+/// part of the document.
+fn is_yaml_document_start(line: &str) -> bool {
+    line.trim_start_matches('\u{feff}')
+        .strip_prefix("---")
+        .map_or(false, |rest| {
+            // comments must be separated from the marker by whitespace
+            let content = rest.trim_start();
+            content.is_empty() || (content.starts_with('#') && content.len() < rest.len())
+        })
+}
+
+/// Returns `true` if `line` is an Emacs modeline, like `-*- mode: foo -*-`, which may be inside a
+/// comment.
+///
+/// Emacs only reads modelines on the first line of a file, or the second after a shebang.
+fn is_emacs_modeline(line: &str) -> bool {
+    line.find("-*-")
+        .map(|start| line[start + 3..].contains("-*-"))
+        .unwrap_or(false)
+}
+
+/// Returns `true` if `line` is a Vim modeline, like `vim: set ts=4:` or `vim: ts=4 sw=4`, which
+/// may be inside a comment, and which Vim only looks for near the start or end of a file.
+///
+/// Only comment syntax may precede `vi:`, `vim:`, or `ex:`, so that mentions like `see vim:help`
+/// aren't mistaken for modelines.
+fn is_vim_modeline(line: &str) -> bool {
+    ["vim:", "vi:", "ex:"].iter().any(|marker| {
+        line.find(marker).map_or(false, |start| {
+            let text = &line[..start];
+            // Vim requires whitespace before the marker, unless it starts the line
+            (text.is_empty() || text.ends_with(char::is_whitespace))
+                && !text.chars().any(char::is_alphanumeric)
+                && is_vim_settings(&line[start + marker.len()..])
+        })
+    })
+}
+
+/// Returns `true` if `options` are the settings of a Vim modeline, either `set ts=4 sw=4:`,
+/// which may be followed by other text, or `ts=4 sw=4` separated by whitespace or `:`.
+fn is_vim_settings(options: &str) -> bool {
+    let options = options.trim_start();
+    let (settings, separators): (&str, &[char]) = match options
+        .strip_prefix("set ")
+        .or_else(|| options.strip_prefix("se "))
+    {
+        Some(rest) => match rest.find(':') {
+            Some(end) => (&rest[..end], &[' ', '\t']),
+            None => return false,
+        },
+        None => (options, &[' ', '\t', '\r', '\n', ':']),
+    };
+    let mut settings = settings
+        .split(separators)
+        // comment terminators, e.g. `*/`, aren't settings
+        .filter(|s| s.chars().any(char::is_alphanumeric))
+        .peekable();
+    settings.peek().is_some() && settings.all(is_vim_setting)
+}
+
+/// Returns `true` if `setting` is a Vim option, e.g. `ts=4`, `sw+=2`, or `noet`.
+fn is_vim_setting(setting: &str) -> bool {
+    let name_len = setting
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .unwrap_or(setting.len());
+    let (name, value) = setting.split_at(name_len);
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && (value.is_empty()
+            || ["=", "+=", "-=", "^="]
+                .iter()
+                .any(|op| value.starts_with(op)))
+}
+
+/// Apply `operation` to each discovered path in `roots` that passes `path_predicate`.
 ///
 /// Return the paths for which `operation` took action, as indicated by `operation` returning
-/// `true`.
-fn recursive_optional_operation<E>(
-    root: &path::Path,
+/// `true`, along with any paths skipped as per `options`.
+#[cfg(feature = "recursive")]
+pub(crate) fn recursive_optional_operation<E>(
+    roots: &[impl AsRef<path::Path>],
+    path_predicate: impl Fn(&path::Path) -> bool,
+    options: &RecursiveOptions,
+    operation: impl Fn(&path::Path) -> Result<bool, E>,
+) -> Result<ModificationResults, E>
+where
+    E: From<filesystem::WalkError>,
+{
+    recursive_optional_operation_partial(roots, path_predicate, options, None, operation)
+        .into_result()
+}
+
+/// Apply `operation` to each discovered path in `roots` that passes `path_predicate`, as per
+/// [`recursive_optional_operation`], returning every error along with the results.
+///
+/// Given `aborts`, errors don't stop the operation unless `aborts` returns `true` for them.
+/// Otherwise, the operation stops at the first error.
+#[cfg(feature = "recursive")]
+fn recursive_optional_operation_partial<E>(
+    roots: &[impl AsRef<path::Path>],
     path_predicate: impl Fn(&path::Path) -> bool,
+    options: &RecursiveOptions,
+    aborts: Option<fn(&E) -> bool>,
     operation: impl Fn(&path::Path) -> Result<bool, E>,
-) -> Result<Vec<path::PathBuf>, E>
+) -> PartialResults<ModificationResults, E>
 where
-    E: From<walkdir::Error>,
+    E: From<filesystem::WalkError>,
 {
+    let mut errors = Vec::new();
+    // unbounded, as the paths are only processed once the search is done
     let (path_tx, path_rx) = crossbeam::channel::unbounded::<path::PathBuf>();
-    find_files(root, path_predicate, path_tx)?;
-    path_rx
-        .into_iter()
-        // keep the paths for which the operation took action, and the errors
-        .filter_map(|p| match operation(&p) {
-            Ok(operation_applied) => {
-                if operation_applied {
-                    Some(Ok(p))
-                } else {
-                    None
+    let found = find_files_with(roots, path_predicate, options, path_tx, |e| {
+        if aborts.is_some() {
+            errors.push(E::from(e));
+            Ok(())
+        } else {
+            Err(e)
+        }
+    });
+    let skipped = match found {
+        Ok(skipped) => skipped,
+        Err(e) => {
+            return PartialResults {
+                results: ModificationResults::default(),
+                errors: vec![e.into()],
+            }
+        }
+    };
+    // keep the paths for which the operation took action, and the errors
+    let mut modified_files = Vec::new();
+    for p in path_rx {
+        let result = operation(&p);
+        options.report_progress(ProgressEvent::Processed(&p));
+        match result {
+            Ok(true) => modified_files.push(p),
+            Ok(false) => {}
+            Err(e) => {
+                let stop = aborts.map_or(true, |aborts| aborts(&e));
+                errors.push(e);
+                if stop {
+                    break;
                 }
             }
-            Err(e) => Some(Err(e)),
-        })
-        .collect::<Result<Vec<_>, _>>()
+        }
+    }
+    PartialResults {
+        results: ModificationResults {
+            modified_files,
+            oversized_files: skipped.oversized,
+            symlinks: skipped.symlinks,
+            ..ModificationResults::default()
+        },
+        errors,
+    }
 }
@@ -0,0 +1,442 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Validating the `SPDX-License-Identifier` lines already present in files.
+//!
+//! License expressions are parsed per the SPDX specification, e.g. `MIT`, `Apache-2.0 OR MIT`,
+//! `GPL-2.0-or-later WITH Classpath-exception-2.0`, or `(MIT AND BSD-3-Clause) OR Apache-2.0`,
+//! and each license and exception must be on the SPDX list. `LicenseRef-` custom identifiers are
+//! accepted as-is, unless the validator is given the [`CustomLicense`]s in use.
+//!
+//! # Examples
+//!
+//! ```
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use file_header::{license::spdx::identifier::*, *};
+//!
+//! let validator = IdentifierValidator::new().allow(["Apache-2.0", "MIT"]);
+//! assert!(validator.validate_expression("Apache-2.0 OR MIT").is_ok());
+//! assert!(validator.validate_expression("GPL-3.0-only").is_err());
+//!
+//! // as a checker, files only pass if they have a valid, allowed identifier
+//! let header = Header::new(
+//!     validator,
+//!     "SPDX-License-Identifier: Apache-2.0".to_string(),
+//! );
+//! assert!(header.check_str("// SPDX-License-Identifier: MIT\n").unwrap());
+//! assert!(!header.check_str("// SPDX-License-Identifier: Apache2\n").unwrap());
+//! ```
+
+use super::{is_valid_license_ref, CustomLicense};
+#[cfg(feature = "recursive")]
+use crate::{filesystem, recursive_optional_operation, RecursiveOptions};
+use crate::{lines, HeaderChecker};
+use std::io;
+#[cfg(feature = "recursive")]
+use std::{cell, collections, path};
+
+/// The tag that starts an identifier line
+const IDENTIFIER_TAG: &str = "SPDX-License-Identifier:";
+
+/// How many lines of each file are searched for an identifier line by default
+pub const SEARCH_LINES: usize = 10;
+
+/// Validates `SPDX-License-Identifier` lines against the SPDX license list, and optionally
+/// against lists of allowed and denied licenses.
+///
+/// As a [`HeaderChecker`], files pass if one of their first several lines is a valid identifier
+/// line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IdentifierValidator {
+    /// If set, only these licenses are allowed
+    allowed: Option<Vec<String>>,
+    denied: Vec<String>,
+    /// If set, only these `LicenseRef-` ids are known
+    custom_licenses: Option<Vec<String>>,
+    max_lines: usize,
+}
+
+impl Default for IdentifierValidator {
+    fn default() -> Self {
+        Self {
+            allowed: None,
+            denied: Vec::new(),
+            custom_licenses: None,
+            max_lines: SEARCH_LINES,
+        }
+    }
+}
+
+impl IdentifierValidator {
+    /// Construct a validator that allows any license on the SPDX list, and searches the first
+    /// [`SEARCH_LINES`] lines of files for an identifier line.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only allow the licenses with the SPDX ids in `ids`, e.g. `Apache-2.0`.
+    ///
+    /// Every license in an expression must be allowed, even those in an `OR`.
+    pub fn allow(mut self, ids: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.allowed = Some(ids.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Disallow the licenses with the SPDX ids in `ids`, e.g. `AGPL-3.0-only`.
+    pub fn deny(mut self, ids: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.denied.extend(ids.into_iter().map(Into::into));
+        self
+    }
+
+    /// Only accept the `LicenseRef-` ids of `licenses`, rather than any well-formed `LicenseRef-`
+    /// id.
+    pub fn custom_licenses<'a>(
+        mut self,
+        licenses: impl IntoIterator<Item = &'a CustomLicense>,
+    ) -> Self {
+        self.custom_licenses
+            .get_or_insert_with(Vec::new)
+            .extend(licenses.into_iter().map(|l| l.id().to_string()));
+        self
+    }
+
+    /// Search the first `max_lines` lines of files for an identifier line.
+    pub fn max_lines(mut self, max_lines: usize) -> Self {
+        self.max_lines = max_lines;
+        self
+    }
+
+    /// Validate the license expression `expression`, e.g. `Apache-2.0 OR MIT`.
+    pub fn validate_expression(&self, expression: &str) -> Result<(), IdentifierError> {
+        let tokens = tokenize(expression);
+        let mut parser = Parser {
+            tokens: &tokens,
+            pos: 0,
+            terms: Vec::new(),
+        };
+        parser.expression()?;
+        if parser.pos != tokens.len() || tokens.is_empty() {
+            return Err(IdentifierError::Malformed(expression.to_string()));
+        }
+        for term in parser.terms {
+            self.validate_term(term)?;
+        }
+        Ok(())
+    }
+
+    /// Find and validate the identifier line in the first lines of `input`.
+    ///
+    /// Returns the validated license expression.
+    pub fn validate_reader(
+        &self,
+        input: &mut impl io::Read,
+    ) -> io::Result<Result<String, IdentifierError>> {
+        let mut result = Err(IdentifierError::Missing);
+        lines::find_line(input, self.max_lines, |line| {
+            identifier_expression(line).map_or(false, |expression| {
+                result = self
+                    .validate_expression(expression)
+                    .map(|_| expression.to_string());
+                true
+            })
+        })?;
+        Ok(result)
+    }
+
+    fn validate_term(&self, term: Term<'_>) -> Result<(), IdentifierError> {
+        let license = term.license.trim_end_matches('+');
+        if is_license_ref(license) {
+            let known = self.custom_licenses.as_ref().map_or(true, |ids| {
+                ids.iter().any(|id| id == without_document_ref(license))
+            });
+            if !known {
+                return Err(IdentifierError::UnknownLicense(license.to_string()));
+            }
+        } else {
+            license
+                .parse::<&dyn license::License>()
+                .map_err(|_| IdentifierError::UnknownLicense(license.to_string()))?;
+        }
+        if let Some(exception) = term.exception {
+            exception
+                .parse::<&dyn license::Exception>()
+                .map_err(|_| IdentifierError::UnknownException(exception.to_string()))?;
+        }
+        let allowed = self
+            .allowed
+            .as_ref()
+            .map_or(true, |allowed| allowed.iter().any(|id| id == license));
+        if !allowed || self.denied.iter().any(|id| id == license) {
+            return Err(IdentifierError::Disallowed(license.to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl HeaderChecker for IdentifierValidator {
+    fn check(&self, input: &mut impl io::Read) -> io::Result<bool> {
+        Ok(self.validate_reader(input)?.is_ok())
+    }
+}
+
+/// Problems with a file's `SPDX-License-Identifier` line
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum IdentifierError {
+    /// There is no identifier line
+    #[error("No SPDX-License-Identifier line")]
+    Missing,
+    /// The license expression could not be parsed
+    #[error("Malformed license expression: {0}")]
+    Malformed(String),
+    /// The license is not on the SPDX license list
+    #[error("Unknown license: {0}")]
+    UnknownLicense(String),
+    /// The exception is not on the SPDX exception list
+    #[error("Unknown exception: {0}")]
+    UnknownException(String),
+    /// The license is not allowed by the validator
+    #[error("Disallowed license: {0}")]
+    Disallowed(String),
+}
+
+/// The license expression on the identifier line in the first `max_lines` lines of `input`, if
+/// any, without validating it.
+pub fn find_expression(input: &mut impl io::Read, max_lines: usize) -> io::Result<Option<String>> {
+    let mut found = None;
+    lines::find_line(input, max_lines, |line| {
+        found = identifier_expression(line).map(str::to_string);
+        found.is_some()
+    })?;
+    Ok(found)
+}
+
+/// Find the license expression on the identifier line in the first [`SEARCH_LINES`] lines of
+/// every file in `root` that matches `path_predicate`, with the behavior adjusted by `options`.
+///
+/// Expressions aren't validated, and no [`crate::Header`] is needed, so this is a quick way to
+/// find out which licenses are in use, and where.
+#[cfg(feature = "recursive")]
+pub fn scan_identifiers_recursively(
+    root: &path::Path,
+    path_predicate: impl Fn(&path::Path) -> bool,
+    options: &RecursiveOptions,
+) -> Result<IdentifierScan, ScanIdentifiersRecursivelyError> {
+    let scan = cell::RefCell::new(IdentifierScan::default());
+    let skipped = recursive_optional_operation::<ScanIdentifiersRecursivelyError>(
+        &[root],
+        path_predicate,
+        options,
+        |p| {
+            let found = options
+                .file_system
+                .open(p)
+                .and_then(|mut f| find_expression(&mut f, SEARCH_LINES));
+            let relative = options.relative_path(&[root], p.to_path_buf());
+            let mut scan = scan.borrow_mut();
+            match found {
+                Ok(Some(expression)) => scan
+                    .identifiers
+                    .entry(expression)
+                    .or_default()
+                    .push(relative),
+                Ok(None) => scan.missing_identifier_files.push(relative),
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                    scan.binary_files.push(relative)
+                }
+                Err(e) => return Err(ScanIdentifiersRecursivelyError::IoError(p.to_path_buf(), e)),
+            }
+            Ok(false)
+        },
+    )?;
+    let mut scan = scan.into_inner();
+    scan.oversized_files = skipped.oversized_files;
+    scan.symlinks = skipped.symlinks;
+    Ok(scan)
+}
+
+/// The files found by [`scan_identifiers_recursively`], by their license expression
+#[cfg(feature = "recursive")]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IdentifierScan {
+    /// The files with each license expression, e.g. `Apache-2.0 OR MIT`, as written in them
+    pub identifiers: collections::BTreeMap<String, Vec<path::PathBuf>>,
+    /// Files without an identifier line
+    pub missing_identifier_files: Vec<path::PathBuf>,
+    /// Paths that appeared to be binary, not UTF-8 text
+    pub binary_files: Vec<path::PathBuf>,
+    /// Paths that were skipped because they exceeded [`RecursiveOptions::max_file_size`]
+    pub oversized_files: Vec<path::PathBuf>,
+    /// Symlinks to files that were skipped, as per [`crate::SymlinkPolicy::Report`]
+    pub symlinks: Vec<path::PathBuf>,
+}
+
+/// Errors that can occur when scanning for identifiers recursively
+#[cfg(feature = "recursive")]
+#[derive(Debug, thiserror::Error)]
+pub enum ScanIdentifiersRecursivelyError {
+    /// An I/O error occurred while reading the path
+    #[error("I/O error at {0:?}: {1}")]
+    IoError(path::PathBuf, io::Error),
+    /// `walkdir` could not navigate the directory structure
+    #[error("Walkdir error: {0}")]
+    WalkdirError(#[from] walkdir::Error),
+}
+
+#[cfg(feature = "recursive")]
+impl From<filesystem::WalkError> for ScanIdentifiersRecursivelyError {
+    fn from(value: filesystem::WalkError) -> Self {
+        match value {
+            filesystem::WalkError::Walkdir(e) => Self::WalkdirError(e),
+            filesystem::WalkError::Io(p, e) => Self::IoError(p, e),
+        }
+    }
+}
+
+/// Returns the license expression in `line`, if it's an identifier line.
+fn identifier_expression(line: &str) -> Option<&str> {
+    let start = line.find(IDENTIFIER_TAG)? + IDENTIFIER_TAG.len();
+    let mut expression = line[start..].trim();
+    // the end of a block comment may follow on the same line
+    for closer in ["*/", "-->", "*)", "-}"] {
+        expression = expression.trim_end_matches(closer).trim_end();
+    }
+    Some(expression)
+}
+
+/// Returns `true` if `id` is a user-defined `LicenseRef-`, optionally in another document.
+fn is_license_ref(id: &str) -> bool {
+    is_valid_license_ref(without_document_ref(id))
+}
+
+/// Strip any `DocumentRef-...:` prefix from `id`
+fn without_document_ref(id: &str) -> &str {
+    match id.split_once(':') {
+        Some((document, id)) if document.starts_with("DocumentRef-") => id,
+        _ => id,
+    }
+}
+
+/// Split a license expression into parentheses and words
+fn tokenize(expression: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    for word in expression.split_whitespace() {
+        let mut rest = word;
+        while !rest.is_empty() {
+            match rest.find(['(', ')']) {
+                Some(0) => {
+                    tokens.push(&rest[..1]);
+                    rest = &rest[1..];
+                }
+                Some(i) => {
+                    tokens.push(&rest[..i]);
+                    rest = &rest[i..];
+                }
+                None => {
+                    tokens.push(rest);
+                    rest = "";
+                }
+            }
+        }
+    }
+    tokens
+}
+
+/// A license, and the exception to it if any, in an expression
+#[derive(Clone, Copy, Debug)]
+struct Term<'a> {
+    license: &'a str,
+    exception: Option<&'a str>,
+}
+
+/// A recursive descent parser for license expressions, collecting the terms they contain
+struct Parser<'t, 'a> {
+    tokens: &'t [&'a str],
+    pos: usize,
+    terms: Vec<Term<'a>>,
+}
+
+impl<'a> Parser<'_, 'a> {
+    /// `and-expression ("OR" and-expression)*`
+    fn expression(&mut self) -> Result<(), IdentifierError> {
+        self.and_expression()?;
+        while self.eat_operator("OR") {
+            self.and_expression()?;
+        }
+        Ok(())
+    }
+
+    /// `term ("AND" term)*`
+    fn and_expression(&mut self) -> Result<(), IdentifierError> {
+        self.term()?;
+        while self.eat_operator("AND") {
+            self.term()?;
+        }
+        Ok(())
+    }
+
+    /// `"(" expression ")"` or `license ["WITH" exception]`
+    fn term(&mut self) -> Result<(), IdentifierError> {
+        match self.next() {
+            Some("(") => {
+                self.expression()?;
+                match self.next() {
+                    Some(")") => Ok(()),
+                    _ => Err(self.malformed()),
+                }
+            }
+            Some(license) if is_id(license) => {
+                let exception = if self.eat_operator("WITH") {
+                    Some(
+                        self.next()
+                            .filter(|e| is_id(e))
+                            .ok_or_else(|| self.malformed())?,
+                    )
+                } else {
+                    None
+                };
+                self.terms.push(Term { license, exception });
+                Ok(())
+            }
+            _ => Err(self.malformed()),
+        }
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.tokens.get(self.pos).copied();
+        self.pos += 1;
+        token
+    }
+
+    /// Consume the next token if it's `operator`, in upper or lower case
+    fn eat_operator(&mut self, operator: &str) -> bool {
+        match self.tokens.get(self.pos) {
+            Some(token) if *token == operator || *token == operator.to_lowercase() => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn malformed(&self) -> IdentifierError {
+        IdentifierError::Malformed(self.tokens.join(" "))
+    }
+}
+
+/// Returns `true` if `token` could be a license or exception id, rather than an operator or
+/// parenthesis.
+fn is_id(token: &str) -> bool {
+    !["(", ")", "AND", "OR", "WITH", "and", "or", "with"].contains(&token)
+}
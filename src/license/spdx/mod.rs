@@ -21,7 +21,8 @@
 //! replaced, [NoTokens] is available for that purpose.
 //!
 //! Several common licenses have structs defined, with `LicenseTokens` already appropriately
-//! implemented (e.g. `APACHE_2_0`).
+//! implemented (e.g. `APACHE_2_0`), using the replaceable fields of the SPDX templates in
+//! [template].
 //!
 //! Most other licenses don't need anything other than the copyright year and the copyright holder,
 //! or have nothing at all, and can be easily turned into headers with [SpdxLicense], a type to
@@ -46,6 +47,94 @@
 //! // use normal header API to check or add
 //!```
 //!
+//! ## Getting a compact copyright and SPDX identifier header
+//!
+//! ```
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use file_header::license::spdx::*;
+//!
+//! // "Copyright 2023 Foo Inc." followed by "SPDX-License-Identifier: Apache-2.0"
+//! let header = short_header(license::licenses::Apache2_0, 2023, "Foo Inc.");
+//! ```
+//!
+//! ## Getting a header for the Rust ecosystem's `Apache-2.0 OR MIT` dual license
+//!
+//! ```
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use file_header::license::spdx::*;
+//!
+//! // with the conventional "Licensed under the Apache License ... or the MIT license" wording
+//! let header = APACHE_2_0_OR_MIT.build_header(YearCopyrightOwnerValue::new(
+//!     2023,
+//!     "Foo Inc.".to_string(),
+//! ));
+//!
+//! // or just the copyright line and "SPDX-License-Identifier: Apache-2.0 OR MIT"
+//! let header = APACHE_2_0_OR_MIT.build_short_header(2023, "Foo Inc.");
+//! ```
+//!
+//! ## Getting a compact header for a license with an exception
+//!
+//! ```
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use file_header::license::spdx::*;
+//!
+//! let with_exception = LicenseWithException::new("Apache-2.0", "LLVM-exception").unwrap();
+//! assert_eq!("Apache-2.0 WITH LLVM-exception", with_exception.expression());
+//! let header = with_exception.build_short_header(2023, "Foo Inc.");
+//!
+//! // the exception only applies to GPL licenses
+//! assert!(LicenseWithException::new("MIT", "Classpath-exception-2.0").is_err());
+//! ```
+//!
+//! ## Getting a header for a custom license
+//!
+//! ```
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use file_header::license::spdx::*;
+//!
+//! let proprietary = CustomLicense::new(
+//!     "LicenseRef-Foo-Proprietary",
+//!     "Proprietary and confidential. Do not distribute.",
+//! )
+//! .unwrap();
+//! let header = proprietary.build_header(YearCopyrightOwnerValue::new(
+//!     2023,
+//!     "Foo Inc.".to_string(),
+//! ));
+//!
+//! // or "SPDX-License-Identifier: LicenseRef-Foo-Proprietary" after the copyright line
+//! let header = proprietary.build_short_header(2023, "Foo Inc.");
+//! ```
+//!
+//! ## Getting a header with a translation of the license
+//!
+//! ```
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use file_header::license::spdx::*;
+//!
+//! let localized = LocalizedLicense::new(&MIT).translation(
+//!     "de",
+//!     "Copyright (c) <year> <copyright holders>\n\nHiermit wird unentgeltlich jeder Person ...",
+//!     "Hiermit wird unentgeltlich",
+//! );
+//! let values = || YearCopyrightOwnerValue::new(2023, "Foo GmbH".to_string());
+//! // "de-CH" falls back to the translation for "de"
+//! let header = localized.build_header("de-CH", values()).unwrap();
+//! assert!(header.text(None).starts_with("Copyright (c) 2023 Foo GmbH\n\nHiermit"));
+//!
+//! // or the English text followed by the translation
+//! let header = localized.build_bilingual_header("de", values()).unwrap();
+//! assert!(header.text(None).starts_with("MIT License\n"));
+//!
+//! assert!(localized.build_header("ja", values()).is_err());
+//! ```
+//!
 //! ## Getting a header for an SPDX license with no tokens to replace
 //!
 //! ```
@@ -148,13 +237,15 @@
 //!
 //! ```
 
-use crate::{Header, SingleLineChecker};
+use crate::{lines, Header, HeaderChecker, HeaderTextProvider, SingleLineChecker};
 use lazy_static::lazy_static;
-use std::marker;
+use std::{borrow, io, marker, path};
 
 /// Re-export of the `license` crate for user convenience
 pub use license;
 
+pub mod identifier;
+pub mod template;
 #[cfg(test)]
 mod tests;
 
@@ -189,29 +280,183 @@ impl<L: LicenseTokens> SpdxLicense<L> {
     /// interpolate into the license.
     /// The license's header is used, if the license offers one, otherwise the main license text
     /// is used instead.
+    ///
+    /// The text is only rendered when it's needed, e.g. to add the header, so checking for the
+    /// header doesn't copy the license text. See also [SpdxLicense::build_checker].
     pub fn build_header(
         &self,
         replacement_values: L::TokenReplacementValues,
     ) -> Header<SingleLineChecker> {
-        let checker = SingleLineChecker::new(self.search_pattern.clone(), self.lines_to_search);
-        // use header, if the license has a specific header else the license text
-        let text = self
-            .license_text
+        Header::with_text_provider(
+            self.build_checker(),
+            LicenseText {
+                text: self.text(),
+                pairs: L::replacement_pairs(replacement_values),
+            },
+        )
+    }
+
+    /// Build just the checker for this license's header, for when the header will only be
+    /// checked for, not added.
+    pub fn build_checker(&self) -> SingleLineChecker {
+        SingleLineChecker::new(self.search_pattern.clone(), self.lines_to_search)
+    }
+
+    /// The license's header, if it offers one, otherwise the main license text.
+    fn text(&self) -> &'static str {
+        self.license_text
             .header()
-            .unwrap_or(self.license_text.text());
+            .unwrap_or(self.license_text.text())
+    }
+}
 
-        let header = L::replacement_pairs(replacement_values).iter().fold(
-            text.to_string(),
-            |current_text, (replace_token, replace_value)| {
-                // replacing only the first occurrence seems wise
-                current_text.replacen(replace_token, replace_value, 1)
-            },
-        );
+/// The text of an [SpdxLicense], with its tokens replaced each time it's needed rather than when
+/// the header is built
+struct LicenseText {
+    text: &'static str,
+    pairs: Vec<(&'static str, String)>,
+}
+
+impl HeaderTextProvider for LicenseText {
+    fn text(&self, _p: Option<&path::Path>) -> borrow::Cow<'_, str> {
+        borrow::Cow::Owned(replace_tokens(self.text, &self.pairs))
+    }
+}
+
+/// `text` with the first occurrence of each token in `pairs` replaced by its value
+fn replace_tokens(text: &str, pairs: &[(&'static str, String)]) -> String {
+    pairs.iter().fold(
+        text.to_string(),
+        |current_text, (replace_token, replace_value)| {
+            // replacing only the first occurrence seems wise
+            current_text.replacen(replace_token, replace_value, 1)
+        },
+    )
+}
+
+/// An [SpdxLicense] with translations of its header text, e.g. the official Japanese
+/// translation, or one approved by an organization's legal team, for each of several locales.
+///
+/// Translations use the same tokens as the license's own text, e.g. `[yyyy]` for Apache 2.0, so
+/// the same replacement values fill in both.
+pub struct LocalizedLicense<'a, L: LicenseTokens> {
+    license: &'a SpdxLicense<L>,
+    translations: Vec<Translation>,
+}
+
+/// The header text of a license in a locale
+struct Translation {
+    locale: String,
+    text: String,
+    search_pattern: String,
+}
 
-        Header::new(checker, header)
+impl<'a, L: LicenseTokens> LocalizedLicense<'a, L> {
+    /// Construct a `LocalizedLicense` for `license`, with no translations.
+    pub fn new(license: &'a SpdxLicense<L>) -> Self {
+        Self {
+            license,
+            translations: Vec::new(),
+        }
+    }
+
+    /// Add `text` as the translation of the license's header for `locale`, a language tag like
+    /// `ja` or `de-CH`, with headers in that locale checked for by searching for
+    /// `search_pattern`.
+    ///
+    /// A later translation for the same locale replaces an earlier one.
+    pub fn translation(mut self, locale: &str, text: &str, search_pattern: &str) -> Self {
+        self.translations
+            .retain(|t| !same_locale(&t.locale, locale));
+        self.translations.push(Translation {
+            locale: locale.to_string(),
+            text: text.to_string(),
+            search_pattern: search_pattern.to_string(),
+        });
+        self
+    }
+
+    /// The locales with translations, in the order they were added.
+    pub fn locales(&self) -> impl Iterator<Item = &str> {
+        self.translations.iter().map(|t| t.locale.as_str())
+    }
+
+    /// Build a header with the translation for `locale`, with tokens replaced as per
+    /// [SpdxLicense::build_header].
+    ///
+    /// Locales are compared ignoring case and treating `_` as `-`, and a locale without a
+    /// translation of its own falls back to the translation for its language, e.g. `de-AT` to
+    /// `de`.
+    pub fn build_header(
+        &self,
+        locale: &str,
+        replacement_values: L::TokenReplacementValues,
+    ) -> Result<Header<SingleLineChecker>, LocalizationError> {
+        let translation = self.find(locale)?;
+        Ok(Header::new(
+            SingleLineChecker::new(
+                translation.search_pattern.clone(),
+                self.license.lines_to_search,
+            ),
+            replace_tokens(&translation.text, &L::replacement_pairs(replacement_values)),
+        ))
+    }
+
+    /// Build a bilingual header: the license's own header text, followed by a blank line and the
+    /// translation for `locale`, with tokens replaced in both.
+    ///
+    /// The header is checked for by searching for the translation's search pattern, so files
+    /// with only the license's own header don't have it. Locales are found as per
+    /// [LocalizedLicense::build_header].
+    pub fn build_bilingual_header(
+        &self,
+        locale: &str,
+        replacement_values: L::TokenReplacementValues,
+    ) -> Result<Header<SingleLineChecker>, LocalizationError> {
+        let translation = self.find(locale)?;
+        let pairs = L::replacement_pairs(replacement_values);
+        // the translation is further down, so search further for it
+        let lines_to_search = self.license.lines_to_search + self.license.text().lines().count();
+        Ok(Header::new(
+            SingleLineChecker::new(translation.search_pattern.clone(), lines_to_search),
+            format!(
+                "{}\n\n{}",
+                replace_tokens(self.license.text(), &pairs).trim_end(),
+                replace_tokens(&translation.text, &pairs)
+            ),
+        ))
+    }
+
+    /// The translation for `locale`, or else for its language
+    fn find(&self, locale: &str) -> Result<&Translation, LocalizationError> {
+        let language = locale.split(['-', '_']).next().unwrap_or("");
+        self.translations
+            .iter()
+            .find(|t| same_locale(&t.locale, locale))
+            .or_else(|| {
+                self.translations
+                    .iter()
+                    .find(|t| same_locale(&t.locale, language))
+            })
+            .ok_or_else(|| LocalizationError::UnknownLocale(locale.to_string()))
     }
 }
 
+/// Returns `true` if `a` and `b` are the same language tag, ignoring case and treating `_` as
+/// `-`.
+fn same_locale(a: &str, b: &str) -> bool {
+    let normalize = |tag: &str| tag.replace('_', "-").to_ascii_lowercase();
+    normalize(a) == normalize(b)
+}
+
+/// Errors that can occur when building a header with a [LocalizedLicense]
+#[derive(Debug, thiserror::Error)]
+pub enum LocalizationError {
+    /// There's no translation for the locale or its language
+    #[error("No translation for locale: {0}")]
+    UnknownLocale(String),
+}
+
 /// Tokens in license text to be replaced, e.g. `yyyy` which will be replaced with the copyright
 /// year.
 pub trait LicenseTokens {
@@ -245,10 +490,9 @@ impl LicenseTokens for Apache2Tokens {
     fn replacement_pairs(
         replacements: Self::TokenReplacementValues,
     ) -> Vec<(&'static str, String)> {
-        vec![
-            ("[yyyy]", replacements.year.to_string()),
-            ("[name of copyright owner]", replacements.copyright_owner),
-        ]
+        // the appendix's boilerplate is a single copyright line, which is repeated for each owner
+        let owners = replacements.owner_lines(&format!("Copyright {} ", replacements.year));
+        copyright_pairs("Apache-2.0", format!("{} {}", replacements.year, owners))
     }
 }
 
@@ -262,10 +506,7 @@ impl LicenseTokens for MitTokens {
     fn replacement_pairs(
         replacements: Self::TokenReplacementValues,
     ) -> Vec<(&'static str, String)> {
-        vec![
-            ("<year>", replacements.year.to_string()),
-            ("<copyright holders>", replacements.copyright_owner),
-        ]
+        copyright_pairs("MIT", replacements.year_and_owners())
     }
 }
 
@@ -279,10 +520,7 @@ impl LicenseTokens for Bsd3ClauseTokens {
     fn replacement_pairs(
         replacements: Self::TokenReplacementValues,
     ) -> Vec<(&'static str, String)> {
-        vec![
-            ("<year>", replacements.year.to_string()),
-            ("<owner>", replacements.copyright_owner),
-        ]
+        copyright_pairs("BSD-3-Clause", replacements.year_and_owners())
     }
 }
 
@@ -296,19 +534,30 @@ impl LicenseTokens for Gpl3Tokens {
     fn replacement_pairs(
         replacements: Self::TokenReplacementValues,
     ) -> Vec<(&'static str, String)> {
-        vec![
-            ("<year>", replacements.year.to_string()),
-            ("<name of author>", replacements.copyright_owner),
-        ]
+        copyright_pairs("GPL-3.0-only", replacements.year_and_owners())
     }
 }
 
+/// The replacement pairs for the `copyright` field of the bundled template of the license with
+/// SPDX id `id`
+fn copyright_pairs(id: &str, copyright: String) -> Vec<(&'static str, String)> {
+    template::template(id)
+        .expect("license has a bundled template")
+        .replacement_pairs([("copyright", copyright)])
+}
+
 /// Replacement values for licenses that use a _year_ and _copyright owner name_.
+///
+/// Files co-owned by several organizations can have [additional
+/// owners](YearCopyrightOwnerValue::additional_owner), which are rendered per each license's
+/// convention, e.g. comma-separated for MIT, or one copyright line each for Apache 2.0.
 pub struct YearCopyrightOwnerValue {
     /// The year of the copyright
     pub year: u32,
     /// The holder of the copyright
     pub copyright_owner: String,
+    /// Any other holders of the copyright
+    pub additional_owners: Vec<String>,
 }
 
 impl YearCopyrightOwnerValue {
@@ -317,10 +566,423 @@ impl YearCopyrightOwnerValue {
         Self {
             year,
             copyright_owner,
+            additional_owners: Vec::new(),
+        }
+    }
+
+    /// Add `owner` as another holder of the copyright.
+    pub fn additional_owner(mut self, owner: String) -> Self {
+        self.additional_owners.push(owner);
+        self
+    }
+
+    /// All the copyright owners, in order.
+    pub fn owners(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.copyright_owner.as_str())
+            .chain(self.additional_owners.iter().map(|o| o.as_str()))
+    }
+
+    /// The copyright owners separated by commas, e.g. `Foo Inc., Bar LLC`.
+    pub fn comma_separated_owners(&self) -> String {
+        self.owners().collect::<Vec<_>>().join(", ")
+    }
+
+    /// The year followed by the comma-separated copyright owners, e.g. `2023 Foo Inc., Bar LLC`,
+    /// for the `copyright` field of most license templates.
+    pub fn year_and_owners(&self) -> String {
+        format!("{} {}", self.year, self.comma_separated_owners())
+    }
+
+    /// The copyright owners on a line each, with each line after the first starting with
+    /// `line_prefix`, for replacing a token at the end of a copyright line that starts with
+    /// `line_prefix`.
+    pub fn owner_lines(&self, line_prefix: &str) -> String {
+        self.owners()
+            .collect::<Vec<_>>()
+            .join(&format!("\n{}", line_prefix))
+    }
+}
+
+/// Build a compact two line header for `license`: a copyright line for `year` and
+/// `copyright_owner`, followed by an `SPDX-License-Identifier` line, e.g.:
+///
+/// ```text
+/// Copyright 2023 Foo Inc.
+/// SPDX-License-Identifier: Apache-2.0
+/// ```
+///
+/// The header's checker is a [CopyrightSpdxChecker], so files with a different copyright year
+/// still have the header.
+pub fn short_header(
+    license: impl license::License,
+    year: u32,
+    copyright_owner: &str,
+) -> Header<CopyrightSpdxChecker> {
+    short_header_for_expression(license.id(), year, copyright_owner)
+}
+
+/// Build a compact two line header as per [short_header], but for an SPDX license expression
+/// like `Apache-2.0 OR MIT` rather than a single license.
+pub fn short_header_for_expression(
+    expression: &str,
+    year: u32,
+    copyright_owner: &str,
+) -> Header<CopyrightSpdxChecker> {
+    Header::new(
+        CopyrightSpdxChecker::new(copyright_owner.to_string(), expression, 10),
+        format!(
+            "Copyright {} {}\nSPDX-License-Identifier: {}",
+            year, copyright_owner, expression
+        ),
+    )
+}
+
+/// Checks for a copyright line naming a copyright owner, immediately followed by an
+/// `SPDX-License-Identifier` line with a particular license expression, in the first several
+/// lines of each file.
+///
+/// The copyright year isn't checked, and the identifier must be the whole expression, so
+/// `Apache-2.0` doesn't match `SPDX-License-Identifier: Apache-2.0 OR MIT`.
+///
+/// The required order of the lines, and how far apart they may be, can be adjusted, e.g. to allow
+/// another copyright owner's line in between. Typographic characters are treated as their ASCII
+/// equivalents, as per [`SingleLineChecker`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CopyrightSpdxChecker {
+    copyright_owner: String,
+    identifier_line: String,
+    max_lines: usize,
+    identifier_first: bool,
+    max_distance: usize,
+}
+
+impl CopyrightSpdxChecker {
+    /// `copyright_owner`: the owner the copyright line must name
+    /// `expression`: the SPDX license expression the identifier line must have
+    /// `max_lines`: how many lines to search before giving up
+    pub fn new(copyright_owner: String, expression: &str, max_lines: usize) -> Self {
+        Self {
+            copyright_owner: lines::normalize_typography(&copyright_owner).into_owned(),
+            identifier_line: format!("SPDX-License-Identifier: {}", expression),
+            max_lines,
+            identifier_first: false,
+            max_distance: 1,
+        }
+    }
+
+    /// Require the `SPDX-License-Identifier` line to come before the copyright line if
+    /// `identifier_first` is `true`, rather than after it.
+    pub fn identifier_first(mut self, identifier_first: bool) -> Self {
+        self.identifier_first = identifier_first;
+        self
+    }
+
+    /// Allow the second of the two lines to be up to `lines` lines after the first, rather than
+    /// immediately after it.
+    ///
+    /// The lines in between must not be blank, so that both lines are in the same header block.
+    pub fn max_distance(mut self, lines: usize) -> Self {
+        self.max_distance = lines;
+        self
+    }
+
+    fn is_copyright_line(&self, line: &str) -> bool {
+        line.contains("Copyright") && line.contains(&self.copyright_owner)
+    }
+
+    fn is_identifier_line(&self, line: &str) -> bool {
+        line.find(&self.identifier_line).map_or(false, |i| {
+            let rest = &line[i + self.identifier_line.len()..];
+            // the expression mustn't continue, e.g. with `+` or `OR ...`
+            match rest.chars().next() {
+                None => true,
+                Some(c) if c.is_alphanumeric() || "-.+:".contains(c) => false,
+                Some(_) => !rest
+                    .trim_start()
+                    .starts_with(|c: char| c.is_alphanumeric() || c == '('),
+            }
+        })
+    }
+}
+
+impl HeaderChecker for CopyrightSpdxChecker {
+    fn check(&self, input: &mut impl io::Read) -> io::Result<bool> {
+        let is_first = |line: &str| {
+            if self.identifier_first {
+                self.is_identifier_line(line)
+            } else {
+                self.is_copyright_line(line)
+            }
+        };
+        let is_second = |line: &str| {
+            if self.identifier_first {
+                self.is_copyright_line(line)
+            } else {
+                self.is_identifier_line(line)
+            }
+        };
+        // lines since the most recent first line, if it's in the current header block
+        let mut since_first: Option<usize> = None;
+        lines::find_line(input, self.max_lines, |line| {
+            let line = &*lines::normalize_typography(line);
+            since_first = since_first.map(|n| n + 1);
+            if since_first.map_or(false, |n| n <= self.max_distance) && is_second(line) {
+                return true;
+            }
+            if is_first(line) {
+                since_first = Some(0);
+            } else if line.trim().is_empty() {
+                since_first = None;
+            }
+            false
+        })
+    }
+}
+
+/// A choice between two licenses, as an SPDX `OR` expression, with the conventional wording for
+/// a header offering that choice.
+pub struct DualLicense {
+    expression: &'static str,
+    /// Header text after the copyright line
+    wording: &'static str,
+    search_pattern: &'static str,
+}
+
+impl DualLicense {
+    /// The SPDX license expression, e.g. `Apache-2.0 OR MIT`.
+    pub fn expression(&self) -> &'static str {
+        self.expression
+    }
+
+    /// Build a header with a copyright line per copyright owner in `values`, followed by the
+    /// conventional wording.
+    pub fn build_header(&self, values: YearCopyrightOwnerValue) -> Header<SingleLineChecker> {
+        let copyright_prefix = format!("Copyright {} ", values.year);
+        Header::new(
+            SingleLineChecker::new(self.search_pattern.to_string(), 10),
+            format!(
+                "{}{}\n\n{}",
+                copyright_prefix,
+                values.owner_lines(&copyright_prefix),
+                self.wording
+            ),
+        )
+    }
+
+    /// Build a compact header with the license expression, as per [short_header].
+    pub fn build_short_header(
+        &self,
+        year: u32,
+        copyright_owner: &str,
+    ) -> Header<CopyrightSpdxChecker> {
+        short_header_for_expression(self.expression, year, copyright_owner)
+    }
+}
+
+/// `Apache-2.0 OR MIT`, the dual license conventional for Rust crates, with the wording used by
+/// the Rust project itself.
+pub const APACHE_2_0_OR_MIT: DualLicense = DualLicense {
+    expression: "Apache-2.0 OR MIT",
+    wording: "Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+<LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+option. This file may not be copied, modified, or distributed
+except according to those terms.",
+    search_pattern: "or the MIT license",
+};
+
+/// A license with an SPDX exception granting additional permissions, as in the SPDX expression
+/// `Apache-2.0 WITH LLVM-exception`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LicenseWithException {
+    license_id: &'static str,
+    exception_id: &'static str,
+}
+
+impl LicenseWithException {
+    /// Combine the license with SPDX id `license_id` with the exception with SPDX id
+    /// `exception_id`.
+    ///
+    /// Fails if either id is unknown, or if the exception is known not to apply to the license,
+    /// e.g. `Classpath-exception-2.0` is only for GPL-2.0. Exceptions whose applicable licenses
+    /// aren't known are allowed with any license.
+    pub fn new(license_id: &str, exception_id: &str) -> Result<Self, ExceptionError> {
+        let license: &dyn license::License = license_id
+            .parse()
+            .map_err(|_| ExceptionError::UnknownLicense(license_id.to_string()))?;
+        let exception: &dyn license::Exception = exception_id
+            .parse()
+            .map_err(|_| ExceptionError::UnknownException(exception_id.to_string()))?;
+        if let Some((_, licenses)) = EXCEPTION_LICENSES
+            .iter()
+            .find(|(exception, _)| *exception == exception_id)
+        {
+            if !licenses.contains(&license_id) {
+                return Err(ExceptionError::NotApplicable(
+                    exception_id.to_string(),
+                    license_id.to_string(),
+                ));
+            }
+        }
+        Ok(Self {
+            license_id: license.id(),
+            exception_id: exception.id(),
+        })
+    }
+
+    /// The SPDX id of the license.
+    pub fn license_id(&self) -> &'static str {
+        self.license_id
+    }
+
+    /// The SPDX id of the exception.
+    pub fn exception_id(&self) -> &'static str {
+        self.exception_id
+    }
+
+    /// The SPDX license expression, e.g. `Apache-2.0 WITH LLVM-exception`.
+    pub fn expression(&self) -> String {
+        format!("{} WITH {}", self.license_id, self.exception_id)
+    }
+
+    /// Build a compact header with the license expression, as per [short_header].
+    pub fn build_short_header(
+        &self,
+        year: u32,
+        copyright_owner: &str,
+    ) -> Header<CopyrightSpdxChecker> {
+        short_header_for_expression(&self.expression(), year, copyright_owner)
+    }
+}
+
+/// A license that isn't on the SPDX list, e.g. a proprietary license, identified by a
+/// `LicenseRef-` custom SPDX identifier like `LicenseRef-MyCompany-Proprietary`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CustomLicense {
+    id: String,
+    notice: String,
+    search_pattern: String,
+}
+
+impl CustomLicense {
+    /// `id`: the custom identifier, which must be `LicenseRef-` followed by letters, digits, `.`
+    /// and `-`
+    /// `notice`: the text to put in headers after the copyright line
+    ///
+    /// Headers are checked for by searching for the first line of `notice`.
+    pub fn new(id: &str, notice: &str) -> Result<Self, CustomLicenseError> {
+        if !is_valid_license_ref(id) {
+            return Err(CustomLicenseError::InvalidId(id.to_string()));
         }
+        let search_pattern = notice
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .ok_or(CustomLicenseError::EmptyNotice)?
+            .to_string();
+        Ok(Self {
+            id: id.to_string(),
+            notice: notice.to_string(),
+            search_pattern,
+        })
+    }
+
+    /// The custom SPDX identifier, e.g. `LicenseRef-MyCompany-Proprietary`.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The notice text.
+    pub fn notice(&self) -> &str {
+        &self.notice
+    }
+
+    /// Build a header with a copyright line per copyright owner in `values`, followed by the
+    /// notice.
+    pub fn build_header(&self, values: YearCopyrightOwnerValue) -> Header<SingleLineChecker> {
+        let copyright_prefix = format!("Copyright {} ", values.year);
+        Header::new(
+            SingleLineChecker::new(self.search_pattern.clone(), 10),
+            format!(
+                "{}{}\n\n{}",
+                copyright_prefix,
+                values.owner_lines(&copyright_prefix),
+                self.notice
+            ),
+        )
+    }
+
+    /// Build a compact header with the custom identifier, as per [short_header].
+    pub fn build_short_header(
+        &self,
+        year: u32,
+        copyright_owner: &str,
+    ) -> Header<CopyrightSpdxChecker> {
+        short_header_for_expression(&self.id, year, copyright_owner)
     }
 }
 
+/// Returns `true` if `id` is `LicenseRef-` followed by the characters SPDX allows in ids.
+pub(crate) fn is_valid_license_ref(id: &str) -> bool {
+    id.strip_prefix("LicenseRef-").map_or(false, |rest| {
+        !rest.is_empty()
+            && rest
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+    })
+}
+
+/// Errors that can occur when defining a [CustomLicense]
+#[derive(Debug, thiserror::Error)]
+pub enum CustomLicenseError {
+    /// The id is not a valid `LicenseRef-` identifier
+    #[error("Invalid custom license id: {0}")]
+    InvalidId(String),
+    /// The notice has no text
+    #[error("Custom license notice is empty")]
+    EmptyNotice,
+}
+
+/// Common exceptions, and the licenses they can be applied to
+const EXCEPTION_LICENSES: &[(&str, &[&str])] = &[
+    (
+        "Autoconf-exception-3.0",
+        &["GPL-3.0-only", "GPL-3.0-or-later"],
+    ),
+    (
+        "Bison-exception-2.2",
+        &[
+            "GPL-2.0-only",
+            "GPL-2.0-or-later",
+            "GPL-3.0-only",
+            "GPL-3.0-or-later",
+        ],
+    ),
+    (
+        "Classpath-exception-2.0",
+        &["GPL-2.0-only", "GPL-2.0-or-later"],
+    ),
+    ("GCC-exception-2.0", &["GPL-2.0-only", "GPL-2.0-or-later"]),
+    ("GCC-exception-3.1", &["GPL-3.0-only", "GPL-3.0-or-later"]),
+    ("Linux-syscall-note", &["GPL-2.0-only", "GPL-2.0-or-later"]),
+    ("LLVM-exception", &["Apache-2.0"]),
+    ("OpenJDK-assembly-exception-1.0", &["GPL-2.0-only"]),
+];
+
+/// Errors that can occur when combining a license with an exception
+#[derive(Debug, thiserror::Error)]
+pub enum ExceptionError {
+    /// The license id is not a known SPDX license
+    #[error("Unknown license: {0}")]
+    UnknownLicense(String),
+    /// The exception id is not a known SPDX exception
+    #[error("Unknown exception: {0}")]
+    UnknownException(String),
+    /// The exception does not apply to the license
+    #[error("{0} does not apply to {1}")]
+    NotApplicable(String, String),
+}
+
 lazy_static! {
     /// Apache 2.0 license
     pub static ref APACHE_2_0: SpdxLicense<Apache2Tokens> = SpdxLicense ::new(
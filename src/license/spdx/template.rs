@@ -0,0 +1,227 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The replaceable fields of SPDX license templates, so the tokens to replace when building a
+//! header, e.g. `<year>` for MIT or `[yyyy]` for Apache 2.0, come from the templates rather than
+//! being maintained by hand.
+//!
+//! SPDX templates mark each replaceable part of a license's text with a tag like
+//! `<<var;name="copyright";original="<year> <owner>";match=".+">>`, where `original` is the
+//! placeholder text in the license as published. The bundled templates are the lines with
+//! replaceable fields of the header templates, or the license templates for licenses without a
+//! standard header, with `original` as in the text of the `license` crate.
+//!
+//! # Examples
+//!
+//! ```
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use file_header::license::spdx::template;
+//!
+//! let mit = template::template("MIT").unwrap();
+//! assert_eq!("<year> <copyright holders>", mit.field("copyright").unwrap().original);
+//! assert_eq!(
+//!     vec![("<year> <copyright holders>", "2023 Foo Inc.".to_string())],
+//!     mit.replacement_pairs([("copyright", "2023 Foo Inc.".to_string())])
+//! );
+//!
+//! // templates can also be parsed from other sources of SPDX data
+//! let fields = template::parse_fields(
+//!     r#"Copyright <<var;name="copyright";original="(c) <year>";match=".+">>"#,
+//! )
+//! .unwrap();
+//! assert_eq!("(c) <year>", fields[0].original);
+//! ```
+
+use lazy_static::lazy_static;
+
+/// The templates of licenses that have replaceable fields, by SPDX id
+const TEMPLATES: &[(&str, &str)] = &[
+    (
+        "AGPL-3.0-only",
+        r#"Copyright (C) <<var;name="copyright";original="<year> <name of author>";match=".+">>"#,
+    ),
+    (
+        "Apache-2.0",
+        r#"Copyright <<var;name="copyright";original="[yyyy] [name of copyright owner]";match=".+">>"#,
+    ),
+    (
+        "BSD-2-Clause",
+        r#"Copyright (c) <<var;name="copyright";original="<year> <owner>";match=".{0,5000}">>"#,
+    ),
+    (
+        "BSD-3-Clause",
+        r#"Copyright (c) <<var;name="copyright";original="<year> <owner>";match=".{0,5000}">>"#,
+    ),
+    (
+        "GPL-2.0-only",
+        r#"Copyright (C) <<var;name="copyright";original="<year> <name of author>";match=".+">>"#,
+    ),
+    (
+        "GPL-3.0-only",
+        r#"Copyright (C) <<var;name="copyright";original="<year> <name of author>";match=".+">>"#,
+    ),
+    (
+        "ISC",
+        r#"Copyright (c) <<var;name="copyright";original="<year> <copyright holders>";match=".{0,5000}">>"#,
+    ),
+    (
+        "LGPL-2.1-only",
+        r#"Copyright (C) <<var;name="copyright";original="<year> <name of author>";match=".+">>"#,
+    ),
+    (
+        "MIT",
+        r#"Copyright (c) <<var;name="copyright";original="<year> <copyright holders>";match=".{0,5000}">>"#,
+    ),
+];
+
+lazy_static! {
+    static ref PARSED_TEMPLATES: Vec<LicenseTemplate> = TEMPLATES
+        .iter()
+        .map(|(id, text)| LicenseTemplate {
+            id,
+            fields: parse_fields(text).expect("bundled templates are valid"),
+        })
+        .collect();
+}
+
+/// The bundled template for the license with SPDX id `id`, if it has replaceable fields.
+pub fn template(id: &str) -> Option<&'static LicenseTemplate> {
+    PARSED_TEMPLATES.iter().find(|t| t.id == id)
+}
+
+/// The SPDX ids of the licenses with bundled templates.
+pub fn ids() -> impl Iterator<Item = &'static str> {
+    TEMPLATES.iter().map(|(id, _)| *id)
+}
+
+/// The replaceable fields of a license's template
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LicenseTemplate {
+    id: &'static str,
+    fields: Vec<Field<'static>>,
+}
+
+impl LicenseTemplate {
+    /// The SPDX id of the license.
+    pub fn id(&self) -> &'static str {
+        self.id
+    }
+
+    /// The replaceable fields, in the order they appear in the template.
+    pub fn fields(&self) -> &[Field<'static>] {
+        &self.fields
+    }
+
+    /// The field named `name`, if any.
+    pub fn field(&self, name: &str) -> Option<&Field<'static>> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+
+    /// The `(token to search for, replacement value)` pairs for [`super::LicenseTokens`], with
+    /// each field named in `values` replaced by its value. Names of fields the template doesn't
+    /// have are ignored.
+    pub fn replacement_pairs<'n>(
+        &self,
+        values: impl IntoIterator<Item = (&'n str, String)>,
+    ) -> Vec<(&'static str, String)> {
+        values
+            .into_iter()
+            .filter_map(|(name, value)| self.field(name).map(|f| (f.original, value)))
+            .collect()
+    }
+}
+
+/// A replaceable field of a license template
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Field<'a> {
+    /// The field's name, e.g. `copyright`
+    pub name: &'a str,
+    /// The placeholder text in the license, e.g. `<year> <owner>`
+    pub original: &'a str,
+    /// The regular expression that text replacing the placeholder must match
+    pub match_pattern: &'a str,
+}
+
+/// Parse the replaceable fields of `template`, a license template in SPDX template syntax.
+///
+/// Other tags, like `<<beginOptional>>`, are skipped.
+pub fn parse_fields(template: &str) -> Result<Vec<Field<'_>>, TemplateError> {
+    let mut fields = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("<<") {
+        let offset = template.len() - rest.len() + start;
+        let tag = &rest[start + 2..];
+        let (attributes, end) = split_attributes(tag).ok_or(TemplateError::Unterminated(offset))?;
+        rest = &tag[end..];
+        if attributes.first() != Some(&("var", "")) {
+            continue;
+        }
+        let attribute = |key| {
+            attributes
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, value)| *value)
+        };
+        fields.push(Field {
+            name: attribute("name").ok_or(TemplateError::MissingName(offset))?,
+            original: attribute("original").unwrap_or(""),
+            match_pattern: attribute("match").unwrap_or(""),
+        });
+    }
+    Ok(fields)
+}
+
+/// The `;`-separated `key="value"` attributes of `tag`, the text after a tag's `<<`, and the
+/// index after its `>>`, or `None` if the tag isn't terminated.
+///
+/// Values are quoted, so they may contain `;` and `>>`. Attributes without a value, like `var`,
+/// have an empty one.
+fn split_attributes(tag: &str) -> Option<(Vec<(&str, &str)>, usize)> {
+    let mut attributes = Vec::new();
+    let mut i = 0;
+    loop {
+        let rest = &tag[i..];
+        let key_end = rest.find(['=', ';', '>'])?;
+        let key = rest[..key_end].trim();
+        let mut value = "";
+        let mut next = i + key_end;
+        if rest[key_end..].starts_with("=\"") {
+            let value_start = next + 2;
+            let value_len = tag[value_start..].find('"')?;
+            value = &tag[value_start..value_start + value_len];
+            next = value_start + value_len + 1;
+        }
+        attributes.push((key, value));
+        let rest = &tag[next..];
+        if let Some(after) = rest.strip_prefix(';') {
+            i = tag.len() - after.len();
+        } else if rest.starts_with(">>") {
+            return Some((attributes, next + 2));
+        } else {
+            return None;
+        }
+    }
+}
+
+/// Errors that can occur when parsing a license template
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum TemplateError {
+    /// A tag isn't terminated by `>>`
+    #[error("Unterminated tag at byte {0}")]
+    Unterminated(usize),
+    /// A `var` tag has no `name`
+    #[error("Field without a name at byte {0}")]
+    MissingName(usize),
+}
@@ -22,7 +22,7 @@ fn apache_2() {
     ));
     assert_eq!(
         "Copyright 2023 Some copyright holder\n\nLicensed under the Apache License, Version 2.0 (the \"License\");\n\nyou may not use this file except in compliance with the License.\n\nYou may obtain a copy of the License at\n\nhttp://www.apache.org/licenses/LICENSE-2.0\n\nUnless required by applicable law or agreed to in writing, software\n\ndistributed under the License is distributed on an \"AS IS\" BASIS,\n\nWITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.\n\nSee the License for the specific language governing permissions and\n\nlimitations under the License.\n\n",
-        license_header.header
+        license_header.text(None)
     );
 }
 
@@ -34,10 +34,33 @@ fn mit() {
     ));
     assert_eq!(
         "MIT License\n\nCopyright (c) 2023 Some copyright holder\n\nPermission is hereby granted, free of charge, to any person obtaining a copy of this software and associated documentation files (the \"Software\"), to deal in the Software without restriction, including without limitation the rights to use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is furnished to do so, subject to the following conditions:\n\nThe above copyright notice and this permission notice shall be included in all copies or substantial portions of the Software.\n\nTHE SOFTWARE IS PROVIDED \"AS IS\", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.\n",
-        license_header.header
+        license_header.text(None)
     );
 }
 
+#[test]
+fn apache_2_multiple_owners() {
+    let license_header = APACHE_2_0.build_header(
+        YearCopyrightOwnerValue::new(2023, "Foo Inc.".to_string())
+            .additional_owner("Bar LLC".to_string()),
+    );
+    assert!(license_header
+        .text(None)
+        .starts_with("Copyright 2023 Foo Inc.\nCopyright 2023 Bar LLC\n\nLicensed under"));
+}
+
+#[test]
+fn mit_multiple_owners() {
+    let license_header = MIT.build_header(
+        YearCopyrightOwnerValue::new(2023, "Foo Inc.".to_string())
+            .additional_owner("Bar LLC".to_string())
+            .additional_owner("Baz Ltd.".to_string()),
+    );
+    assert!(license_header
+        .text(None)
+        .starts_with("MIT License\n\nCopyright (c) 2023 Foo Inc., Bar LLC, Baz Ltd.\n\n"));
+}
+
 #[test]
 fn gpl3() {
     let license_header = GPL_3_0_ONLY.build_header(YearCopyrightOwnerValue::new(
@@ -46,7 +69,7 @@ fn gpl3() {
     ));
     assert_eq!(
         "Copyright (C) 2023 Some copyright holder\n\nThis program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, version 3.\n\nThis program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.\n\nYou should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.\n\n",
-        license_header.header
+        license_header.text(None)
     );
 }
 
@@ -58,7 +81,7 @@ fn bsd3() {
     ));
     assert_eq!(
         "Copyright (c) 2023 Some copyright holder. \n\nRedistribution and use in source and binary forms, with or without modification, are permitted provided that the following conditions are met:\n\n1. Redistributions of source code must retain the above copyright notice, this list of conditions and the following disclaimer.\n\n2. Redistributions in binary form must reproduce the above copyright notice, this list of conditions and the following disclaimer in the documentation and/or other materials provided with the distribution.\n\n3. Neither the name of the copyright holder nor the names of its contributors may be used to endorse or promote products derived from this software without specific prior written permission.\n\nTHIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS \"AS IS\" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.\n",
-        license_header.header
+        license_header.text(None)
     );
 }
 
@@ -66,7 +89,7 @@ fn bsd3() {
 fn epl2() {
     let license_header = EPL_2_0.build_header(());
     // epl is very long
-    assert!(license_header.header.starts_with("Eclipse Public License - v 2.0\nTHE ACCOMPANYING PROGRAM IS PROVIDED UNDER THE TERMS OF THIS ECLIPSE PUBLIC LICENSE"));
+    assert!(license_header.text(None).starts_with("Eclipse Public License - v 2.0\nTHE ACCOMPANYING PROGRAM IS PROVIDED UNDER THE TERMS OF THIS ECLIPSE PUBLIC LICENSE"));
 }
 
 #[test]
@@ -74,6 +97,354 @@ fn mpl() {
     let license_header = MPL_2_0.build_header(());
     assert_eq!(
         "This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0. If a copy of the MPL was not distributed with this file, You can obtain one at https://mozilla.org/MPL/2.0/.\n\n",
-        license_header.header
+        license_header.text(None)
+    );
+}
+
+#[test]
+fn short_header_requires_adjacent_lines() {
+    let header = short_header(license::licenses::Apache2_0, 2023, "Foo Inc.");
+    assert_eq!(
+        "Copyright 2023 Foo Inc.\nSPDX-License-Identifier: Apache-2.0",
+        header.text(None)
+    );
+    let delimiters = crate::HeaderDelimiters::for_path(std::path::Path::new("main.rs")).unwrap();
+    let added = header
+        .add_header_to_str(&delimiters, "fn main() {}\n")
+        .unwrap()
+        .unwrap();
+    assert!(header.check_str(&added).unwrap());
+    // other years still match
+    assert!(header
+        .check_str("// Copyright 2019, 2021 Foo Inc.\n// SPDX-License-Identifier: Apache-2.0\n")
+        .unwrap());
+    // not adjacent
+    assert!(!header
+        .check_str("// Copyright 2023 Foo Inc.\n//\n// SPDX-License-Identifier: Apache-2.0\n")
+        .unwrap());
+    // a different expression
+    assert!(!header
+        .check_str("// Copyright 2023 Foo Inc.\n// SPDX-License-Identifier: Apache-2.0 OR MIT\n")
+        .unwrap());
+    assert!(!header
+        .check_str("// Copyright 2023 Bar LLC\n// SPDX-License-Identifier: Apache-2.0\n")
+        .unwrap());
+}
+
+#[test]
+fn copyright_spdx_checker_order_and_distance() {
+    let checker = CopyrightSpdxChecker::new("Foo Inc.".to_string(), "MIT", 10);
+    let identifier_first = "// SPDX-License-Identifier: MIT\n// Copyright 2023 Foo Inc.\n";
+    assert!(!checker.check(&mut identifier_first.as_bytes()).unwrap());
+    assert!(checker
+        .clone()
+        .identifier_first(true)
+        .check(&mut identifier_first.as_bytes())
+        .unwrap());
+
+    let other_owner_between =
+        "// Copyright 2023 Foo Inc.\n// Copyright 2023 Bar LLC\n// SPDX-License-Identifier: MIT\n";
+    assert!(!checker.check(&mut other_owner_between.as_bytes()).unwrap());
+    let checker = checker.max_distance(2);
+    assert!(checker.check(&mut other_owner_between.as_bytes()).unwrap());
+    // a blank line ends the header block
+    assert!(!checker
+        .check(&mut "// Copyright 2023 Foo Inc.\n\n// SPDX-License-Identifier: MIT\n".as_bytes())
+        .unwrap());
+    // typographic characters pasted from web pages
+    assert!(checker
+        .check(
+            &mut "// Copyright 2021\u{2013}2023 Foo\u{a0}Inc.\n// SPDX-License-Identifier: MIT\n"
+                .as_bytes()
+        )
+        .unwrap());
+}
+
+#[test]
+fn apache_2_or_mit() {
+    let header = APACHE_2_0_OR_MIT.build_header(
+        YearCopyrightOwnerValue::new(2023, "Foo Inc.".to_string())
+            .additional_owner("Bar LLC".to_string()),
+    );
+    assert!(header.text(None).starts_with(
+        "Copyright 2023 Foo Inc.\nCopyright 2023 Bar LLC\n\nLicensed under the Apache License, Version 2.0 <LICENSE-APACHE or\n"
+    ));
+    assert!(header.check_str(&header.text(None)).unwrap());
+    // the Apache-only header isn't mistaken for the dual license
+    let apache =
+        APACHE_2_0.build_header(YearCopyrightOwnerValue::new(2023, "Foo Inc.".to_string()));
+    assert!(!header.check_str(&apache.text(None)).unwrap());
+
+    let short = APACHE_2_0_OR_MIT.build_short_header(2023, "Foo Inc.");
+    assert_eq!(
+        "Copyright 2023 Foo Inc.\nSPDX-License-Identifier: Apache-2.0 OR MIT",
+        short.text(None)
+    );
+}
+
+#[test]
+fn license_with_exception() {
+    let with_exception = LicenseWithException::new("Apache-2.0", "LLVM-exception").unwrap();
+    assert_eq!(
+        "Copyright 2023 Foo Inc.\nSPDX-License-Identifier: Apache-2.0 WITH LLVM-exception",
+        with_exception
+            .build_short_header(2023, "Foo Inc.")
+            .text(None)
+    );
+    assert!(matches!(
+        LicenseWithException::new("Apache-2.0", "Classpath-exception-2.0"),
+        Err(ExceptionError::NotApplicable(_, _))
+    ));
+    assert!(matches!(
+        LicenseWithException::new("Apache-2.0", "No-such-exception"),
+        Err(ExceptionError::UnknownException(_))
+    ));
+    assert!(matches!(
+        LicenseWithException::new("No-such-license", "LLVM-exception"),
+        Err(ExceptionError::UnknownLicense(_))
+    ));
+}
+
+#[test]
+fn validate_identifier_expressions() {
+    use identifier::*;
+
+    let validator = IdentifierValidator::new();
+    for expression in [
+        "MIT",
+        "Apache-2.0 OR MIT",
+        "(MIT AND BSD-3-Clause) OR Apache-2.0",
+        "GPL-2.0-only WITH Classpath-exception-2.0",
+        "Apache-2.0+",
+        "LicenseRef-Foo-Proprietary",
+        "DocumentRef-spdx:LicenseRef-Foo",
+    ] {
+        assert_eq!(
+            Ok(()),
+            validator.validate_expression(expression),
+            "{}",
+            expression
+        );
+    }
+    assert_eq!(
+        Err(IdentifierError::UnknownLicense("Apache2".to_string())),
+        validator.validate_expression("MIT OR Apache2")
+    );
+    assert_eq!(
+        Err(IdentifierError::UnknownException(
+            "Foo-exception".to_string()
+        )),
+        validator.validate_expression("MIT WITH Foo-exception")
+    );
+    for expression in [
+        "",
+        "MIT OR",
+        "(MIT",
+        "MIT Apache-2.0",
+        "MIT WITH",
+        "LicenseRef-",
+    ] {
+        assert!(
+            matches!(
+                validator.validate_expression(expression),
+                Err(IdentifierError::Malformed(_)) | Err(IdentifierError::UnknownLicense(_))
+            ),
+            "{}",
+            expression
+        );
+    }
+
+    let restricted = IdentifierValidator::new()
+        .allow(["Apache-2.0", "MIT", "GPL-3.0-only"])
+        .deny(["GPL-3.0-only"]);
+    assert_eq!(Ok(()), restricted.validate_expression("Apache-2.0 OR MIT"));
+    assert_eq!(
+        Err(IdentifierError::Disallowed("BSD-3-Clause".to_string())),
+        restricted.validate_expression("MIT OR BSD-3-Clause")
+    );
+    assert_eq!(
+        Err(IdentifierError::Disallowed("GPL-3.0-only".to_string())),
+        restricted.validate_expression("GPL-3.0-only")
+    );
+}
+
+#[test]
+fn validate_identifier_lines() {
+    use identifier::*;
+
+    let validator = IdentifierValidator::new().max_lines(2);
+    assert_eq!(
+        Ok("Apache-2.0 OR MIT".to_string()),
+        validator
+            .validate_reader(&mut "/* SPDX-License-Identifier: Apache-2.0 OR MIT */\n".as_bytes())
+            .unwrap()
+    );
+    assert_eq!(
+        Err(IdentifierError::Missing),
+        validator
+            .validate_reader(&mut "\n\n// SPDX-License-Identifier: MIT\n".as_bytes())
+            .unwrap()
+    );
+    assert!(validator
+        .check(&mut "#!/bin/sh\n# SPDX-License-Identifier: MIT\n".as_bytes())
+        .unwrap());
+    assert!(!validator
+        .check(&mut "# SPDX-License-Identifier: Foo\n".as_bytes())
+        .unwrap());
+}
+
+#[test]
+fn custom_license() {
+    use identifier::*;
+
+    let proprietary = CustomLicense::new(
+        "LicenseRef-Foo-Proprietary",
+        "\nProprietary and confidential.\nDo not distribute.",
+    )
+    .unwrap();
+    let header = proprietary.build_header(
+        YearCopyrightOwnerValue::new(2023, "Foo Inc.".to_string())
+            .additional_owner("Bar LLC".to_string()),
+    );
+    assert_eq!(
+        "Copyright 2023 Foo Inc.\nCopyright 2023 Bar LLC\n\n\nProprietary and confidential.\nDo not distribute.",
+        header.text(None)
+    );
+    assert!(header.check_str(&header.text(None)).unwrap());
+    assert_eq!(
+        "Copyright 2023 Foo Inc.\nSPDX-License-Identifier: LicenseRef-Foo-Proprietary",
+        proprietary.build_short_header(2023, "Foo Inc.").text(None)
+    );
+
+    assert!(matches!(
+        CustomLicense::new("Foo-Proprietary", "Proprietary"),
+        Err(CustomLicenseError::InvalidId(_))
+    ));
+    assert!(matches!(
+        CustomLicense::new("LicenseRef-Foo Proprietary", "Proprietary"),
+        Err(CustomLicenseError::InvalidId(_))
+    ));
+    assert!(matches!(
+        CustomLicense::new("LicenseRef-Foo", " \n"),
+        Err(CustomLicenseError::EmptyNotice)
+    ));
+
+    let validator = IdentifierValidator::new().custom_licenses([&proprietary]);
+    assert_eq!(
+        Ok(()),
+        validator.validate_expression("LicenseRef-Foo-Proprietary OR MIT")
+    );
+    assert_eq!(
+        Err(IdentifierError::UnknownLicense(
+            "LicenseRef-Bar".to_string()
+        )),
+        validator.validate_expression("LicenseRef-Bar")
+    );
+}
+
+#[test]
+fn localized_license() {
+    let localized = LocalizedLicense::new(&APACHE_2_0)
+        .translation(
+            "ja",
+            "Copyright [yyyy] [name of copyright owner]\n\nApache License, Version 2.0 に基づいてライセンスされています。",
+            "Apache License, Version 2.0 に基づいて",
+        )
+        .translation("de_DE", "Lizenziert unter der Apache-Lizenz", "Apache-Lizenz")
+        .translation("de-de", "Lizenziert unter der Apache License, Version 2.0", "Lizenziert");
+    assert_eq!(vec!["ja", "de-de"], localized.locales().collect::<Vec<_>>());
+
+    let header = localized
+        .build_header(
+            "JA_jp",
+            YearCopyrightOwnerValue::new(2023, "Foo Inc.".to_string())
+                .additional_owner("Bar LLC".to_string()),
+        )
+        .unwrap();
+    assert_eq!(
+        "Copyright 2023 Foo Inc.\nCopyright 2023 Bar LLC\n\nApache License, Version 2.0 に基づいてライセンスされています。",
+        header.text(None)
+    );
+    assert!(header.check_str(&header.text(None)).unwrap());
+
+    let bilingual = localized
+        .build_bilingual_header(
+            "de-DE",
+            YearCopyrightOwnerValue::new(2023, "Foo GmbH".to_string()),
+        )
+        .unwrap();
+    let text = bilingual.text(None);
+    assert!(text.starts_with("Copyright 2023 Foo GmbH\n\nLicensed under the Apache License"));
+    assert!(text.ends_with(
+        "limitations under the License.\n\nLizenziert unter der Apache License, Version 2.0"
+    ));
+    assert!(bilingual.check_str(&text).unwrap());
+    // the English header alone isn't bilingual
+    let english =
+        APACHE_2_0.build_header(YearCopyrightOwnerValue::new(2023, "Foo GmbH".to_string()));
+    assert!(!bilingual.check_str(&english.text(None)).unwrap());
+
+    assert!(matches!(
+        localized.build_header("fr", YearCopyrightOwnerValue::new(2023, "Foo".to_string())),
+        Err(LocalizationError::UnknownLocale(locale)) if locale == "fr"
+    ));
+}
+
+#[test]
+fn templates() {
+    use template::*;
+
+    let apache = template::template("Apache-2.0").unwrap();
+    assert_eq!("Apache-2.0", apache.id());
+    assert_eq!(
+        &[Field {
+            name: "copyright",
+            original: "[yyyy] [name of copyright owner]",
+            match_pattern: ".+",
+        }],
+        apache.fields()
     );
+    assert_eq!(
+        Vec::<(&str, String)>::new(),
+        apache.replacement_pairs([("year", "2023".to_string())])
+    );
+    assert!(template::template("Rpsl-1.0").is_none());
+    assert!(ids().all(|id| template::template(id).is_some()));
+
+    assert_eq!(
+        vec![
+            Field {
+                name: "title",
+                original: "The; >> License",
+                match_pattern: "",
+            },
+            Field {
+                name: "copyright",
+                original: "<year>",
+                match_pattern: ".{0,5000}",
+            },
+        ],
+        parse_fields(
+            r#"<<beginOptional>><<var;name="title";original="The; >> License">><<endOptional>>
+Copyright (c) <<var;name="copyright";original="<year>";match=".{0,5000}">>"#
+        )
+        .unwrap()
+    );
+    assert_eq!(
+        Err(TemplateError::Unterminated(10)),
+        parse_fields(r#"Copyright <<var;name="copyright""#)
+    );
+    assert_eq!(
+        Err(TemplateError::MissingName(0)),
+        parse_fields(r#"<<var;original="<year>">>"#)
+    );
+}
+
+#[test]
+fn license_checker_without_text() {
+    let checker = APACHE_2_0.build_checker();
+    let header = APACHE_2_0.build_header(YearCopyrightOwnerValue::new(2023, "Foo".to_string()));
+    let text = header.text(None);
+    assert!(checker.check(&mut text.as_bytes()).unwrap());
+    assert!(!checker.check(&mut "MIT License".as_bytes()).unwrap());
 }
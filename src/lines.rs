@@ -0,0 +1,111 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Scanning the first lines of files without allocating for each line.
+
+use std::{borrow, io, str};
+
+/// Size of the stack buffer lines are read into. Lines longer than this are assembled on the heap.
+const LINE_BUF_LEN: usize = 8 * 1024;
+
+/// Call `found` with each of the first `max_lines` lines of `input`, including their line endings,
+/// until it returns `true`.
+///
+/// Returns `true` if `found` did. As with [`io::BufRead::read_line`], a line that isn't valid UTF-8
+/// is an [`io::ErrorKind::InvalidData`] error.
+pub(crate) fn find_line(
+    input: &mut impl io::Read,
+    max_lines: usize,
+    mut found: impl FnMut(&str) -> bool,
+) -> io::Result<bool> {
+    let mut buf = [0_u8; LINE_BUF_LEN];
+    // unprocessed bytes are `buf[start..end]`
+    let mut start = 0;
+    let mut end = 0;
+    // the start of a line that didn't fit in `buf`
+    let mut long_line = Vec::new();
+    let mut eof = false;
+    let mut lines_read = 0;
+    while lines_read < max_lines {
+        let line_end = match memchr::memchr(b'\n', &buf[start..end]) {
+            Some(i) => start + i + 1,
+            None if eof => {
+                if start == end && long_line.is_empty() {
+                    return Ok(false);
+                }
+                end
+            }
+            None => {
+                if start == 0 && end == buf.len() {
+                    long_line.extend_from_slice(&buf);
+                    end = 0;
+                } else {
+                    buf.copy_within(start..end, 0);
+                    end -= start;
+                }
+                start = 0;
+                match input.read(&mut buf[end..]) {
+                    Ok(0) => eof = true,
+                    Ok(n) => end += n,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+                continue;
+            }
+        };
+        let is_found = if long_line.is_empty() {
+            found(utf8(&buf[start..line_end])?)
+        } else {
+            long_line.extend_from_slice(&buf[start..line_end]);
+            let is_found = found(utf8(&long_line)?);
+            long_line.clear();
+            is_found
+        };
+        if is_found {
+            return Ok(true);
+        }
+        start = line_end;
+        lines_read += 1;
+    }
+    Ok(false)
+}
+
+fn utf8(line: &[u8]) -> io::Result<&str> {
+    str::from_utf8(line).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "stream did not contain valid UTF-8",
+        )
+    })
+}
+
+/// `text` with the typographic characters that text pasted from web pages often has, i.e. smart
+/// quotes, dashes, and non-breaking spaces, replaced with their ASCII equivalents.
+pub(crate) fn normalize_typography(text: &str) -> borrow::Cow<'_, str> {
+    if text.is_ascii() || !text.chars().any(|c| ascii_equivalent(c) != c) {
+        return borrow::Cow::Borrowed(text);
+    }
+    borrow::Cow::Owned(text.chars().map(ascii_equivalent).collect())
+}
+
+/// The ASCII equivalent of `c` if it's a typographic quote, dash, or space, otherwise `c`
+fn ascii_equivalent(c: char) -> char {
+    match c {
+        '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' | '\u{2032}' => '\'',
+        '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' | '\u{2033}' => '"',
+        '\u{2010}'..='\u{2015}' | '\u{2212}' => '-',
+        '\u{00A0}' | '\u{2007}' | '\u{202F}' => ' ',
+        c => c,
+    }
+}
@@ -0,0 +1,668 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Linting the structure of existing headers, to find headers that a checker detects but that
+//! have drifted from the canonical text, e.g. with lines reordered, missing, or added, and
+//! rewriting them to the canonical text.
+//!
+//! The expected text is given as a template, in which [`YEAR_PLACEHOLDER`] matches any year list
+//! like `2019, 2021-2023`, so that headers from different years all conform. The header is the
+//! first comment block near the start of the file with a line from the template. A header commented
+//! in another common style than its file type's, e.g. `//` comments in a `.h` file, is still found,
+//! and is rewritten in that style to minimize the change.
+//!
+//! # Examples
+//!
+//! ```
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use file_header::{lint::*, *};
+//! use std::path::Path;
+//!
+//! let lint = HeaderLint::new("Copyright {year} Foo Inc.\nSPDX-License-Identifier: MIT");
+//! let delimiters = HeaderDelimiters::for_path(Path::new("main.rs")).unwrap();
+//! assert!(lint
+//!     .lint_str(
+//!         &delimiters,
+//!         "// Copyright 2021 Foo Inc.\n// SPDX-License-Identifier: MIT\n"
+//!     )
+//!     .is_empty());
+//! assert_eq!(
+//!     vec![LintIssue::OutOfOrder("Copyright 2021 Foo Inc.".to_string())],
+//!     lint.lint_str(
+//!         &delimiters,
+//!         "// SPDX-License-Identifier: MIT\n// Copyright 2021 Foo Inc.\n"
+//!     )
+//! );
+//! ```
+
+use crate::HeaderDelimiters;
+#[cfg(feature = "recursive")]
+use crate::{
+    filesystem, recursive_optional_operation, Header, HeaderChecker, ModificationResults,
+    RecursiveOptions,
+};
+#[cfg(feature = "recursive")]
+use std::{
+    cell, collections,
+    io::{self, Read as _},
+    path,
+};
+use std::{iter, ops};
+
+/// The placeholder in a [`HeaderLint`] template that matches a year list
+pub const YEAR_PLACEHOLDER: &str = "{year}";
+
+/// The expected structure of a header, as lines of text without comment delimiters.
+#[derive(Clone, Debug)]
+pub struct HeaderLint {
+    template: Vec<String>,
+    max_lines: usize,
+}
+
+impl HeaderLint {
+    /// Construct a lint for headers with the text of `template`, in which [`YEAR_PLACEHOLDER`]
+    /// matches any year list.
+    ///
+    /// Lines are compared ignoring leading and trailing whitespace. The header must start within
+    /// the first 10 lines of a file.
+    pub fn new(template: &str) -> Self {
+        Self {
+            template: template.split('\n').map(|l| l.to_string()).collect(),
+            max_lines: 10,
+        }
+    }
+
+    /// Look for the start of the header in the first `max_lines` lines of each file.
+    pub fn max_lines(mut self, max_lines: usize) -> Self {
+        self.max_lines = max_lines;
+        self
+    }
+
+    /// Lint the header in `contents`, which is commented with `delimiters`.
+    ///
+    /// Returns the ways the header deviates from the template, which is empty if the header is
+    /// well-formed. If there's no header, every line of the template is missing.
+    pub fn lint_str(&self, delimiters: &HeaderDelimiters, contents: &str) -> Vec<LintIssue> {
+        match self.find_header(delimiters, contents) {
+            Some((block, _)) => self.issues(&block.lines),
+            None => self
+                .template
+                .iter()
+                .map(|t| LintIssue::MissingLine(t.trim().to_string()))
+                .collect(),
+        }
+    }
+
+    /// Rewrite the malformed header in `contents`, which is commented with `delimiters`, to the
+    /// template wrapped in `delimiters`.
+    ///
+    /// If the header is commented in another style, e.g. `//` comments rather than `/* */`, it's
+    /// rewritten in the same style.
+    ///
+    /// The year list of the existing header is kept in place of [`YEAR_PLACEHOLDER`], and the
+    /// header keeps the existing header's line endings. The rest of `contents` is unchanged.
+    ///
+    /// Returns `None` if there's no header, the header is well-formed, or the template has a year
+    /// placeholder but the header has no year list to keep.
+    pub fn fix_str(&self, delimiters: &HeaderDelimiters, contents: &str) -> Option<String> {
+        let (block, delimiters) = self.find_header(delimiters, contents)?;
+        if self.issues(&block.lines).is_empty() {
+            return None;
+        }
+        let mut wrapped = crate::wrap_header(&self.canonical_text(&block)?, &delimiters);
+        if contents[block.range.clone()]
+            .split_inclusive('\n')
+            .next()
+            .map_or(false, |line| line.ends_with("\r\n"))
+        {
+            wrapped = wrapped.replace('\n', "\r\n");
+        }
+        let mut fixed = String::with_capacity(contents.len() + wrapped.len());
+        fixed.push_str(&contents[..block.range.start]);
+        fixed.push_str(&wrapped);
+        fixed.push_str(&contents[block.range.end..]);
+        Some(fixed)
+    }
+
+    /// Returns a line diff from the canonical rendering of the header in `contents`, which is
+    /// commented with `delimiters`, to the actual header, e.g. for reviewers to see how it drifted.
+    ///
+    /// The canonical rendering is the header [`HeaderLint::fix_str`] would write, or the template
+    /// with [`YEAR_PLACEHOLDER`] if the header has no year list. Lines only in the canonical header
+    /// start with `-`, lines only in the actual header start with `+`, and lines in both start
+    /// with a space.
+    ///
+    /// Returns `None` if there's no header or the header is well-formed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// // Copyright 2023 Google LLC.
+    /// // SPDX-License-Identifier: Apache-2.0
+    /// use file_header::{lint::*, *};
+    ///
+    /// let lint = HeaderLint::new("Copyright {year} Foo Inc.\nSPDX-License-Identifier: MIT");
+    /// assert_eq!(
+    ///     Some(
+    ///         "--- expected\n+++ actual\n \
+    ///         // Copyright 2021 Foo Inc.\n\
+    ///         -// SPDX-License-Identifier: MIT\n\
+    ///         +// SPDX-License-Identifier: BSD\n"
+    ///             .to_string()
+    ///     ),
+    ///     lint.diff_str(
+    ///         &HeaderDelimiters::new("", "// ", ""),
+    ///         "// Copyright 2021 Foo Inc.\n// SPDX-License-Identifier: BSD\n\nfn main() {}\n"
+    ///     )
+    /// );
+    /// ```
+    pub fn diff_str(&self, delimiters: &HeaderDelimiters, contents: &str) -> Option<String> {
+        let (block, delimiters) = self.find_header(delimiters, contents)?;
+        if self.issues(&block.lines).is_empty() {
+            return None;
+        }
+        let text = self
+            .canonical_text(&block)
+            .unwrap_or_else(|| self.template.join("\n"));
+        let expected = crate::wrap_header(&text, &delimiters);
+        Some(line_diff(&expected, &contents[block.range]))
+    }
+
+    /// Returns the kind of comment the header in `contents` is written in, which may differ from
+    /// that of `delimiters` as per [`HeaderLint::fix_str`].
+    pub fn comment_style(&self, delimiters: &HeaderDelimiters, contents: &str) -> CommentStyle {
+        match self.find_header(delimiters, contents) {
+            Some((_, delimiters)) if delimiters.first_line.is_empty() => CommentStyle::Line,
+            Some(_) => CommentStyle::Block,
+            None => CommentStyle::NoHeader,
+        }
+    }
+
+    /// The template, with the year list of the header `block` in place of [`YEAR_PLACEHOLDER`], or
+    /// `None` if the template has a placeholder but the header has no year list
+    fn canonical_text(&self, block: &CommentBlock) -> Option<String> {
+        let text = self.template.join("\n");
+        if !text.contains(YEAR_PLACEHOLDER) {
+            return Some(text);
+        }
+        let years = block.lines.iter().find_map(|line| {
+            self.template
+                .iter()
+                .filter_map(|t| match_line(t, line))
+                .find(|years| !years.is_empty())
+        })?;
+        Some(text.replace(YEAR_PLACEHOLDER, years))
+    }
+
+    /// The ways `lines` deviate from the template
+    fn issues(&self, lines: &[String]) -> Vec<LintIssue> {
+        let mut issues = Vec::new();
+        let mut matched = vec![false; self.template.len()];
+        // the template index of the last line that was in order
+        let mut last = None;
+        for line in lines {
+            let index = (0..self.template.len())
+                .find(|&i| !matched[i] && match_line(&self.template[i], line).is_some());
+            match index {
+                Some(i) => {
+                    matched[i] = true;
+                    if last.map_or(false, |last| i < last) {
+                        issues.push(LintIssue::OutOfOrder(line.clone()));
+                    } else {
+                        last = Some(i);
+                    }
+                }
+                None => issues.push(LintIssue::UnexpectedLine(line.clone())),
+            }
+        }
+        issues.extend(
+            self.template
+                .iter()
+                .zip(matched)
+                .filter(|(_, matched)| !matched)
+                .map(|(t, _)| LintIssue::MissingLine(t.trim().to_string())),
+        );
+        issues
+    }
+
+    /// Returns the first comment block in `contents` that has a line from the template, along
+    /// with the delimiters it's commented with.
+    ///
+    /// Blocks commented with `delimiters` are preferred over those in other [`comment_styles`].
+    fn find_header(
+        &self,
+        delimiters: &HeaderDelimiters,
+        contents: &str,
+    ) -> Option<(CommentBlock, HeaderDelimiters)> {
+        iter::once(delimiters.clone())
+            .chain(comment_styles())
+            .find_map(|delimiters| {
+                comment_blocks(&delimiters, contents, self.max_lines)
+                    .into_iter()
+                    .find(|block| {
+                        block
+                            .lines
+                            .iter()
+                            .any(|line| self.template.iter().any(|t| match_line(t, line).is_some()))
+                    })
+                    .map(|block| (block, delimiters))
+            })
+    }
+}
+
+/// A way in which a header deviates from a [`HeaderLint`] template
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LintIssue {
+    /// The template line, which is missing from the header
+    MissingLine(String),
+    /// The header line, which isn't in the template
+    UnexpectedLine(String),
+    /// The header line, which is from the template, but comes after a line that should follow it
+    OutOfOrder(String),
+}
+
+/// The kind of comment a header is written in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CommentStyle {
+    /// A block comment, like `/* ... */`
+    Block,
+    /// Line comments, like `// ...`
+    Line,
+    /// There's no header
+    NoHeader,
+}
+
+/// Lint the headers of every file in `root` that matches `path_predicate` and has `header` as
+/// determined by its checker, as per [`HeaderLint::lint_str`].
+///
+/// Files are commented as per `header`, and files that aren't UTF-8 text or have an unrecognized
+/// extension are skipped.
+#[cfg(feature = "recursive")]
+pub fn lint_headers_recursively(
+    root: &path::Path,
+    path_predicate: impl Fn(&path::Path) -> bool,
+    header: Header<impl HeaderChecker>,
+    lint: &HeaderLint,
+    options: &RecursiveOptions,
+) -> Result<LintResults, LintHeadersRecursivelyError> {
+    let malformed_files = cell::RefCell::new(Vec::new());
+    let diffs = cell::RefCell::new(Vec::new());
+    let results = recursive_optional_operation::<LintHeadersRecursivelyError>(
+        &[root],
+        path_predicate,
+        options,
+        |p| {
+            let (delimiters, contents) =
+                match read_with_header(options.file_system.as_ref(), &header, p)
+                    .map_err(|e| LintHeadersRecursivelyError::IoError(p.to_path_buf(), e))?
+                {
+                    Some(found) => found,
+                    None => return Ok(false),
+                };
+            let issues = lint.lint_str(&delimiters, &contents);
+            if !issues.is_empty() {
+                let p = options.relative_path(&[root], p.to_path_buf());
+                if let Some(diff) = lint.diff_str(&delimiters, &contents) {
+                    diffs.borrow_mut().push((p.clone(), diff));
+                }
+                malformed_files.borrow_mut().push((p, issues));
+            }
+            Ok(false)
+        },
+    )?;
+    Ok(LintResults {
+        malformed_files: malformed_files.into_inner(),
+        diffs: diffs.into_inner(),
+        oversized_files: results.oversized_files,
+    })
+}
+
+/// Rewrite the malformed header of every file in `root` that matches `path_predicate` and has
+/// `header` as determined by its checker, as per [`HeaderLint::fix_str`], with the behavior
+/// adjusted by `options`.
+///
+/// Files that [`lint_headers_recursively`] would skip are left untouched.
+///
+/// Returns the files that were rewritten.
+#[cfg(feature = "recursive")]
+pub fn fix_malformed_headers_recursively(
+    root: &path::Path,
+    path_predicate: impl Fn(&path::Path) -> bool,
+    header: Header<impl HeaderChecker>,
+    lint: &HeaderLint,
+    options: &RecursiveOptions,
+) -> Result<ModificationResults, LintHeadersRecursivelyError> {
+    let (modification_fs, staging) = options.staged_modification_file_system();
+    let mut results = recursive_optional_operation::<LintHeadersRecursivelyError>(
+        &[root],
+        path_predicate,
+        options,
+        |p| {
+            let err_mapper = |e| LintHeadersRecursivelyError::IoError(p.to_path_buf(), e);
+            let (delimiters, contents) =
+                match read_with_header(modification_fs.as_ref(), &header, p).map_err(err_mapper)? {
+                    Some(found) => found,
+                    None => return Ok(false),
+                };
+            match lint.fix_str(&delimiters, &contents) {
+                Some(fixed) => {
+                    modification_fs
+                        .replace(p, fixed.as_bytes(), &mut io::empty())
+                        .map_err(err_mapper)?;
+                    Ok(true)
+                }
+                None => Ok(false),
+            }
+        },
+    )?;
+    if let Some(staging) = staging {
+        staging
+            .commit()
+            .map_err(|(p, e)| LintHeadersRecursivelyError::IoError(p, e))?;
+    }
+    options.relativize(&[root], results.path_lists_mut());
+    Ok(results)
+}
+
+/// Report the comment style of the header of every file in `root` that matches
+/// `path_predicate`, as per [`HeaderLint::comment_style`], e.g. to plan normalizing a codebase's
+/// headers with [`fix_malformed_headers_recursively`].
+///
+/// Unlike the other recursive operations, files without `header` as determined by its checker
+/// are included, as [`CommentStyle::NoHeader`] unless a header is found in a comment. Files are
+/// commented as per `header`, and files that aren't UTF-8 text or have an unrecognized extension
+/// are skipped.
+#[cfg(feature = "recursive")]
+pub fn report_comment_styles_recursively(
+    root: &path::Path,
+    path_predicate: impl Fn(&path::Path) -> bool,
+    header: Header<impl HeaderChecker>,
+    lint: &HeaderLint,
+    options: &RecursiveOptions,
+) -> Result<CommentStyleReport, LintHeadersRecursivelyError> {
+    let files = cell::RefCell::new(collections::BTreeMap::new());
+    let results = recursive_optional_operation::<LintHeadersRecursivelyError>(
+        &[root],
+        path_predicate,
+        options,
+        |p| {
+            let (delimiters, contents) =
+                match read_commented(options.file_system.as_ref(), &header, p)
+                    .map_err(|e| LintHeadersRecursivelyError::IoError(p.to_path_buf(), e))?
+                {
+                    Some(found) => found,
+                    None => return Ok(false),
+                };
+            files.borrow_mut().insert(
+                options.relative_path(&[root], p.to_path_buf()),
+                lint.comment_style(&delimiters, &contents),
+            );
+            Ok(false)
+        },
+    )?;
+    Ok(CommentStyleReport {
+        files: files.into_inner(),
+        oversized_files: results.oversized_files,
+    })
+}
+
+/// Results of [`report_comment_styles_recursively`]
+#[cfg(feature = "recursive")]
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct CommentStyleReport {
+    /// The comment style of each file's header
+    pub files: collections::BTreeMap<path::PathBuf, CommentStyle>,
+    /// Paths that were skipped because they exceeded [`RecursiveOptions::max_file_size`]
+    pub oversized_files: Vec<path::PathBuf>,
+}
+
+#[cfg(feature = "recursive")]
+impl CommentStyleReport {
+    /// The number of files with each comment style, by lowercase extension, or by lowercase file
+    /// name for files without an extension, e.g. `dockerfile`.
+    pub fn by_extension(
+        &self,
+    ) -> collections::BTreeMap<String, collections::BTreeMap<CommentStyle, usize>> {
+        let mut counts = collections::BTreeMap::<_, collections::BTreeMap<_, _>>::new();
+        for (p, style) in &self.files {
+            let key = p
+                .extension()
+                .or_else(|| p.file_name())
+                .map_or_else(String::new, |s| s.to_string_lossy().to_ascii_lowercase());
+            *counts.entry(key).or_default().entry(*style).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+/// Results of [`lint_headers_recursively`]
+#[cfg(feature = "recursive")]
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct LintResults {
+    /// Paths whose headers deviate from the template, and how
+    pub malformed_files: Vec<(path::PathBuf, Vec<LintIssue>)>,
+    /// Paths whose headers deviate from the template, with a diff from the canonical header to the
+    /// actual one, as per [`HeaderLint::diff_str`]
+    pub diffs: Vec<(path::PathBuf, String)>,
+    /// Paths that were skipped because they exceeded [`RecursiveOptions::max_file_size`]
+    pub oversized_files: Vec<path::PathBuf>,
+}
+
+#[cfg(feature = "recursive")]
+impl LintResults {
+    /// Returns `true` if any headers are malformed
+    pub fn has_failure(&self) -> bool {
+        !self.malformed_files.is_empty()
+    }
+}
+
+/// Errors that can occur when linting or fixing headers recursively
+#[cfg(feature = "recursive")]
+#[derive(Debug, thiserror::Error)]
+pub enum LintHeadersRecursivelyError {
+    /// An I/O error occurred while linting or fixing the path
+    #[error("I/O error at {0:?}: {1}")]
+    IoError(path::PathBuf, io::Error),
+    /// `walkdir` could not navigate the directory structure
+    #[error("Walkdir error: {0}")]
+    WalkdirError(#[from] walkdir::Error),
+}
+
+#[cfg(feature = "recursive")]
+impl From<filesystem::WalkError> for LintHeadersRecursivelyError {
+    fn from(value: filesystem::WalkError) -> Self {
+        match value {
+            filesystem::WalkError::Walkdir(e) => Self::WalkdirError(e),
+            filesystem::WalkError::Io(p, e) => Self::IoError(p, e),
+        }
+    }
+}
+
+/// Returns the delimiters and contents of the file at `p`, or `None` if it isn't UTF-8 text, its
+/// extension isn't recognized, or its checker doesn't find `header`.
+#[cfg(feature = "recursive")]
+fn read_with_header(
+    fs: &dyn filesystem::FileSystem,
+    header: &Header<impl HeaderChecker>,
+    p: &path::Path,
+) -> io::Result<Option<(HeaderDelimiters, String)>> {
+    match read_commented(fs, header, p)? {
+        Some((delimiters, contents)) if header.check_str(&contents)? => {
+            Ok(Some((delimiters, contents)))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Returns the delimiters and contents of the file at `p`, or `None` if it isn't UTF-8 text or
+/// neither its path nor its shebang line is recognized.
+#[cfg(feature = "recursive")]
+fn read_commented(
+    fs: &dyn filesystem::FileSystem,
+    header: &Header<impl HeaderChecker>,
+    p: &path::Path,
+) -> io::Result<Option<(HeaderDelimiters, String)>> {
+    let mut contents = String::new();
+    match fs.open(p)?.read_to_string(&mut contents) {
+        Ok(_) => {}
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let delimiters = (header.delimiters_for_path)(p).or_else(|| {
+        contents
+            .lines()
+            .next()
+            .and_then(HeaderDelimiters::for_shebang)
+    });
+    Ok(delimiters.map(|delimiters| (delimiters, contents)))
+}
+
+/// Returns the year list matched by the placeholder if `line` matches `template`, or an empty
+/// string if `template` has no placeholder.
+fn match_line<'l>(template: &str, line: &'l str) -> Option<&'l str> {
+    let template = template.trim();
+    let (before, after) = match template.split_once(YEAR_PLACEHOLDER) {
+        Some(parts) => parts,
+        None => return (template == line).then_some(""),
+    };
+    let years = line
+        .strip_prefix(before)?
+        .strip_suffix(after)
+        .filter(|years| years.starts_with(|c: char| c.is_ascii_digit()))?;
+    years
+        .chars()
+        .all(|c| c.is_ascii_digit() || ", -–".contains(c))
+        .then_some(years)
+}
+
+/// Common comment styles that a header may have been written in rather than its file type's
+fn comment_styles() -> [HeaderDelimiters; 5] {
+    [
+        HeaderDelimiters::new("", "// ", ""),
+        HeaderDelimiters::new("/*", " * ", " */"),
+        HeaderDelimiters::new("/**", " * ", " */"),
+        HeaderDelimiters::new("", "# ", ""),
+        HeaderDelimiters::new("", "-- ", ""),
+    ]
+}
+
+/// Returns a line diff from `expected` to `actual`, as per [`HeaderLint::diff_str`].
+fn line_diff(expected: &str, actual: &str) -> String {
+    let expected = expected.lines().collect::<Vec<_>>();
+    let actual = actual.lines().collect::<Vec<_>>();
+    // lengths of the longest common subsequences of the suffixes of each
+    let mut lcs = vec![vec![0_usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lcs[i][j] = if expected[i] == actual[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut diff = String::from("--- expected\n+++ actual\n");
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        let (prefix, line) = if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            i += 1;
+            j += 1;
+            (' ', expected[i - 1])
+        } else if j == actual.len() || (i < expected.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            i += 1;
+            ('-', expected[i - 1])
+        } else {
+            j += 1;
+            ('+', actual[j - 1])
+        };
+        diff.push(prefix);
+        diff.push_str(line);
+        diff.push('\n');
+    }
+    diff
+}
+
+/// A comment block in a file
+pub(crate) struct CommentBlock {
+    /// The byte range of the block's lines, including the final line ending
+    range: ops::Range<usize>,
+    /// The block's lines without comment delimiters or surrounding whitespace
+    pub(crate) lines: Vec<String>,
+}
+
+/// Returns the comment blocks, commented with `delimiters`, that start in the first `max_lines`
+/// lines of `contents`.
+pub(crate) fn comment_blocks(
+    delimiters: &HeaderDelimiters,
+    contents: &str,
+    max_lines: usize,
+) -> Vec<CommentBlock> {
+    let first_line = delimiters.first_line.trim();
+    let prefix = delimiters.content_line_prefix.trim();
+    let last_line = delimiters.last_line.trim();
+    let mut blocks = Vec::new();
+    let mut current: Option<CommentBlock> = None;
+    let mut offset = 0;
+    for (index, line) in contents.split_inclusive('\n').enumerate() {
+        let start = offset;
+        offset += line.len();
+        let trimmed = line.trim();
+        if let Some(block) = current.as_mut() {
+            // block comments end with their last line, and line comments with the first
+            // uncommented line
+            if !first_line.is_empty() && trimmed == last_line {
+                block.range.end = offset;
+                blocks.extend(current.take());
+                continue;
+            }
+            if !first_line.is_empty() || (!prefix.is_empty() && trimmed.starts_with(prefix)) {
+                block.range.end = offset;
+                block.lines.push(
+                    trimmed
+                        .strip_prefix(prefix)
+                        .unwrap_or(trimmed)
+                        .trim()
+                        .to_string(),
+                );
+                continue;
+            }
+            blocks.extend(current.take());
+        }
+        if index >= max_lines {
+            break;
+        }
+        let starts_block = if first_line.is_empty() {
+            !prefix.is_empty() && trimmed.starts_with(prefix)
+        } else {
+            trimmed == first_line
+        };
+        if starts_block {
+            let mut block = CommentBlock {
+                range: start..offset,
+                lines: Vec::new(),
+            };
+            if first_line.is_empty() {
+                block.lines.push(trimmed[prefix.len()..].trim().to_string());
+            }
+            current = Some(block);
+        }
+    }
+    // a line comment block may run to the end of the file, but an unterminated block comment
+    // isn't a comment
+    if first_line.is_empty() {
+        blocks.extend(current);
+    }
+    blocks
+}
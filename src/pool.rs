@@ -0,0 +1,83 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A fixed size pool of threads to run jobs on.
+
+use std::{panic, thread};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A pool of threads that run submitted jobs until the pool is dropped.
+pub(crate) struct ThreadPool {
+    jobs: Option<crossbeam::channel::Sender<Job>>,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+impl ThreadPool {
+    /// Start a pool of `num_threads` threads, or a single thread if `num_threads` is 0.
+    pub(crate) fn new(num_threads: usize) -> Self {
+        let (jobs, job_rx) = crossbeam::channel::unbounded::<Job>();
+        let workers = (0..num_threads.max(1))
+            .map(|_| {
+                let job_rx = job_rx.clone();
+                thread::spawn(move || {
+                    for job in job_rx {
+                        job()
+                    }
+                })
+            })
+            .collect();
+        Self {
+            jobs: Some(jobs),
+            workers,
+        }
+    }
+
+    /// The number of threads in the pool.
+    pub(crate) fn size(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Run `job` on one of the threads once one is free.
+    ///
+    /// The returned receiver yields the outcome of the job when it completes, with the panic
+    /// payload if it panicked, as with [`thread::JoinHandle::join`]. A panicking job doesn't take
+    /// its thread down with it.
+    pub(crate) fn execute(
+        &self,
+        job: impl FnOnce() + Send + 'static,
+    ) -> crossbeam::channel::Receiver<thread::Result<()>> {
+        let (done_tx, done_rx) = crossbeam::channel::bounded(1);
+        let job = move || {
+            // the receiver may have been dropped if the outcome isn't of interest
+            let _ = done_tx.send(panic::catch_unwind(panic::AssertUnwindSafe(job)));
+        };
+        self.jobs
+            .as_ref()
+            .expect("jobs are only taken when dropped")
+            .send(Box::new(job))
+            .expect("workers outlive the pool");
+        done_rx
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        // closing the job channel ends each worker once queued jobs are done
+        drop(self.jobs.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
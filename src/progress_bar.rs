@@ -0,0 +1,66 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Showing the progress of recursive operations with an [`indicatif`] progress bar.
+//!
+//! This module requires the `indicatif` feature.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use file_header::{progress_bar::default_progress_bar, *};
+//! use std::path::Path;
+//!
+//! let header = Header::new(
+//!     SingleLineChecker::new("Foo License".to_string(), 10),
+//!     "Foo License".to_string(),
+//! );
+//! let bar = default_progress_bar();
+//! let results = check_headers_recursively_with_options(
+//!     Path::new("/some/dir"),
+//!     |_p| true,
+//!     header,
+//!     4,
+//!     &RecursiveOptions::new().progress_bar(bar.clone()),
+//! );
+//! bar.finish();
+//! ```
+
+use crate::{ProgressCallback, ProgressEvent};
+use std::sync;
+
+/// Returns a progress bar with a style suited to the recursive operations.
+pub fn default_progress_bar() -> indicatif::ProgressBar {
+    indicatif::ProgressBar::new(0).with_style(
+        indicatif::ProgressStyle::with_template(
+            "{spinner} [{elapsed_precise}] {wide_bar} {pos}/{len} files",
+        )
+        .expect("template is valid"),
+    )
+}
+
+/// Returns a [`ProgressCallback`] that shows progress on `bar`.
+///
+/// The bar's length is increased as files are found, and its position as files are processed.
+/// Spinners without a length are given one.
+pub fn progress_callback(bar: indicatif::ProgressBar) -> ProgressCallback {
+    sync::Arc::new(move |event| match event {
+        // files are only found on a single thread, so this doesn't race
+        ProgressEvent::Found(_) => bar.set_length(bar.length().unwrap_or(0) + 1),
+        ProgressEvent::Processed(_) => bar.inc(1),
+        ProgressEvent::SearchFinished => {}
+    })
+}
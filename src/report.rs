@@ -0,0 +1,195 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rendering [`FileResults`] as a human-readable terminal report.
+//!
+//! # Examples
+//!
+//! ```
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use file_header::{report::Report, *};
+//!
+//! let results = FileResults {
+//!     no_header_files: vec!["src/main.rs".into()],
+//!     ..FileResults::default()
+//! };
+//! print!("{}", Report::new(&results).colored(true));
+//! ```
+
+use crate::FileResults;
+use std::fmt;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// A report of [`FileResults`], rendered via [`fmt::Display`].
+///
+/// Paths are grouped by outcome, followed by a one line summary.
+#[derive(Clone, Debug)]
+pub struct Report<'a> {
+    results: &'a FileResults,
+    errors: Vec<String>,
+    colored: bool,
+}
+
+impl<'a> Report<'a> {
+    /// Construct an uncolored report of `results`.
+    pub fn new(results: &'a FileResults) -> Self {
+        Self {
+            results,
+            errors: Vec::new(),
+            colored: false,
+        }
+    }
+
+    /// Use ANSI escape codes to colorize the report if `colored` is `true`.
+    ///
+    /// Callers should typically only enable this when writing to a terminal.
+    pub fn colored(mut self, colored: bool) -> Self {
+        self.colored = colored;
+        self
+    }
+
+    /// Include `error` in the report, e.g. an error from a separate operation whose results are
+    /// being reported alongside these.
+    pub fn error(mut self, error: impl fmt::Display) -> Self {
+        self.errors.push(error.to_string());
+        self
+    }
+
+    /// Write `text` wrapped in the `style` escape code, if coloring is enabled.
+    fn styled(&self, f: &mut fmt::Formatter<'_>, style: &str, text: &str) -> fmt::Result {
+        if self.colored {
+            write!(f, "{}{}{}", style, text, RESET)
+        } else {
+            f.write_str(text)
+        }
+    }
+
+    /// Write a group of `items` under `title`, if there are any.
+    fn group<T: fmt::Display>(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        title: &str,
+        style: &str,
+        items: impl ExactSizeIterator<Item = T>,
+    ) -> fmt::Result {
+        if items.len() == 0 {
+            return Ok(());
+        }
+        self.styled(f, BOLD, &format!("{} ({}):", title, items.len()))?;
+        writeln!(f)?;
+        for item in items {
+            f.write_str("  ")?;
+            self.styled(f, style, &item.to_string())?;
+            writeln!(f)?;
+        }
+        writeln!(f)
+    }
+}
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let results = self.results;
+        self.group(
+            f,
+            "Missing headers",
+            RED,
+            results.no_header_files.iter().map(|p| p.display()),
+        )?;
+        self.group(
+            f,
+            "Partial headers",
+            RED,
+            results.partial_header_files.iter().map(|p| p.display()),
+        )?;
+        self.group(
+            f,
+            "Drifted headers",
+            YELLOW,
+            results.drifted_header_files.iter().map(|p| p.display()),
+        )?;
+        self.group(
+            f,
+            "Binary files",
+            YELLOW,
+            results.binary_files.iter().map(|p| p.display()),
+        )?;
+        self.group(
+            f,
+            "Skipped oversized files",
+            YELLOW,
+            results.oversized_files.iter().map(|p| p.display()),
+        )?;
+        self.group(
+            f,
+            "Skipped symlinks",
+            YELLOW,
+            results.symlinks.iter().map(|p| p.display()),
+        )?;
+        self.group(f, "Errors", RED, self.errors.iter())?;
+
+        let counts = [
+            (
+                results.no_header_files.len(),
+                "missing header",
+                "missing headers",
+            ),
+            (
+                results.partial_header_files.len(),
+                "partial header",
+                "partial headers",
+            ),
+            (
+                results.drifted_header_files.len(),
+                "drifted header",
+                "drifted headers",
+            ),
+            (results.binary_files.len(), "binary file", "binary files"),
+            (
+                results.oversized_files.len(),
+                "oversized file skipped",
+                "oversized files skipped",
+            ),
+            (
+                results.symlinks.len(),
+                "symlink skipped",
+                "symlinks skipped",
+            ),
+            (self.errors.len(), "error", "errors"),
+        ];
+        let summary = counts
+            .iter()
+            .filter(|(count, _, _)| *count > 0)
+            .map(|(count, singular, plural)| {
+                format!("{} {}", count, if *count == 1 { singular } else { plural })
+            })
+            .collect::<Vec<_>>();
+        if summary.is_empty() {
+            self.styled(f, GREEN, "All files have headers")?;
+        } else {
+            let failed = results.has_failure() || !self.errors.is_empty();
+            let mut summary = summary.join(", ");
+            if results.stopped_early {
+                summary.push_str(" (stopped early, so there may be more)");
+            }
+            self.styled(f, if failed { RED } else { YELLOW }, &summary)?;
+        }
+        writeln!(f)
+    }
+}
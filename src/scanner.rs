@@ -0,0 +1,124 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A reusable [`Scanner`] for tools that run many header operations, e.g. in watch mode or in a
+//! language server.
+//!
+//! # Examples
+//!
+//! ```
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use file_header::{scanner::Scanner, *};
+//!
+//! # let dir = tempfile::tempdir().unwrap();
+//! # let root = dir.path();
+//! let header = Header::new(
+//!     SingleLineChecker::new("Foo License".to_string(), 10),
+//!     "Foo License".to_string(),
+//! );
+//! // the scanner's 4 threads are reused by every check
+//! let scanner = Scanner::new(header, 4, RecursiveOptions::new());
+//! for _ in 0..3 {
+//!     let results = scanner.check(root, |_p| true).unwrap();
+//!     println!("files without the header: {:?}", results.no_header_files);
+//! }
+//! ```
+
+use crate::{
+    add_headers_recursively_with_options, check_found_files_in_pool,
+    delete_headers_recursively_with_options, find_files, pool, AddHeadersRecursivelyError,
+    CheckHeadersRecursivelyError, DeleteHeadersRecursivelyError, FileResults, Header,
+    HeaderChecker, ModificationResults, RecursiveOptions,
+};
+use std::{fmt, path};
+
+/// A header and [`RecursiveOptions`] to check for, add, or delete the header with, along with a
+/// pool of threads that is reused by every check.
+///
+/// The recursive functions like [`crate::check_headers_recursively`] start new threads on every
+/// call, which is wasteful when scanning repeatedly. The threads are stopped when the scanner is
+/// dropped.
+pub struct Scanner<C: HeaderChecker + 'static> {
+    header: Header<C>,
+    options: RecursiveOptions,
+    pool: pool::ThreadPool,
+}
+
+impl<C: HeaderChecker + 'static> Scanner<C> {
+    /// Construct a scanner for `header` that checks files across `num_threads` threads, with the
+    /// behavior adjusted by `options`.
+    pub fn new(header: Header<C>, num_threads: usize, options: RecursiveOptions) -> Self {
+        Self {
+            header,
+            options,
+            pool: pool::ThreadPool::new(num_threads),
+        }
+    }
+
+    /// Recursively check for the header in every file in `root` that matches `path_predicate`, as
+    /// per [`crate::check_headers_recursively_with_options`].
+    pub fn check(
+        &self,
+        root: &path::Path,
+        path_predicate: impl Fn(&path::Path) -> bool,
+    ) -> Result<FileResults, CheckHeadersRecursivelyError> {
+        let mut results =
+            check_found_files_in_pool(self.header.clone(), &self.pool, &self.options, |path_tx| {
+                find_files(&[root], path_predicate, &self.options, path_tx)
+            })
+            .map_err(|e| e.relativize(&[root], &self.options))?;
+        self.options.relativize(&[root], results.path_lists_mut());
+        Ok(results)
+    }
+
+    /// Recursively add the header to files in `root` that match `path_predicate`, as per
+    /// [`crate::add_headers_recursively_with_options`].
+    pub fn add(
+        &self,
+        root: &path::Path,
+        path_predicate: impl Fn(&path::Path) -> bool,
+    ) -> Result<ModificationResults, AddHeadersRecursivelyError> {
+        add_headers_recursively_with_options(
+            root,
+            path_predicate,
+            self.header.clone(),
+            &self.options,
+        )
+    }
+
+    /// Recursively delete the header from files in `root` that match `path_predicate`, as per
+    /// [`crate::delete_headers_recursively_with_options`].
+    pub fn delete(
+        &self,
+        root: &path::Path,
+        path_predicate: impl Fn(&path::Path) -> bool,
+    ) -> Result<ModificationResults, DeleteHeadersRecursivelyError> {
+        delete_headers_recursively_with_options(
+            root,
+            path_predicate,
+            self.header.clone(),
+            &self.options,
+        )
+    }
+}
+
+impl<C: HeaderChecker + 'static> fmt::Debug for Scanner<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scanner")
+            .field("options", &self.options)
+            .field("num_threads", &self.pool.size())
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,141 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checking for headers by how similar the start of a file is to the header text, so that headers
+//! that drifted from the canonical text, e.g. by being edited or reformatted, can be told apart
+//! from missing ones.
+//!
+//! # Examples
+//!
+//! ```
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use file_header::{similarity::SimilarityChecker, *};
+//!
+//! let text = "Licensed under the Foo License, Version 2.0.\nSee the License for details.";
+//! let checker = SimilarityChecker::new(text, 20);
+//! // rewrapping and comment syntax don't matter
+//! assert!(checker
+//!     .check(&mut "// Licensed under the Foo License,\n// Version 2.0. See the License for details.\n".as_bytes())
+//!     .unwrap());
+//! // a header missing a few words has drifted
+//! let edited = "// Licensed under the Foo License. See the License.\n";
+//! assert!(!checker.check(&mut edited.as_bytes()).unwrap());
+//! assert!(checker.drifted(&mut edited.as_bytes()).unwrap());
+//! ```
+
+use crate::{lines, HeaderChecker};
+use std::io;
+
+/// Checks for header text by the proportion of its words that appear in order in the first
+/// several lines of each file.
+///
+/// Words are compared ignoring case, punctuation around them (including comment delimiters),
+/// typographic characters as per [`crate::SingleLineChecker`], and line wrapping.
+///
+/// Files with at least [`SimilarityChecker::present_threshold`] of the words have the header.
+/// Files with fewer, but at least [`SimilarityChecker::drift_threshold`], have a drifted header,
+/// as per [`HeaderChecker::drifted`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimilarityChecker {
+    /// The words of the header text
+    words: Vec<String>,
+    /// Number of lines to search through
+    max_lines: usize,
+    present_threshold: f64,
+    drift_threshold: f64,
+}
+
+impl SimilarityChecker {
+    /// Construct a `SimilarityChecker` that looks for the words of `text` in the first
+    /// `max_lines` of a file.
+    ///
+    /// By default, all of the words must be present for a file to have the header, and files
+    /// with 60% of them have a drifted header.
+    pub fn new(text: &str, max_lines: usize) -> Self {
+        Self {
+            words: words(text),
+            max_lines,
+            present_threshold: 1.0,
+            drift_threshold: 0.6,
+        }
+    }
+
+    /// Files with at least `threshold`, between 0 and 1, of the header's words in order have the
+    /// header.
+    pub fn present_threshold(mut self, threshold: f64) -> Self {
+        self.present_threshold = threshold;
+        self
+    }
+
+    /// Files without the header, but with at least `threshold`, between 0 and 1, of the header's
+    /// words in order have a drifted header.
+    pub fn drift_threshold(mut self, threshold: f64) -> Self {
+        self.drift_threshold = threshold;
+        self
+    }
+
+    /// The proportion of the header's words, between 0 and 1, that are in order in the first lines
+    /// of `input`.
+    pub fn similarity(&self, input: &mut impl io::Read) -> io::Result<f64> {
+        if self.words.is_empty() {
+            return Ok(1.0);
+        }
+        let mut head = Vec::new();
+        lines::find_line(input, self.max_lines, |line| {
+            head.extend(words(line));
+            false
+        })?;
+        Ok(common_subsequence_len(&self.words, &head) as f64 / self.words.len() as f64)
+    }
+}
+
+impl HeaderChecker for SimilarityChecker {
+    fn check(&self, file: &mut impl io::Read) -> io::Result<bool> {
+        Ok(self.similarity(file)? >= self.present_threshold)
+    }
+
+    fn drifted(&self, file: &mut impl io::Read) -> io::Result<bool> {
+        let similarity = self.similarity(file)?;
+        Ok(similarity >= self.drift_threshold && similarity < self.present_threshold)
+    }
+}
+
+/// The lowercase words of `text`, without surrounding punctuation
+fn words(text: &str) -> Vec<String> {
+    lines::normalize_typography(text)
+        .split_whitespace()
+        .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()))
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+/// The length of the longest common subsequence of `a` and `b`
+fn common_subsequence_len(a: &[String], b: &[String]) -> usize {
+    // only the previous row of the table is needed
+    let mut previous = vec![0_usize; b.len() + 1];
+    let mut current = vec![0_usize; b.len() + 1];
+    for word in a {
+        for (j, other) in b.iter().enumerate() {
+            current[j + 1] = if word == other {
+                previous[j] + 1
+            } else {
+                previous[j + 1].max(current[j])
+            };
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
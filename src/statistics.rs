@@ -0,0 +1,246 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Periodic statistics about recursive checks, e.g. to tell whether a slow check is I/O bound or
+//! stuck on a pathological file.
+//!
+//! If the number of bytes read keeps growing while few files are processed, the check is reading
+//! large files. If neither grows, a file is taking a long time to check, e.g. because it's on a
+//! slow network filesystem.
+//!
+//! # Examples
+//!
+//! ```
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use file_header::{statistics::*, *};
+//! use std::{sync::Arc, time::Duration};
+//!
+//! # let dir = tempfile::tempdir().unwrap();
+//! # let root = dir.path();
+//! # std::fs::write(root.join("main.rs"), "fn main() {}\n").unwrap();
+//! let header = Header::new(
+//!     SingleLineChecker::new("Foo License".to_string(), 10),
+//!     "Foo License".to_string(),
+//! );
+//! let options = RecursiveOptions::new().statistics(
+//!     Duration::from_secs(10),
+//!     Arc::new(|stats: RunStatistics| {
+//!         eprintln!(
+//!             "{:.1} files/s, {} bytes read, {} queued",
+//!             stats.files_per_second(),
+//!             stats.bytes_read,
+//!             stats.queue_depth
+//!         )
+//!     }),
+//! );
+//! check_headers_recursively_with_options(root, |_p| true, header, 4, &options).unwrap();
+//! ```
+
+use crate::{filesystem, ProgressEvent};
+use std::{
+    io, path,
+    sync::{self, atomic},
+    thread, time,
+};
+
+/// A callback for [`RunStatistics`], as used by [`crate::RecursiveOptions::statistics`].
+pub type StatisticsCallback = sync::Arc<dyn Fn(RunStatistics) + Send + Sync>;
+
+/// Statistics about a recursive check so far, as reported to
+/// [`crate::RecursiveOptions::statistics`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RunStatistics {
+    /// Time since the check started
+    pub elapsed: time::Duration,
+    /// Files found to check
+    pub files_found: u64,
+    /// Files checked, successfully or not
+    pub files_processed: u64,
+    /// Bytes read from the files checked
+    pub bytes_read: u64,
+    /// Files found but not yet checked, including those being checked
+    pub queue_depth: u64,
+}
+
+impl RunStatistics {
+    /// The average number of files checked per second.
+    pub fn files_per_second(&self) -> f64 {
+        per_second(self.files_processed, self.elapsed)
+    }
+
+    /// The average number of bytes read per second.
+    pub fn bytes_per_second(&self) -> f64 {
+        per_second(self.bytes_read, self.elapsed)
+    }
+}
+
+/// `count` per second over `elapsed`
+fn per_second(count: u64, elapsed: time::Duration) -> f64 {
+    match elapsed.as_secs_f64() {
+        secs if secs > 0.0 => count as f64 / secs,
+        _ => 0.0,
+    }
+}
+
+/// The counters behind [`RunStatistics`], and where to report them
+pub(crate) struct Statistics {
+    interval: time::Duration,
+    callback: StatisticsCallback,
+    files_found: atomic::AtomicU64,
+    files_processed: atomic::AtomicU64,
+    bytes_read: atomic::AtomicU64,
+}
+
+impl Statistics {
+    pub(crate) fn new(interval: time::Duration, callback: StatisticsCallback) -> Self {
+        Self {
+            interval,
+            callback,
+            files_found: atomic::AtomicU64::new(0),
+            files_processed: atomic::AtomicU64::new(0),
+            bytes_read: atomic::AtomicU64::new(0),
+        }
+    }
+
+    /// Count the file in `event`
+    pub(crate) fn record(&self, event: ProgressEvent<'_>) {
+        let counter = match event {
+            ProgressEvent::Found(_) => &self.files_found,
+            ProgressEvent::Processed(_) => &self.files_processed,
+            ProgressEvent::SearchFinished => return,
+        };
+        counter.fetch_add(1, atomic::Ordering::Relaxed);
+    }
+
+    /// `fs`, counting the bytes read from files opened in it
+    pub(crate) fn counting<'a>(
+        &'a self,
+        fs: &'a dyn filesystem::FileSystem,
+    ) -> CountingFileSystem<'a> {
+        CountingFileSystem {
+            inner: fs,
+            bytes_read: &self.bytes_read,
+        }
+    }
+
+    /// Reset the counters, and report them every interval until the returned ticker is dropped,
+    /// when they're reported a final time.
+    pub(crate) fn start(self: &sync::Arc<Self>) -> Ticker {
+        for counter in [&self.files_found, &self.files_processed, &self.bytes_read] {
+            counter.store(0, atomic::Ordering::Relaxed);
+        }
+        let (stop_tx, stop_rx) = crossbeam::channel::bounded::<()>(0);
+        let statistics = self.clone();
+        let start = time::Instant::now();
+        let thread = thread::spawn(move || {
+            while let Err(crossbeam::channel::RecvTimeoutError::Timeout) =
+                stop_rx.recv_timeout(statistics.interval)
+            {
+                (statistics.callback)(statistics.snapshot(start.elapsed()));
+            }
+            (statistics.callback)(statistics.snapshot(start.elapsed()));
+        });
+        Ticker {
+            stop: Some(stop_tx),
+            thread: Some(thread),
+        }
+    }
+
+    fn snapshot(&self, elapsed: time::Duration) -> RunStatistics {
+        let files_processed = self.files_processed.load(atomic::Ordering::Relaxed);
+        let files_found = self.files_found.load(atomic::Ordering::Relaxed);
+        RunStatistics {
+            elapsed,
+            files_found,
+            files_processed,
+            bytes_read: self.bytes_read.load(atomic::Ordering::Relaxed),
+            queue_depth: files_found.saturating_sub(files_processed),
+        }
+    }
+}
+
+/// Reports statistics periodically until dropped, as per [`Statistics::start`]
+pub(crate) struct Ticker {
+    stop: Option<crossbeam::channel::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Drop for Ticker {
+    fn drop(&mut self) {
+        // disconnecting the channel stops the thread
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            // a panicking callback has already been reported by the thread
+            let _ = thread.join();
+        }
+    }
+}
+
+/// A [`filesystem::FileSystem`] that counts the bytes read from the files it opens
+pub(crate) struct CountingFileSystem<'a> {
+    inner: &'a dyn filesystem::FileSystem,
+    bytes_read: &'a atomic::AtomicU64,
+}
+
+impl filesystem::FileSystem for CountingFileSystem<'_> {
+    fn open(&self, p: &path::Path) -> io::Result<Box<dyn io::Read + Send + '_>> {
+        Ok(Box::new(CountingReader {
+            inner: self.inner.open(p)?,
+            bytes_read: self.bytes_read,
+        }))
+    }
+
+    fn file_size(&self, p: &path::Path) -> io::Result<u64> {
+        self.inner.file_size(p)
+    }
+
+    fn modified(&self, p: &path::Path) -> io::Result<time::SystemTime> {
+        self.inner.modified(p)
+    }
+
+    fn is_symlink(&self, p: &path::Path) -> io::Result<bool> {
+        self.inner.is_symlink(p)
+    }
+
+    fn replace(&self, p: &path::Path, head: &[u8], rest: &mut dyn io::Read) -> io::Result<()> {
+        self.inner.replace(p, head, rest)
+    }
+
+    fn lock(&self, p: &path::Path) -> io::Result<Option<filesystem::FileLock>> {
+        self.inner.lock(p)
+    }
+
+    fn walk<'a>(
+        &'a self,
+        root: &path::Path,
+        options: filesystem::WalkOptions<'a>,
+    ) -> Box<dyn Iterator<Item = Result<filesystem::WalkEntry, filesystem::WalkError>> + 'a> {
+        self.inner.walk(root, options)
+    }
+}
+
+struct CountingReader<'a, R> {
+    inner: R,
+    bytes_read: &'a atomic::AtomicU64,
+}
+
+impl<R: io::Read> io::Read for CountingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.bytes_read
+            .fetch_add(n as u64, atomic::Ordering::Relaxed);
+        Ok(n)
+    }
+}
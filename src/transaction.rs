@@ -0,0 +1,117 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Staging modifications in memory so that a batch of them can be applied all or nothing.
+
+use crate::filesystem::{FileSystem, WalkEntry, WalkError, WalkOptions};
+use std::{
+    collections,
+    io::{self, Read as _},
+    path, sync, time,
+};
+
+/// A [`FileSystem`] that holds replaced contents in memory until they are committed to the
+/// underlying filesystem.
+pub(crate) struct StagingFileSystem {
+    inner: sync::Arc<dyn FileSystem>,
+    /// Staged files, with their original and new contents
+    staged: sync::Mutex<collections::BTreeMap<path::PathBuf, StagedFile>>,
+}
+
+struct StagedFile {
+    original: Vec<u8>,
+    new: Vec<u8>,
+}
+
+impl StagingFileSystem {
+    pub(crate) fn new(inner: sync::Arc<dyn FileSystem>) -> Self {
+        Self {
+            inner,
+            staged: sync::Mutex::new(collections::BTreeMap::new()),
+        }
+    }
+
+    /// Write all staged contents to the underlying filesystem.
+    ///
+    /// If a write fails, the files already written are restored to their original contents on a
+    /// best effort basis, and the path that failed is returned with the error.
+    pub(crate) fn commit(&self) -> Result<(), (path::PathBuf, io::Error)> {
+        let staged = std::mem::take(&mut *self.staged.lock().unwrap());
+        let mut committed: Vec<(&path::PathBuf, &StagedFile)> = Vec::new();
+        for (p, file) in staged.iter() {
+            // files aren't locked while staged, so they're locked while they're written instead
+            let replaced = self
+                .inner
+                .lock(p)
+                .and_then(|_lock| self.inner.replace(p, &file.new, &mut io::empty()));
+            if let Err(e) = replaced {
+                for (p, file) in committed {
+                    // there's nothing more to be done if restoring fails too
+                    let _ = self.inner.replace(p, &file.original, &mut io::empty());
+                }
+                return Err((p.clone(), e));
+            }
+            committed.push((p, file));
+        }
+        Ok(())
+    }
+}
+
+impl FileSystem for StagingFileSystem {
+    fn open(&self, p: &path::Path) -> io::Result<Box<dyn io::Read + Send + '_>> {
+        match self.staged.lock().unwrap().get(p) {
+            Some(file) => Ok(Box::new(io::Cursor::new(file.new.clone()))),
+            None => self.inner.open(p),
+        }
+    }
+
+    fn file_size(&self, p: &path::Path) -> io::Result<u64> {
+        match self.staged.lock().unwrap().get(p) {
+            Some(file) => Ok(file.new.len() as u64),
+            None => self.inner.file_size(p),
+        }
+    }
+
+    fn modified(&self, p: &path::Path) -> io::Result<time::SystemTime> {
+        self.inner.modified(p)
+    }
+
+    fn is_symlink(&self, p: &path::Path) -> io::Result<bool> {
+        self.inner.is_symlink(p)
+    }
+
+    fn replace(&self, p: &path::Path, head: &[u8], rest: &mut dyn io::Read) -> io::Result<()> {
+        let mut new = head.to_vec();
+        rest.read_to_end(&mut new)?;
+        let mut staged = self.staged.lock().unwrap();
+        let original = match staged.remove(p) {
+            Some(file) => file.original,
+            None => {
+                let mut original = Vec::new();
+                self.inner.open(p)?.read_to_end(&mut original)?;
+                original
+            }
+        };
+        staged.insert(p.to_path_buf(), StagedFile { original, new });
+        Ok(())
+    }
+
+    fn walk<'a>(
+        &'a self,
+        root: &path::Path,
+        options: WalkOptions<'a>,
+    ) -> Box<dyn Iterator<Item = Result<WalkEntry, WalkError>> + 'a> {
+        self.inner.walk(root, options)
+    }
+}
@@ -0,0 +1,169 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Watching a directory tree, and checking for or adding headers as files are created or changed.
+//!
+//! This module requires the `watch` feature.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! // Copyright 2023 Google LLC.
+//! // SPDX-License-Identifier: Apache-2.0
+//! use file_header::{watch::*, *};
+//! use std::path::Path;
+//!
+//! let header = Header::new(
+//!     SingleLineChecker::new("Foo License".to_string(), 10),
+//!     "Foo License".to_string(),
+//! );
+//! let watcher = watch(
+//!     Path::new("/some/dir"),
+//!     |p| p.extension().map(|ext| ext == "rs").unwrap_or(false),
+//!     header,
+//!     WatchAction::AddMissing,
+//!     |event| println!("{:?}", event),
+//! )
+//! .unwrap();
+//!
+//! // headers are added as files are saved until the watcher is stopped
+//! watcher.stop();
+//! ```
+
+use crate::{AddHeaderError, Header, HeaderChecker};
+use std::{fs, io, path, sync::mpsc, thread};
+
+use notify::Watcher as _;
+
+/// What to do when a watched file is created or changed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchAction {
+    /// Check for the header, reporting [`WatchEvent::HeaderMissing`] if it's not present
+    Check,
+    /// Add the header if it's not present, reporting [`WatchEvent::HeaderAdded`]
+    AddMissing,
+}
+
+/// The outcome of handling a created or changed file
+#[derive(Debug)]
+pub enum WatchEvent {
+    /// The file at the path has the header
+    HeaderPresent(path::PathBuf),
+    /// The file at the path does not have the header
+    HeaderMissing(path::PathBuf),
+    /// The header was added to the file at the path
+    HeaderAdded(path::PathBuf),
+    /// The file at the path appears to be binary, not UTF-8 text
+    BinaryFile(path::PathBuf),
+    /// An error occurred
+    Error(WatchError),
+}
+
+/// Errors that can be reported while watching
+#[derive(Debug, thiserror::Error)]
+pub enum WatchError {
+    /// I/O error while checking the path
+    #[error("I/O error at {0:?}: {1}")]
+    IoError(path::PathBuf, io::Error),
+    /// The header could not be added
+    #[error("Could not add header: {0}")]
+    AddHeaderError(#[from] AddHeaderError),
+    /// The filesystem notification mechanism reported an error
+    #[error("Notify error: {0}")]
+    NotifyError(#[from] notify::Error),
+}
+
+/// A running watch, as started by [`watch`].
+///
+/// Watching stops when this is dropped, or when [`Watcher::stop`] is called.
+pub struct Watcher {
+    watcher: notify::RecommendedWatcher,
+    handle: thread::JoinHandle<()>,
+}
+
+impl Watcher {
+    /// Stop watching, waiting for any in-progress events to be handled.
+    pub fn stop(self) {
+        // dropping the watcher closes the event channel, which ends the handler thread
+        drop(self.watcher);
+        self.handle.join().unwrap();
+    }
+}
+
+/// Watch `root` recursively, and apply `action` with `header` to every file that matches
+/// `path_predicate` when it is created or changed.
+///
+/// The outcome for each file is passed to `callback` from a background thread.
+///
+/// Adding a header changes the file, so with [`WatchAction::AddMissing`] a
+/// [`WatchEvent::HeaderAdded`] is typically followed by a [`WatchEvent::HeaderPresent`] for the
+/// same file.
+pub fn watch(
+    root: &path::Path,
+    path_predicate: impl Fn(&path::Path) -> bool + Send + 'static,
+    header: Header<impl HeaderChecker + 'static>,
+    action: WatchAction,
+    mut callback: impl FnMut(WatchEvent) + Send + 'static,
+) -> Result<Watcher, notify::Error> {
+    let (event_tx, event_rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(event_tx)?;
+    watcher.watch(root, notify::RecursiveMode::Recursive)?;
+    let handle = thread::spawn(move || {
+        for res in event_rx {
+            let event = match res {
+                Ok(event) => event,
+                Err(e) => {
+                    callback(WatchEvent::Error(e.into()));
+                    continue;
+                }
+            };
+            if !matches!(
+                event.kind,
+                notify::EventKind::Create(_) | notify::EventKind::Modify(_)
+            ) {
+                continue;
+            }
+            for p in event.paths {
+                // renames and deletions may leave paths that no longer exist
+                if !p.is_file() || !path_predicate(&p) {
+                    continue;
+                }
+                callback(handle_file(p, &header, action));
+            }
+        }
+    });
+    Ok(Watcher { watcher, handle })
+}
+
+/// Apply `action` to the file at `p`
+fn handle_file(
+    p: path::PathBuf,
+    header: &Header<impl HeaderChecker>,
+    action: WatchAction,
+) -> WatchEvent {
+    match fs::File::open(&p).and_then(|mut f| header.header_present(&mut f)) {
+        Ok(true) => WatchEvent::HeaderPresent(p),
+        Ok(false) => match action {
+            WatchAction::Check => WatchEvent::HeaderMissing(p),
+            WatchAction::AddMissing => match header.add_header_if_missing(&p) {
+                Ok(true) => WatchEvent::HeaderAdded(p),
+                // it was added concurrently
+                Ok(false) => WatchEvent::HeaderPresent(p),
+                Err(e) => WatchEvent::Error(e.into()),
+            },
+        },
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => WatchEvent::BinaryFile(p),
+        Err(e) => WatchEvent::Error(WatchError::IoError(p, e)),
+    }
+}
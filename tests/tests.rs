@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use file_header::*;
-use std::{fs, io, path};
+use std::{fs, io, ops, path, sync};
 
 #[test]
 fn single_line_checker_finds_header_when_present() {
@@ -31,6 +31,54 @@ fn single_line_checker_doesnt_find_header_when_missing() {
     assert!(!test_checker().check(&mut input.as_bytes()).unwrap())
 }
 
+#[test]
+fn single_line_checker_tolerates_typographic_characters() {
+    let checker = SingleLineChecker::new(
+        "Licensed under the \"License\" \u{2013} see it".to_string(),
+        10,
+    );
+    assert!(checker
+        .check(
+            &mut "// Licensed under the \u{201c}License\u{201d}\u{a0}\u{2014} see it\n".as_bytes()
+        )
+        .unwrap());
+    let checker = SingleLineChecker::new("Foo\u{2019}s \u{201c}License\u{201d}".to_string(), 10);
+    assert!(checker
+        .check(&mut "# Foo's \"License\"\n".as_bytes())
+        .unwrap());
+    assert!(!checker.check(&mut "# Foo's License\n".as_bytes()).unwrap());
+}
+
+#[test]
+fn single_line_checker_handles_long_lines_and_short_reads() {
+    /// Reads one byte at a time
+    struct ByteReader<'a>(&'a [u8]);
+
+    impl io::Read for ByteReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.0.len().min(buf.len()).min(1);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    let long_line = "x".repeat(20_000);
+    let input = format!("{}\n{}some license{}\nbar", long_line, long_line, long_line);
+    assert!(test_checker().check(&mut input.as_bytes()).unwrap());
+    assert!(test_checker()
+        .check(&mut ByteReader(input.as_bytes()))
+        .unwrap());
+    // the pattern is beyond the lines searched
+    assert!(!SingleLineChecker::new("some license".to_string(), 1)
+        .check(&mut input.as_bytes())
+        .unwrap());
+    // the last line has no line ending
+    assert!(test_checker()
+        .check(&mut format!("{}\nsome license", long_line).as_bytes())
+        .unwrap());
+}
+
 #[test]
 fn adds_header_with_empty_delimiters() {
     let file = tempfile::Builder::new().suffix(".rs").tempfile().unwrap();
@@ -79,6 +127,293 @@ not a license",
     );
 }
 
+#[test]
+fn adds_header_matching_extension_case_insensitively() {
+    for (name, expected) in [
+        (
+            "legacy.C",
+            "/*\n * some license etc etc etc\n */\n\nnot a license",
+        ),
+        ("MAIN.RS", "// some license etc etc etc\n\nnot a license"),
+        ("DOCKERFILE", "# some license etc etc etc\n\nnot a license"),
+    ] {
+        let dir = tempfile::tempdir().unwrap();
+        let p = dir.path().join(name);
+        fs::write(&p, "not a license").unwrap();
+        assert!(test_header().add_header_if_missing(&p).unwrap());
+        assert_eq!(expected, fs::read_to_string(&p).unwrap());
+    }
+}
+
+#[test]
+fn adds_and_deletes_header_with_configured_delimiters() {
+    // e.g. a dialect chosen on the command line
+    let sql_delimiters = HeaderDelimiters::new("", "# ", "");
+    let header = test_header().delimiters(move |p| match p.extension() {
+        Some(ext) if ext == "sql" => Some(sql_delimiters.clone()),
+        _ => HeaderDelimiters::for_path(p),
+    });
+    let dir = tempfile::tempdir().unwrap();
+    let sql = dir.path().join("dump.sql");
+    fs::write(&sql, "SELECT 1;\n").unwrap();
+    let rs = dir.path().join("main.rs");
+    fs::write(&rs, "fn main() {}\n").unwrap();
+
+    assert!(header.add_header_if_missing(&sql).unwrap());
+    assert!(header.add_header_if_missing(&rs).unwrap());
+    assert_eq!(
+        "# some license etc etc etc\n\nSELECT 1;\n",
+        fs::read_to_string(&sql).unwrap()
+    );
+    assert_eq!(
+        "// some license etc etc etc\n\nfn main() {}\n",
+        fs::read_to_string(&rs).unwrap()
+    );
+
+    assert!(header.delete_header_if_present(&sql).unwrap());
+    assert_eq!("SELECT 1;\n", fs::read_to_string(&sql).unwrap());
+}
+
+#[test]
+fn recognizes_dockerfile_variants() {
+    for name in [
+        "Dockerfile",
+        "Dockerfile.dev",
+        "dev.Dockerfile",
+        "Containerfile",
+        "Containerfile.prod",
+        "prod.containerfile",
+    ] {
+        assert_eq!(
+            Some(HeaderDelimiters::new("", "# ", "")),
+            HeaderDelimiters::for_path(path::Path::new(name)),
+            "{}",
+            name
+        );
+    }
+    assert_eq!(
+        None,
+        HeaderDelimiters::for_path(path::Path::new("Dockerfiles"))
+    );
+}
+
+#[test]
+fn supported_file_types_match_for_path() {
+    let file_types = HeaderDelimiters::supported_file_types();
+    for file_type in &file_types {
+        for ext in file_type.extensions {
+            assert_eq!(
+                Some(&file_type.delimiters),
+                HeaderDelimiters::for_path(&path::Path::new("foo").with_extension(ext)).as_ref(),
+                "{}",
+                ext
+            );
+        }
+        for name in file_type.filenames {
+            assert_eq!(
+                Some(&file_type.delimiters),
+                HeaderDelimiters::for_path(path::Path::new(name)).as_ref(),
+                "{}",
+                name
+            );
+        }
+    }
+    let html = file_types
+        .iter()
+        .find(|t| t.extensions.contains(&"html"))
+        .unwrap();
+    assert_eq!("<!--", html.delimiters.first_line());
+    assert_eq!(" ", html.delimiters.content_line_prefix());
+    assert_eq!("-->", html.delimiters.last_line());
+}
+
+#[test]
+fn add_recursively_supports_infrastructure_languages() {
+    let root = tempfile::tempdir().unwrap();
+    fs::write(root.path().join("default.nix"), "{ pkgs }: pkgs.hello\n").unwrap();
+    fs::write(root.path().join("config.dhall"), "{ port = 8080 }\n").unwrap();
+    fs::write(root.path().join("schema.cue"), "port: int\n").unwrap();
+
+    let results = add_headers_recursively(root.path(), |_p| true, test_header()).unwrap();
+    assert_eq!(3, results.len());
+    for (file, expected) in [
+        (
+            "default.nix",
+            "# some license etc etc etc\n\n{ pkgs }: pkgs.hello\n",
+        ),
+        (
+            "config.dhall",
+            "-- some license etc etc etc\n\n{ port = 8080 }\n",
+        ),
+        ("schema.cue", "// some license etc etc etc\n\nport: int\n"),
+    ] {
+        assert_eq!(
+            expected,
+            fs::read_to_string(root.path().join(file)).unwrap(),
+            "{}",
+            file
+        );
+    }
+}
+
+#[test]
+fn add_recursively_supports_documentation_formats() {
+    let root = tempfile::tempdir().unwrap();
+    fs::write(root.path().join("index.rst"), "Title\n=====\n").unwrap();
+    fs::write(root.path().join("guide.adoc"), "= Guide\n").unwrap();
+
+    let results = add_headers_recursively(root.path(), |_p| true, test_header()).unwrap();
+    assert_eq!(2, results.len());
+    assert_eq!(
+        ".. some license etc etc etc\n\nTitle\n=====\n",
+        fs::read_to_string(root.path().join("index.rst")).unwrap()
+    );
+    assert_eq!(
+        "// some license etc etc etc\n\n= Guide\n",
+        fs::read_to_string(root.path().join("guide.adoc")).unwrap()
+    );
+    let results = delete_headers_recursively(root.path(), |_p| true, test_header()).unwrap();
+    assert_eq!(2, results.len());
+    assert_eq!(
+        "Title\n=====\n",
+        fs::read_to_string(root.path().join("index.rst")).unwrap()
+    );
+}
+
+#[test]
+fn add_recursively_supports_template_languages() {
+    let root = tempfile::tempdir().unwrap();
+    for file in [
+        "nginx.conf.j2",
+        "page.jinja",
+        "card.hbs",
+        "show.erb",
+        "index.pug",
+    ] {
+        fs::write(root.path().join(file), "body\n").unwrap();
+    }
+
+    let results = add_headers_recursively(root.path(), |_p| true, test_header()).unwrap();
+    assert_eq!(5, results.len());
+    for (file, expected) in [
+        (
+            "nginx.conf.j2",
+            "{#\n some license etc etc etc\n#}\n\nbody\n",
+        ),
+        ("page.jinja", "{#\n some license etc etc etc\n#}\n\nbody\n"),
+        (
+            "card.hbs",
+            "{{!--\n some license etc etc etc\n--}}\n\nbody\n",
+        ),
+        ("show.erb", "<%#\n some license etc etc etc\n%>\n\nbody\n"),
+        ("index.pug", "//- some license etc etc etc\n\nbody\n"),
+    ] {
+        assert_eq!(
+            expected,
+            fs::read_to_string(root.path().join(file)).unwrap(),
+            "{}",
+            file
+        );
+    }
+}
+
+#[test]
+fn add_recursively_supports_stylesheets() {
+    let root = tempfile::tempdir().unwrap();
+    for file in ["theme.less", "theme.styl", "theme.postcss", "theme.pcss"] {
+        fs::write(root.path().join(file), "a { color: red }\n").unwrap();
+    }
+
+    let results = add_headers_recursively(root.path(), |_p| true, test_header()).unwrap();
+    assert_eq!(4, results.len());
+    for p in results {
+        assert_eq!(
+            "/**\n * some license etc etc etc\n */\n\na { color: red }\n",
+            fs::read_to_string(&p).unwrap(),
+            "{}",
+            p.display()
+        );
+    }
+}
+
+#[test]
+fn refuses_header_that_ends_its_comment_early() {
+    let header = Header::new(
+        SingleLineChecker::new("Foo License".to_string(), 10),
+        "Foo License */ and more".to_string(),
+    );
+    let c_delimiters = HeaderDelimiters::for_path(path::Path::new("foo.c")).unwrap();
+    assert_eq!(
+        Some("*/".to_string()),
+        c_delimiters.comment_breaking_sequence("Foo License */ and more")
+    );
+    assert_eq!(
+        io::ErrorKind::InvalidInput,
+        header
+            .add_header_to_str(&c_delimiters, "int main() {}\n")
+            .unwrap_err()
+            .kind()
+    );
+    // line comments can't end early
+    assert!(header
+        .add_header_to_str(&HeaderDelimiters::new("", "// ", ""), "fn main() {}\n")
+        .unwrap()
+        .is_some());
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("foo.xml");
+    fs::write(&file, "<foo/>\n").unwrap();
+    let header = Header::new(
+        SingleLineChecker::new("Foo License".to_string(), 10),
+        "Foo License -- see LICENSE".to_string(),
+    );
+    assert!(matches!(
+        header.add_header_if_missing(&file),
+        Err(AddHeaderError::CommentTerminator(p, sequence)) if p == file && sequence == "--"
+    ));
+    assert_eq!("<foo/>\n", fs::read_to_string(&file).unwrap());
+    assert_eq!(
+        None,
+        HeaderDelimiters::for_path(&file)
+            .unwrap()
+            .comment_breaking_sequence("Foo License - see LICENSE")
+    );
+}
+
+#[test]
+fn adds_and_deletes_header_in_scripts_without_extension() {
+    let root = tempfile::tempdir().unwrap();
+    let bin = root.path().join("bin");
+    fs::create_dir(&bin).unwrap();
+    fs::write(bin.join("build"), "#!/usr/bin/env bash\necho hi\n").unwrap();
+    fs::write(bin.join("serve"), "#!/usr/bin/node\nserve();\n").unwrap();
+
+    let header = test_header();
+    let mut added = add_headers_recursively(root.path(), |_p| true, header.clone()).unwrap();
+    added.sort();
+    assert_eq!(vec![bin.join("build"), bin.join("serve")], added);
+    assert_eq!(
+        "#!/usr/bin/env bash\n# some license etc etc etc\n\necho hi\n",
+        fs::read_to_string(bin.join("build")).unwrap()
+    );
+    assert_eq!(
+        "#!/usr/bin/node\n/**\n * some license etc etc etc\n */\n\nserve();\n",
+        fs::read_to_string(bin.join("serve")).unwrap()
+    );
+
+    assert!(header.delete_header_if_present(&bin.join("build")).unwrap());
+    assert_eq!(
+        "#!/usr/bin/env bash\necho hi\n",
+        fs::read_to_string(bin.join("build")).unwrap()
+    );
+
+    fs::write(bin.join("run"), "#!/opt/bin/unknown\nrun\n").unwrap();
+    assert!(matches!(
+        header.add_header_if_missing(&bin.join("run")),
+        Err(AddHeaderError::UnrecognizedExtension(p)) if p == bin.join("run")
+    ));
+}
+
 #[test]
 fn doesnt_add_header_when_already_present() {
     let file = tempfile::Builder::new().suffix(".rs").tempfile().unwrap();
@@ -114,219 +449,3648 @@ fn adds_header_after_magic_first_line() {
 }
 
 #[test]
-fn header_present_on_binary_file_produces_error_invalid_data() {
-    let file = tempfile::Builder::new().suffix(".xml").tempfile().unwrap();
-    fs::write(file.path(), [0xFF_u8; 100]).unwrap();
+fn add_and_delete_header_preserve_large_contents() {
+    let file = tempfile::Builder::new().suffix(".rs").tempfile().unwrap();
+    let contents = "fn main() {}\n".repeat(100_000);
+    fs::write(file.path(), &contents).unwrap();
+    assert!(test_header().add_header_if_missing(file.path()).unwrap());
+    assert_eq!(
+        format!("// some license etc etc etc\n\n{}", contents),
+        fs::read_to_string(file.path()).unwrap()
+    );
+    assert!(test_header().delete_header_if_present(file.path()).unwrap());
+    assert_eq!(contents, fs::read_to_string(file.path()).unwrap());
+}
+
+#[test]
+fn add_and_delete_header_reject_files_that_are_not_utf8() {
+    let dir = tempfile::tempdir().unwrap();
+    let p = dir.path().join("latin1.rs");
+    // the lines the checker reads are valid, but not the rest
+    let mut contents = b"fn main() {}\n".repeat(1000);
+    contents.extend_from_slice(b"// caf\xe9\n");
+    fs::write(&p, &contents).unwrap();
+    assert!(matches!(
+        test_header().add_header_if_missing(&p),
+        Err(AddHeaderError::IoError(_, e)) if e.kind() == io::ErrorKind::InvalidData
+    ));
+    assert_eq!(contents, fs::read(&p).unwrap());
+
+    let with_header = [b"// some license etc etc etc\n\n".as_slice(), &contents].concat();
+    fs::write(&p, &with_header).unwrap();
+    assert!(matches!(
+        test_header().delete_header_if_present(&p),
+        Err(DeleteHeaderError::IoError(_, e)) if e.kind() == io::ErrorKind::InvalidData
+    ));
+    assert_eq!(with_header, fs::read(&p).unwrap());
 
+    // characters split across reads are still valid
+    let text = "// café ☕ 🦀\n".repeat(10_000);
+    fs::write(&p, &text).unwrap();
+    assert!(test_header().add_header_if_missing(&p).unwrap());
     assert_eq!(
-        io::ErrorKind::InvalidData,
-        test_header()
-            .header_present(&mut fs::File::open(file.path()).unwrap())
-            .unwrap_err()
-            .kind()
+        format!("// some license etc etc etc\n\n{}", text),
+        fs::read_to_string(&p).unwrap()
     );
+    assert!(test_header().delete_header_if_present(&p).unwrap());
+    assert_eq!(text, fs::read_to_string(&p).unwrap());
 }
 
+#[cfg(unix)]
 #[test]
-fn deletes_header_with_empty_delimiters() {
-    let file = tempfile::Builder::new().suffix(".rs").tempfile().unwrap();
-    fs::write(
-        file.path(),
-        r#"// some license etc etc etc
+fn adds_header_preserving_permissions() {
+    use std::os::unix::fs::PermissionsExt as _;
 
-not a license"#,
-    )
-    .unwrap();
-    let ok = test_header().delete_header_if_present(file.path()).unwrap();
-    assert!(ok);
-    assert_eq!("not a license", fs::read_to_string(file.path()).unwrap());
+    let file = tempfile::Builder::new().suffix(".sh").tempfile().unwrap();
+    fs::write(file.path(), "#!/bin/sh\necho hi\n").unwrap();
+    fs::set_permissions(file.path(), fs::Permissions::from_mode(0o755)).unwrap();
+    // only root can give files away, so the owner is only changed when running as root
+    let chowned = std::process::Command::new("chown")
+        .arg("1:1")
+        .arg(file.path())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map_or(false, |status| status.success());
+    let owner = |p: &path::Path| {
+        use std::os::unix::fs::MetadataExt as _;
+        let metadata = fs::metadata(p).unwrap();
+        (metadata.uid(), metadata.gid())
+    };
+    let before = owner(file.path());
+    assert!(!chowned || before == (1, 1));
+    test_header().add_header_if_missing(file.path()).unwrap();
+    assert_eq!(
+        0o755,
+        fs::metadata(file.path()).unwrap().permissions().mode() & 0o777
+    );
+    assert_eq!(before, owner(file.path()));
 }
 
+#[cfg(unix)]
 #[test]
-fn deletes_header_with_nonempty_delimiters() {
-    let file = tempfile::Builder::new().suffix(".c").tempfile().unwrap();
-    fs::write(
-        file.path(),
-        r#"/*
- * some license etc etc etc
- */
+fn adds_header_to_symlink_target() {
+    let dir = tempfile::tempdir().unwrap();
+    let target = dir.path().join("target.rs");
+    let link = dir.path().join("link.rs");
+    fs::write(&target, "fn main() {}\n").unwrap();
+    std::os::unix::fs::symlink(&target, &link).unwrap();
 
-not a license"#,
-    )
-    .unwrap();
-    let ok = test_header().delete_header_if_present(file.path()).unwrap();
-    assert!(ok);
-    assert_eq!("not a license", fs::read_to_string(file.path()).unwrap());
+    assert!(test_header().add_header_if_missing(&link).unwrap());
+    assert!(fs::symlink_metadata(&link)
+        .unwrap()
+        .file_type()
+        .is_symlink());
+    assert_eq!(target, fs::read_link(&link).unwrap());
+    assert_eq!(
+        "// some license etc etc etc\n\nfn main() {}\n",
+        fs::read_to_string(&target).unwrap()
+    );
 }
 
+#[cfg(unix)]
 #[test]
-fn deletes_header_trim_trailing_whitespace() {
-    let file = tempfile::Builder::new().suffix(".c").tempfile().unwrap();
-    fs::write(
-        file.path(),
-        r#"/*
- * some license
- * line with trailing whitespace.
- *
- * etc
- */
+fn adds_and_deletes_header_keeping_hard_links() {
+    use std::os::unix::fs::MetadataExt as _;
 
-not a license"#,
-    )
-    .unwrap();
-    let ok = test_header_with_blank_lines_and_trailing_whitespace()
-        .delete_header_if_present(file.path())
-        .unwrap();
-    assert!(ok);
-    assert_eq!("not a license", fs::read_to_string(file.path()).unwrap());
+    let dir = tempfile::tempdir().unwrap();
+    let original = dir.path().join("original.rs");
+    let linked = dir.path().join("linked.rs");
+    fs::write(&original, "fn main() {}\n").unwrap();
+    fs::hard_link(&original, &linked).unwrap();
+    let ino = fs::metadata(&original).unwrap().ino();
+
+    assert!(test_header().add_header_if_missing(&original).unwrap());
+    assert_eq!(ino, fs::metadata(&original).unwrap().ino());
+    assert_eq!(
+        "// some license etc etc etc\n\nfn main() {}\n",
+        fs::read_to_string(&linked).unwrap()
+    );
+    assert!(test_header().delete_header_if_present(&linked).unwrap());
+    assert_eq!("fn main() {}\n", fs::read_to_string(&original).unwrap());
+    assert_eq!(2, fs::metadata(&original).unwrap().nlink());
 }
 
+#[cfg(unix)]
 #[test]
-fn deletes_header_after_magic_first_line() {
-    let file = tempfile::Builder::new().suffix(".xml").tempfile().unwrap();
-    fs::write(
-        file.path(),
-        r#"<?xml version="1.0" encoding="UTF-8"?>
-<!--
- some license etc etc etc
--->
+fn std_file_system_locks_files_while_modifying_them() {
+    use filesystem::FileSystem as _;
 
-<root />
-"#,
-    )
-    .unwrap();
-    let ok = test_header().delete_header_if_present(file.path()).unwrap();
-    assert!(ok);
+    let file = tempfile::Builder::new().suffix(".rs").tempfile().unwrap();
+    fs::write(file.path(), "fn main() {}\n").unwrap();
+    let lock = filesystem::StdFileSystem
+        .lock(file.path())
+        .unwrap()
+        .unwrap();
+    let p = file.path().to_path_buf();
+    let adder = std::thread::spawn(move || test_header().add_header_if_missing(&p).unwrap());
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    // the header isn't added until the lock is released
+    assert_eq!("fn main() {}\n", fs::read_to_string(file.path()).unwrap());
+    drop(lock);
+    assert!(adder.join().unwrap());
     assert_eq!(
-        r#"<?xml version="1.0" encoding="UTF-8"?>
-<root />
-"#,
+        "// some license etc etc etc\n\nfn main() {}\n",
         fs::read_to_string(file.path()).unwrap()
     );
 }
 
 #[test]
-fn deletes_header_without_touching_contents() {
-    let file = tempfile::Builder::new().suffix(".rs").tempfile().unwrap();
-    fs::write(
-        file.path(),
-        r#"// some license etc etc etc
+fn file_system_implementations_can_lock_files() {
+    /// Counts the locks taken and released
+    struct LockingFileSystem {
+        inner: filesystem::MemoryFileSystem,
+        locks: sync::Arc<sync::atomic::AtomicUsize>,
+        released: sync::Arc<sync::atomic::AtomicUsize>,
+    }
 
-license in file:
-// some license etc etc etc
+    struct Guard(sync::Arc<sync::atomic::AtomicUsize>);
 
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, sync::atomic::Ordering::SeqCst);
+        }
+    }
 
-contents after license"#,
-    )
-    .unwrap();
-    let ok = test_header().delete_header_if_present(file.path()).unwrap();
-    assert!(ok);
-    assert_eq!(
-        r#"license in file:
-// some license etc etc etc
+    impl filesystem::FileSystem for LockingFileSystem {
+        fn open(&self, p: &path::Path) -> io::Result<Box<dyn io::Read + Send + '_>> {
+            self.inner.open(p)
+        }
 
+        fn file_size(&self, p: &path::Path) -> io::Result<u64> {
+            self.inner.file_size(p)
+        }
 
-contents after license"#,
-        fs::read_to_string(file.path()).unwrap()
-    );
-}
+        fn replace(&self, p: &path::Path, head: &[u8], rest: &mut dyn io::Read) -> io::Result<()> {
+            // the lock is held while the file is replaced
+            assert_eq!(
+                self.locks.load(sync::atomic::Ordering::SeqCst),
+                self.released.load(sync::atomic::Ordering::SeqCst) + 1
+            );
+            self.inner.replace(p, head, rest)
+        }
 
-#[test]
-fn deletes_header_requires_exact_wrapped_header() {
-    let file = tempfile::Builder::new().suffix(".rs").tempfile().unwrap();
-    // does not have // prefix for the wrapped header, so the checker will find it, but it shouldn't
-    // actually be deleted
-    let orig = r#"some license etc etc etc
+        fn lock(&self, _p: &path::Path) -> io::Result<Option<filesystem::FileLock>> {
+            self.locks.fetch_add(1, sync::atomic::Ordering::SeqCst);
+            Ok(Some(filesystem::FileLock::new(Guard(
+                self.released.clone(),
+            ))))
+        }
 
-not a license"#;
-    fs::write(file.path(), orig).unwrap();
-    let ok = test_header().delete_header_if_present(file.path()).unwrap();
-    assert!(!ok);
-    assert_eq!(orig, fs::read_to_string(file.path()).unwrap());
-}
+        fn walk<'a>(
+            &'a self,
+            root: &path::Path,
+            options: filesystem::WalkOptions<'a>,
+        ) -> Box<dyn Iterator<Item = Result<filesystem::WalkEntry, filesystem::WalkError>> + 'a>
+        {
+            self.inner.walk(root, options)
+        }
+    }
 
-#[test]
-fn check_recursively_finds_no_header_file() {
-    let header = test_header();
-    let root = path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources/test/example_check");
-    let results = check_headers_recursively(&root, |_p| true, header, 4).unwrap();
-    assert_eq!(
-        vec![path::PathBuf::from("no_header.rs")],
-        results
-            .no_header_files
-            .iter()
-            .map(|p| p.strip_prefix(&root).unwrap().to_path_buf())
-            .collect::<Vec<_>>()
-    );
+    let fs = LockingFileSystem {
+        inner: filesystem::MemoryFileSystem::new(),
+        locks: sync::Arc::default(),
+        released: sync::Arc::default(),
+    };
+    let p = path::Path::new("main.rs");
+    fs.inner.insert(p, "fn main() {}\n");
+    assert!(test_header().add_header_if_missing_with_fs(&fs, p).unwrap());
+    assert_eq!(1, fs.locks.load(sync::atomic::Ordering::SeqCst));
+    assert_eq!(1, fs.released.load(sync::atomic::Ordering::SeqCst));
 }
 
+#[cfg(unix)]
 #[test]
-fn check_recursively_detects_binary_file() {
-    let header = test_header();
+fn lift_read_only_modifies_read_only_files_and_restores_them() {
+    use std::os::unix::fs::PermissionsExt as _;
 
     let root = tempfile::tempdir().unwrap();
+    let p = root.path().join("main.rs");
+    fs::write(&p, "fn main() {}\n").unwrap();
+    fs::set_permissions(&p, fs::Permissions::from_mode(0o444)).unwrap();
+    let options = RecursiveOptions::new().lift_read_only(true);
 
-    let no_header = root.path().join("no_header.rs");
-    fs::write(&no_header, "// no header\n").unwrap();
+    let results =
+        add_headers_recursively_with_options(root.path(), |_p| true, test_header(), &options)
+            .unwrap();
+    assert_eq!(vec![p.clone()], results.modified_files);
+    assert_eq!(
+        "// some license etc etc etc\n\nfn main() {}\n",
+        fs::read_to_string(&p).unwrap()
+    );
+    assert_eq!(
+        0o444,
+        fs::metadata(&p).unwrap().permissions().mode() & 0o777
+    );
 
-    let binary = root.path().join("binary.rs");
-    fs::write(&binary, [0xFF; 100]).unwrap();
+    let results =
+        delete_headers_recursively_with_options(root.path(), |_p| true, test_header(), &options)
+            .unwrap();
+    assert_eq!(vec![p.clone()], results.modified_files);
+    assert_eq!("fn main() {}\n", fs::read_to_string(&p).unwrap());
+    assert_eq!(
+        0o444,
+        fs::metadata(&p).unwrap().permissions().mode() & 0o777
+    );
 
-    let results = check_headers_recursively(root.path(), |_p| true, header, 4).unwrap();
+    // without lifting, read-only files are left as they are
+    let options = RecursiveOptions::new().lift_read_only(false);
+    match add_headers_recursively_with_options(root.path(), |_p| true, test_header(), &options) {
+        Err(AddHeadersRecursivelyError::IoError(failed, e)) => {
+            assert_eq!(p, failed);
+            assert_eq!(io::ErrorKind::PermissionDenied, e.kind());
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert_eq!("fn main() {}\n", fs::read_to_string(&p).unwrap());
+    assert!(matches!(
+        test_header().add_header_if_missing(&p),
+        Err(AddHeaderError::IoError(_, e)) if e.kind() == io::ErrorKind::PermissionDenied
+    ));
+}
+
+#[test]
+fn adds_and_deletes_header_in_str() {
+    let delimiters = HeaderDelimiters::for_path(path::Path::new("foo.sh")).unwrap();
+    let orig = "#!/bin/sh\necho hi\n";
+    let with_header = test_header()
+        .add_header_to_str(&delimiters, orig)
+        .unwrap()
+        .unwrap();
     assert_eq!(
-        vec![path::PathBuf::from("no_header.rs")],
-        results
-            .no_header_files
-            .iter()
-            .map(|p| p.strip_prefix(&root).unwrap().to_path_buf())
-            .collect::<Vec<_>>()
+        "#!/bin/sh\n# some license etc etc etc\n\necho hi\n",
+        with_header
     );
+    assert!(test_header().check_str(&with_header).unwrap());
     assert_eq!(
-        vec![path::PathBuf::from("binary.rs")],
-        results
-            .binary_files
-            .iter()
-            .map(|p| p.strip_prefix(&root).unwrap().to_path_buf())
-            .collect::<Vec<_>>()
+        None,
+        test_header()
+            .add_header_to_str(&delimiters, &with_header)
+            .unwrap()
+    );
+    assert_eq!(
+        Some(orig.to_string()),
+        test_header()
+            .delete_header_from_str(&delimiters, &with_header)
+            .unwrap()
     );
 }
 
 #[test]
-fn add_recursively_adds_where_needed() {
-    let header = test_header();
+fn adds_and_deletes_header_after_xml_prolog() {
+    let delimiters = HeaderDelimiters::for_path(path::Path::new("foo.xml")).unwrap();
+    let prolog = r#"<?xml version="1.0"
+      encoding="UTF-8"?>
+<!-- generated -->
+<!DOCTYPE note [
+  <!ENTITY writer "Foo > Bar">
+  <!-- a comment with ] and > -->
+]>
+"#;
+    let orig = format!("{}<note>&writer;</note>\n", prolog);
+    let with_header = test_header()
+        .add_header_to_str(&delimiters, &orig)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        format!(
+            "{}<!--\n some license etc etc etc\n-->\n\n<note>&writer;</note>\n",
+            prolog
+        ),
+        with_header
+    );
+    assert_eq!(
+        Some(orig),
+        test_header()
+            .delete_header_from_str(&delimiters, &with_header)
+            .unwrap()
+    );
+
+    // content on the same line as the prolog moves after the header
+    assert_eq!(
+        Some(
+            "<?xml version=\"1.0\"?>\n<!--\n some license etc etc etc\n-->\n\n<root/>\n"
+                .to_string()
+        ),
+        test_header()
+            .add_header_to_str(&delimiters, "<?xml version=\"1.0\"?><root/>\n")
+            .unwrap()
+    );
+}
+
+#[test]
+fn adds_and_deletes_header_after_html_doctype() {
+    let delimiters = HeaderDelimiters::for_path(path::Path::new("index.html")).unwrap();
+    for doctype in ["<!DOCTYPE html>", "<!doctype html>", "<!DocType html>"] {
+        let orig = format!("{}\n<html></html>\n", doctype);
+        let with_header = test_header()
+            .add_header_to_str(&delimiters, &orig)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            format!(
+                "{}\n<!--\n some license etc etc etc\n-->\n\n<html></html>\n",
+                doctype
+            ),
+            with_header
+        );
+        assert_eq!(
+            Some(orig),
+            test_header()
+                .delete_header_from_str(&delimiters, &with_header)
+                .unwrap()
+        );
+    }
+
+    // content on the same line as the doctype moves after the header
+    assert_eq!(
+        Some(
+            "<!DOCTYPE html>\n<!--\n some license etc etc etc\n-->\n\n<html lang=\"en\">\n</html>\n"
+                .to_string()
+        ),
+        test_header()
+            .add_header_to_str(&delimiters, "<!DOCTYPE html> <html lang=\"en\">\n</html>\n")
+            .unwrap()
+    );
+}
+
+#[test]
+fn adds_and_deletes_header_after_yaml_directives() {
+    let delimiters = HeaderDelimiters::for_path(path::Path::new("config.yaml")).unwrap();
+    for prolog in [
+        "---\n",
+        "%YAML 1.2\n%TAG ! tag:example.com,2000:\n---\n",
+        "--- # doc\n",
+    ] {
+        let orig = format!("{}foo: bar\n", prolog);
+        let with_header = test_header()
+            .add_header_to_str(&delimiters, &orig)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            format!("{}# some license etc etc etc\n\nfoo: bar\n", prolog),
+            with_header
+        );
+        assert_eq!(
+            Some(orig),
+            test_header()
+                .delete_header_from_str(&delimiters, &with_header)
+                .unwrap()
+        );
+    }
+
+    // a header after a marker with content would be part of the document
+    assert_eq!(
+        Some("# some license etc etc etc\n\n--- |\n  text\n".to_string()),
+        test_header()
+            .add_header_to_str(&delimiters, "--- |\n  text\n")
+            .unwrap()
+    );
+    // later document markers are ordinary content
+    assert_eq!(
+        Some("# some license etc etc etc\n\nfoo: bar\n---\n".to_string()),
+        test_header()
+            .add_header_to_str(&delimiters, "foo: bar\n---\n")
+            .unwrap()
+    );
+
+    // other files with `#` comments have no YAML prolog
+    let python = HeaderDelimiters::for_path(path::Path::new("foo.py")).unwrap();
+    for orig in ["---\nx = 1\n", "%foo\nx = 1\n"] {
+        assert_eq!(
+            Some(format!("# some license etc etc etc\n\n{}", orig)),
+            test_header().add_header_to_str(&python, orig).unwrap()
+        );
+    }
+}
+
+#[test]
+fn adds_and_deletes_header_without_blank_line() {
+    let delimiters = HeaderDelimiters::for_path(path::Path::new("foo.rs")).unwrap();
+    let orig = "fn main() {}\n";
+    for (placement, expected) in [
+        (
+            Placement::Top,
+            "// some license etc etc etc\nfn main() {}\n",
+        ),
+        (
+            Placement::Bottom,
+            "fn main() {}\n// some license etc etc etc\n",
+        ),
+    ] {
+        let header = test_header().placement(placement).blank_line(false);
+        let with_header = header
+            .add_header_to_str(&delimiters, orig)
+            .unwrap()
+            .unwrap();
+        assert_eq!(expected, with_header);
+        assert_eq!(
+            Some(orig.to_string()),
+            header
+                .delete_header_from_str(&delimiters, &with_header)
+                .unwrap()
+        );
+    }
+
+    // deleting honors the configuration, leaving a blank line that was added by other means
+    assert_eq!(
+        Some("\nfn main() {}\n".to_string()),
+        test_header()
+            .blank_line(false)
+            .delete_header_from_str(&delimiters, "// some license etc etc etc\n\nfn main() {}\n")
+            .unwrap()
+    );
+}
+
+#[test]
+fn adds_and_deletes_framed_header() {
+    let delimiters = HeaderDelimiters::for_path(path::Path::new("foo.c")).unwrap();
+    let orig = "int main() {}\n";
+    for (frame, expected) in [
+        (
+            FrameStyle::Rule {
+                character: '-',
+                width: 8,
+            },
+            "/*\n * --------\n * some license etc etc etc\n * --------\n */\n\nint main() {}\n",
+        ),
+        (
+            FrameStyle::Box,
+            "/*\n * ┌──────────────────────────┐\n * │ some license etc etc etc │\n * └──────────────────────────┘\n */\n\nint main() {}\n",
+        ),
+    ] {
+        let header = test_header().frame(frame);
+        let with_header = header
+            .add_header_to_str(&delimiters, orig)
+            .unwrap()
+            .unwrap();
+        assert_eq!(expected, with_header);
+        assert_eq!(
+            Some(orig.to_string()),
+            header
+                .delete_header_from_str(&delimiters, &with_header)
+                .unwrap()
+        );
+    }
+}
+
+#[test]
+fn header_builder_sets_options() {
+    assert!(matches!(
+        Header::<SingleLineChecker>::builder()
+            .text("some license etc etc etc".to_string())
+            .build(),
+        Err(HeaderBuilderError::MissingChecker)
+    ));
+    assert!(matches!(
+        Header::builder().checker(test_checker()).build(),
+        Err(HeaderBuilderError::MissingText)
+    ));
+
+    let header = Header::builder()
+        .checker(test_checker())
+        .text("some license etc etc etc\n  indented text that wraps".to_string())
+        .line_width(20)
+        .blank_line(false)
+        .placement(Placement::Top)
+        .delimiters(|_p| Some(HeaderDelimiters::new("", "-- ", "")))
+        .magic_first_line("-- !")
+        .build()
+        .unwrap();
+    let orig = "-- ! magic\nselect 1;\n";
+    let with_header = header
+        .add_header_to_str(&HeaderDelimiters::new("", "-- ", ""), orig)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        "-- ! magic\n-- some license etc\n-- etc etc\n--   indented text\n--   that wraps\nselect 1;\n",
+        with_header
+    );
+    assert_eq!(
+        Some(orig.to_string()),
+        header
+            .delete_header_from_str(&HeaderDelimiters::new("", "-- ", ""), &with_header)
+            .unwrap()
+    );
+}
+
+#[test]
+fn wraps_and_frames_wide_characters_by_display_width() {
+    let delimiters = HeaderDelimiters::new("", "-- ", "");
+    let orig = "select 1;\n";
+    let header = Header::builder()
+        .checker(SingleLineChecker::new("Copyright".to_string(), 10))
+        .text("Copyright 山田太郎株式会社".to_string())
+        .line_width(13)
+        .blank_line(false)
+        .delimiters(|_p| Some(HeaderDelimiters::new("", "-- ", "")))
+        .build()
+        .unwrap();
+    let with_header = header
+        .add_header_to_str(&delimiters, orig)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        "-- Copyright\n-- 山田太郎株\n-- 式会社\nselect 1;\n",
+        with_header
+    );
+    assert_eq!(
+        Some(orig.to_string()),
+        header
+            .delete_header_from_str(&delimiters, &with_header)
+            .unwrap()
+    );
+
+    let header = Header::new(test_checker(), "山田\nab".to_string()).frame(FrameStyle::Box);
+    assert_eq!(
+        "-- ┌──────┐\n-- │ 山田 │\n-- │ ab   │\n-- └──────┘\n\nselect 1;\n",
+        header
+            .add_header_to_str(&delimiters, orig)
+            .unwrap()
+            .unwrap()
+    );
+}
+
+#[test]
+fn adds_header_in_str_with_custom_delimiters() {
+    let delimiters = HeaderDelimiters::new("{#", "  ", "#}");
+    assert_eq!(
+        Some("{#\n  some license etc etc etc\n#}\n\nnot a license".to_string()),
+        test_header()
+            .add_header_to_str(&delimiters, "not a license")
+            .unwrap()
+    );
+}
+
+#[test]
+fn adds_and_deletes_header_at_bottom() {
+    let header = test_header().placement(Placement::Bottom);
+    let delimiters = HeaderDelimiters::for_path(path::Path::new("data.sh")).unwrap();
+    let orig = "# some license etc etc etc\n\necho 1\necho 2\necho 3\necho 4\necho 5\necho 6";
+    // a copy of the header far from the bottom doesn't count
+    assert!(!header.check_str(orig).unwrap());
+
+    let with_header = header
+        .add_header_to_str(&delimiters, orig)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        format!("{}\n\n# some license etc etc etc\n", orig),
+        with_header
+    );
+    assert!(header.check_str(&with_header).unwrap());
+    assert_eq!(
+        format!("{}\n", orig),
+        header
+            .delete_header_from_str(&delimiters, &with_header)
+            .unwrap()
+            .unwrap()
+    );
+}
+
+#[test]
+fn adds_and_deletes_header_after_line() {
+    let header = test_header().placement(Placement::AfterLine(2));
+    let file = tempfile::Builder::new().suffix(".rs").tempfile().unwrap();
+    fs::write(file.path(), "// line 1\n// line 2\n// line 3\n").unwrap();
+
+    assert!(header.add_header_if_missing(file.path()).unwrap());
+    assert_eq!(
+        "// line 1\n// line 2\n// some license etc etc etc\n\n// line 3\n",
+        fs::read_to_string(file.path()).unwrap()
+    );
+    assert!(!header.add_header_if_missing(file.path()).unwrap());
+    // the header isn't found in the lines before the placement
+    assert!(!test_header()
+        .placement(Placement::AfterLine(3))
+        .header_present(&mut fs::File::open(file.path()).unwrap())
+        .unwrap());
+
+    assert!(header.delete_header_if_present(file.path()).unwrap());
+    assert_eq!(
+        "// line 1\n// line 2\n// line 3\n",
+        fs::read_to_string(file.path()).unwrap()
+    );
+}
+
+#[test]
+fn adds_header_without_displacing_modelines() {
+    let delimiters = HeaderDelimiters::for_path(path::Path::new("script.py")).unwrap();
+    assert_eq!(
+        "# -*- coding: utf-8 -*-\n# some license etc etc etc\n\nprint(1)\n",
+        test_header()
+            .add_header_to_str(&delimiters, "# -*- coding: utf-8 -*-\nprint(1)\n")
+            .unwrap()
+            .unwrap()
+    );
+    assert_eq!(
+        "#!/usr/bin/env python\n# vim: set ts=4:\n# some license etc etc etc\n\nprint(1)\n",
+        test_header()
+            .add_header_to_str(
+                &delimiters,
+                "#!/usr/bin/env python\n# vim: set ts=4:\nprint(1)\n"
+            )
+            .unwrap()
+            .unwrap()
+    );
+    assert_eq!(
+        "print(1)\n\n# some license etc etc etc\n# vim: set ts=4:\n",
+        test_header()
+            .placement(Placement::Bottom)
+            .add_header_to_str(&delimiters, "print(1)\n# vim: set ts=4:")
+            .unwrap()
+            .unwrap()
+    );
+    // a mention of vim isn't a modeline
+    assert_eq!(
+        "# some license etc etc etc\n\n# works with vim\n",
+        test_header()
+            .add_header_to_str(&delimiters, "# works with vim\n")
+            .unwrap()
+            .unwrap()
+    );
+    for comment in ["# see vim:help\n", "#vim:ts=4\n"] {
+        assert_eq!(
+            format!("# some license etc etc etc\n\n{}", comment),
+            test_header()
+                .add_header_to_str(&delimiters, comment)
+                .unwrap()
+                .unwrap()
+        );
+    }
+    assert_eq!(
+        "# vim: ts=4 sw=4\n# some license etc etc etc\n\nprint(1)\n",
+        test_header()
+            .add_header_to_str(&delimiters, "# vim: ts=4 sw=4\nprint(1)\n")
+            .unwrap()
+            .unwrap()
+    );
+    // Emacs only reads the first line, or the second after a shebang
+    assert_eq!(
+        "#!/usr/bin/env python\n# -*- coding: utf-8 -*-\n# some license etc etc etc\n\nprint(1)\n",
+        test_header()
+            .add_header_to_str(
+                &delimiters,
+                "#!/usr/bin/env python\n# -*- coding: utf-8 -*-\nprint(1)\n"
+            )
+            .unwrap()
+            .unwrap()
+    );
+    assert_eq!(
+        "# vim: set ts=4:\n# some license etc etc etc\n\n# -*- coding: utf-8 -*-\n",
+        test_header()
+            .add_header_to_str(&delimiters, "# vim: set ts=4:\n# -*- coding: utf-8 -*-\n")
+            .unwrap()
+            .unwrap()
+    );
+    assert_eq!(
+        "print(1)\n# -*- coding: utf-8 -*-\n\n# some license etc etc etc\n",
+        test_header()
+            .placement(Placement::Bottom)
+            .add_header_to_str(&delimiters, "print(1)\n# -*- coding: utf-8 -*-\n")
+            .unwrap()
+            .unwrap()
+    );
+    // C comments can end after the settings
+    let c = HeaderDelimiters::for_path(path::Path::new("main.c")).unwrap();
+    assert_eq!(
+        "int x;\n\n/*\n * some license etc etc etc\n */\n/* vim: ts=4 sw=4 */\n",
+        test_header()
+            .placement(Placement::Bottom)
+            .add_header_to_str(&c, "int x;\n/* vim: ts=4 sw=4 */\n")
+            .unwrap()
+            .unwrap()
+    );
+}
+
+#[test]
+fn adds_and_deletes_header_in_style() {
+    let header = Header::new(
+        test_checker(),
+        "some license etc etc etc\n        indented".to_string(),
+    )
+    .style(|_p| HeaderStyle {
+        line_ending: LineEnding::CrLf,
+        indent: Some(Indent::Tabs { width: 4 }),
+        insert_final_newline: true,
+    });
+    let file = tempfile::Builder::new().suffix(".rs").tempfile().unwrap();
+    fs::write(file.path(), "fn main() {}\r\n").unwrap();
+
+    assert!(header.add_header_if_missing(file.path()).unwrap());
+    assert_eq!(
+        "// some license etc etc etc\r\n// \t\tindented\r\n\r\nfn main() {}\r\n",
+        fs::read_to_string(file.path()).unwrap()
+    );
+    assert!(header.delete_header_if_present(file.path()).unwrap());
+    assert_eq!("fn main() {}\r\n", fs::read_to_string(file.path()).unwrap());
+}
+
+#[test]
+fn adds_and_deletes_bottom_header_without_final_newline() {
+    let header = test_header()
+        .placement(Placement::Bottom)
+        .style(|_p| HeaderStyle {
+            insert_final_newline: false,
+            ..HeaderStyle::default()
+        });
+    let file = tempfile::Builder::new().suffix(".sh").tempfile().unwrap();
+    fs::write(file.path(), "echo 1").unwrap();
+
+    assert!(header.add_header_if_missing(file.path()).unwrap());
+    assert_eq!(
+        "echo 1\n\n# some license etc etc etc",
+        fs::read_to_string(file.path()).unwrap()
+    );
+    assert!(header.delete_header_if_present(file.path()).unwrap());
+    assert_eq!("echo 1\n", fs::read_to_string(file.path()).unwrap());
+}
+
+#[cfg(feature = "editorconfig")]
+#[test]
+fn editorconfig_determines_header_style() {
+    let root = tempfile::tempdir().unwrap();
+    fs::write(
+        root.path().join(".editorconfig"),
+        "root = true\n\n[*.rs]\nend_of_line = crlf\nindent_style = space\nindent_size = 2\n",
+    )
+    .unwrap();
+    assert_eq!(
+        HeaderStyle {
+            line_ending: LineEnding::CrLf,
+            indent: Some(Indent::Spaces { width: 2 }),
+            insert_final_newline: true,
+        },
+        file_header::editorconfig::header_style_for_path(&root.path().join("main.rs"))
+    );
+    assert_eq!(
+        HeaderStyle::default(),
+        file_header::editorconfig::header_style_for_path(&root.path().join("main.py"))
+    );
+}
+
+#[test]
+fn appends_year_to_copyright_line() {
+    use copyright::*;
+
+    assert_eq!(
+        Some("// Copyright (c) 2019, 2021, 2025 Foo Inc.\nfn main() {}\n".to_string()),
+        append_year_to_str("// Copyright (c) 2019, 2021 Foo Inc.\nfn main() {}\n", 2025)
+    );
+    assert_eq!(
+        Some("# Copyright 2019-2021, 2025 Foo Inc.\r\n".to_string()),
+        append_year_to_str("# Copyright 2019-2021 Foo Inc.\r\n", 2025)
+    );
+    assert_eq!(
+        None,
+        append_year_to_str("// Copyright 2019-2025 Foo\n", 2021)
+    );
+    assert_eq!(None, append_year_to_str("// no copyright line\n", 2025));
+
+    let root = tempfile::tempdir().unwrap();
+    let updated = root.path().join("updated.rs");
+    fs::write(&updated, "// Copyright 2023 Foo Inc.\n\nfn main() {}\n").unwrap();
+    let current = root.path().join("current.rs");
+    fs::write(&current, "// Copyright 2025 Foo Inc.\n").unwrap();
+    fs::write(root.path().join("binary.bin"), [0xFF; 10]).unwrap();
+
+    let results =
+        append_year_recursively(root.path(), |_p| true, 2025, &RecursiveOptions::new()).unwrap();
+    assert_eq!(vec![updated.clone()], results.modified_files);
+    assert_eq!(
+        "// Copyright 2023, 2025 Foo Inc.\n\nfn main() {}\n",
+        fs::read_to_string(&updated).unwrap()
+    );
+}
+
+#[test]
+fn header_present_on_binary_file_produces_error_invalid_data() {
+    let file = tempfile::Builder::new().suffix(".xml").tempfile().unwrap();
+    fs::write(file.path(), [0xFF_u8; 100]).unwrap();
+
+    assert_eq!(
+        io::ErrorKind::InvalidData,
+        test_header()
+            .header_present(&mut fs::File::open(file.path()).unwrap())
+            .unwrap_err()
+            .kind()
+    );
+}
+
+#[test]
+fn deletes_header_with_empty_delimiters() {
+    let file = tempfile::Builder::new().suffix(".rs").tempfile().unwrap();
+    fs::write(
+        file.path(),
+        r#"// some license etc etc etc
+
+not a license"#,
+    )
+    .unwrap();
+    let ok = test_header().delete_header_if_present(file.path()).unwrap();
+    assert!(ok);
+    assert_eq!("not a license", fs::read_to_string(file.path()).unwrap());
+}
+
+#[test]
+fn deletes_header_with_nonempty_delimiters() {
+    let file = tempfile::Builder::new().suffix(".c").tempfile().unwrap();
+    fs::write(
+        file.path(),
+        r#"/*
+ * some license etc etc etc
+ */
+
+not a license"#,
+    )
+    .unwrap();
+    let ok = test_header().delete_header_if_present(file.path()).unwrap();
+    assert!(ok);
+    assert_eq!("not a license", fs::read_to_string(file.path()).unwrap());
+}
+
+#[test]
+fn deletes_header_trim_trailing_whitespace() {
+    let file = tempfile::Builder::new().suffix(".c").tempfile().unwrap();
+    fs::write(
+        file.path(),
+        r#"/*
+ * some license
+ * line with trailing whitespace.
+ *
+ * etc
+ */
+
+not a license"#,
+    )
+    .unwrap();
+    let ok = test_header_with_blank_lines_and_trailing_whitespace()
+        .delete_header_if_present(file.path())
+        .unwrap();
+    assert!(ok);
+    assert_eq!("not a license", fs::read_to_string(file.path()).unwrap());
+}
+
+#[test]
+fn deletes_header_after_magic_first_line() {
+    let file = tempfile::Builder::new().suffix(".xml").tempfile().unwrap();
+    fs::write(
+        file.path(),
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!--
+ some license etc etc etc
+-->
+
+<root />
+"#,
+    )
+    .unwrap();
+    let ok = test_header().delete_header_if_present(file.path()).unwrap();
+    assert!(ok);
+    assert_eq!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<root />
+"#,
+        fs::read_to_string(file.path()).unwrap()
+    );
+}
+
+#[test]
+fn deletes_header_without_touching_contents() {
+    let file = tempfile::Builder::new().suffix(".rs").tempfile().unwrap();
+    fs::write(
+        file.path(),
+        r#"// some license etc etc etc
+
+license in file:
+// some license etc etc etc
+
+
+contents after license"#,
+    )
+    .unwrap();
+    let ok = test_header().delete_header_if_present(file.path()).unwrap();
+    assert!(ok);
+    assert_eq!(
+        r#"license in file:
+// some license etc etc etc
+
+
+contents after license"#,
+        fs::read_to_string(file.path()).unwrap()
+    );
+}
+
+#[test]
+fn deletes_header_requires_exact_wrapped_header() {
+    let file = tempfile::Builder::new().suffix(".rs").tempfile().unwrap();
+    // does not have // prefix for the wrapped header, so the checker will find it, but it shouldn't
+    // actually be deleted
+    let orig = r#"some license etc etc etc
+
+not a license"#;
+    fs::write(file.path(), orig).unwrap();
+    let ok = test_header().delete_header_if_present(file.path()).unwrap();
+    assert!(!ok);
+    assert_eq!(orig, fs::read_to_string(file.path()).unwrap());
+}
+
+#[test]
+fn check_recursively_finds_no_header_file() {
+    let header = test_header();
+    let root = path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources/test/example_check");
+    let results = check_headers_recursively(&root, |_p| true, header, 4).unwrap();
+    assert_eq!(
+        vec![path::PathBuf::from("no_header.rs")],
+        results
+            .no_header_files
+            .iter()
+            .map(|p| p.strip_prefix(&root).unwrap().to_path_buf())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn check_recursively_detects_binary_file() {
+    let header = test_header();
+
+    let root = tempfile::tempdir().unwrap();
+
+    let no_header = root.path().join("no_header.rs");
+    fs::write(&no_header, "// no header\n").unwrap();
+
+    let binary = root.path().join("binary.rs");
+    fs::write(&binary, [0xFF; 100]).unwrap();
+
+    let results = check_headers_recursively(root.path(), |_p| true, header, 4).unwrap();
+    assert_eq!(
+        vec![path::PathBuf::from("no_header.rs")],
+        results
+            .no_header_files
+            .iter()
+            .map(|p| p.strip_prefix(&root).unwrap().to_path_buf())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec![path::PathBuf::from("binary.rs")],
+        results
+            .binary_files
+            .iter()
+            .map(|p| p.strip_prefix(&root).unwrap().to_path_buf())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn check_recursively_detects_binary_file_by_content() {
+    let header = test_header();
+
+    let root = tempfile::tempdir().unwrap();
+
+    // valid UTF-8, so only detectable as binary by its NUL bytes
+    let nul = root.path().join("nul.rs");
+    fs::write(&nul, b"\0\0\0\0 some license etc etc etc\n").unwrap();
+    assert!(is_probably_binary(&nul).unwrap());
+
+    let text = root.path().join("text.rs");
+    fs::write(&text, "// no header ü\n".repeat(1000)).unwrap();
+    assert!(!is_probably_binary(&text).unwrap());
+
+    let results = check_headers_recursively(root.path(), |_p| true, header, 4).unwrap();
+    assert_eq!(vec![nul], results.binary_files);
+    assert_eq!(vec![text], results.no_header_files);
+}
+
+#[test]
+fn check_recursively_applies_binary_file_policy() {
+    let memory_fs = sync::Arc::new(filesystem::MemoryFileSystem::new());
+    memory_fs.insert("root/licensed.png", [0xFF; 10]);
+    memory_fs.insert("root/licensed.png.license", "some license etc etc etc\n");
+    memory_fs.insert("root/unlicensed.png", [0xFF; 10]);
+    let check = |policy| {
+        let mut results = check_headers_recursively_with_options(
+            path::Path::new("root"),
+            |p| p.extension().map_or(false, |ext| ext == "png"),
+            test_header(),
+            2,
+            &RecursiveOptions::new()
+                .file_system(memory_fs.clone())
+                .binary_files(policy),
+        )
+        .unwrap();
+        results.no_header_files.sort();
+        results.binary_files.sort();
+        results
+    };
+    let licensed = path::PathBuf::from("root/licensed.png");
+    let unlicensed = path::PathBuf::from("root/unlicensed.png");
+
+    let results = check(BinaryFilePolicy::Report);
+    assert_eq!(
+        vec![licensed.clone(), unlicensed.clone()],
+        results.binary_files
+    );
+
+    assert!(!check(BinaryFilePolicy::Skip).has_failure());
+
+    let results = check(BinaryFilePolicy::Fail);
+    assert_eq!(vec![licensed, unlicensed.clone()], results.no_header_files);
+    assert!(results.binary_files.is_empty());
+
+    let results = check(BinaryFilePolicy::Sidecar);
+    assert_eq!(vec![unlicensed], results.no_header_files);
+    assert!(results.binary_files.is_empty());
+}
+
+#[test]
+fn check_recursively_skips_oversized_file() {
+    let header = test_header();
+
+    let root = tempfile::tempdir().unwrap();
+
+    let small = root.path().join("small.rs");
+    fs::write(&small, "// no header\n").unwrap();
+
+    let large = root.path().join("large.rs");
+    fs::write(&large, "// no header\n".repeat(100)).unwrap();
+
+    let results = check_headers_recursively_with_options(
+        root.path(),
+        |_p| true,
+        header,
+        4,
+        &RecursiveOptions::new().max_file_size(100),
+    )
+    .unwrap();
+    assert_eq!(vec![small], results.no_header_files);
+    assert_eq!(vec![large], results.oversized_files);
+    assert!(results.has_failure());
+}
+
+#[test]
+fn check_recursively_stops_after_max_failures() {
+    let header = test_header();
+
+    let root = tempfile::tempdir().unwrap();
+    for i in 0..20 {
+        fs::write(root.path().join(format!("{}.rs", i)), "// no header\n").unwrap();
+    }
+
+    let results = check_headers_recursively_with_options(
+        root.path(),
+        |_p| true,
+        header.clone(),
+        4,
+        &RecursiveOptions::new().max_failures(3),
+    )
+    .unwrap();
+    assert_eq!(3, results.no_header_files.len());
+    assert!(results.stopped_early);
+    assert!(results
+        .render_summary()
+        .ends_with("3 missing headers (stopped early, so there may be more)\n"));
+
+    let results = check_headers_recursively_with_options(
+        root.path(),
+        |_p| true,
+        header.clone(),
+        4,
+        &RecursiveOptions::new().fail_fast(true),
+    )
+    .unwrap();
+    assert_eq!(1, results.no_header_files.len());
+    assert!(results.has_failure());
+    assert!(results.stopped_early);
+
+    let results = check_headers_recursively_with_options(
+        root.path(),
+        |_p| true,
+        header.clone(),
+        4,
+        &RecursiveOptions::new().max_failures(30),
+    )
+    .unwrap();
+    assert_eq!(20, results.no_header_files.len());
+    assert!(!results.stopped_early);
+
+    // reaching the limit with the last file leaves nothing unchecked
+    let results = check_headers_recursively_with_options(
+        root.path(),
+        |_p| true,
+        header,
+        4,
+        &RecursiveOptions::new().max_failures(20),
+    )
+    .unwrap();
+    assert_eq!(20, results.no_header_files.len());
+    assert!(!results.stopped_early);
+    assert!(results.render_summary().ends_with("20 missing headers\n"));
+}
+
+#[test]
+fn check_recursively_accepts_borrowed_checker() {
+    /// A checker that borrows its pattern, so it isn't `'static`
+    #[derive(Clone)]
+    struct BorrowedChecker<'a> {
+        pattern: &'a str,
+    }
+
+    impl HeaderChecker for BorrowedChecker<'_> {
+        fn check(&self, file: &mut impl io::Read) -> io::Result<bool> {
+            let mut contents = String::new();
+            file.read_to_string(&mut contents)?;
+            Ok(contents.contains(self.pattern))
+        }
+    }
+
+    let root = tempfile::tempdir().unwrap();
+    let with_header = root.path().join("with_header.rs");
+    fs::write(&with_header, "// some license etc etc etc\n").unwrap();
+    let no_header = root.path().join("no_header.rs");
+    fs::write(&no_header, "// no header\n").unwrap();
+
+    let pattern = "some license".to_string();
+    let header = Header::new(
+        BorrowedChecker { pattern: &pattern },
+        "some license etc etc etc".to_string(),
+    );
+    let results = check_headers_recursively(root.path(), |_p| true, header, 2).unwrap();
+    assert_eq!(vec![no_header], results.no_header_files);
+}
+
+#[test]
+fn scanner_reuses_threads_across_operations() {
+    let root = tempfile::tempdir().unwrap();
+    let path = root.path().join("main.rs");
+    fs::write(&path, "fn main() {}\n").unwrap();
+
+    let scanner = scanner::Scanner::new(test_header(), 2, RecursiveOptions::new());
+    assert_eq!(
+        vec![path.clone()],
+        scanner
+            .check(root.path(), |_p| true)
+            .unwrap()
+            .no_header_files
+    );
+    assert_eq!(
+        vec![path.clone()],
+        scanner.add(root.path(), |_p| true).unwrap().modified_files
+    );
+    assert!(!scanner.check(root.path(), |_p| true).unwrap().has_failure());
+    assert_eq!(
+        vec![path],
+        scanner
+            .delete(root.path(), |_p| true)
+            .unwrap()
+            .modified_files
+    );
+    assert!(scanner.check(root.path(), |_p| true).unwrap().has_failure());
+}
+
+#[cfg(unix)]
+#[test]
+fn check_recursively_filters_by_file_metadata() {
+    let header = test_header();
+
+    let root = tempfile::tempdir().unwrap();
+    let top = root.path().join("top.rs");
+    fs::write(&top, "// no header\n").unwrap();
+    fs::create_dir(root.path().join("sub")).unwrap();
+    fs::write(root.path().join("sub/nested.rs"), "// no header\n").unwrap();
+    std::os::unix::fs::symlink(&top, root.path().join("link.rs")).unwrap();
+
+    let results = check_headers_recursively_with_options(
+        root.path(),
+        |_p| true,
+        header,
+        2,
+        &RecursiveOptions::new().file_filter(sync::Arc::new(|_p, metadata| {
+            metadata.depth == 1 && !metadata.is_symlink && metadata.size == 13
+        })),
+    )
+    .unwrap();
+    assert_eq!(vec![top], results.no_header_files);
+}
+
+#[test]
+fn check_recursively_prunes_filtered_directories() {
+    let header = test_header();
+    let options = RecursiveOptions::new().dir_filter(sync::Arc::new(|dir| {
+        dir.file_name().map_or(true, |name| name != "node_modules")
+    }));
+
+    let root = tempfile::tempdir().unwrap();
+    let kept = root.path().join("src/main.rs");
+    fs::create_dir_all(root.path().join("src")).unwrap();
+    fs::write(&kept, "// no header\n").unwrap();
+    fs::create_dir_all(root.path().join("node_modules/dep")).unwrap();
+    fs::write(
+        root.path().join("node_modules/dep/index.js"),
+        "// no header\n",
+    )
+    .unwrap();
+
+    let results =
+        check_headers_recursively_with_options(root.path(), |_p| true, header.clone(), 2, &options)
+            .unwrap();
+    assert_eq!(vec![kept], results.no_header_files);
+
+    let memory_fs = sync::Arc::new(filesystem::MemoryFileSystem::new());
+    memory_fs.insert("root/src/main.rs", "// no header\n");
+    memory_fs.insert("root/node_modules/dep/index.js", "// no header\n");
+    let results = check_headers_recursively_with_options(
+        path::Path::new("root"),
+        |_p| true,
+        header,
+        2,
+        &options.file_system(memory_fs),
+    )
+    .unwrap();
+    assert_eq!(
+        vec![path::PathBuf::from("root/src/main.rs")],
+        results.no_header_files
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn add_recursively_following_symlinks_modifies_each_file_once() {
+    let header = test_header();
+
+    let root = tempfile::tempdir().unwrap();
+    let src = root.path().join("src");
+    fs::create_dir(&src).unwrap();
+    let main = src.join("main.rs");
+    fs::write(&main, "fn main() {}\n").unwrap();
+    // a second route to the same file, and a cycle
+    std::os::unix::fs::symlink(&src, root.path().join("link")).unwrap();
+    std::os::unix::fs::symlink(root.path(), src.join("cycle")).unwrap();
+
+    let results = add_headers_recursively_with_options(
+        root.path(),
+        |_p| true,
+        header,
+        &RecursiveOptions::new().follow_symlinks(true),
+    )
+    .unwrap();
+    assert_eq!(1, results.modified_files.len());
+    assert_eq!(
+        "// some license etc etc etc\n\nfn main() {}\n",
+        fs::read_to_string(&main).unwrap()
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn symlink_policy_follows_skips_or_reports_symlinks() {
+    let outside = tempfile::tempdir().unwrap();
+    let target = outside.path().join("target.rs");
+    fs::write(&target, "fn main() {}\n").unwrap();
+    let root = tempfile::tempdir().unwrap();
+    fs::write(root.path().join("main.rs"), "fn main() {}\n").unwrap();
+    let link = root.path().join("link.rs");
+    std::os::unix::fs::symlink(&target, &link).unwrap();
+
+    let check = |policy| {
+        check_headers_recursively_with_options(
+            root.path(),
+            |_p| true,
+            test_header(),
+            2,
+            &RecursiveOptions::new().symlinks(policy),
+        )
+        .unwrap()
+    };
+    let mut followed = check(SymlinkPolicy::Follow);
+    followed.no_header_files.sort();
+    assert_eq!(
+        vec![link.clone(), root.path().join("main.rs")],
+        followed.no_header_files
+    );
+    assert!(followed.symlinks.is_empty());
+    let skipped = check(SymlinkPolicy::Skip);
+    assert_eq!(vec![root.path().join("main.rs")], skipped.no_header_files);
+    assert!(skipped.symlinks.is_empty());
+    let reported = check(SymlinkPolicy::Report);
+    assert_eq!(vec![root.path().join("main.rs")], reported.no_header_files);
+    assert_eq!(vec![link.clone()], reported.symlinks);
+
+    // listed paths are symlinks as per the file system, rather than whatever is on disk
+    let listed = |file_system: sync::Arc<dyn filesystem::FileSystem>| {
+        check_headers_recursively_with_options(
+            root.path(),
+            |_p| true,
+            test_header(),
+            2,
+            &RecursiveOptions::new()
+                .file_system(file_system)
+                .paths(vec![link.clone()])
+                .symlinks(SymlinkPolicy::Report),
+        )
+        .unwrap()
+    };
+    let memory_fs = sync::Arc::new(filesystem::MemoryFileSystem::new());
+    memory_fs.insert(link.clone(), "fn main() {}\n");
+    let in_memory = listed(memory_fs);
+    assert_eq!(vec![link.clone()], in_memory.no_header_files);
+    assert!(in_memory.symlinks.is_empty());
+    let on_disk = listed(sync::Arc::new(filesystem::StdFileSystem));
+    assert!(on_disk.no_header_files.is_empty());
+    assert_eq!(vec![link.clone()], on_disk.symlinks);
+
+    // the target outside the tree isn't modified
+    let results = add_headers_recursively_with_options(
+        root.path(),
+        |_p| true,
+        test_header(),
+        &RecursiveOptions::new().symlinks(SymlinkPolicy::Report),
+    )
+    .unwrap();
+    assert_eq!(vec![root.path().join("main.rs")], results.modified_files);
+    assert_eq!(vec![link.clone()], results.symlinks);
+    assert_eq!("fn main() {}\n", fs::read_to_string(&target).unwrap());
+
+    // following the link modifies the target, and leaves the link in place
+    let results = add_headers_recursively_with_options(
+        root.path(),
+        |_p| true,
+        test_header(),
+        &RecursiveOptions::new().symlinks(SymlinkPolicy::Follow),
+    )
+    .unwrap();
+    assert_eq!(vec![link.clone()], results.modified_files);
+    assert!(fs::symlink_metadata(&link)
+        .unwrap()
+        .file_type()
+        .is_symlink());
+    assert_eq!(target, fs::read_link(&link).unwrap());
+    assert_eq!(
+        "// some license etc etc etc\n\nfn main() {}\n",
+        fs::read_to_string(&target).unwrap()
+    );
+}
+
+#[test]
+fn add_recursively_handles_long_paths() {
+    let root = tempfile::tempdir().unwrap();
+    // deeper than Windows' 260 character `MAX_PATH`
+    let dir = (0..25).fold(root.path().to_path_buf(), |dir, i| {
+        dir.join(format!("directory_{:02}", i))
+    });
+    let file = dir.join("no_header.rs");
+    assert!(file.as_os_str().len() > 300);
+    fs::create_dir_all(filesystem::long_path(&dir)).unwrap();
+    fs::write(filesystem::long_path(&file), "// no header\n").unwrap();
+
+    let results = add_headers_recursively(root.path(), |_p| true, test_header()).unwrap();
+    assert_eq!(vec![file.clone()], results);
+    assert_eq!(
+        "// some license etc etc etc\n\n// no header\n",
+        fs::read_to_string(filesystem::long_path(&file)).unwrap()
+    );
+}
+
+#[test]
+fn add_recursively_skips_oversized_file() {
+    let header = test_header();
+
+    let root = tempfile::tempdir().unwrap();
+
+    let small = root.path().join("small.rs");
+    fs::write(&small, "// no header\n").unwrap();
+
+    let large = root.path().join("large.rs");
+    let large_contents = "// no header\n".repeat(100);
+    fs::write(&large, &large_contents).unwrap();
+
+    let results = add_headers_recursively_with_options(
+        root.path(),
+        |_p| true,
+        header,
+        &RecursiveOptions::new().max_file_size(100),
+    )
+    .unwrap();
+    assert_eq!(vec![small], results.modified_files);
+    assert_eq!(vec![large.clone()], results.oversized_files);
+    assert_eq!(large_contents, fs::read_to_string(&large).unwrap());
+}
+
+#[test]
+fn add_recursively_uses_per_file_header_text() {
+    struct FileNameText;
+
+    impl HeaderTextProvider for FileNameText {
+        fn text(&self, p: Option<&path::Path>) -> std::borrow::Cow<'_, str> {
+            let name = p.and_then(|p| p.file_name()).unwrap_or_default();
+            format!("some license etc etc etc\nfor {}", name.to_string_lossy()).into()
+        }
+    }
+
+    let header = Header::with_text_provider(test_checker(), FileNameText);
+    let root = tempfile::tempdir().unwrap();
+    let (a, b) = (root.path().join("a.rs"), root.path().join("b.rs"));
+    fs::write(&a, "fn a() {}\n").unwrap();
+    fs::write(&b, "fn b() {}\n").unwrap();
+
+    add_headers_recursively(root.path(), |_p| true, header.clone()).unwrap();
+    assert_eq!(
+        "// some license etc etc etc\n// for a.rs\n\nfn a() {}\n",
+        fs::read_to_string(&a).unwrap()
+    );
+    assert_eq!(
+        "// some license etc etc etc\n// for b.rs\n\nfn b() {}\n",
+        fs::read_to_string(&b).unwrap()
+    );
+
+    assert!(header.delete_header_if_present(&b).unwrap());
+    assert_eq!("fn b() {}\n", fs::read_to_string(&b).unwrap());
+}
+
+#[cfg(feature = "archive")]
+#[test]
+fn check_archive_finds_no_header_files() {
+    use std::io::Write as _;
+
+    let root = tempfile::tempdir().unwrap();
+
+    let zip_path = root.path().join("release.zip");
+    let mut zip = zip::ZipWriter::new(fs::File::create(&zip_path).unwrap());
+    zip.add_directory("src/", Default::default()).unwrap();
+    zip.start_file("src/with_header.rs", Default::default())
+        .unwrap();
+    zip.write_all(b"// some license etc etc etc\n").unwrap();
+    zip.start_file("src/no_header.rs", Default::default())
+        .unwrap();
+    zip.write_all(b"// no header\n").unwrap();
+    zip.start_file("README.md", Default::default()).unwrap();
+    zip.write_all(b"no header\n").unwrap();
+    zip.finish().unwrap();
+
+    let results = file_header::archive::check_headers_in_archive(
+        &zip_path,
+        |p| p.extension().map(|ext| ext == "rs").unwrap_or(false),
+        test_header(),
+    )
+    .unwrap();
+    assert_eq!(
+        vec![path::PathBuf::from(format!(
+            "{}!src/no_header.rs",
+            zip_path.display()
+        ))],
+        results.no_header_files
+    );
+
+    let tgz_path = root.path().join("release.tar.gz");
+    let mut tar = tar::Builder::new(flate2::write::GzEncoder::new(
+        fs::File::create(&tgz_path).unwrap(),
+        Default::default(),
+    ));
+    for (name, contents) in [
+        ("src/with_header.rs", &b"// some license etc etc etc\n"[..]),
+        ("src/binary.rs", &[0xFF_u8; 100][..]),
+    ] {
+        let mut tar_header = tar::Header::new_gnu();
+        tar_header.set_size(contents.len() as u64);
+        tar_header.set_cksum();
+        tar.append_data(&mut tar_header, name, contents).unwrap();
+    }
+    tar.into_inner().unwrap().finish().unwrap();
+
+    let results =
+        file_header::archive::check_headers_in_archive(&tgz_path, |_p| true, test_header())
+            .unwrap();
+    assert!(results.no_header_files.is_empty());
+    assert_eq!(
+        vec![path::PathBuf::from(format!(
+            "{}!src/binary.rs",
+            tgz_path.display()
+        ))],
+        results.binary_files
+    );
+}
+
+#[cfg(feature = "cargo")]
+#[test]
+fn cargo_workspace_checks_rust_sources() {
+    let root = tempfile::tempdir().unwrap();
+    fs::write(
+        root.path().join("Cargo.toml"),
+        "[package]\nname = \"example\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+    )
+    .unwrap();
+    fs::create_dir_all(root.path().join("src/nested")).unwrap();
+    fs::write(root.path().join("src/lib.rs"), "// no header\n").unwrap();
+    fs::write(
+        root.path().join("src/nested/mod.rs"),
+        "// some license etc etc etc\n",
+    )
+    .unwrap();
+    fs::write(root.path().join("src/notes.txt"), "no header\n").unwrap();
+    fs::create_dir_all(root.path().join("benches")).unwrap();
+    fs::write(root.path().join("benches/bench.rs"), "// no header\n").unwrap();
+
+    let workspace =
+        file_header::cargo::Workspace::from_manifest(&root.path().join("Cargo.toml")).unwrap();
+    let results = workspace.check_headers(test_header(), 2).unwrap();
+    assert_eq!(
+        vec![path::PathBuf::from("src/lib.rs")],
+        results
+            .no_header_files
+            .iter()
+            .map(|p| p.strip_prefix(workspace.root()).unwrap().to_path_buf())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[cfg(feature = "watch")]
+#[test]
+fn watch_adds_header_to_new_file() {
+    use file_header::watch::*;
+    use std::{sync::mpsc, time};
+
+    let root = tempfile::tempdir().unwrap();
+    let (tx, rx) = mpsc::channel();
+    let watcher = watch(
+        root.path(),
+        |p| p.extension().map(|ext| ext == "rs").unwrap_or(false),
+        test_header(),
+        WatchAction::AddMissing,
+        move |event| {
+            let _ = tx.send(event);
+        },
+    )
+    .unwrap();
+
+    let new_file = root.path().join("new.rs");
+    // write then rename, so the watcher never sees a partially written file
+    let staged_file = root.path().join("new.tmp");
+    fs::write(&staged_file, "// no header\n").unwrap();
+    fs::rename(&staged_file, &new_file).unwrap();
+    fs::write(root.path().join("ignored.txt"), "// no header\n").unwrap();
+
+    let added = loop {
+        match rx.recv_timeout(time::Duration::from_secs(10)).unwrap() {
+            WatchEvent::HeaderAdded(p) => break p,
+            WatchEvent::Error(e) => panic!("{}", e),
+            _ => {}
+        }
+    };
+    watcher.stop();
+    assert_eq!(new_file.file_name(), added.file_name());
+    assert_eq!(
+        "// some license etc etc etc\n\n// no header\n",
+        fs::read_to_string(&new_file).unwrap()
+    );
+    assert_eq!(
+        "// no header\n",
+        fs::read_to_string(root.path().join("ignored.txt")).unwrap()
+    );
+}
+
+#[test]
+fn check_recursively_reuses_cached_results() {
+    let root = tempfile::tempdir().unwrap();
+    let cache_dir = tempfile::tempdir().unwrap();
+    let cache_path = cache_dir.path().join("cache");
+
+    let no_header = root.path().join("no_header.rs");
+    fs::write(&no_header, "// no header\n").unwrap();
+    let with_header = root.path().join("with_header.rs");
+    fs::write(&with_header, "// some license etc etc etc\n").unwrap();
+
+    let run = |expected_hits| {
+        let cache =
+            sync::Arc::new(file_header::cache::CheckCache::load(&cache_path, "key").unwrap());
+        let results = check_headers_recursively_with_options(
+            root.path(),
+            |_p| true,
+            test_header(),
+            2,
+            &RecursiveOptions::new().cache(cache.clone()),
+        )
+        .unwrap();
+        cache.save().unwrap();
+        assert_eq!(expected_hits, cache.hits());
+        results
+    };
+
+    assert_eq!(vec![no_header.clone()], run(0).no_header_files);
+    assert_eq!(vec![no_header.clone()], run(2).no_header_files);
+
+    // changed files are checked again
+    fs::write(
+        &no_header,
+        "// some license etc etc etc\n// now has a header\n",
+    )
+    .unwrap();
+    assert!(run(1).no_header_files.is_empty());
+
+    // results aren't reused with options that change them
+    let partial = root.path().join("partial.rs");
+    fs::write(&partial, "// some license etc etc etc\n").unwrap();
+    let header = Header::new(
+        SingleLineChecker::new("some license".to_string(), 10),
+        "some license etc etc etc\nand more".to_string(),
+    );
+    let check = |options: RecursiveOptions| {
+        let cache =
+            sync::Arc::new(file_header::cache::CheckCache::load(&cache_path, "key").unwrap());
+        let results = check_headers_recursively_with_options(
+            root.path(),
+            |p| p == partial,
+            header.clone(),
+            2,
+            &options.cache(cache.clone()),
+        )
+        .unwrap();
+        cache.save().unwrap();
+        (results, cache.hits())
+    };
+    let (lenient, _) = check(RecursiveOptions::new());
+    assert!(lenient.partial_header_files.is_empty());
+    let (strict, hits) = check(RecursiveOptions::new().strict_headers(true));
+    assert_eq!(0, hits);
+    assert_eq!(vec![partial.clone()], strict.partial_header_files);
+    let (strict, hits) = check(RecursiveOptions::new().strict_headers(true));
+    assert_eq!(1, hits);
+    assert_eq!(vec![partial.clone()], strict.partial_header_files);
+
+    // a different key discards the cache
+    let cache =
+        sync::Arc::new(file_header::cache::CheckCache::load(&cache_path, "other key").unwrap());
+    check_headers_recursively_with_options(
+        root.path(),
+        |_p| true,
+        test_header(),
+        2,
+        &RecursiveOptions::new().cache(cache.clone()),
+    )
+    .unwrap();
+    assert_eq!(0, cache.hits());
+}
+
+#[test]
+fn check_changed_files_only_checks_files_changed_between_refs() {
+    let root = tempfile::tempdir().unwrap();
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git")
+            .arg("-C")
+            .arg(root.path())
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?} failed", args);
+    };
+    git(&["init", "-q"]);
+    fs::write(root.path().join("old.rs"), "// no header\n").unwrap();
+    fs::write(root.path().join("touched.rs"), "// no header\n").unwrap();
+    fs::write(root.path().join("deleted.rs"), "// no header\n").unwrap();
+    git(&["add", "."]);
+    git(&["commit", "-q", "-m", "base"]);
+    git(&["tag", "base"]);
+
+    fs::write(root.path().join("touched.rs"), "// still no header\n").unwrap();
+    fs::write(root.path().join("new.rs"), "// some license etc etc etc\n").unwrap();
+    fs::write(root.path().join("new.txt"), "// no header\n").unwrap();
+    fs::remove_file(root.path().join("deleted.rs")).unwrap();
+    git(&["add", "-A"]);
+    git(&["commit", "-q", "-m", "change"]);
+
+    let mut changed = file_header::git::changed_files(root.path(), "base", "HEAD").unwrap();
+    changed.sort();
+    assert_eq!(
+        vec![
+            root.path().join("new.rs"),
+            root.path().join("new.txt"),
+            root.path().join("touched.rs")
+        ],
+        changed
+    );
+
+    let results = file_header::git::check_headers_in_changed_files(
+        root.path(),
+        "base",
+        "HEAD",
+        |p| p.extension().map(|ext| ext == "rs").unwrap_or(false),
+        test_header(),
+        2,
+    )
+    .unwrap();
+    assert_eq!(
+        vec![root.path().join("touched.rs")],
+        results.no_header_files
+    );
+
+    assert!(file_header::git::changed_files(root.path(), "nonexistent", "HEAD").is_err());
+
+    // a ref that looks like an option isn't passed to git
+    let output = root.path().join("output");
+    let base = format!("--output={}", output.display());
+    assert!(matches!(
+        file_header::git::changed_files(root.path(), &base, "HEAD"),
+        Err(file_header::git::GitError::InvalidRef(r)) if r == base
+    ));
+    assert!(matches!(
+        file_header::git::changed_files(root.path(), "base", "-p"),
+        Err(file_header::git::GitError::InvalidRef(r)) if r == "-p"
+    ));
+    assert!(!output.exists());
+}
+
+#[test]
+fn diff_results_reports_regressions_and_fixed_files() {
+    let previous = FileResults {
+        no_header_files: vec!["still_missing.rs".into(), "fixed.rs".into()],
+        binary_files: vec!["binary.dat".into()],
+        oversized_files: vec![],
+        partial_header_files: vec![],
+        drifted_header_files: vec![],
+        stopped_early: false,
+        symlinks: vec![],
+    };
+    let current = FileResults {
+        no_header_files: vec!["new.rs".into(), "still_missing.rs".into()],
+        binary_files: vec!["binary.dat".into()],
+        oversized_files: vec!["huge.rs".into()],
+        partial_header_files: vec!["partial.rs".into()],
+        drifted_header_files: vec![],
+        stopped_early: false,
+        symlinks: vec![],
+    };
+
+    let diff = current.diff(&previous);
+    assert!(diff.has_regression());
+    assert_eq!(
+        vec![
+            path::PathBuf::from("new.rs"),
+            path::PathBuf::from("partial.rs")
+        ],
+        diff.regressions
+    );
+    assert_eq!(vec![path::PathBuf::from("fixed.rs")], diff.fixed);
+
+    assert!(!previous.diff(&previous).has_regression());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn results_round_trip_through_serde() {
+    let results = FileResults {
+        no_header_files: vec!["a.rs".into()],
+        binary_files: vec!["b.dat".into()],
+        oversized_files: vec!["c.rs".into()],
+        partial_header_files: vec!["d.rs".into()],
+        drifted_header_files: vec![],
+        stopped_early: false,
+        symlinks: vec!["e.rs".into()],
+    };
+    let json = serde_json::to_string(&results).unwrap();
+    assert_eq!(results, serde_json::from_str::<FileResults>(&json).unwrap());
+}
+
+#[test]
+fn report_groups_results() {
+    let results = FileResults {
+        no_header_files: vec!["a.rs".into(), "b.rs".into()],
+        binary_files: vec!["c.dat".into()],
+        oversized_files: vec![],
+        partial_header_files: vec!["d.rs".into()],
+        drifted_header_files: vec![],
+        stopped_early: false,
+        symlinks: vec![],
+    };
+    assert_eq!(
+        "Missing headers (2):\n  a.rs\n  b.rs\n\n\
+        Partial headers (1):\n  d.rs\n\n\
+        Binary files (1):\n  c.dat\n\n\
+        Errors (1):\n  oops\n\n\
+        2 missing headers, 1 partial header, 1 binary file, 1 error\n",
+        file_header::report::Report::new(&results)
+            .error("oops")
+            .to_string()
+    );
+
+    let colored = file_header::report::Report::new(&results)
+        .colored(true)
+        .to_string();
+    assert!(colored.contains("\x1b[31ma.rs\x1b[0m"));
+
+    assert_eq!(
+        "All files have headers\n",
+        file_header::report::Report::new(&FileResults::default()).to_string()
+    );
+}
+
+#[test]
+fn results_map_to_exit_codes_and_summaries() {
+    let clean = FileResults {
+        oversized_files: vec!["huge.rs".into()],
+        ..FileResults::default()
+    };
+    let failed = FileResults {
+        no_header_files: vec!["a.rs".into()],
+        ..FileResults::default()
+    };
+    assert_eq!(0, clean.exit_code());
+    assert_eq!(1, failed.exit_code());
+    assert_eq!(
+        1,
+        FileResults::exit_code_of::<io::Error>(&Ok(failed.clone()))
+    );
+    assert_eq!(
+        2,
+        FileResults::exit_code_of(&Err(io::Error::new(io::ErrorKind::Other, "oops")))
+    );
+
+    assert_eq!(
+        "Missing headers (1):\n  a.rs\n\n1 missing header\n",
+        failed.render_summary()
+    );
+    assert_eq!(failed.render_summary(), failed.to_string());
+}
+
+#[test]
+fn headers_and_checkers_can_be_debugged_and_compared() {
+    let checker = SingleLineChecker::new("Foo License".to_string(), 10);
+    assert_eq!(
+        checker,
+        SingleLineChecker::new("Foo License".to_string(), 10)
+    );
+    assert_ne!(
+        checker,
+        SingleLineChecker::new("Foo License".to_string(), 5)
+    );
+
+    let header = Header::new(checker, "Copyright 2023 Foo Inc.\nFoo License".to_string());
+    let debug = format!("{:?}", header);
+    assert!(debug.contains("pattern: \"Foo License\""), "{}", debug);
+    assert!(
+        debug.contains("text: \"Copyright 2023 Foo Inc.\\nFoo License\""),
+        "{}",
+        debug
+    );
+}
+
+#[cfg(feature = "clap")]
+#[test]
+fn cli_args_parse_into_options_and_header() {
+    use clap::Parser;
+    use file_header::cli::*;
+
+    let root = tempfile::tempdir().unwrap();
+    fs::create_dir_all(root.path().join("src/generated")).unwrap();
+    for name in ["src/main.rs", "src/generated/gen.rs", "README.md"] {
+        fs::write(root.path().join(name), "no header\n").unwrap();
+    }
+    let args = CliArgs::parse_from([
+        "tool",
+        "add",
+        root.path().to_str().unwrap(),
+        "--glob",
+        "**/*.rs",
+        "--exclude",
+        "src/generated",
+        "--license",
+        "Apache-2.0 OR MIT",
+        "--owner",
+        "Foo Inc.",
+        "--year",
+        "2021",
+        "--threads",
+        "2",
+    ]);
+    assert_eq!(Mode::Add, args.mode);
+    assert_eq!(2, args.paths.threads);
+    assert_eq!(2021, args.license.year());
+    assert!(args.paths.is_included(path::Path::new("src/main.rs")));
+    assert!(!args.paths.is_included(path::Path::new("README.md")));
+
+    let results = add_headers_recursively_with_options(
+        &args.paths.root,
+        |_p| true,
+        args.license.header(),
+        &args.paths.recursive_options(RecursiveOptions::new()),
+    )
+    .unwrap();
+    assert_eq!(
+        vec![root.path().join("src/main.rs")],
+        results.modified_files
+    );
+    assert_eq!(
+        "// Copyright 2021 Foo Inc.\n// SPDX-License-Identifier: Apache-2.0 OR MIT\n\nno header\n",
+        fs::read_to_string(root.path().join("src/main.rs")).unwrap()
+    );
+
+    assert!(
+        CliArgs::try_parse_from(["tool", "check", "--owner", "Foo", "--license", "Nope-1.0"])
+            .is_err()
+    );
+    assert_eq!(
+        path::PathBuf::from("."),
+        CliArgs::parse_from(["tool", "check", "--owner", "Foo"])
+            .paths
+            .root
+    );
+}
+
+#[test]
+fn recursive_operations_report_progress() {
+    let root = tempfile::tempdir().unwrap();
+    for name in ["a.rs", "b.rs", "c.rs"] {
+        fs::write(root.path().join(name), "// no header\n").unwrap();
+    }
+    let found = sync::Arc::new(sync::atomic::AtomicUsize::new(0));
+    let processed = sync::Arc::new(sync::atomic::AtomicUsize::new(0));
+    let options = {
+        let found = found.clone();
+        let processed = processed.clone();
+        RecursiveOptions::new().progress(sync::Arc::new(move |event| match event {
+            ProgressEvent::Found(_) => {
+                found.fetch_add(1, sync::atomic::Ordering::SeqCst);
+            }
+            ProgressEvent::Processed(_) => {
+                processed.fetch_add(1, sync::atomic::Ordering::SeqCst);
+            }
+            ProgressEvent::SearchFinished => {}
+        }))
+    };
+
+    check_headers_recursively_with_options(root.path(), |_p| true, test_header(), 2, &options)
+        .unwrap();
+    assert_eq!(3, found.load(sync::atomic::Ordering::SeqCst));
+    assert_eq!(3, processed.load(sync::atomic::Ordering::SeqCst));
+
+    add_headers_recursively_with_options(root.path(), |_p| true, test_header(), &options).unwrap();
+    assert_eq!(6, found.load(sync::atomic::Ordering::SeqCst));
+    assert_eq!(6, processed.load(sync::atomic::Ordering::SeqCst));
+}
+
+#[test]
+fn recursive_checks_report_statistics() {
+    let root = tempfile::tempdir().unwrap();
+    for name in ["a.rs", "b.rs", "c.rs"] {
+        fs::write(root.path().join(name), "// no header\n").unwrap();
+    }
+    let reports = sync::Arc::new(sync::Mutex::new(Vec::new()));
+    let options = {
+        let reports = reports.clone();
+        RecursiveOptions::new().statistics(
+            std::time::Duration::from_millis(1),
+            sync::Arc::new(move |stats| reports.lock().unwrap().push(stats)),
+        )
+    };
+
+    for _ in 0..2 {
+        check_headers_recursively_with_options(root.path(), |_p| true, test_header(), 2, &options)
+            .unwrap();
+        // the final report is made when the check finishes
+        let last = reports.lock().unwrap().pop().unwrap();
+        assert_eq!(3, last.files_found);
+        assert_eq!(3, last.files_processed);
+        assert_eq!(3 * "// no header\n".len() as u64, last.bytes_read);
+        assert_eq!(0, last.queue_depth);
+        assert!(last.files_per_second() > 0.0);
+    }
+}
+
+#[cfg(feature = "indicatif")]
+#[test]
+fn progress_bar_tracks_files() {
+    let root = tempfile::tempdir().unwrap();
+    for name in ["a.rs", "b.rs"] {
+        fs::write(root.path().join(name), "// no header\n").unwrap();
+    }
+    let bar = indicatif::ProgressBar::hidden();
+    check_headers_recursively_with_options(
+        root.path(),
+        |_p| true,
+        test_header(),
+        2,
+        &RecursiveOptions::new().progress_bar(bar.clone()),
+    )
+    .unwrap();
+    assert_eq!(Some(2), bar.length());
+    assert_eq!(2, bar.position());
+}
+
+#[test]
+fn add_and_delete_recursively_consult_confirmation() {
+    let root = tempfile::tempdir().unwrap();
+    for name in ["skip.rs", "apply.rs"] {
+        fs::write(root.path().join(name), "// no header\n").unwrap();
+    }
+    fs::write(
+        root.path().join("present.rs"),
+        "// some license etc etc etc\n",
+    )
+    .unwrap();
+    let asked = sync::Arc::new(sync::Mutex::new(Vec::new()));
+    let options = {
+        let asked = asked.clone();
+        RecursiveOptions::new().confirm(sync::Arc::new(move |p, change| {
+            let name = p.file_name().unwrap().to_str().unwrap().to_string();
+            asked.lock().unwrap().push((name.clone(), *change));
+            if name == "skip.rs" {
+                Decision::Skip
+            } else {
+                Decision::Apply
+            }
+        }))
+    };
+
+    let results =
+        add_headers_recursively_with_options(root.path(), |_p| true, test_header(), &options)
+            .unwrap();
+    assert_eq!(vec![root.path().join("apply.rs")], results.modified_files);
+    let mut asked_names = asked.lock().unwrap().drain(..).collect::<Vec<_>>();
+    asked_names.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        vec![
+            ("apply.rs".to_string(), PlannedChange::AddHeader),
+            ("skip.rs".to_string(), PlannedChange::AddHeader)
+        ],
+        asked_names
+    );
+
+    let abort = RecursiveOptions::new().confirm(sync::Arc::new(|_p, _change| Decision::Abort));
+    match delete_headers_recursively_with_options(root.path(), |_p| true, test_header(), &abort) {
+        Err(DeleteHeadersRecursivelyError::Aborted(_)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert_eq!(
+        "// some license etc etc etc\n",
+        fs::read_to_string(root.path().join("present.rs")).unwrap()
+    );
+}
+
+#[test]
+fn journal_rolls_back_modifications() {
+    let root = tempfile::tempdir().unwrap();
+    let original = "// no header\n";
+    fs::write(root.path().join("a.rs"), original).unwrap();
+    fs::write(root.path().join("b.rs"), original).unwrap();
+    let journal = sync::Arc::new(file_header::journal::Journal::new());
+
+    add_headers_recursively_with_options(
+        root.path(),
+        |_p| true,
+        test_header(),
+        &RecursiveOptions::new().journal(journal.clone()),
+    )
+    .unwrap();
+    assert_eq!(2, journal.paths().len());
+
+    // save and reload, as would be done to roll back from a later process
+    let journal_dir = tempfile::tempdir().unwrap();
+    let journal_path = journal_dir.path().join("journal");
+    journal.save(&journal_path).unwrap();
+    let journal = file_header::journal::Journal::load(&journal_path).unwrap();
+
+    assert_eq!(2, file_header::journal::rollback(&journal).unwrap().len());
+    assert_eq!(
+        original,
+        fs::read_to_string(root.path().join("a.rs")).unwrap()
+    );
+    assert_eq!(
+        original,
+        fs::read_to_string(root.path().join("b.rs")).unwrap()
+    );
+    // rolling back again has no effect
+    assert!(file_header::journal::rollback(&journal).unwrap().is_empty());
+}
+
+#[test]
+fn journal_rollback_preserves_later_edits() {
+    let root = tempfile::tempdir().unwrap();
+    fs::write(root.path().join("a.rs"), "// no header\n").unwrap();
+    let journal = sync::Arc::new(file_header::journal::Journal::new());
+    add_headers_recursively_with_options(
+        root.path(),
+        |_p| true,
+        test_header(),
+        &RecursiveOptions::new().journal(journal.clone()),
+    )
+    .unwrap();
+
+    fs::write(root.path().join("a.rs"), "// edited\n").unwrap();
+    match file_header::journal::rollback(&journal) {
+        Err(file_header::journal::RollbackError::ChangedSinceModified(p)) => {
+            assert_eq!(root.path().join("a.rs"), p)
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert_eq!(
+        "// edited\n",
+        fs::read_to_string(root.path().join("a.rs")).unwrap()
+    );
+}
+
+#[test]
+fn transactional_add_leaves_files_untouched_on_error() {
+    let root = tempfile::tempdir().unwrap();
+    fs::write(root.path().join("a.rs"), "// no header\n").unwrap();
+    fs::write(root.path().join("b.rs"), "// no header\n").unwrap();
+    fs::write(root.path().join("c.unknown"), "no header\n").unwrap();
+    let options = RecursiveOptions::new().transactional(true);
+
+    match add_headers_recursively_with_options(root.path(), |_p| true, test_header(), &options) {
+        Err(AddHeadersRecursivelyError::UnrecognizedExtension(_)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    for name in ["a.rs", "b.rs"] {
+        assert_eq!(
+            "// no header\n",
+            fs::read_to_string(root.path().join(name)).unwrap()
+        );
+    }
+
+    // a checker that can't detect the header fails validation
+    let header = Header::new(
+        SingleLineChecker::new("not in the header".to_string(), 100),
+        "some license etc etc etc".to_string(),
+    );
+    match add_headers_recursively_with_options(
+        root.path(),
+        |p| p.extension().map(|ext| ext == "rs").unwrap_or(false),
+        header,
+        &options,
+    ) {
+        Err(AddHeadersRecursivelyError::ValidationFailed(_)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let results = add_headers_recursively_with_options(
+        root.path(),
+        |p| p.extension().map(|ext| ext == "rs").unwrap_or(false),
+        test_header(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(2, results.modified_files.len());
+    assert!(test_header()
+        .header_present(&mut fs::File::open(root.path().join("a.rs")).unwrap())
+        .unwrap());
+}
+
+#[test]
+fn add_recursively_adds_where_needed() {
+    let header = test_header();
+
+    let root = tempfile::tempdir().unwrap();
+
+    let no_header = root.path().join("no_header.rs");
+    fs::write(&no_header, "// no header\n").unwrap();
+
+    let with_header = root.path().join("with_header.rs");
+    let mut contents = "some license etc etc etc".to_string();
+    contents.push_str("\n// has a header\n");
+    fs::write(&with_header, &contents).unwrap();
+
+    // should not have header since it will fail the path predicate
+    let ignored = root.path().join("ignored.txt");
+    fs::write(&ignored, "// no header\n").unwrap();
+
+    assert_eq!(
+        vec![path::PathBuf::from("no_header.rs")],
+        add_headers_recursively(
+            root.path(),
+            |p| p.extension().map(|ext| ext == "rs").unwrap_or(false),
+            header
+        )
+        .map(|paths| paths
+            .iter()
+            .map(|p| p.strip_prefix(&root).unwrap().to_path_buf())
+            .collect::<Vec<_>>())
+        .unwrap()
+    );
+
+    assert_eq!(
+        "// some license etc etc etc\n\n// no header\n",
+        String::from_utf8(fs::read(&no_header).unwrap()).unwrap()
+    );
+}
+
+#[test]
+fn fix_recursively_adds_missing_headers_in_one_pass() {
+    let root = tempfile::tempdir().unwrap();
+    let no_header = root.path().join("no_header.rs");
+    fs::write(&no_header, "// no header\n").unwrap();
+    let with_header = root.path().join("with_header.rs");
+    fs::write(
+        &with_header,
+        "// some license etc etc etc\n\nfn main() {}\n",
+    )
+    .unwrap();
+    let binary = root.path().join("binary.rs");
+    fs::write(&binary, [0xff, 0xfe, 0x00]).unwrap();
+
+    let results = fix_headers_recursively(
+        root.path(),
+        |_p| true,
+        test_header(),
+        4,
+        &RecursiveOptions::new(),
+    )
+    .unwrap();
+    assert_eq!(vec![no_header.clone()], results.fixed_files);
+    assert_eq!(vec![with_header], results.compliant_files);
+    assert_eq!(vec![binary], results.binary_files);
+    assert!(results.has_failure());
+    assert_eq!(
+        "// some license etc etc etc\n\n// no header\n",
+        fs::read_to_string(&no_header).unwrap()
+    );
+
+    // a second pass finds nothing to fix
+    let results = fix_headers_recursively(
+        root.path(),
+        |_p| true,
+        test_header(),
+        4,
+        &RecursiveOptions::new().binary_files(BinaryFilePolicy::Skip),
+    )
+    .unwrap();
+    assert!(results.fixed_files.is_empty());
+    assert_eq!(2, results.compliant_files.len());
+    assert!(!results.has_failure());
+}
+
+#[test]
+fn add_recursively_verifies_round_trip() {
+    let root = tempfile::tempdir().unwrap();
+    let ok = root.path().join("ok.rs");
+    fs::write(&ok, "fn main() {}\n").unwrap();
+    // deleting the header would leave `<root/>` on its own line
+    let same_line = root.path().join("same_line.xml");
+    let same_line_contents = "<?xml version=\"1.0\"?><root/>\n";
+    fs::write(&same_line, same_line_contents).unwrap();
+
+    let options = RecursiveOptions::new().verify_round_trip(true);
+    let results =
+        add_headers_recursively_with_options(root.path(), |_p| true, test_header(), &options)
+            .unwrap();
+    assert_eq!(vec![ok.clone()], results.modified_files);
+    assert_eq!(vec![same_line.clone()], results.round_trip_failures);
+    assert_eq!(
+        "// some license etc etc etc\n\nfn main() {}\n",
+        fs::read_to_string(&ok).unwrap()
+    );
+    assert_eq!(same_line_contents, fs::read_to_string(&same_line).unwrap());
+
+    let results =
+        fix_headers_recursively(root.path(), |_p| true, test_header(), 4, &options).unwrap();
+    assert_eq!(vec![ok], results.compliant_files);
+    assert_eq!(vec![same_line.clone()], results.round_trip_failures);
+    assert!(results.has_failure());
+    assert_eq!(same_line_contents, fs::read_to_string(&same_line).unwrap());
+}
+
+#[test]
+fn recursive_operations_work_with_small_channel_capacities() {
+    let root = tempfile::tempdir().unwrap();
+    for i in 0..50 {
+        fs::write(root.path().join(format!("{}.rs", i)), "// no header\n").unwrap();
+    }
+    let options = RecursiveOptions::new().channel_capacities(1, 1);
+
+    // more results than fit in the channels, so the search, the threads, and the collection of
+    // results must all make progress together
+    let results =
+        check_headers_recursively_with_options(root.path(), |_p| true, test_header(), 4, &options)
+            .unwrap();
+    assert_eq!(50, results.no_header_files.len());
+
+    let results =
+        fix_headers_recursively(root.path(), |_p| true, test_header(), 4, &options).unwrap();
+    assert_eq!(50, results.fixed_files.len());
+}
+
+#[test]
+fn recursive_operations_report_panics_with_path() {
+    #[derive(Clone)]
+    struct PanickingChecker;
+
+    impl HeaderChecker for PanickingChecker {
+        fn check(&self, file: &mut impl io::Read) -> io::Result<bool> {
+            let mut contents = String::new();
+            file.read_to_string(&mut contents)?;
+            if contents.contains("boom") {
+                panic!("checker exploded");
+            }
+            Ok(contents.contains("some license etc etc etc"))
+        }
+    }
+
+    let root = tempfile::tempdir().unwrap();
+    fs::write(root.path().join("ok.rs"), "// no header\n").unwrap();
+    let boom = root.path().join("boom.rs");
+    fs::write(&boom, "// boom\n").unwrap();
+    let header = Header::new(PanickingChecker, "some license etc etc etc".to_string());
+
+    let unprocessed = vec![(boom.clone(), "checker exploded".to_string())];
+    // one thread, so that the file after the panic is handled by the same thread
+    match check_headers_recursively(root.path(), |_p| true, header.clone(), 1) {
+        Err(CheckHeadersRecursivelyError::Unprocessed(results, files)) => {
+            assert_eq!(vec![root.path().join("ok.rs")], results.no_header_files);
+            assert_eq!(unprocessed, files);
+        }
+        res => panic!("unexpected result: {:?}", res),
+    }
+    let options = RecursiveOptions::new().relative_paths(true);
+    let err = fix_headers_recursively(root.path(), |_p| true, header, 1, &options).unwrap_err();
+    assert_eq!(
+        "Failed to process \"boom.rs\" (checker exploded)",
+        err.to_string()
+    );
+    match err {
+        AddHeadersRecursivelyError::Unprocessed(results, files) => {
+            assert_eq!(vec![path::PathBuf::from("ok.rs")], results.fixed_files);
+            assert_eq!(
+                vec![(
+                    path::PathBuf::from("boom.rs"),
+                    "checker exploded".to_string()
+                )],
+                files
+            );
+        }
+        e => panic!("unexpected error: {:?}", e),
+    }
+}
+
+#[test]
+fn partial_operations_carry_on_past_errors() {
+    #[derive(Clone)]
+    struct FailingChecker;
+
+    impl HeaderChecker for FailingChecker {
+        fn check(&self, file: &mut impl io::Read) -> io::Result<bool> {
+            let mut contents = String::new();
+            file.read_to_string(&mut contents)?;
+            if contents.contains("bad") {
+                return Err(io::Error::new(io::ErrorKind::Other, "checker failed"));
+            }
+            Ok(contents.contains("some license etc etc etc"))
+        }
+    }
+
+    let root = tempfile::tempdir().unwrap();
+    for name in ["a.rs", "c.rs"] {
+        fs::write(root.path().join(name), "// no header\n").unwrap();
+    }
+    let bad = root.path().join("b.rs");
+    fs::write(&bad, "// bad\n").unwrap();
+    let header = Header::new(FailingChecker, "some license etc etc etc".to_string());
+    let options = RecursiveOptions::new();
+    let expected = vec![root.path().join("a.rs"), root.path().join("c.rs")];
+
+    assert!(check_headers_recursively(root.path(), |_p| true, header.clone(), 4).is_err());
+    let mut partial =
+        check_headers_recursively_partial(root.path(), |_p| true, header.clone(), 4, &options);
+    assert!(!partial.is_complete());
+    partial.results.no_header_files.sort();
+    assert_eq!(expected, partial.results.no_header_files);
+    match partial.errors.as_slice() {
+        [CheckHeadersRecursivelyError::IoError(p, _)] => assert_eq!(&bad, p),
+        errors => panic!("unexpected errors: {:?}", errors),
+    }
+
+    let mut partial = add_headers_recursively_partial(root.path(), |_p| true, header, &options);
+    partial.results.modified_files.sort();
+    assert_eq!(expected, partial.results.modified_files);
+    match partial.errors.as_slice() {
+        [AddHeadersRecursivelyError::IoError(p, _)] => assert_eq!(&bad, p),
+        errors => panic!("unexpected errors: {:?}", errors),
+    }
+    assert!(partial.into_result().is_err());
+}
+
+#[test]
+fn recursive_operations_in_multiple_roots() {
+    let root = tempfile::tempdir().unwrap();
+    for dir in ["src", "tools", "other"] {
+        fs::create_dir(root.path().join(dir)).unwrap();
+        fs::write(root.path().join(dir).join("main.rs"), "// no header\n").unwrap();
+    }
+    let src = root.path().join("src");
+    let tools = root.path().join("tools");
+    // nested roots don't cause files to be processed twice
+    let roots = [&src, &tools, &src];
+    let expected = vec![src.join("main.rs"), tools.join("main.rs")];
+
+    let results = check_headers_recursively_in_roots(
+        &roots,
+        |_p| true,
+        test_header(),
+        4,
+        &RecursiveOptions::new(),
+    )
+    .unwrap();
+    assert_eq!(expected, results.no_header_files);
+
+    let results = add_headers_recursively_in_roots(
+        &roots,
+        |_p| true,
+        test_header(),
+        &RecursiveOptions::new(),
+    )
+    .unwrap();
+    assert_eq!(expected, results.modified_files);
+    assert_eq!(
+        "// no header\n",
+        fs::read_to_string(root.path().join("other").join("main.rs")).unwrap()
+    );
+
+    let results = delete_headers_recursively_in_roots(
+        &roots,
+        |_p| true,
+        test_header(),
+        &RecursiveOptions::new(),
+    )
+    .unwrap();
+    assert_eq!(expected, results.modified_files);
+}
+
+#[test]
+fn recursive_operations_report_relative_paths() {
+    let root = tempfile::tempdir().unwrap();
+    fs::create_dir(root.path().join("src")).unwrap();
+    fs::write(root.path().join("src").join("main.rs"), "// no header\n").unwrap();
+    let options = RecursiveOptions::new().relative_paths(true);
+    let expected = vec![path::PathBuf::from("src").join("main.rs")];
+
+    let results =
+        check_headers_recursively_with_options(root.path(), |_p| true, test_header(), 4, &options)
+            .unwrap();
+    assert_eq!(expected, results.no_header_files);
+
+    let results =
+        add_headers_recursively_with_options(root.path(), |_p| true, test_header(), &options)
+            .unwrap();
+    assert_eq!(expected, results.modified_files);
+
+    // paths are relative to the root they were found in
+    let results = delete_headers_recursively_in_roots(
+        &[root.path().join("src")],
+        |_p| true,
+        test_header(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(vec![path::PathBuf::from("main.rs")], results.modified_files);
+}
+
+#[test]
+fn recursive_operations_share_non_clone_checker() {
+    /// Counts the files it checks, so it can't be cloned without losing count
+    struct CountingChecker {
+        inner: SingleLineChecker,
+        count: sync::Mutex<usize>,
+    }
+
+    impl HeaderChecker for CountingChecker {
+        fn check(&self, file: &mut impl io::Read) -> io::Result<bool> {
+            *self.count.lock().unwrap() += 1;
+            self.inner.check(file)
+        }
+    }
+
+    let root = tempfile::tempdir().unwrap();
+    for i in 0..10 {
+        fs::write(root.path().join(format!("{}.rs", i)), "fn main() {}\n").unwrap();
+    }
+    let header = Header::new(
+        CountingChecker {
+            inner: test_checker(),
+            count: sync::Mutex::new(0),
+        },
+        "some license etc etc etc".to_string(),
+    );
+
+    let results = check_headers_recursively(root.path(), |_p| true, header.clone(), 4).unwrap();
+    assert_eq!(10, results.no_header_files.len());
+    let checked = *header.checker().count.lock().unwrap();
+    assert_eq!(10, checked);
+}
+
+#[test]
+fn recursive_operations_accept_dyn_headers() {
+    #[derive(Clone)]
+    struct EmptyChecker;
+
+    impl HeaderChecker for EmptyChecker {
+        fn check(&self, file: &mut impl io::Read) -> io::Result<bool> {
+            Ok(file.read(&mut [0])? == 0)
+        }
+    }
+
+    let root = tempfile::tempdir().unwrap();
+    fs::write(root.path().join("empty.rs"), "").unwrap();
+    fs::write(root.path().join("main.rs"), "fn main() {}\n").unwrap();
+    let headers: Vec<(&str, DynHeader)> = vec![
+        ("license", test_header().into_dyn()),
+        (
+            "empty",
+            Header::new(EmptyChecker, "empty".to_string()).into_dyn(),
+        ),
+    ];
+
+    for (name, header) in headers {
+        let results = check_headers_recursively(root.path(), |_p| true, header.clone(), 2).unwrap();
+        let mut missing = results
+            .no_header_files
+            .iter()
+            .map(|p| p.file_name().unwrap().to_str().unwrap())
+            .collect::<Vec<_>>();
+        missing.sort();
+        match name {
+            "license" => assert_eq!(vec!["empty.rs", "main.rs"], missing),
+            _ => assert_eq!(vec!["main.rs"], missing),
+        }
+    }
+}
+
+#[test]
+fn delete_header_removes_span_reported_by_checker() {
+    /// Finds a leading `/* ... */` block mentioning a copyright, whatever its formatting
+    #[derive(Clone)]
+    struct BlockChecker;
+
+    impl HeaderChecker for BlockChecker {
+        fn check(&self, file: &mut impl io::Read) -> io::Result<bool> {
+            Ok(self.header_span(file)?.is_some())
+        }
+
+        fn header_span(&self, file: &mut impl io::Read) -> io::Result<Option<ops::Range<usize>>> {
+            let mut contents = String::new();
+            file.read_to_string(&mut contents)?;
+            let end = match contents.find("*/\n") {
+                Some(end) if contents.starts_with("/*") => end + 3,
+                _ => return Ok(None),
+            };
+            if !contents[..end].contains("Copyright") {
+                return Ok(None);
+            }
+            let blank = usize::from(contents[end..].starts_with('\n'));
+            Ok(Some(0..end + blank))
+        }
+    }
+
+    let third_party =
+        "/*****\n ** Copyright (c) Foo Inc.\n ** All rights reserved\n *****/\n\nint main() {}\n";
+    let header = Header::new(BlockChecker, "unused".to_string()).into_dyn();
+    let delimiters = HeaderDelimiters::for_path(path::Path::new("main.c")).unwrap();
+    assert_eq!(
+        Some("int main() {}\n".to_string()),
+        header
+            .delete_header_from_str(&delimiters, third_party)
+            .unwrap()
+    );
+
+    let dir = tempfile::tempdir().unwrap();
+    let p = dir.path().join("main.c");
+    fs::write(&p, format!("#!/usr/bin/tcc -run\n{}", third_party)).unwrap();
+    let header = Header::new(BlockChecker, "unused".to_string()).placement(Placement::AfterLine(1));
+    assert!(header.delete_header_if_present(&p).unwrap());
+    assert_eq!(
+        "#!/usr/bin/tcc -run\nint main() {}\n",
+        fs::read_to_string(&p).unwrap()
+    );
+    assert!(!header.delete_header_if_present(&p).unwrap());
+}
+
+#[test]
+fn check_recursively_detailed_reports_every_file() {
+    #[derive(Clone)]
+    struct FailingChecker;
+
+    impl HeaderChecker for FailingChecker {
+        fn check(&self, file: &mut impl io::Read) -> io::Result<bool> {
+            let mut contents = String::new();
+            file.read_to_string(&mut contents)?;
+            if contents.contains("unreadable") {
+                return Err(io::Error::new(io::ErrorKind::Other, "unreadable"));
+            }
+            Ok(contents.contains("some license etc etc etc"))
+        }
+    }
+
+    let root = tempfile::tempdir().unwrap();
+    fs::write(root.path().join("ok.rs"), "// some license etc etc etc\n").unwrap();
+    fs::write(root.path().join("missing.rs"), "// no header\n").unwrap();
+    fs::write(root.path().join("binary.rs"), [0xff, 0xfe, 0x00]).unwrap();
+    fs::write(root.path().join("error.rs"), "// unreadable\n").unwrap();
+    fs::write(root.path().join("huge.rs"), "// no header\n".repeat(100)).unwrap();
+    let header = Header::new(FailingChecker, "some license etc etc etc".to_string());
+
+    let statuses = check_headers_recursively_detailed(
+        root.path(),
+        |_p| true,
+        header,
+        4,
+        &RecursiveOptions::new()
+            .max_file_size(100)
+            .relative_paths(true),
+    )
+    .unwrap();
+    let statuses = statuses
+        .iter()
+        .map(|(p, status)| {
+            let status = match status {
+                FileStatus::Error(e) => format!("Error({})", e),
+                status => format!("{:?}", status),
+            };
+            (p.to_str().unwrap(), status)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            ("binary.rs", "Binary".to_string()),
+            ("error.rs", "Error(unreadable)".to_string()),
+            ("huge.rs", "Skipped".to_string()),
+            ("missing.rs", "MissingHeader".to_string()),
+            ("ok.rs", "Ok".to_string()),
+        ],
+        statuses
+    );
+
+    #[cfg(unix)]
+    {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(root.path().join("ok.rs"), "// some license etc etc etc\n").unwrap();
+        std::os::unix::fs::symlink(root.path().join("ok.rs"), root.path().join("link.rs")).unwrap();
+        let statuses = check_headers_recursively_detailed(
+            root.path(),
+            |_p| true,
+            test_header(),
+            2,
+            &RecursiveOptions::new()
+                .symlinks(SymlinkPolicy::Report)
+                .relative_paths(true),
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                ("link.rs", "Symlink".to_string()),
+                ("ok.rs", "Ok".to_string())
+            ],
+            statuses
+                .iter()
+                .map(|(p, status)| (p.to_str().unwrap(), format!("{:?}", status)))
+                .collect::<Vec<_>>()
+        );
+    }
+}
+
+#[test]
+fn strict_check_reports_partial_headers() {
+    let root = tempfile::tempdir().unwrap();
+    fs::write(
+        root.path().join("whole.rs"),
+        "// Copyright 2023 Foo Inc.\n// SPDX-License-Identifier: MIT\n\nfn main() {}\n",
+    )
+    .unwrap();
+    fs::write(
+        root.path().join("partial.rs"),
+        "// SPDX-License-Identifier: MIT\n\nfn main() {}\n",
+    )
+    .unwrap();
+    fs::write(root.path().join("missing.rs"), "fn main() {}\n").unwrap();
+    let header = Header::new(
+        SingleLineChecker::new("SPDX-License-Identifier: MIT".to_string(), 10),
+        "Copyright 2023 Foo Inc.\nSPDX-License-Identifier: MIT".to_string(),
+    );
+
+    let lenient = check_headers_recursively_with_options(
+        root.path(),
+        |_p| true,
+        header.clone(),
+        2,
+        &RecursiveOptions::new().relative_paths(true),
+    )
+    .unwrap();
+    assert_eq!(
+        vec![path::PathBuf::from("missing.rs")],
+        lenient.no_header_files
+    );
+    assert!(lenient.partial_header_files.is_empty());
+
+    let options = RecursiveOptions::new()
+        .relative_paths(true)
+        .strict_headers(true);
+    let strict =
+        check_headers_recursively_with_options(root.path(), |_p| true, header.clone(), 2, &options)
+            .unwrap();
+    assert_eq!(
+        vec![path::PathBuf::from("missing.rs")],
+        strict.no_header_files
+    );
+    assert_eq!(
+        vec![path::PathBuf::from("partial.rs")],
+        strict.partial_header_files
+    );
+    assert!(strict.has_failure());
+
+    let statuses =
+        check_headers_recursively_detailed(root.path(), |_p| true, header.clone(), 2, &options)
+            .unwrap();
+    assert!(matches!(
+        statuses[path::Path::new("partial.rs")],
+        FileStatus::PartialHeader
+    ));
+    assert!(matches!(
+        statuses[path::Path::new("whole.rs")],
+        FileStatus::Ok
+    ));
+
+    // fixing reports partial headers in strict mode too, rather than adding to them
+    let fixed = fix_headers_recursively(root.path(), |_p| true, header, 2, &options).unwrap();
+    assert_eq!(vec![path::PathBuf::from("missing.rs")], fixed.fixed_files);
+    assert_eq!(
+        vec![path::PathBuf::from("partial.rs")],
+        fixed.partial_header_files
+    );
+    assert_eq!(vec![path::PathBuf::from("whole.rs")], fixed.compliant_files);
+    assert!(fixed.has_failure());
+    assert_eq!(
+        "// SPDX-License-Identifier: MIT\n\nfn main() {}\n",
+        fs::read_to_string(root.path().join("partial.rs")).unwrap()
+    );
+}
+
+#[test]
+fn normalized_check_accepts_rewrapped_headers() {
+    let root = tempfile::tempdir().unwrap();
+    let rewrapped = "//   Licensed under the Foo License, Version 2.0 (the\n\
+        // \"License\");\n//\n//  you may not use this file except in compliance\n\
+        // with the License.\n\nfn main() {}\n";
+    fs::write(root.path().join("rewrapped.rs"), rewrapped).unwrap();
+    fs::write(
+        root.path().join("other.rs"),
+        "// Licensed under the Bar License.\n\nfn main() {}\n",
+    )
+    .unwrap();
+    let header = Header::new(
+        SingleLineChecker::new("Version 2.0 (the \"License\")".to_string(), 10),
+        "Licensed under the Foo License, Version 2.0 (the \"License\");\n\
+        you may not use this file except in compliance with the License."
+            .to_string(),
+    );
+    let options = RecursiveOptions::new().relative_paths(true);
+
+    let results =
+        check_headers_recursively_with_options(root.path(), |_p| true, header.clone(), 2, &options)
+            .unwrap();
+    assert_eq!(
+        vec![
+            path::PathBuf::from("other.rs"),
+            path::PathBuf::from("rewrapped.rs")
+        ],
+        {
+            let mut files = results.no_header_files;
+            files.sort();
+            files
+        }
+    );
+
+    let options = options.normalize_whitespace(true);
+    let results =
+        check_headers_recursively_with_options(root.path(), |_p| true, header.clone(), 2, &options)
+            .unwrap();
+    assert_eq!(
+        vec![path::PathBuf::from("other.rs")],
+        results.no_header_files
+    );
+
+    let strict = options.clone().strict_headers(true);
+    let results =
+        check_headers_recursively_with_options(root.path(), |_p| true, header.clone(), 2, &strict)
+            .unwrap();
+    assert_eq!(
+        vec![path::PathBuf::from("other.rs")],
+        results.no_header_files
+    );
+    assert!(results.partial_header_files.is_empty());
+
+    let results =
+        add_headers_recursively_with_options(root.path(), |_p| true, header, &options).unwrap();
+    assert_eq!(
+        vec![path::PathBuf::from("other.rs")],
+        results.modified_files
+    );
+    assert_eq!(
+        rewrapped,
+        fs::read_to_string(root.path().join("rewrapped.rs")).unwrap()
+    );
+}
+
+#[test]
+fn check_reports_drifted_headers_separately() {
+    let root = tempfile::tempdir().unwrap();
+    fs::write(
+        root.path().join("whole.rs"),
+        "// Licensed under the Foo License, Version 2.0;\n// see LICENSE for details.\n\nfn main() {}\n",
+    )
+    .unwrap();
+    fs::write(
+        root.path().join("drifted.rs"),
+        "// Licensed under Foo License 2.0;\n// see LICENSE.\n\nfn main() {}\n",
+    )
+    .unwrap();
+    fs::write(root.path().join("missing.rs"), "fn main() {}\n").unwrap();
+    let header = Header::new(
+        similarity::SimilarityChecker::new(
+            "Licensed under the Foo License, Version 2.0; see LICENSE for details.",
+            10,
+        ),
+        "Licensed under the Foo License, Version 2.0;\nsee LICENSE for details.".to_string(),
+    );
+
+    let results = check_headers_recursively_with_options(
+        root.path(),
+        |_p| true,
+        header.clone(),
+        2,
+        &RecursiveOptions::new().relative_paths(true),
+    )
+    .unwrap();
+    assert_eq!(
+        vec![path::PathBuf::from("missing.rs")],
+        results.no_header_files
+    );
+    assert_eq!(
+        vec![path::PathBuf::from("drifted.rs")],
+        results.drifted_header_files
+    );
+    assert!(results.has_failure());
+    assert!(results.render_summary().contains("1 drifted header"));
+
+    // the default checkers don't report drift
+    let exact = Header::new(
+        SingleLineChecker::new("Licensed under the Foo License".to_string(), 10),
+        header.text(None).into_owned(),
+    );
+    let results = check_headers_recursively(root.path(), |_p| true, exact, 2).unwrap();
+    assert_eq!(2, results.no_header_files.len());
+    assert!(results.drifted_header_files.is_empty());
+
+    // fixing leaves the drifted header to be rewritten, rather than adding a second header
+    let drifted = fs::read_to_string(root.path().join("drifted.rs")).unwrap();
+    let results = fix_headers_recursively(
+        root.path(),
+        |_p| true,
+        header.clone(),
+        2,
+        &RecursiveOptions::new().relative_paths(true),
+    )
+    .unwrap();
+    assert_eq!(vec![path::PathBuf::from("missing.rs")], results.fixed_files);
+    assert_eq!(
+        vec![path::PathBuf::from("drifted.rs")],
+        results.drifted_header_files
+    );
+    assert!(results.has_failure());
+    let fixed = fs::read_to_string(root.path().join("drifted.rs")).unwrap();
+    assert_eq!(drifted, fixed);
+    assert_eq!(1, fixed.matches("Licensed under").count());
+
+    // a drifted header is no substitute for the header
+    assert!(header
+        .add_header_if_missing(&root.path().join("drifted.rs"))
+        .unwrap());
+}
+
+#[test]
+fn check_matching_attributes_each_file_to_a_header() {
+    let root = tempfile::tempdir().unwrap();
+    fs::create_dir(root.path().join("third_party")).unwrap();
+    fs::write(
+        root.path().join("main.rs"),
+        "// Copyright 2023 Foo Inc.\n// SPDX-License-Identifier: Apache-2.0\n",
+    )
+    .unwrap();
+    fs::write(
+        root.path().join("third_party/gpl.rs"),
+        "// SPDX-License-Identifier: GPL-3.0-only\n",
+    )
+    .unwrap();
+    fs::write(root.path().join("missing.rs"), "fn main() {}\n").unwrap();
+    fs::write(root.path().join("image.png"), [0_u8, 1, 2]).unwrap();
+    fs::write(
+        root.path().join("image.png.license"),
+        "SPDX-License-Identifier: GPL-3.0-only\n",
+    )
+    .unwrap();
+    let headers = vec![
+        license::spdx::short_header_for_expression("Apache-2.0", 2023, "Foo Inc.").into_dyn(),
+        Header::new(
+            SingleLineChecker::new("SPDX-License-Identifier: GPL-3.0-only".to_string(), 10),
+            "SPDX-License-Identifier: GPL-3.0-only".to_string(),
+        )
+        .into_dyn(),
+    ];
+
+    let matches = check_headers_recursively_matching(
+        root.path(),
+        |_p| true,
+        &headers,
+        2,
+        &RecursiveOptions::new()
+            .relative_paths(true)
+            .binary_files(BinaryFilePolicy::Sidecar),
+    )
+    .unwrap();
+    assert_eq!(
+        vec![path::Path::new("main.rs")],
+        matches.files_matching(0).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec![
+            path::Path::new("image.png"),
+            path::Path::new("image.png.license"),
+            path::Path::new("third_party/gpl.rs")
+        ],
+        matches.files_matching(1).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec![path::PathBuf::from("missing.rs")],
+        matches.results.no_header_files
+    );
+    assert!(matches.results.binary_files.is_empty());
+}
 
+#[test]
+fn discover_finds_files_as_recursive_operations_would() {
     let root = tempfile::tempdir().unwrap();
+    fs::create_dir_all(root.path().join("src/generated")).unwrap();
+    fs::create_dir_all(root.path().join("target")).unwrap();
+    fs::write(root.path().join("src/main.rs"), "fn main() {}\n").unwrap();
+    fs::write(root.path().join("src/big.rs"), "x".repeat(100)).unwrap();
+    fs::write(root.path().join("src/notes.txt"), "notes\n").unwrap();
+    fs::write(root.path().join("src/generated/gen.rs"), "\n").unwrap();
+    fs::write(root.path().join("target/out.rs"), "\n").unwrap();
+    let options = RecursiveOptions::new()
+        .max_file_size(50)
+        .dir_filter(sync::Arc::new(|p| !p.ends_with("target")))
+        .file_filter(sync::Arc::new(|p, _metadata| {
+            !p.components().any(|c| c.as_os_str() == "generated")
+        }));
 
-    let no_header = root.path().join("no_header.rs");
-    fs::write(&no_header, "// no header\n").unwrap();
+    let mut found = discover(
+        root.path(),
+        |p| p.extension().map_or(false, |e| e == "rs"),
+        &options,
+    )
+    .collect::<Result<Vec<_>, _>>()
+    .unwrap();
+    found.sort();
+    assert_eq!(vec![root.path().join("src/main.rs")], found);
 
-    let with_header = root.path().join("with_header.rs");
-    let mut contents = "some license etc etc etc".to_string();
-    contents.push_str("\n// has a header\n");
-    fs::write(&with_header, &contents).unwrap();
+    assert!(
+        discover(&root.path().join("nonexistent"), |_p| true, &options)
+            .next()
+            .unwrap()
+            .is_err()
+    );
+}
 
-    // should not have header since it will fail the path predicate
-    let ignored = root.path().join("ignored.txt");
-    fs::write(&ignored, "// no header\n").unwrap();
+#[test]
+fn recursive_operations_process_paths_from_a_path_source() {
+    let root = tempfile::tempdir().unwrap();
+    fs::create_dir_all(root.path().join("src")).unwrap();
+    fs::write(root.path().join("src/listed.rs"), "fn main() {}\n").unwrap();
+    fs::write(root.path().join("src/listed.txt"), "notes\n").unwrap();
+    fs::write(root.path().join("src/unlisted.rs"), "fn main() {}\n").unwrap();
+    // e.g. from `git ls-files`
+    let listed = ["src/listed.rs", "src/listed.txt"]
+        .iter()
+        .map(|name| root.path().join(name))
+        .collect::<Vec<_>>();
+    let options = RecursiveOptions::new().paths(listed.clone());
+    let is_rust = |p: &path::Path| p.extension().map_or(false, |e| e == "rs");
 
+    let results =
+        check_headers_recursively_with_options(root.path(), is_rust, test_header(), 2, &options)
+            .unwrap();
     assert_eq!(
-        vec![path::PathBuf::from("no_header.rs")],
-        add_headers_recursively(
-            root.path(),
-            |p| p.extension().map(|ext| ext == "rs").unwrap_or(false),
-            header
-        )
-        .map(|paths| paths
+        vec![root.path().join("src/listed.rs")],
+        results.no_header_files
+    );
+    assert_eq!(
+        vec![root.path().join("src/listed.rs")],
+        discover(root.path(), is_rust, &options)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    );
+
+    add_headers_recursively_with_options(root.path(), is_rust, test_header(), &options).unwrap();
+    assert!(test_header()
+        .header_present(&mut fs::File::open(root.path().join("src/listed.rs")).unwrap())
+        .unwrap());
+    assert!(!test_header()
+        .header_present(&mut fs::File::open(root.path().join("src/unlisted.rs")).unwrap())
+        .unwrap());
+
+    // listed paths that don't exist are errors
+    let options = RecursiveOptions::new().paths(vec![root.path().join("src/nonexistent.rs")]);
+    assert!(check_headers_recursively_with_options(
+        root.path(),
+        is_rust,
+        test_header(),
+        2,
+        &options
+    )
+    .is_err());
+}
+
+#[test]
+fn gitattributes_linguist_marked_files_are_skipped() {
+    let root = tempfile::tempdir().unwrap();
+    for dir in ["src", "src/gen", "third_party/lib"] {
+        fs::create_dir_all(root.path().join(dir)).unwrap();
+    }
+    fs::write(
+        root.path().join(".gitattributes"),
+        "# not authored here\n*.pb.rs linguist-generated\nthird_party/** linguist-vendored=true\n\
+         *.rs text eol=lf\n",
+    )
+    .unwrap();
+    fs::write(
+        root.path().join("src/gen/.gitattributes"),
+        "* linguist-generated\nkeep.rs -linguist-generated\n",
+    )
+    .unwrap();
+    for file in [
+        "src/main.rs",
+        "src/api.pb.rs",
+        "src/gen/out.rs",
+        "src/gen/keep.rs",
+        "third_party/lib/lib.rs",
+    ] {
+        fs::write(root.path().join(file), "fn main() {}\n").unwrap();
+    }
+    let is_rust = |p: &path::Path| p.extension().map_or(false, |e| e == "rs");
+
+    let options = RecursiveOptions::new()
+        .respect_gitattributes(true)
+        .relative_paths(true);
+    let mut results =
+        check_headers_recursively_with_options(root.path(), is_rust, test_header(), 2, &options)
+            .unwrap();
+    results.no_header_files.sort();
+    assert_eq!(
+        vec![
+            path::PathBuf::from("src/gen/keep.rs"),
+            path::PathBuf::from("src/main.rs")
+        ],
+        results.no_header_files
+    );
+
+    let results = check_headers_recursively_with_options(
+        root.path(),
+        is_rust,
+        test_header(),
+        2,
+        &RecursiveOptions::new(),
+    )
+    .unwrap();
+    assert_eq!(5, results.no_header_files.len());
+}
+
+#[test]
+fn exclusions_skip_literal_paths_and_prefixes() {
+    let root = tempfile::tempdir().unwrap();
+    fs::create_dir_all(root.path().join("src/gen")).unwrap();
+    for file in [
+        "src/main.rs",
+        "src/lib.rs",
+        "src/gen/api.rs",
+        "src/generated.rs",
+    ] {
+        fs::write(root.path().join(file), "fn main() {}\n").unwrap();
+    }
+    let exclusions = PathExclusions::new()
+        .path("./src/lib.rs")
+        .prefix("src/gen")
+        .path(root.path().join("src/main.rs"));
+    assert!(!exclusions.excludes(path::Path::new("src/generated.rs")));
+
+    let results = check_headers_recursively_with_options(
+        root.path(),
+        |_p| true,
+        test_header(),
+        2,
+        &RecursiveOptions::new()
+            .exclusions(exclusions)
+            .relative_paths(true),
+    )
+    .unwrap();
+    assert_eq!(
+        vec![path::PathBuf::from("src/generated.rs")],
+        results.no_header_files
+    );
+}
+
+#[test]
+fn audit_reports_each_rule_in_one_pass() {
+    let root = tempfile::tempdir().unwrap();
+    let files = [
+        (
+            "ok.rs",
+            "// Copyright 2025 Foo Inc.\n// SPDX-License-Identifier: MIT\n",
+        ),
+        (
+            "late.rs",
+            "fn a() {}\n\n\n// Copyright 2025 Foo Inc.\n// SPDX-License-Identifier: MIT\n",
+        ),
+        (
+            "stale.rs",
+            "// Copyright 2021 Foo Inc.\n// SPDX-License-Identifier: MIT\n",
+        ),
+        (
+            "other.rs",
+            "// Copyright 2025 Bar Inc.\n// SPDX-License-Identifier: Nonsense-1.0\n",
+        ),
+        ("none.rs", "fn main() {}\n"),
+    ];
+    for (name, contents) in files {
+        fs::write(root.path().join(name), contents).unwrap();
+    }
+    fs::write(root.path().join("image.png"), [0xff, 0xfe, 0x00]).unwrap();
+    let rules = audit::AuditRules::new(Header::new(
+        SingleLineChecker::new("SPDX-License-Identifier:".to_string(), 10),
+        "Copyright 2025 Foo Inc.\nSPDX-License-Identifier: MIT".to_string(),
+    ))
+    .within_lines(3)
+    .year_policy(copyright::YearPolicyChecker::includes_year(2025))
+    .holder("Foo Inc.")
+    .identifiers(license::spdx::identifier::IdentifierValidator::new());
+
+    let mut results = audit::audit_headers_recursively(
+        root.path(),
+        |_p| true,
+        &rules,
+        &RecursiveOptions::new().relative_paths(true),
+    )
+    .unwrap();
+    results.year_violation_files.sort();
+    results.wrong_holder_files.sort();
+    results
+        .invalid_identifier_files
+        .sort_by(|a, b| a.0.cmp(&b.0));
+    let paths = |names: &[&str]| names.iter().map(path::PathBuf::from).collect::<Vec<_>>();
+    assert!(results.has_failure());
+    assert_eq!(paths(&["none.rs"]), results.missing_header_files);
+    assert_eq!(paths(&["late.rs"]), results.misplaced_header_files);
+    assert_eq!(
+        paths(&["none.rs", "stale.rs"]),
+        results.year_violation_files
+    );
+    assert_eq!(paths(&["none.rs", "other.rs"]), results.wrong_holder_files);
+    assert_eq!(
+        vec![
+            path::PathBuf::from("none.rs"),
+            path::PathBuf::from("other.rs")
+        ],
+        results
+            .invalid_identifier_files
             .iter()
-            .map(|p| p.strip_prefix(&root).unwrap().to_path_buf())
-            .collect::<Vec<_>>())
-        .unwrap()
+            .map(|(p, _)| p.clone())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(paths(&["image.png"]), results.binary_files);
+}
+
+#[test]
+fn scan_identifiers_groups_files_by_expression() {
+    use file_header::license::spdx::identifier::*;
+
+    let root = tempfile::tempdir().unwrap();
+    fs::create_dir(root.path().join("sub")).unwrap();
+    for (name, contents) in [
+        (
+            "a.rs",
+            "// SPDX-License-Identifier: MIT
+",
+        ),
+        (
+            "sub/b.c",
+            "/* SPDX-License-Identifier: MIT */
+",
+        ),
+        (
+            "c.py",
+            "#!/usr/bin/env python
+# SPDX-License-Identifier: Apache-2.0 OR MIT
+",
+        ),
+        (
+            "d.rs",
+            "// SPDX-License-Identifier: Nonsense-1.0
+",
+        ),
+        (
+            "none.rs",
+            "fn main() {}
+",
+        ),
+    ] {
+        fs::write(root.path().join(name), contents).unwrap();
+    }
+    fs::write(root.path().join("image.png"), [0xff, 0xfe, 0x00]).unwrap();
+
+    let mut scan = scan_identifiers_recursively(
+        root.path(),
+        |_p| true,
+        &RecursiveOptions::new().relative_paths(true),
+    )
+    .unwrap();
+    scan.identifiers.values_mut().for_each(|paths| paths.sort());
+    let paths = |names: &[&str]| names.iter().map(path::PathBuf::from).collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            ("Apache-2.0 OR MIT", paths(&["c.py"])),
+            ("MIT", paths(&["a.rs", "sub/b.c"])),
+            ("Nonsense-1.0", paths(&["d.rs"])),
+        ],
+        scan.identifiers
+            .iter()
+            .map(|(expression, paths)| (expression.as_str(), paths.clone()))
+            .collect::<Vec<_>>()
     );
+    assert_eq!(paths(&["none.rs"]), scan.missing_identifier_files);
+    assert_eq!(paths(&["image.png"]), scan.binary_files);
 
     assert_eq!(
-        "// some license etc etc etc\n\n// no header\n",
-        String::from_utf8(fs::read(&no_header).unwrap()).unwrap()
+        Some("MIT".to_string()),
+        find_expression(&mut "\n// SPDX-License-Identifier: MIT\n".as_bytes(), 2).unwrap()
+    );
+    assert_eq!(
+        None,
+        find_expression(&mut "\n// SPDX-License-Identifier: MIT\n".as_bytes(), 1).unwrap()
+    );
+}
+
+#[test]
+fn lint_recursively_reports_malformed_headers() {
+    let root = tempfile::tempdir().unwrap();
+    fs::write(
+        root.path().join("ok.rs"),
+        "// Copyright 2021 Foo Inc.\n// SPDX-License-Identifier: MIT\n\nfn main() {}\n",
+    )
+    .unwrap();
+    fs::write(
+        root.path().join("reordered.rs"),
+        "// SPDX-License-Identifier: MIT\n// Copyright 2021 Foo Inc.\n\nfn main() {}\n",
+    )
+    .unwrap();
+    fs::write(
+        root.path().join("extra.c"),
+        "/*\n * Copyright 2019, 2021-2023 Foo Inc.\n * All rights reserved.\n * SPDX-License-Identifier: MIT\n */\n",
+    )
+    .unwrap();
+    fs::write(
+        root.path().join("missing.rs"),
+        "// SPDX-License-Identifier: MIT\n\nfn main() {}\n",
+    )
+    .unwrap();
+    fs::write(root.path().join("no_header.rs"), "fn main() {}\n").unwrap();
+    let header = Header::new(
+        SingleLineChecker::new("SPDX-License-Identifier: MIT".to_string(), 10),
+        "Copyright 2023 Foo Inc.\nSPDX-License-Identifier: MIT".to_string(),
+    );
+    let lint = lint::HeaderLint::new("Copyright {year} Foo Inc.\nSPDX-License-Identifier: MIT");
+
+    let mut results = lint::lint_headers_recursively(
+        root.path(),
+        |_p| true,
+        header,
+        &lint,
+        &RecursiveOptions::new().relative_paths(true),
+    )
+    .unwrap();
+    results.malformed_files.sort_by(|a, b| a.0.cmp(&b.0));
+    assert!(results.has_failure());
+    assert_eq!(
+        vec![
+            (
+                path::PathBuf::from("extra.c"),
+                vec![lint::LintIssue::UnexpectedLine(
+                    "All rights reserved.".to_string()
+                )]
+            ),
+            (
+                path::PathBuf::from("missing.rs"),
+                vec![lint::LintIssue::MissingLine(
+                    "Copyright {year} Foo Inc.".to_string()
+                )]
+            ),
+            (
+                path::PathBuf::from("reordered.rs"),
+                vec![lint::LintIssue::OutOfOrder(
+                    "Copyright 2021 Foo Inc.".to_string()
+                )]
+            ),
+        ],
+        results.malformed_files
+    );
+    results.diffs.sort();
+    assert_eq!(
+        vec![
+            (
+                path::PathBuf::from("extra.c"),
+                "--- expected\n+++ actual\n /*\n  * Copyright 2019, 2021-2023 Foo Inc.\n+ * All rights reserved.\n  * SPDX-License-Identifier: MIT\n  */\n"
+                    .to_string()
+            ),
+            (
+                path::PathBuf::from("missing.rs"),
+                "--- expected\n+++ actual\n-// Copyright {year} Foo Inc.\n // SPDX-License-Identifier: MIT\n"
+                    .to_string()
+            ),
+            (
+                path::PathBuf::from("reordered.rs"),
+                "--- expected\n+++ actual\n-// Copyright 2021 Foo Inc.\n // SPDX-License-Identifier: MIT\n+// Copyright 2021 Foo Inc.\n"
+                    .to_string()
+            ),
+        ],
+        results.diffs
+    );
+}
+
+#[test]
+fn fix_recursively_rewrites_malformed_headers() {
+    let root = tempfile::tempdir().unwrap();
+    fs::write(
+        root.path().join("ok.rs"),
+        "// Copyright 2021 Foo Inc.\n// SPDX-License-Identifier: MIT\n\nfn main() {}\n",
+    )
+    .unwrap();
+    fs::write(
+        root.path().join("reordered.rs"),
+        "#![allow(unused)]\r\n// SPDX-License-Identifier: MIT\r\n// Copyright 2019, 2021 Foo Inc.\r\n\r\nfn main() {}\r\n",
+    )
+    .unwrap();
+    fs::write(
+        root.path().join("extra.c"),
+        "/*\n * Copyright 2022 Foo Inc.\n * All rights reserved.\n * SPDX-License-Identifier: MIT\n */\nint main() {}\n",
+    )
+    .unwrap();
+    fs::write(
+        root.path().join("line_comments.h"),
+        "// SPDX-License-Identifier: MIT\n// Copyright 2020 Foo Inc.\n\nint main();\n",
+    )
+    .unwrap();
+    fs::write(
+        root.path().join("no_year.rs"),
+        "// SPDX-License-Identifier: MIT\n\nfn main() {}\n",
+    )
+    .unwrap();
+    let header = Header::new(
+        SingleLineChecker::new("SPDX-License-Identifier: MIT".to_string(), 10),
+        "Copyright 2023 Foo Inc.\nSPDX-License-Identifier: MIT".to_string(),
+    );
+    let lint = lint::HeaderLint::new("Copyright {year} Foo Inc.\nSPDX-License-Identifier: MIT");
+
+    let mut results = lint::fix_malformed_headers_recursively(
+        root.path(),
+        |_p| true,
+        header,
+        &lint,
+        &RecursiveOptions::new().relative_paths(true),
+    )
+    .unwrap();
+    results.modified_files.sort();
+    assert_eq!(
+        vec![
+            path::PathBuf::from("extra.c"),
+            path::PathBuf::from("line_comments.h"),
+            path::PathBuf::from("reordered.rs")
+        ],
+        results.modified_files
+    );
+    assert_eq!(
+        "#![allow(unused)]\r\n// Copyright 2019, 2021 Foo Inc.\r\n// SPDX-License-Identifier: MIT\r\n\r\nfn main() {}\r\n",
+        fs::read_to_string(root.path().join("reordered.rs")).unwrap()
+    );
+    assert_eq!(
+        "/*\n * Copyright 2022 Foo Inc.\n * SPDX-License-Identifier: MIT\n */\nint main() {}\n",
+        fs::read_to_string(root.path().join("extra.c")).unwrap()
+    );
+    assert_eq!(
+        "// SPDX-License-Identifier: MIT\n\nfn main() {}\n",
+        fs::read_to_string(root.path().join("no_year.rs")).unwrap()
+    );
+    // the existing comment style is kept, even though `.h` files use `/* */` comments
+    assert_eq!(
+        "// Copyright 2020 Foo Inc.\n// SPDX-License-Identifier: MIT\n\nint main();\n",
+        fs::read_to_string(root.path().join("line_comments.h")).unwrap()
+    );
+}
+
+#[test]
+fn report_comment_styles_recursively_counts_by_extension() {
+    let root = tempfile::tempdir().unwrap();
+    fs::write(
+        root.path().join("block.h"),
+        "/*\n * Copyright 2021 Foo Inc.\n */\nint main();\n",
+    )
+    .unwrap();
+    fs::write(
+        root.path().join("line.h"),
+        "// Copyright 2021 Foo Inc.\nint main();\n",
+    )
+    .unwrap();
+    fs::write(root.path().join("none.h"), "int main();\n").unwrap();
+    fs::write(
+        root.path().join("main.RS"),
+        "// Copyright 2021 Foo Inc.\nfn main() {}\n",
+    )
+    .unwrap();
+    fs::write(root.path().join("unknown.txt"), "Copyright 2021 Foo Inc.\n").unwrap();
+    let lint = lint::HeaderLint::new("Copyright {year} Foo Inc.");
+
+    let report = lint::report_comment_styles_recursively(
+        root.path(),
+        |_p| true,
+        test_header(),
+        &lint,
+        &RecursiveOptions::new().relative_paths(true),
+    )
+    .unwrap();
+    assert_eq!(
+        Some(&lint::CommentStyle::Line),
+        report.files.get(path::Path::new("line.h"))
+    );
+    assert_eq!(4, report.files.len());
+    let by_extension = report
+        .by_extension()
+        .into_iter()
+        .map(|(ext, counts)| (ext, counts.into_iter().collect::<Vec<_>>()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            (
+                "h".to_string(),
+                vec![
+                    (lint::CommentStyle::Block, 1),
+                    (lint::CommentStyle::Line, 1),
+                    (lint::CommentStyle::NoHeader, 1)
+                ]
+            ),
+            ("rs".to_string(), vec![(lint::CommentStyle::Line, 1)]),
+        ],
+        by_extension
+    );
+}
+
+#[test]
+fn recursive_operations_use_configured_file_system() {
+    let fs = sync::Arc::new(filesystem::MemoryFileSystem::new());
+    fs.insert("root/no_header.rs", "// no header\n");
+    fs.insert(
+        "root/with_header.rs",
+        "// some license etc etc etc\n\n// has a header\n",
+    );
+    fs.insert("elsewhere/no_header.rs", "// no header\n");
+    let options = RecursiveOptions::new().file_system(fs.clone());
+
+    let results = check_headers_recursively_with_options(
+        path::Path::new("root"),
+        |_p| true,
+        test_header(),
+        4,
+        &options,
+    )
+    .unwrap();
+    assert_eq!(
+        vec![path::PathBuf::from("root/no_header.rs")],
+        results.no_header_files
+    );
+
+    let results = add_headers_recursively_with_options(
+        path::Path::new("root"),
+        |_p| true,
+        test_header(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(
+        vec![path::PathBuf::from("root/no_header.rs")],
+        results.modified_files
+    );
+    assert_eq!(
+        b"// some license etc etc etc\n\n// no header\n".to_vec(),
+        fs.get("root/no_header.rs").unwrap()
+    );
+    assert_eq!(
+        b"// no header\n".to_vec(),
+        fs.get("elsewhere/no_header.rs").unwrap()
+    );
+}
+
+#[test]
+fn add_and_delete_header_open_each_file_once() {
+    /// Counts the files opened
+    struct CountingFileSystem {
+        inner: filesystem::MemoryFileSystem,
+        opens: sync::atomic::AtomicUsize,
+    }
+
+    impl filesystem::FileSystem for CountingFileSystem {
+        fn open(&self, p: &path::Path) -> io::Result<Box<dyn io::Read + Send + '_>> {
+            self.opens.fetch_add(1, sync::atomic::Ordering::SeqCst);
+            self.inner.open(p)
+        }
+
+        fn file_size(&self, p: &path::Path) -> io::Result<u64> {
+            self.inner.file_size(p)
+        }
+
+        fn replace(&self, p: &path::Path, head: &[u8], rest: &mut dyn io::Read) -> io::Result<()> {
+            self.inner.replace(p, head, rest)
+        }
+
+        fn walk<'a>(
+            &'a self,
+            root: &path::Path,
+            options: filesystem::WalkOptions<'a>,
+        ) -> Box<dyn Iterator<Item = Result<filesystem::WalkEntry, filesystem::WalkError>> + 'a>
+        {
+            self.inner.walk(root, options)
+        }
+    }
+
+    let fs = CountingFileSystem {
+        inner: filesystem::MemoryFileSystem::new(),
+        opens: sync::atomic::AtomicUsize::new(0),
+    };
+    let p = path::Path::new("main.sh");
+    fs.inner.insert(p, "#!/bin/sh\necho hi\n");
+    let header = test_header();
+
+    assert!(header.add_header_if_missing_with_fs(&fs, p).unwrap());
+    assert_eq!(1, fs.opens.load(sync::atomic::Ordering::SeqCst));
+    assert_eq!(
+        b"#!/bin/sh\n# some license etc etc etc\n\necho hi\n".to_vec(),
+        fs.inner.get(p).unwrap()
     );
+
+    assert!(header.delete_header_if_present_with_fs(&fs, p).unwrap());
+    assert_eq!(2, fs.opens.load(sync::atomic::Ordering::SeqCst));
+    assert_eq!(b"#!/bin/sh\necho hi\n".to_vec(), fs.inner.get(p).unwrap());
+}
+
+#[test]
+fn add_and_delete_header_leave_files_changed_during_the_rewrite() {
+    /// Edits each file just before it's replaced, as another process might
+    struct EditingFileSystem {
+        inner: filesystem::MemoryFileSystem,
+        edit: &'static str,
+    }
+
+    impl filesystem::FileSystem for EditingFileSystem {
+        fn open(&self, p: &path::Path) -> io::Result<Box<dyn io::Read + Send + '_>> {
+            self.inner.open(p)
+        }
+
+        fn file_size(&self, p: &path::Path) -> io::Result<u64> {
+            self.inner.file_size(p)
+        }
+
+        fn replace(&self, p: &path::Path, head: &[u8], rest: &mut dyn io::Read) -> io::Result<()> {
+            self.inner.insert(p, self.edit);
+            self.inner.replace(p, head, rest)
+        }
+
+        fn walk<'a>(
+            &'a self,
+            root: &path::Path,
+            options: filesystem::WalkOptions<'a>,
+        ) -> Box<dyn Iterator<Item = Result<filesystem::WalkEntry, filesystem::WalkError>> + 'a>
+        {
+            self.inner.walk(root, options)
+        }
+    }
+
+    let fs = EditingFileSystem {
+        inner: filesystem::MemoryFileSystem::new(),
+        edit: "fn main() { edited(); }\n",
+    };
+    let p = path::Path::new("main.rs");
+    fs.inner.insert(p, "fn main() {}\n");
+    match test_header().add_header_if_missing_with_fs(&fs, p) {
+        Err(AddHeaderError::ConcurrentModification(changed)) => assert_eq!(p, changed),
+        res => panic!("unexpected result: {:?}", res),
+    }
+    assert_eq!(fs.edit.as_bytes().to_vec(), fs.inner.get(p).unwrap());
+
+    fs.inner
+        .insert(p, "// some license etc etc etc\n\nfn main() {}\n");
+    match test_header().delete_header_if_present_with_fs(&fs, p) {
+        Err(DeleteHeaderError::ConcurrentModification(changed)) => assert_eq!(p, changed),
+        res => panic!("unexpected result: {:?}", res),
+    }
+    assert_eq!(fs.edit.as_bytes().to_vec(), fs.inner.get(p).unwrap());
 }
 
 #[test]
@@ -371,6 +4135,179 @@ fn delete_recursively() {
     );
 }
 
+#[test]
+fn addlicense_config_from_args() {
+    let dir = tempfile::tempdir().unwrap();
+    let template = dir.path().join("template.txt");
+    fs::write(&template, "{{ .Holder }} owns this as of {{.Year}}\n").unwrap();
+    let config = compat::addlicense::AddlicenseConfig::from_args([
+        "-c=Foo Inc.",
+        "-y",
+        "2020",
+        "-f",
+        template.to_str().unwrap(),
+        "-ignore",
+        "**/*.pb.go",
+        "-ignore=third_party/**",
+        "-check",
+        "src",
+        "lib",
+    ])
+    .unwrap();
+    assert!(config.check);
+    assert_eq!(
+        vec![path::PathBuf::from("src"), path::PathBuf::from("lib")],
+        config.paths
+    );
+    assert_eq!(
+        "Foo Inc. owns this as of 2020",
+        config.header_text().unwrap()
+    );
+    assert!(config.is_ignored(path::Path::new("foo.pb.go")));
+    assert!(config.is_ignored(path::Path::new("./a/b/foo.pb.go")));
+    assert!(config.is_ignored(path::Path::new("third_party/x/y.rs")));
+    assert!(!config.is_ignored(path::Path::new("src/foo.go")));
+
+    let header = config.header().unwrap();
+    assert!(header
+        .check_str("/* SPDX-License-Identifier: MIT */\n")
+        .unwrap());
+    assert!(!header.check_str("fn main() {}\n").unwrap());
+
+    assert!(matches!(
+        compat::addlicense::AddlicenseConfig::from_args(["-x"]),
+        Err(compat::addlicense::AddlicenseError::UnknownFlag(f)) if f == "x"
+    ));
+    assert!(matches!(
+        compat::addlicense::AddlicenseConfig::from_args(["-l", "gpl"])
+            .unwrap()
+            .header_text(),
+        Err(compat::addlicense::AddlicenseError::UnknownLicense(_))
+    ));
+}
+
+#[test]
+fn license_eye_config_round_trips() {
+    let yaml = r#"
+# license-eye configuration
+header:
+  license:
+    spdx-id: Apache-2.0
+    copyright-owner: "Foo Inc."
+    copyright-year: '2022'
+    content: >-
+      Copyright [year] [owner]
+      Licensed under the Foo License.
+
+      All rights reserved.
+  paths:
+  - 'src/**'
+  - 'Dockerfile'
+  paths-ignore: ['src/generated', '**/*.json'] # generated code
+  comment: on-failure
+  language:
+    Lua:
+      extensions:
+        - ".lua"
+      comment_style_id: DoubleDash
+    Docker:
+      filenames: [Dockerfile]
+      comment_style_id: Hashtag
+dependency:
+  files:
+    - go.mod
+"#;
+    let config = compat::license_eye::LicenseEyeConfig::parse(yaml).unwrap();
+    assert_eq!(Some("Foo Inc."), config.copyright_owner.as_deref());
+    assert_eq!(
+        "Copyright 2022 Foo Inc. Licensed under the Foo License.\nAll rights reserved.",
+        config.header_text().unwrap()
+    );
+    assert!(config.is_included(path::Path::new("src/main.rs")));
+    assert!(config.is_included(path::Path::new("Dockerfile")));
+    assert!(!config.is_included(path::Path::new("README.md")));
+    assert!(!config.is_included(path::Path::new("src/generated/foo.rs")));
+    assert!(!config.is_included(path::Path::new("src/data.json")));
+    assert_eq!(
+        Some(HeaderDelimiters::new("", "-- ", "")),
+        config.delimiters_for_path(path::Path::new("src/init.lua"))
+    );
+    assert_eq!(
+        Some(HeaderDelimiters::new("", "# ", "")),
+        config.delimiters_for_path(path::Path::new("Dockerfile"))
+    );
+    assert_eq!(
+        HeaderDelimiters::for_path(path::Path::new("main.rs")),
+        config.delimiters_for_path(path::Path::new("main.rs"))
+    );
+
+    let header = config.header().unwrap();
+    assert!(header
+        .check_str(
+            "/*\n * Copyright 2019-2021 Foo Inc. Licensed under\n * the Foo License.\n *\n * All rights reserved.\n */\n"
+        )
+        .unwrap());
+    assert!(!header
+        .check_str("// Copyright 2019 Bar Inc. Licensed under the Foo License.\n")
+        .unwrap());
+
+    let dir = tempfile::tempdir().unwrap();
+    let saved = dir.path().join(".licenserc.yaml");
+    config.save(&saved).unwrap();
+    assert_eq!(
+        config,
+        compat::license_eye::LicenseEyeConfig::load(&saved).unwrap()
+    );
+
+    assert!(matches!(
+        compat::license_eye::LicenseEyeConfig::parse("header:\n  - license:\n      spdx-id: MIT\n"),
+        Err(compat::license_eye::LicenseEyeError::MultipleHeaders)
+    ));
+}
+
+#[test]
+fn check_year_policy_recursively_reports_violations() {
+    let root = tempfile::tempdir().unwrap();
+    fs::write(root.path().join("new.rs"), "// Copyright 2024 Foo Inc.\n").unwrap();
+    fs::write(
+        root.path().join("old.rs"),
+        "// Copyright 2015-2021 Foo Inc.\n",
+    )
+    .unwrap();
+    fs::write(root.path().join("none.rs"), "fn main() {}\n").unwrap();
+
+    let checker = copyright::YearPolicyChecker::each_year(|year| year >= 2020);
+    let mut results = copyright::check_year_policy_recursively(
+        root.path(),
+        |_p| true,
+        &checker,
+        &RecursiveOptions::new().relative_paths(true),
+    )
+    .unwrap();
+    results.no_copyright_files.sort();
+    assert!(results.has_failure());
+    assert_eq!(
+        vec![path::PathBuf::from("none.rs")],
+        results.no_copyright_files
+    );
+    assert_eq!(
+        vec![(path::PathBuf::from("old.rs"), vec![2015..=2021])],
+        results.violating_files
+    );
+
+    let header = Header::new(checker, "Copyright 2024 Foo Inc.".to_string());
+    assert!(header
+        .check_str("// Copyright 2020, 2023 Foo Inc.\n")
+        .unwrap());
+    assert!(!header.check_str("// Copyright 2019 Foo Inc.\n").unwrap());
+    let single_year = copyright::YearPolicyChecker::new(|years| {
+        years.len() == 1 && years[0].start() == years[0].end()
+    });
+    assert!(!single_year
+        .check(&mut "// Copyright 2020-2023 Foo Inc.\n".as_bytes())
+        .unwrap());
+}
+
 fn test_checker() -> SingleLineChecker {
     SingleLineChecker::new("some license".to_string(), 100)
 }